@@ -0,0 +1,54 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Running the protocol on large voter sets with every voter on the thread running the world,
+//! against every voter on a thread of its own.
+//!
+//! Run with `cargo bench --bench execution`.
+
+use accountable_safety::{
+	behaviour::Adversary, execution::Execution, fixtures, world::WorldBuilder,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn run_until_settled(c: &mut Criterion) {
+	let mut group = c.benchmark_group("run_until_settled");
+	group.sample_size(10);
+	for num_voters in [10, 40] {
+		for execution in [Execution::SingleThreaded, Execution::ThreadPerVoter] {
+			group.bench_with_input(
+				BenchmarkId::new(format!("{:?}", execution), num_voters),
+				&num_voters,
+				|b, &num_voters| {
+					b.iter(|| {
+						WorldBuilder::new()
+							.with_voters(fixtures::large_voterset(
+								num_voters,
+								Adversary::ReturnPrecommits,
+							))
+							.with_execution(execution)
+							.build()
+							.run_until_settled()
+					})
+				},
+			);
+		}
+	}
+	group.finish();
+}
+
+criterion_group!(benches, run_until_settled);
+criterion_main!(benches);
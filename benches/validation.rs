@@ -0,0 +1,95 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Checking the responses to estimate queries on large voter sets, against the previous approach of
+//! collecting the voters into hash sets for every check.
+//!
+//! Run with `cargo bench --bench validation`.
+
+use accountable_safety::{
+	block::BlockNumber,
+	chain::Chain,
+	protocol::QueryResponse,
+	voter::VoterId,
+	voting::{check_query_reply_is_valid, Precommit, VoterSet},
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::HashSet;
+
+// How responses were checked before, allocating the ids, targets and sets of voters on every call.
+fn check_with_hash_sets(
+	response: &QueryResponse,
+	block: BlockNumber,
+	voters: &[VoterId],
+	chain: &Chain,
+) -> bool {
+	let ids: Vec<VoterId> = response.ids().map(String::from).collect();
+	let unique_voters: HashSet<VoterId> = ids.iter().cloned().collect();
+	assert_eq!(ids.len(), unique_voters.len());
+	let targets: Vec<BlockNumber> = response.target_numbers().collect();
+	let includes_block = targets
+		.into_iter()
+		.filter(|target| chain.block_includes(*target, block))
+		.count();
+	let voters: HashSet<_> = voters.iter().cloned().collect();
+	let absent_voters = voters.difference(&unique_voters).count();
+	3 * (includes_block + absent_voters) <= 2 * voters.len()
+}
+
+// A response from a voter set of the given size, with just over a third of the precommits on the
+// other fork, so it is valid.
+fn setup(num_voters: usize) -> (QueryResponse, Vec<VoterId>, Chain) {
+	let names: Vec<_> = (0..num_voters)
+		.map(|index| format!("voter{}", index))
+		.collect();
+	let voters = VoterSet::new(&names).voter_ids();
+	let precommits = voters
+		.iter()
+		.enumerate()
+		.map(|(index, voter)| {
+			let target = if index <= num_voters / 3 { 3 } else { 2 };
+			Precommit::new(2, target, voter)
+		})
+		.collect();
+	let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+	(QueryResponse::Precommits(precommits), voters, chain)
+}
+
+fn check_query_reply(c: &mut Criterion) {
+	let mut group = c.benchmark_group("check_query_reply");
+	for num_voters in [100, 1000] {
+		let (response, voters, chain) = setup(num_voters);
+		assert!(check_query_reply_is_valid(&response, 2, &voters, &chain)
+			.unwrap()
+			.is_none());
+		assert!(check_with_hash_sets(&response, 2, &voters, &chain));
+
+		group.bench_with_input(
+			BenchmarkId::new("bitset", num_voters),
+			&num_voters,
+			|b, _| b.iter(|| check_query_reply_is_valid(black_box(&response), 2, &voters, &chain)),
+		);
+		group.bench_with_input(
+			BenchmarkId::new("hash_sets", num_voters),
+			&num_voters,
+			|b, _| b.iter(|| check_with_hash_sets(black_box(&response), 2, &voters, &chain)),
+		);
+	}
+	group.finish();
+}
+
+criterion_group!(benches, check_query_reply);
+criterion_main!(benches);
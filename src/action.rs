@@ -16,9 +16,10 @@
 
 use crate::{
 	block::BlockNumber,
-	message::Request,
-	protocol::{PrevoteQuery, Query},
-	voter::VoterId,
+	message::{Gossip, Request},
+	protocol::{ConflictId, PrevoteQuery, Query, SignedEvidenceBundle},
+	voter::{QueryKind, VoterId},
+	voting::{Commit, RoundNumber},
 };
 
 pub type TriggerAtTick = usize;
@@ -26,8 +27,36 @@ pub type TriggerAtTick = usize;
 #[derive(Debug, Clone)]
 pub enum Action {
 	BroadcastCommits,
+	// Send a commit as given, without checking it against our chain.
+	SendCommit(VoterId, RoundNumber, Commit),
 	SendBlock(VoterId, BlockNumber),
+	RequestBlock(VoterId, BlockNumber),
+	RequestCommit(VoterId, BlockNumber),
 	RequeueRequest((VoterId, Request)),
 	AskVotersAboutEstimate(Query),
 	AskVotersWhichPrevotesSeen(PrevoteQuery),
+	// Put the query to its receivers, unless the instance asking has a valid answer about the
+	// round by now.
+	AskIfUnanswered(Query),
+	// Send our evidence to all other voters, repeating the given number of times.
+	GossipEvidence(usize),
+	// Check the signature on evidence again, after the given number of failed attempts.
+	RetryEvidenceVerification(SignedEvidenceBundle, usize),
+	// Stop waiting for the remaining chunks of a response from the voter about the round.
+	ExpireResponseChunks(RoundNumber, VoterId),
+	// Stop waiting for the voter to answer our query about the round, unless it has by now.
+	ExpireQuery(RoundNumber, QueryKind, VoterId),
+	// Put deferred queries to their receivers, as far as the cap on outstanding queries allows.
+	ReleaseDeferredQueries,
+	// Pass gossip received from the voter on to our other peers.
+	RelayGossip(VoterId, Gossip),
+	// Tell our peers we detected the conflict, when verifiers coordinate.
+	ClaimConflict(ConflictId),
+	// Once the claims of the other detectors had time to arrive, start querying if we have the
+	// lowest id among them, or stand by otherwise.
+	DesignateVerifier(ConflictId),
+	// Start the queries we stood by with, unless the primary verifier's evidence has arrived.
+	TakeOverVerification(ConflictId),
+	// Take the next step in the rounds we vote in, which we do every tick while voting.
+	Vote,
 }
@@ -0,0 +1,202 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Long-term storage of voting history, that voters fall back to when asked about rounds they no
+//! longer keep in memory.
+
+use crate::{
+	block::BlockNumber,
+	voting::{Precommit, Prevote, RoundNumber, VoterSet, VotingRound, VotingRounds},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	fmt::Debug,
+	fs::File,
+	io::{self, BufReader, BufWriter},
+	path::{Path, PathBuf},
+};
+
+/// Source of voting rounds that are no longer kept in memory.
+pub trait ArchiveBackend: Debug + Send + Sync {
+	/// All the voting rounds we took part in for the given round number, if archived.
+	fn voting_rounds(
+		&self,
+		round: RoundNumber,
+		voter_set: &VoterSet,
+	) -> io::Result<Option<Vec<VotingRound>>>;
+}
+
+/// Archive kept in memory, mostly useful for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryArchive(VotingRounds);
+
+impl InMemoryArchive {
+	pub fn new(voting_rounds: VotingRounds) -> Self {
+		Self(voting_rounds)
+	}
+}
+
+impl ArchiveBackend for InMemoryArchive {
+	fn voting_rounds(
+		&self,
+		round: RoundNumber,
+		_: &VoterSet,
+	) -> io::Result<Option<Vec<VotingRound>>> {
+		Ok(self.0.get(&round))
+	}
+}
+
+// Serialized form of a voting round. Voters are stored by name and resolved against the voter
+// set when loading.
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedRound {
+	round: RoundNumber,
+	tag: u32,
+	prevotes: Vec<(BlockNumber, String)>,
+	precommits: Vec<(BlockNumber, String)>,
+	finalized: Option<BlockNumber>,
+}
+
+/// Archive stored as a JSON file, read on every lookup.
+#[derive(Debug)]
+pub struct FileArchive {
+	path: PathBuf,
+}
+
+impl FileArchive {
+	pub fn open(path: impl AsRef<Path>) -> Self {
+		Self {
+			path: path.as_ref().to_path_buf(),
+		}
+	}
+
+	/// Write the voting rounds to a new archive file.
+	pub fn create(path: impl AsRef<Path>, voting_rounds: &VotingRounds) -> io::Result<Self> {
+		let mut archived: Vec<_> = voting_rounds
+			.iter()
+			.map(|voting_round| ArchivedRound {
+				round: voting_round.round_number,
+				tag: voting_round.tag,
+				prevotes: voting_round
+					.prevotes
+					.iter()
+					.map(|prevote| (prevote.target_number, prevote.id.to_string()))
+					.collect(),
+				precommits: voting_round
+					.precommits
+					.iter()
+					.map(|precommit| (precommit.target_number, precommit.id.to_string()))
+					.collect(),
+				finalized: voting_round.finalized,
+			})
+			.collect();
+		archived.sort_by_key(|round| (round.round, round.tag));
+
+		let writer = BufWriter::new(File::create(path.as_ref())?);
+		serde_json::to_writer(writer, &archived)?;
+		Ok(Self::open(path))
+	}
+
+	fn load(&self) -> io::Result<Vec<ArchivedRound>> {
+		let reader = BufReader::new(File::open(&self.path)?);
+		Ok(serde_json::from_reader(reader)?)
+	}
+}
+
+impl ArchiveBackend for FileArchive {
+	fn voting_rounds(
+		&self,
+		round: RoundNumber,
+		voter_set: &VoterSet,
+	) -> io::Result<Option<Vec<VotingRound>>> {
+		let archived = self.load().map_err(|err| {
+			io::Error::new(
+				err.kind(),
+				format!("failed to read archive {}: {}", self.path.display(), err),
+			)
+		})?;
+
+		let voting_rounds: Vec<_> = archived
+			.into_iter()
+			.filter(|archived| archived.round == round)
+			.filter_map(|archived| {
+				let mut voting_round =
+					VotingRound::new_with_tag(archived.round, voter_set.clone(), archived.tag);
+				for (target, name) in archived.prevotes {
+					voting_round.prevotes.push(Prevote::new(
+						archived.round,
+						target,
+						voter_set.lookup(&name)?,
+					));
+				}
+				for (target, name) in archived.precommits {
+					voting_round.precommits.push(Precommit::new(
+						archived.round,
+						target,
+						voter_set.lookup(&name)?,
+					));
+				}
+				voting_round.finalized = archived.finalized;
+				Some(voting_round)
+			})
+			.collect();
+
+		if voting_rounds.is_empty() {
+			Ok(None)
+		} else {
+			Ok(Some(voting_rounds))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn file_archive_round_trip() {
+		let voter_set = VoterSet::new(&["Alice", "Bob", "Carol"]);
+		let mut voting_rounds = VotingRounds::new();
+		let mut round = VotingRound::new_with_tag(2, voter_set.clone(), 1);
+		round.prevote(&[(3, "Alice"), (3, "Bob")]);
+		round.precommit(&[(3, "Carol")]);
+		voting_rounds.add(round);
+
+		let path = std::env::temp_dir().join(format!(
+			"accountable-safety-archive-{}.json",
+			std::process::id()
+		));
+		let archive = FileArchive::create(&path, &voting_rounds).unwrap();
+
+		let loaded = archive.voting_rounds(2, &voter_set).unwrap().unwrap();
+		assert_eq!(loaded.len(), 1);
+		assert_eq!(loaded[0].tag, 1);
+		assert_eq!(
+			loaded[0].prevotes,
+			vec![Prevote::new(2, 3, "Alice"), Prevote::new(2, 3, "Bob")]
+		);
+		assert_eq!(loaded[0].precommits, vec![Precommit::new(2, 3, "Carol")]);
+		assert!(archive.voting_rounds(1, &voter_set).unwrap().is_none());
+
+		// Votes from voters outside the set are not accepted.
+		let other_set = VoterSet::new(&["Alice", "Bob"]);
+		assert!(archive.voting_rounds(2, &other_set).unwrap().is_none());
+
+		// Once the file is gone, lookups fail rather than finding nothing.
+		std::fs::remove_file(path).unwrap();
+		assert!(archive.voting_rounds(2, &voter_set).is_err());
+	}
+}
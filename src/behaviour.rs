@@ -0,0 +1,348 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! How voters act wherever they could deviate from the protocol: answering queries, serving blocks
+//! and taking in commits. A [`Behaviour`] can be plugged into any voter, so that experiments can
+//! try adversaries of their own, and the strategies explored so far come built in as
+//! [`Adversary`].
+
+use crate::{
+	block::{Block, BlockNumber},
+	chain::{Chain, ChainError},
+	protocol::{QueryResponse, ReplyKinds, ResponseChunk},
+	voter::{VoterId, VoterName},
+	voting::{check_query_reply_is_valid, Commit, Precommit, RoundNumber, VotingRound},
+};
+use std::{fmt::Debug, sync::Arc};
+
+/// A query about why our estimate for a round didn't include a block, with what we know to answer
+/// it.
+pub struct EstimateQuestion<'a> {
+	pub verifier: &'a VoterId,
+	pub round: RoundNumber,
+	pub block_not_included: BlockNumber,
+	pub accepted_replies: ReplyKinds,
+	// Our histories of the round before the one asked about, which the estimate is explained with.
+	pub histories: &'a [VotingRound],
+	// Our histories of the round before that, if we have any.
+	pub earlier_histories: Option<&'a [VotingRound]>,
+	// The voters of the round before, sorted, which an answer is checked against.
+	pub voters: &'a [VoterId],
+	pub chain: &'a Chain,
+}
+
+impl EstimateQuestion<'_> {
+	/// The precommits of each of our histories, if the verifier accepts precommits.
+	pub fn precommits(&self) -> impl Iterator<Item = QueryResponse> + '_ {
+		self.histories
+			.iter()
+			.filter(move |_| self.accepted_replies.allows_precommits())
+			.map(|voting_round| QueryResponse::Precommits(voting_round.precommits.clone()))
+	}
+
+	/// The prevotes of each of our histories, if the verifier accepts prevotes.
+	pub fn prevotes(&self) -> impl Iterator<Item = QueryResponse> + '_ {
+		self.histories
+			.iter()
+			.filter(move |_| self.accepted_replies.allows_prevotes())
+			.map(|voting_round| QueryResponse::Prevotes(voting_round.prevotes.clone()))
+	}
+
+	/// The first of the answers that shows the block couldn't have been included. With more than
+	/// two forks, several of our histories can exclude it. Answers with votes for blocks we can't
+	/// trace back to genesis show nothing.
+	pub fn first_valid(
+		&self,
+		mut answers: impl Iterator<Item = QueryResponse>,
+	) -> Option<QueryResponse> {
+		answers.find(|answer| {
+			matches!(
+				check_query_reply_is_valid(
+					answer,
+					self.block_not_included,
+					self.voters,
+					self.chain
+				),
+				Ok(None)
+			)
+		})
+	}
+}
+
+/// A query about which prevotes we saw in a round.
+pub struct PrevoteQuestion<'a> {
+	pub verifier: &'a VoterId,
+	pub round: RoundNumber,
+	// Our histories of the round.
+	pub histories: &'a [VotingRound],
+	// The history an equivocator would answer from: the one it already committed to answering
+	// about the round, or else one whose prevotes justify its precommit.
+	pub selected: &'a VotingRound,
+}
+
+/// What a voter does wherever it could deviate from the protocol. Every method defaults to what an
+/// honest voter does, so an adversary only overrides where it deviates.
+pub trait Behaviour: Debug + Send + Sync {
+	/// Explain why our estimate for the round didn't include the block, `None` if we can't. Votes
+	/// are explained with precommits where they show it, and otherwise with prevotes, which is the
+	/// case when only prevotes were equivocated on.
+	fn answer_estimate_query(&self, question: &EstimateQuestion) -> Option<QueryResponse> {
+		question
+			.first_valid(question.precommits())
+			.or_else(|| question.first_valid(question.prevotes()))
+	}
+
+	/// Tell which prevotes we saw in the round.
+	fn answer_prevotes_query(&self, question: &PrevoteQuestion) -> QueryResponse {
+		QueryResponse::Prevotes(question.selected.prevotes.clone())
+	}
+
+	/// Whether to take in a commit sent to us, which is how conflicts are detected.
+	fn on_commit(&self, _sender: &VoterId, _round: RoundNumber, _commit: &Commit) -> bool {
+		true
+	}
+
+	/// Whether to leave the queries of the verifier unanswered.
+	fn ignores_queries_from(&self, _verifier: &VoterId) -> bool {
+		false
+	}
+
+	/// Whether to refuse to serve blocks to peers.
+	fn withholds_blocks(&self) -> bool {
+		false
+	}
+
+	/// The chain of blocks to serve a peer asking for the block.
+	fn serve_blocks(&self, chain: &Chain, block: BlockNumber) -> Result<Vec<Block>, ChainError> {
+		chain.try_get_chain_of_blocks(block)
+	}
+
+	/// Whether to split responses larger than the message budget into chunks.
+	fn respects_message_budget(&self) -> bool {
+		true
+	}
+
+	/// Which of the chunks of a response split over the message budget to send.
+	fn send_chunks(&self, chunks: Vec<ResponseChunk>) -> Vec<ResponseChunk> {
+		chunks
+	}
+
+	/// Whether to add to the evidence we gossip accusations of the voters whose answers held up.
+	fn fabricates_accusations(&self) -> bool {
+		false
+	}
+}
+
+/// The adversary strategies that come built in. Voters without a behaviour act like
+/// `ReturnPrecommits`, which is what an honest voter does.
+#[derive(Clone, Debug)]
+pub enum Adversary {
+	ReturnPrecommits,
+	ReturnPrevotes,
+	// Answer queries like `ReturnPrecommits`, but never serve any blocks to peers.
+	WithholdBlocks,
+	// Answer queries like `ReturnPrecommits`, except from the listed verifiers which are ignored.
+	IgnoreQueriesFrom(Vec<VoterId>),
+	// Answer queries like `ReturnPrecommits`, but only send the first chunk of chunked responses.
+	TruncateResponses,
+	// Answer queries like `ReturnPrecommits`, but send every response in a single message whatever
+	// the message budget.
+	IgnoreMessageBudget,
+	// Answer estimate queries with the precommits of the round before the one that was asked about.
+	ReplayEarlierVotes,
+	// Keep a consistent history, and only when that can't explain an estimate make up precommits
+	// that do, on behalf of the listed colluders.
+	FabricateOnQuery(Vec<VoterId>),
+	// Answer queries like `ReturnPrecommits`, but serve blocks with their parent rewritten to hang
+	// them off another fork, so that they seem not to conflict with it.
+	DoctorBlocks,
+	// Answer queries like `ReturnPrecommits`, but add to the evidence we gossip accusations of the
+	// voters whose answers held up.
+	FabricateAccusations,
+}
+
+impl Behaviour for Adversary {
+	fn answer_estimate_query(&self, question: &EstimateQuestion) -> Option<QueryResponse> {
+		match self {
+			Adversary::ReturnPrevotes => question
+				.first_valid(question.prevotes())
+				.or_else(|| question.first_valid(question.precommits())),
+			Adversary::ReplayEarlierVotes => question
+				.earlier_histories
+				.and_then(|histories| histories.first())
+				.map(|voting_round| QueryResponse::Precommits(voting_round.precommits.clone()))
+				.or_else(|| question.first_valid(question.precommits())),
+			Adversary::FabricateOnQuery(colluders) => question
+				.first_valid(question.precommits())
+				.or_else(|| fabricate_precommits(question, colluders)),
+			_ => question
+				.first_valid(question.precommits())
+				.or_else(|| question.first_valid(question.prevotes())),
+		}
+	}
+
+	fn ignores_queries_from(&self, verifier: &VoterId) -> bool {
+		match self {
+			Adversary::IgnoreQueriesFrom(ignored) => ignored.contains(verifier),
+			_ => false,
+		}
+	}
+
+	fn withholds_blocks(&self) -> bool {
+		matches!(self, Adversary::WithholdBlocks)
+	}
+
+	fn serve_blocks(&self, chain: &Chain, block: BlockNumber) -> Result<Vec<Block>, ChainError> {
+		match self {
+			Adversary::DoctorBlocks => doctored_chain_of_blocks(chain, block),
+			_ => chain.try_get_chain_of_blocks(block),
+		}
+	}
+
+	fn respects_message_budget(&self) -> bool {
+		!matches!(self, Adversary::IgnoreMessageBudget)
+	}
+
+	fn send_chunks(&self, mut chunks: Vec<ResponseChunk>) -> Vec<ResponseChunk> {
+		if matches!(self, Adversary::TruncateResponses) {
+			chunks.truncate(1);
+		}
+		chunks
+	}
+
+	fn fabricates_accusations(&self) -> bool {
+		matches!(self, Adversary::FabricateAccusations)
+	}
+}
+
+/// When a scheduled change of behaviour takes effect.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BehaviourTrigger {
+	/// At the first tick from this one on that the voter acts.
+	AtTick(usize),
+	/// Once the voter holds a proof of its own misbehaviour, whether it detected it itself or the
+	/// proof was gossiped to it.
+	OnceAccused,
+}
+
+/// Changes to a voter's behaviour during a run, so that an adversary can adapt to the protocol, say
+/// by going silent once it learns it has been caught.
+#[derive(Clone, Debug, Default)]
+pub struct BehaviourSchedule {
+	changes: Vec<(BehaviourTrigger, Arc<dyn Behaviour>)>,
+}
+
+impl BehaviourSchedule {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Switch to the behaviour at the tick.
+	pub fn at_tick(mut self, tick: usize, behaviour: impl Behaviour + 'static) -> Self {
+		self.changes
+			.push((BehaviourTrigger::AtTick(tick), Arc::new(behaviour)));
+		self
+	}
+
+	/// Switch to the behaviour once accused.
+	pub fn once_accused(mut self, behaviour: impl Behaviour + 'static) -> Self {
+		self.changes
+			.push((BehaviourTrigger::OnceAccused, Arc::new(behaviour)));
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+
+	// Take the changes that are due, returning the behaviour to switch to. When several are due at
+	// once the one scheduled last wins.
+	pub(crate) fn take_due(&mut self, tick: usize, accused: bool) -> Option<Arc<dyn Behaviour>> {
+		let is_due = |trigger: &BehaviourTrigger| match trigger {
+			BehaviourTrigger::AtTick(at) => *at <= tick,
+			BehaviourTrigger::OnceAccused => accused,
+		};
+		let (due, pending) = std::mem::take(&mut self.changes)
+			.into_iter()
+			.partition::<Vec<_>, _>(|(trigger, _)| is_due(trigger));
+		self.changes = pending;
+		due.into_iter().last().map(|(_, behaviour)| behaviour)
+	}
+}
+
+// Make up precommits for the round that rule out the block: the colluders precommit for its
+// parent, and of the precommits we actually saw only those not including the block are kept.
+fn fabricate_precommits(
+	question: &EstimateQuestion,
+	colluders: &[VoterId],
+) -> Option<QueryResponse> {
+	let voting_round = question.histories.first()?;
+	let parent = question
+		.chain
+		.get_block(question.block_not_included)?
+		.parent;
+	let is_colluder = |id: &VoterName| colluders.iter().any(|colluder| id == colluder);
+	let mut precommits: Vec<_> = voting_round
+		.precommits
+		.iter()
+		.filter(|precommit| {
+			!is_colluder(&precommit.id)
+				&& matches!(
+					question
+						.chain
+						.try_block_includes(precommit.target_number, question.block_not_included),
+					Ok(false)
+				)
+		})
+		.cloned()
+		.collect();
+	precommits.extend(
+		colluders
+			.iter()
+			.filter(|colluder| voting_round.voter_set.is_member(colluder))
+			.map(|colluder| {
+				Precommit::new(voting_round.round_number, parent, colluder.as_str())
+					.in_set(voting_round.voter_set.set_id)
+			}),
+	);
+	Some(QueryResponse::Precommits(precommits))
+}
+
+// The chain of blocks up to the block, except that the block is hung off the best block of a fork
+// it conflicts with.
+fn doctored_chain_of_blocks(chain: &Chain, block: BlockNumber) -> Result<Vec<Block>, ChainError> {
+	let mut blocks = chain.try_get_chain_of_blocks(block)?;
+	let mut graft = None;
+	for leaf in chain.leaves() {
+		if chain.try_blocks_conflict(leaf, block)? {
+			graft = graft.max(Some(leaf));
+		}
+	}
+	match (graft, blocks.pop()) {
+		(Some(graft), Some(tip)) => {
+			let mut doctored = chain.try_get_chain_of_blocks(graft)?;
+			doctored.push(Block {
+				parent: graft,
+				..tip
+			});
+			Ok(doctored)
+		}
+		(_, tip) => {
+			blocks.extend(tip);
+			Ok(blocks)
+		}
+	}
+}
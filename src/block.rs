@@ -14,24 +14,76 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::fmt::{Display, Formatter};
+use crate::voter::VoterId;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::hash_map::DefaultHasher,
+	fmt::{Display, Formatter},
+	hash::{Hash, Hasher},
+};
 
 pub type BlockNumber = u32;
+pub type BlockHash = u64;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
 	pub number: BlockNumber,
 	pub parent: BlockNumber,
+	pub metadata: Option<BlockMetadata>,
+}
+
+/// Optional information about how a block came to be, used by fork-choice rules and to describe
+/// why voters ended up on different forks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockMetadata {
+	pub author: Option<VoterId>,
+	pub weight: u64,
+	pub slot: Option<u64>,
+}
+
+impl BlockMetadata {
+	pub fn new(author: Option<VoterId>, weight: u64, slot: Option<u64>) -> Self {
+		Self {
+			author,
+			weight,
+			slot,
+		}
+	}
 }
 
 impl Block {
 	pub fn new(number: BlockNumber, parent: BlockNumber) -> Self {
-		Self { number, parent }
+		Self {
+			number,
+			parent,
+			metadata: None,
+		}
+	}
+
+	pub fn with_metadata(mut self, metadata: BlockMetadata) -> Self {
+		self.metadata = Some(metadata);
+		self
+	}
+
+	/// Identifies the block independently of its number, derived from the number and the parent.
+	pub fn hash(&self) -> BlockHash {
+		let mut hasher = DefaultHasher::new();
+		self.number.hash(&mut hasher);
+		self.parent.hash(&mut hasher);
+		hasher.finish()
 	}
 
 	pub fn is_genesis(&self) -> bool {
 		self.number == 0 && self.parent == 0
 	}
+
+	// Blocks without metadata count as having unit weight.
+	pub fn weight(&self) -> u64 {
+		self.metadata
+			.as_ref()
+			.map(|metadata| metadata.weight)
+			.unwrap_or(1)
+	}
 }
 
 impl Display for Block {
@@ -14,35 +14,150 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fmt::Debug,
+	sync::Arc,
+};
 
 use crate::{
-	block::{Block, BlockNumber},
+	block::{Block, BlockHash, BlockNumber},
+	verification::ProofId,
+	voter::VoterId,
 	voting::{Commit, RoundNumber},
 };
+use serde::{Deserialize, Serialize};
+
+/// Rule for selecting the best head among the leaves of the chain.
+pub trait ForkChoice: Debug + Send + Sync {
+	/// Score of the chain ending at `head`. The leaf with the highest score is the best head.
+	fn score(&self, chain: &Chain, head: BlockNumber) -> u64;
+}
+
+/// Prefer the chain with the most blocks.
+#[derive(Debug, Clone, Copy)]
+pub struct LongestChain;
+
+impl ForkChoice for LongestChain {
+	fn score(&self, chain: &Chain, head: BlockNumber) -> u64 {
+		chain.block_height(head) as u64
+	}
+}
+
+/// Prefer the chain with the highest accumulated block weight.
+#[derive(Debug, Clone, Copy)]
+pub struct HeaviestChain;
+
+impl ForkChoice for HeaviestChain {
+	fn score(&self, chain: &Chain, head: BlockNumber) -> u64 {
+		chain
+			.get_chain_of_blocks(head)
+			.iter()
+			.map(|block| block.weight())
+			.sum()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+	UnknownParent {
+		block: BlockNumber,
+		parent: BlockNumber,
+	},
+	DuplicateBlock(BlockNumber),
+	UnknownBlock(BlockNumber),
+	CommitTargetMismatch {
+		block: BlockNumber,
+		commit_target: BlockNumber,
+	},
+	// The block is already finalized, by a different commit or in a different round.
+	AlreadyFinalized(BlockNumber),
+	// Walking back from the block never reaches genesis.
+	Cycle(BlockNumber),
+}
+
+/// What finalizing a block did to the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finalization {
+	Finalized,
+	// The block was already finalized by the same commit in the same round, as happens when the
+	// commit reaches us more than once.
+	Unchanged,
+}
 
+impl std::fmt::Display for ChainError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			ChainError::UnknownParent { block, parent } => {
+				write!(f, "block {} has unknown parent {}", block, parent)
+			}
+			ChainError::DuplicateBlock(block) => write!(f, "block {} added twice", block),
+			ChainError::UnknownBlock(block) => write!(f, "unknown block {}", block),
+			ChainError::CommitTargetMismatch {
+				block,
+				commit_target,
+			} => write!(
+				f,
+				"finalizing block {} with a commit for block {}",
+				block, commit_target
+			),
+			ChainError::AlreadyFinalized(block) => {
+				write!(f, "block {} is already finalized by another commit", block)
+			}
+			ChainError::Cycle(block) => write!(f, "the ancestry of block {} is a cycle", block),
+		}
+	}
+}
+
+impl std::error::Error for ChainError {}
+
+/// Who sent us commits for a block, and when the first of them arrived.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitProvenance {
+	pub first_received: usize,
+	pub senders: BTreeSet<VoterId>,
+}
+
+/// The block tree a voter knows about.
+///
+/// Each query walking the ancestry of a block comes as a `try_` method, which returns the error
+/// where the walk fails, and a plain one that panics on it instead. The plain ones are for blocks
+/// we put in the chain ourselves; anything a peer sent us or asked about goes through the `try_`
+/// methods.
 #[derive(Debug, Clone)]
 pub struct Chain {
 	blocks: HashMap<BlockNumber, Block>,
+	// The children of each block that has any, for walking down the tree.
+	children: HashMap<BlockNumber, BTreeSet<BlockNumber>>,
 	commits: HashMap<BlockNumber, Commit>,
 	finalized_rounds: HashMap<BlockNumber, RoundNumber>,
+	// Where the commits received for each block came from, whether or not they were stored.
+	provenance: HashMap<BlockNumber, CommitProvenance>,
+	fork_choice: Arc<dyn ForkChoice>,
+	// The blocks each proof refers to, with their ancestry, which pruning keeps.
+	pinned: BTreeMap<ProofId, BTreeSet<BlockNumber>>,
 }
 
 impl Chain {
 	pub fn new() -> Self {
 		let mut blocks = HashMap::new();
-		let genesis = Block {
-			number: 0,
-			parent: 0,
-		};
+		let genesis = Block::new(0, 0);
 		blocks.insert(genesis.number, genesis);
 		Self {
 			blocks,
+			children: Default::default(),
 			commits: Default::default(),
 			finalized_rounds: Default::default(),
+			provenance: Default::default(),
+			fork_choice: Arc::new(LongestChain),
+			pinned: Default::default(),
 		}
 	}
 
+	pub fn set_fork_choice(&mut self, fork_choice: Arc<dyn ForkChoice>) {
+		self.fork_choice = fork_choice;
+	}
+
 	pub fn new_from(blocks: &[(BlockNumber, BlockNumber)]) -> Self {
 		let mut chain = Chain::new();
 
@@ -53,37 +168,114 @@ impl Chain {
 		chain
 	}
 
+	/// Panics where [`Chain::try_add_block`] fails.
 	pub fn add_block(&mut self, block: Block) {
+		self.try_add_block(block).unwrap();
+	}
+
+	pub fn try_add_block(&mut self, block: Block) -> Result<(), ChainError> {
+		// The block is new and its parent isn't, so the only cycle it can close is with itself.
+		if block.parent == block.number {
+			return Err(ChainError::Cycle(block.number));
+		}
 		// Check that parent exists
-		assert!(matches!(self.blocks.get(&block.parent), Some(_)));
-		assert!(matches!(self.blocks.insert(block.number, block), None));
+		if !self.blocks.contains_key(&block.parent) {
+			return Err(ChainError::UnknownParent {
+				block: block.number,
+				parent: block.parent,
+			});
+		}
+		if self.blocks.contains_key(&block.number) {
+			return Err(ChainError::DuplicateBlock(block.number));
+		}
+		self.children
+			.entry(block.parent)
+			.or_default()
+			.insert(block.number);
+		self.blocks.insert(block.number, block);
+		Ok(())
 	}
 
+	/// Panics where [`Chain::try_finalize_block`] fails.
 	pub fn finalize_block(
 		&mut self,
 		block: BlockNumber,
 		round_number: RoundNumber,
 		commit: Commit,
-	) {
-		// self.last_finalized = block;
-		assert_eq!(block, commit.target_number);
-		assert!(matches!(self.commits.insert(block, commit), None));
-		assert!(matches!(
-			self.finalized_rounds.insert(block, round_number),
-			None
-		));
+	) -> Finalization {
+		self.try_finalize_block(block, round_number, commit)
+			.unwrap()
 	}
 
+	/// Finalize the block with the commit. Finalizing it again with the same commit in the same
+	/// round changes nothing, while doing so with a different one is an error.
+	pub fn try_finalize_block(
+		&mut self,
+		block: BlockNumber,
+		round_number: RoundNumber,
+		commit: Commit,
+	) -> Result<Finalization, ChainError> {
+		if block != commit.target_number {
+			return Err(ChainError::CommitTargetMismatch {
+				block,
+				commit_target: commit.target_number,
+			});
+		}
+		if !self.blocks.contains_key(&block) {
+			return Err(ChainError::UnknownBlock(block));
+		}
+		if let Some(existing) = self.commits.get(&block) {
+			if *existing == commit && self.finalized_rounds.get(&block) == Some(&round_number) {
+				return Ok(Finalization::Unchanged);
+			}
+			return Err(ChainError::AlreadyFinalized(block));
+		}
+		self.commits.insert(block, commit);
+		self.finalized_rounds.insert(block, round_number);
+		Ok(Finalization::Finalized)
+	}
+
+	// Visit the block and then its ancestors in turn, until `visit` returns true or genesis is
+	// reached.
+	fn walk_ancestry(
+		&self,
+		block: BlockNumber,
+		mut visit: impl FnMut(&Block) -> bool,
+	) -> Result<(), ChainError> {
+		let mut current = self
+			.blocks
+			.get(&block)
+			.ok_or(ChainError::UnknownBlock(block))?;
+		// Unless there's a cycle, the walk can't take more steps than there are blocks.
+		for _ in 0..self.blocks.len() {
+			if visit(current) || current.is_genesis() {
+				return Ok(());
+			}
+			current = self
+				.blocks
+				.get(&current.parent)
+				.ok_or(ChainError::UnknownParent {
+					block: current.number,
+					parent: current.parent,
+				})?;
+		}
+		Err(ChainError::Cycle(block))
+	}
+
+	/// Panics for blocks whose ancestry doesn't lead back to genesis.
 	pub fn block_height(&self, block: BlockNumber) -> u32 {
-		let mut block = self.blocks.get(&block).unwrap();
+		self.try_block_height(block).unwrap()
+	}
+
+	pub fn try_block_height(&self, block: BlockNumber) -> Result<u32, ChainError> {
 		let mut height = 0;
-		const MAX_HEIGHT: u32 = 10000;
-		while block.number > 0 && height < MAX_HEIGHT {
-			block = self.blocks.get(&block.parent).unwrap();
-			height += 1;
-		}
-		assert!(height < MAX_HEIGHT, "Maybe a loop");
-		height
+		self.walk_ancestry(block, |block| {
+			if !block.is_genesis() {
+				height += 1;
+			}
+			false
+		})?;
+		Ok(height)
 	}
 
 	pub fn commit_for_block(&self, block: BlockNumber) -> Option<&Commit> {
@@ -94,25 +286,74 @@ impl Chain {
 		&self.commits
 	}
 
+	/// Note that the sender sent us a commit for the block at the tick.
+	pub fn record_commit_receipt(&mut self, block: BlockNumber, sender: &str, tick: usize) {
+		let provenance = self
+			.provenance
+			.entry(block)
+			.or_insert_with(|| CommitProvenance {
+				first_received: tick,
+				senders: Default::default(),
+			});
+		provenance.first_received = provenance.first_received.min(tick);
+		provenance.senders.insert(sender.to_string());
+	}
+
+	/// Who sent us commits for the block, if anyone did, as opposed to us finalizing it ourselves.
+	pub fn commit_provenance(&self, block: BlockNumber) -> Option<&CommitProvenance> {
+		self.provenance.get(&block)
+	}
+
+	/// Panics for blocks whose ancestry doesn't lead back to genesis.
 	pub fn is_descendent(&self, block: BlockNumber, ancestor: BlockNumber) -> bool {
-		const MAX_BLOCK_LENGTH: u32 = 10000;
-		let mut length = 0;
+		self.try_is_descendent(block, ancestor).unwrap()
+	}
 
-		let mut block = self.blocks.get(&block).unwrap();
-		while !block.is_genesis() && length < MAX_BLOCK_LENGTH {
-			if block.parent == ancestor {
-				return true;
-			}
-			block = self.blocks.get(&block.parent).unwrap();
-			length += 1;
-		}
-		false
+	pub fn try_is_descendent(
+		&self,
+		block: BlockNumber,
+		ancestor: BlockNumber,
+	) -> Result<bool, ChainError> {
+		let mut found = false;
+		self.walk_ancestry(block, |block| {
+			found = !block.is_genesis() && block.parent == ancestor;
+			found
+		})?;
+		Ok(found)
 	}
 
 	/// Returns true if the chain leading up to `ancestor` is included in the chain leading up to
-	/// `block`. That is, if `block` is a descendant of `ancestor` or the same block.
+	/// `block`. That is, if `block` is a descendant of `ancestor` or the same block. Panics where
+	/// [`Chain::try_block_includes`] fails.
 	pub fn block_includes(&self, block: BlockNumber, ancestor: BlockNumber) -> bool {
-		block == ancestor || self.is_descendent(block, ancestor)
+		self.try_block_includes(block, ancestor).unwrap()
+	}
+
+	pub fn try_block_includes(
+		&self,
+		block: BlockNumber,
+		ancestor: BlockNumber,
+	) -> Result<bool, ChainError> {
+		Ok(block == ancestor || self.try_is_descendent(block, ancestor)?)
+	}
+
+	/// Returns true if neither block is included in the chain leading up to the other, that is, they
+	/// are on different forks. Panics where [`Chain::try_blocks_conflict`] fails.
+	pub fn blocks_conflict(&self, block: BlockNumber, other: BlockNumber) -> bool {
+		self.try_blocks_conflict(block, other).unwrap()
+	}
+
+	pub fn try_blocks_conflict(
+		&self,
+		block: BlockNumber,
+		other: BlockNumber,
+	) -> Result<bool, ChainError> {
+		Ok(!self.try_block_includes(block, other)? && !self.try_block_includes(other, block)?)
+	}
+
+	/// Number of blocks known, not counting genesis.
+	pub fn block_count(&self) -> usize {
+		self.blocks.len() - 1
 	}
 
 	pub fn knows_about_block(&self, block: BlockNumber) -> bool {
@@ -123,41 +364,200 @@ impl Chain {
 		self.blocks.get(&block)
 	}
 
-	pub fn get_chain_of_blocks(&self, block: BlockNumber) -> Vec<Block> {
-		const MAX_BLOCK_LENGTH: u32 = 10000;
-		let mut length = 0;
-		let mut blocks = Vec::new();
+	pub fn block_hash(&self, block: BlockNumber) -> Option<BlockHash> {
+		self.blocks.get(&block).map(Block::hash)
+	}
 
-		let mut block = if let Some(block) = self.get_block(block) {
-			if block.is_genesis() {
-				return blocks;
-			}
-			blocks.push(block.clone());
-			block
-		} else {
-			return blocks;
-		};
+	/// The number of the known block with the given hash.
+	pub fn block_with_hash(&self, hash: BlockHash) -> Option<BlockNumber> {
+		self.blocks
+			.values()
+			.find(|block| block.hash() == hash)
+			.map(|block| block.number)
+	}
 
-		while !block.is_genesis() && length < MAX_BLOCK_LENGTH {
-			block = if let Some(block) = self.get_block(block.parent) {
-				if block.is_genesis() {
-					break;
-				}
-				blocks.push(block.clone());
-				block
-			} else {
-				break;
-			};
-			length += 1;
+	/// The blocks leading up to and including the block, from the first one after genesis on. Empty
+	/// for blocks we don't know about, and panics for those whose ancestry is broken.
+	pub fn get_chain_of_blocks(&self, block: BlockNumber) -> Vec<Block> {
+		match self.try_get_chain_of_blocks(block) {
+			Ok(blocks) => blocks,
+			Err(ChainError::UnknownBlock(_)) => Vec::new(),
+			Err(error) => panic!("{}", error),
 		}
+	}
 
+	pub fn try_get_chain_of_blocks(&self, block: BlockNumber) -> Result<Vec<Block>, ChainError> {
+		let mut blocks = Vec::new();
+		self.walk_ancestry(block, |block| {
+			if !block.is_genesis() {
+				blocks.push(block.clone());
+			}
+			false
+		})?;
 		blocks.reverse();
-		blocks
+		Ok(blocks)
 	}
 
 	pub fn finalized_round(&self, block: BlockNumber) -> Option<&RoundNumber> {
 		self.finalized_rounds.get(&block)
 	}
+
+	/// The blocks whose parent is the given block, in ascending order.
+	pub fn children(
+		&self,
+		block: BlockNumber,
+	) -> impl DoubleEndedIterator<Item = BlockNumber> + '_ {
+		self.children.get(&block).into_iter().flatten().cloned()
+	}
+
+	/// The block and all its descendants, depth first with children in ascending order. Empty for
+	/// blocks we don't know about.
+	pub fn subtree(&self, block: BlockNumber) -> impl Iterator<Item = BlockNumber> + '_ {
+		let mut stack: Vec<_> = self
+			.blocks
+			.get(&block)
+			.map(|block| block.number)
+			.into_iter()
+			.collect();
+		std::iter::from_fn(move || {
+			let block = stack.pop()?;
+			stack.extend(self.children(block).rev());
+			Some(block)
+		})
+	}
+
+	/// Blocks without any children.
+	pub fn leaves(&self) -> Vec<BlockNumber> {
+		let mut leaves: Vec<_> = self
+			.blocks
+			.keys()
+			.filter(|number| !self.children.contains_key(number))
+			.cloned()
+			.collect();
+		leaves.sort_unstable();
+		leaves
+	}
+
+	/// Keep the blocks, their ancestry and any commits for them through pruning, for as long as the
+	/// proof refers to them.
+	pub fn pin_for_proof(
+		&mut self,
+		proof_id: ProofId,
+		blocks: impl IntoIterator<Item = BlockNumber>,
+	) -> Result<(), ChainError> {
+		let mut ancestry = BTreeSet::new();
+		for block in blocks {
+			ancestry.extend(
+				self.try_get_chain_of_blocks(block)?
+					.into_iter()
+					.map(|block| block.number),
+			);
+		}
+		self.pinned.entry(proof_id).or_default().extend(ancestry);
+		Ok(())
+	}
+
+	pub fn unpin_proof(&mut self, proof_id: ProofId) {
+		self.pinned.remove(&proof_id);
+	}
+
+	pub fn is_pinned(&self, block: BlockNumber) -> bool {
+		self.pinned.values().any(|blocks| blocks.contains(&block))
+	}
+
+	/// Discard the blocks on forks that don't include the finalized block, along with their
+	/// commits, unless they are pinned for a proof. Returns the blocks discarded, in ascending order.
+	pub fn prune(&mut self, finalized: BlockNumber) -> Result<Vec<BlockNumber>, ChainError> {
+		let mut keep: BTreeSet<_> = self
+			.try_get_chain_of_blocks(finalized)?
+			.into_iter()
+			.map(|block| block.number)
+			.collect();
+		keep.insert(0);
+		keep.extend(self.subtree(finalized));
+		keep.extend(self.pinned.values().flatten());
+
+		let mut pruned: Vec<_> = self
+			.blocks
+			.keys()
+			.filter(|number| !keep.contains(number))
+			.cloned()
+			.collect();
+		pruned.sort_unstable();
+		for number in &pruned {
+			let block = self.blocks.remove(number).expect("pruning known blocks");
+			self.commits.remove(number);
+			self.finalized_rounds.remove(number);
+			self.provenance.remove(number);
+			self.children.remove(number);
+			self.unlink_child(block.parent, *number);
+		}
+		Ok(pruned)
+	}
+
+	// Leaves are the blocks without an entry in `children`, so don't leave empty ones behind.
+	fn unlink_child(&mut self, parent: BlockNumber, child: BlockNumber) {
+		if let Some(siblings) = self.children.get_mut(&parent) {
+			siblings.remove(&child);
+			if siblings.is_empty() {
+				self.children.remove(&parent);
+			}
+		}
+	}
+
+	/// The best head according to the fork-choice rule. Ties are broken in favour of the lowest
+	/// block number.
+	pub fn best_head(&self) -> BlockNumber {
+		self.leaves()
+			.into_iter()
+			.max_by_key(|leaf| {
+				(
+					self.fork_choice.score(self, *leaf),
+					std::cmp::Reverse(*leaf),
+				)
+			})
+			.expect("there is always at least the genesis block")
+	}
+}
+
+/// Deliberately breaking a chain, for tests of how the protocol copes with inconsistent local
+/// state. None of these keep the chain consistent, which is the point.
+#[cfg(any(test, feature = "testing"))]
+impl Chain {
+	/// Make the block a child of another parent, which needn't exist, without checking for cycles.
+	/// Returns the parent it had.
+	pub fn corrupt_parent(
+		&mut self,
+		block: BlockNumber,
+		new_parent: BlockNumber,
+	) -> Result<BlockNumber, ChainError> {
+		let entry = self
+			.blocks
+			.get_mut(&block)
+			.ok_or(ChainError::UnknownBlock(block))?;
+		let old_parent = std::mem::replace(&mut entry.parent, new_parent);
+		self.unlink_child(old_parent, block);
+		self.children.entry(new_parent).or_default().insert(block);
+		Ok(old_parent)
+	}
+
+	/// Forget the block, leaving its children with a parent we don't know and any commit for it in
+	/// place.
+	pub fn drop_block(&mut self, block: BlockNumber) -> Result<Block, ChainError> {
+		let dropped = self
+			.blocks
+			.remove(&block)
+			.ok_or(ChainError::UnknownBlock(block))?;
+		self.unlink_child(dropped.parent, block);
+		self.children.remove(&block);
+		Ok(dropped)
+	}
+
+	/// Put the commit in place of the one finalizing the block, whatever it is for, and return the
+	/// one it replaced. The round the block was finalized in stays as it was.
+	pub fn replace_commit(&mut self, block: BlockNumber, commit: Commit) -> Option<Commit> {
+		self.commits.insert(block, commit)
+	}
 }
 
 impl Default for Chain {
@@ -166,9 +566,198 @@ impl Default for Chain {
 	}
 }
 
+/// The subset of a chain that a single voter knows about, for setting up scenarios where voters
+/// have diverging views of the block tree.
+///
+/// A view is internally consistent: every block in it has its parent in the view as well, all the
+/// way back to genesis. Voters missing blocks in the middle of a fork then simply don't know about
+/// anything past the gap, and have to fetch the rest from their peers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainView {
+	// Blocks as (number, parent), ordered so that parents come before their children.
+	blocks: Vec<(BlockNumber, BlockNumber)>,
+}
+
+impl ChainView {
+	/// The view of `chain` consisting of exactly `blocks`.
+	pub fn subset_of(chain: &Chain, blocks: &[BlockNumber]) -> Result<Self, ChainError> {
+		let mut view = Vec::new();
+		for number in blocks {
+			let block = chain
+				.get_block(*number)
+				.ok_or(ChainError::UnknownBlock(*number))?;
+			if block.is_genesis() {
+				continue;
+			}
+			if block.parent != 0 && !blocks.contains(&block.parent) {
+				return Err(ChainError::UnknownParent {
+					block: block.number,
+					parent: block.parent,
+				});
+			}
+			if view.iter().any(|(n, _)| n == number) {
+				return Err(ChainError::DuplicateBlock(*number));
+			}
+			view.push((block.number, block.parent));
+		}
+		view.sort_by_key(|(number, _)| (chain.block_height(*number), *number));
+		Ok(Self { blocks: view })
+	}
+
+	/// The view consisting of all blocks leading up to and including the given heads.
+	pub fn up_to(chain: &Chain, heads: &[BlockNumber]) -> Result<Self, ChainError> {
+		let mut blocks = Vec::new();
+		for head in heads {
+			if !chain.knows_about_block(*head) {
+				return Err(ChainError::UnknownBlock(*head));
+			}
+			for block in chain.get_chain_of_blocks(*head) {
+				if !blocks.contains(&block.number) {
+					blocks.push(block.number);
+				}
+			}
+		}
+		Self::subset_of(chain, &blocks)
+	}
+
+	pub fn blocks(&self) -> &[(BlockNumber, BlockNumber)] {
+		&self.blocks
+	}
+
+	pub fn contains(&self, block: BlockNumber) -> bool {
+		block == 0 || self.blocks.iter().any(|(number, _)| *number == block)
+	}
+
+	pub fn to_chain(&self) -> Chain {
+		Chain::new_from(&self.blocks)
+	}
+}
+
+/// Fluent construction of a block tree out of runs of blocks, each block built on the one before
+/// it, so that parents don't have to be spelled out block by block. Runs can be named, to get the
+/// view of the chain up to the end of each.
+///
+/// ```
+/// use accountable_safety::chain::ChainBuilder;
+///
+/// let forks = ChainBuilder::new()
+///     .chain([1, 2, 3, 4])
+///     .named("a")
+///     .fork_at(1, [5, 6, 7, 8])
+///     .named("b")
+///     .build()
+///     .unwrap();
+/// assert!(forks.chain.blocks_conflict(4, 8));
+/// assert_eq!(forks.view("b").unwrap().blocks(), &[(1, 0), (5, 1), (6, 5), (7, 6), (8, 7)]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChainBuilder {
+	// Blocks as (number, parent), in the order added.
+	blocks: Vec<(BlockNumber, BlockNumber)>,
+	// The last block of the latest run, which the next call to `chain` builds on.
+	tip: BlockNumber,
+	names: BTreeMap<String, BlockNumber>,
+	// The first mistake made, reported when building.
+	error: Option<ChainError>,
+}
+
+impl ChainBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Build the blocks on the end of the latest run, or on genesis if this is the first.
+	pub fn chain(self, blocks: impl IntoIterator<Item = BlockNumber>) -> Self {
+		let parent = self.tip;
+		self.fork_at(parent, blocks)
+	}
+
+	/// Build the blocks on `parent`, which must be genesis or a block added before.
+	pub fn fork_at(
+		mut self,
+		parent: BlockNumber,
+		blocks: impl IntoIterator<Item = BlockNumber>,
+	) -> Self {
+		let known = |builder: &Self, block| {
+			block == 0 || builder.blocks.iter().any(|(number, _)| *number == block)
+		};
+		let mut parent = parent;
+		for block in blocks {
+			if self.error.is_some() {
+				break;
+			}
+			if !known(&self, parent) {
+				self.error = Some(ChainError::UnknownParent { block, parent });
+			} else if known(&self, block) {
+				self.error = Some(ChainError::DuplicateBlock(block));
+			} else {
+				self.blocks.push((block, parent));
+			}
+			parent = block;
+		}
+		self.tip = parent;
+		self
+	}
+
+	/// Name the latest run, for its view in the built chain.
+	pub fn named(mut self, name: &str) -> Self {
+		self.names.insert(name.to_string(), self.tip);
+		self
+	}
+
+	pub fn build(self) -> Result<Forks, ChainError> {
+		if let Some(error) = self.error {
+			return Err(error);
+		}
+		let chain = Chain::new_from(&self.blocks);
+		let views = self
+			.names
+			.into_iter()
+			.map(|(name, tip)| Ok((name, ChainView::up_to(&chain, &[tip])?)))
+			.collect::<Result<_, ChainError>>()?;
+		Ok(Forks { chain, views })
+	}
+}
+
+/// A chain made with a [`ChainBuilder`], with the view of each named run: the run and all the
+/// blocks it was built on.
+#[derive(Debug, Clone)]
+pub struct Forks {
+	pub chain: Chain,
+	views: BTreeMap<String, ChainView>,
+}
+
+impl Forks {
+	pub fn view(&self, name: &str) -> Option<&ChainView> {
+		self.views.get(name)
+	}
+
+	pub fn views(&self) -> &BTreeMap<String, ChainView> {
+		&self.views
+	}
+
+	/// The view with every block of the chain.
+	pub fn all(&self) -> ChainView {
+		ChainView::up_to(&self.chain, &self.chain.leaves()).expect("the leaves are in the chain")
+	}
+
+	/// The view with the blocks of all the named runs given.
+	pub fn union(&self, names: &[&str]) -> Option<ChainView> {
+		let tips: Vec<_> = names
+			.iter()
+			.map(|name| {
+				self.view(name)
+					.map(|view| view.blocks().last().map_or(0, |(tip, _)| *tip))
+			})
+			.collect::<Option<_>>()?;
+		ChainView::up_to(&self.chain, &tips).ok()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::block::BlockMetadata;
 
 	fn create_test_chain() -> Chain {
 		// 0 -> 1 -> 2 -> 3 -> 4
@@ -254,6 +843,48 @@ mod tests {
 		assert!(!chain.is_descendent(6, 2));
 		assert!(!chain.is_descendent(7, 2));
 		assert!(!chain.is_descendent(8, 2));
+
+		assert!(chain.blocks_conflict(4, 8));
+		assert!(chain.blocks_conflict(2, 5));
+		assert!(!chain.blocks_conflict(1, 8));
+		assert!(!chain.blocks_conflict(8, 1));
+		assert!(!chain.blocks_conflict(4, 4));
+	}
+
+	#[test]
+	fn cycles_are_errors() {
+		let mut chain = create_test_chain();
+		assert_eq!(
+			chain.try_add_block(Block::new(9, 9)),
+			Err(ChainError::Cycle(9))
+		);
+
+		// Blocks that are each other's parent can't be added, but walking them must still end.
+		chain.blocks.insert(9, Block::new(9, 10));
+		chain.blocks.insert(10, Block::new(10, 9));
+		assert_eq!(chain.try_block_height(9), Err(ChainError::Cycle(9)));
+		assert_eq!(chain.try_is_descendent(10, 1), Err(ChainError::Cycle(10)));
+		assert_eq!(chain.try_blocks_conflict(4, 9), Err(ChainError::Cycle(9)));
+		assert_eq!(chain.try_get_chain_of_blocks(9), Err(ChainError::Cycle(9)));
+		assert_eq!(chain.try_block_height(8), Ok(5));
+
+		chain.blocks.insert(11, Block::new(11, 12));
+		assert_eq!(
+			chain.try_block_height(11),
+			Err(ChainError::UnknownParent {
+				block: 11,
+				parent: 12
+			})
+		);
+	}
+
+	#[test]
+	fn long_ancestry() {
+		// Walks aren't cut short on long chains.
+		let blocks: Vec<_> = (1..=20_000).map(|number| (number, number - 1)).collect();
+		let chain = Chain::new_from(&blocks);
+		assert_eq!(chain.block_height(20_000), 20_000);
+		assert!(chain.is_descendent(20_000, 1));
 	}
 
 	#[test]
@@ -261,20 +892,255 @@ mod tests {
 		let chain = create_test_chain();
 		assert_eq!(
 			chain.get_chain_of_blocks(3),
-			vec![
-				Block {
-					number: 1,
-					parent: 0,
-				},
-				Block {
-					number: 2,
-					parent: 1,
-				},
-				Block {
-					number: 3,
-					parent: 2,
-				},
-			]
+			vec![Block::new(1, 0), Block::new(2, 1), Block::new(3, 2),]
+		);
+	}
+
+	#[test]
+	fn children_and_subtree() {
+		let mut chain = create_test_chain();
+		chain.add_block(Block::new(9, 1));
+		assert_eq!(chain.children(1).collect::<Vec<_>>(), vec![2, 5, 9]);
+		assert_eq!(chain.children(4).count(), 0);
+		assert_eq!(chain.children(10).count(), 0);
+		assert_eq!(
+			chain.subtree(1).collect::<Vec<_>>(),
+			vec![1, 2, 3, 4, 5, 6, 7, 8, 9]
+		);
+		assert_eq!(chain.subtree(6).collect::<Vec<_>>(), vec![6, 7, 8]);
+		assert_eq!(chain.subtree(0).count(), 10);
+		assert_eq!(chain.subtree(10).count(), 0);
+
+		// A block that fails to be added leaves no trace in the index.
+		assert!(chain.try_add_block(Block::new(3, 8)).is_err());
+		assert_eq!(chain.children(8).count(), 0);
+	}
+
+	#[test]
+	fn best_head_follows_fork_choice() {
+		let mut chain = create_test_chain();
+		assert_eq!(chain.leaves(), vec![4, 8]);
+		assert_eq!(chain.best_head(), 8);
+
+		// A single heavy block makes the shorter fork preferable under the heaviest chain rule
+		chain.add_block(Block::new(9, 4).with_metadata(BlockMetadata::new(
+			Some("Carol".to_string()),
+			5,
+			None,
+		)));
+		assert_eq!(chain.best_head(), 8);
+		chain.set_fork_choice(Arc::new(HeaviestChain));
+		assert_eq!(chain.best_head(), 9);
+	}
+
+	#[test]
+	fn block_hashes() {
+		let chain = create_test_chain();
+		let hash = chain.block_hash(3).unwrap();
+		assert_eq!(hash, Block::new(3, 2).hash());
+		assert_ne!(hash, Block::new(3, 1).hash());
+		assert_eq!(chain.block_with_hash(hash), Some(3));
+		assert_eq!(chain.block_hash(42), None);
+		assert_eq!(chain.block_with_hash(Block::new(42, 8).hash()), None);
+	}
+
+	#[test]
+	fn finalizing_twice() {
+		use crate::voting::Precommit;
+
+		let mut chain = create_test_chain();
+		let precommits = |voters: &[&str]| {
+			voters
+				.iter()
+				.map(|voter| Precommit::new(2, 3, *voter))
+				.collect()
+		};
+		let commit = Commit::new(3, precommits(&["Alice", "Bob", "Carol"]));
+		assert_eq!(
+			chain.finalize_block(3, 2, commit.clone()),
+			Finalization::Finalized
+		);
+
+		// The same commit again, say from gossip, changes nothing.
+		assert_eq!(
+			chain.finalize_block(3, 2, commit.clone()),
+			Finalization::Unchanged
+		);
+
+		// Different data for the same block is reported, and the first commit kept.
+		let other = Commit::new(3, precommits(&["Alice", "Bob", "Dave"]));
+		assert_eq!(
+			chain.try_finalize_block(3, 2, other),
+			Err(ChainError::AlreadyFinalized(3))
+		);
+		assert_eq!(
+			chain.try_finalize_block(3, 4, commit.clone()),
+			Err(ChainError::AlreadyFinalized(3))
+		);
+		assert_eq!(chain.commit_for_block(3), Some(&commit));
+		assert_eq!(chain.finalized_round(3), Some(&2));
+	}
+
+	#[test]
+	fn pruning_keeps_pinned_blocks() {
+		use crate::voting::Precommit;
+
+		let mut unpinned = create_test_chain();
+		let commit = Commit::new(3, vec![Precommit::new(2, 3, "Alice")]);
+		unpinned.finalize_block(3, 2, commit.clone());
+		let mut pinned = unpinned.clone();
+
+		// Finalizing block 6 on the second fork makes the first one stale.
+		assert_eq!(unpinned.prune(6), Ok(vec![2, 3, 4]));
+		assert_eq!(unpinned.leaves(), vec![8]);
+		assert_eq!(unpinned.commit_for_block(3), None);
+
+		// Block 3 and what leads up to it are kept for the proof, along with its commit, but not
+		// what was built on top of it.
+		pinned.pin_for_proof(1, vec![3]).unwrap();
+		assert!(pinned.is_pinned(2));
+		assert_eq!(pinned.prune(6), Ok(vec![4]));
+		assert_eq!(pinned.leaves(), vec![3, 8]);
+		assert_eq!(pinned.commit_for_block(3), Some(&commit));
+		assert!(pinned.blocks_conflict(3, 8));
+
+		// Once the proof no longer needs them, they go as well.
+		pinned.unpin_proof(1);
+		assert_eq!(pinned.prune(6), Ok(vec![2, 3]));
+		assert_eq!(
+			pinned.pin_for_proof(2, vec![3]),
+			Err(ChainError::UnknownBlock(3))
+		);
+	}
+
+	#[test]
+	fn mutations_for_tests() {
+		use crate::voting::Precommit;
+
+		let mut chain = create_test_chain();
+		assert_eq!(chain.corrupt_parent(8, 4), Ok(7));
+		assert!(!chain.blocks_conflict(4, 8));
+		assert_eq!(chain.leaves(), vec![7, 8]);
+		assert_eq!(
+			chain.corrupt_parent(42, 1),
+			Err(ChainError::UnknownBlock(42))
+		);
+
+		// Walks through the dropped block fail rather than end early.
+		assert_eq!(chain.drop_block(3).map(|block| block.parent), Ok(2));
+		assert_eq!(chain.leaves(), vec![2, 7, 8]);
+		assert_eq!(
+			chain.try_block_height(8),
+			Err(ChainError::UnknownParent {
+				block: 4,
+				parent: 3
+			})
+		);
+		assert_eq!(chain.drop_block(3), Err(ChainError::UnknownBlock(3)));
+
+		// The replacement needn't be for the block.
+		let commit = Commit::new(4, vec![Precommit::new(2, 4, "Alice")]);
+		assert_eq!(chain.replace_commit(6, commit.clone()), None);
+		assert_eq!(chain.commit_for_block(6), Some(&commit));
+	}
+
+	#[test]
+	fn commit_provenance() {
+		let mut chain = create_test_chain();
+		assert_eq!(chain.commit_provenance(4), None);
+
+		// The first receipt is kept even if an earlier one is recorded late.
+		chain.record_commit_receipt(4, "Bob", 12);
+		chain.record_commit_receipt(4, "Alice", 10);
+		chain.record_commit_receipt(4, "Bob", 15);
+		assert_eq!(
+			chain.commit_provenance(4),
+			Some(&CommitProvenance {
+				first_received: 10,
+				senders: ["Alice", "Bob"].iter().map(|id| id.to_string()).collect(),
+			})
+		);
+	}
+
+	#[test]
+	fn chain_views() {
+		let chain = create_test_chain();
+
+		// Blocks are ordered parents first regardless of the order they are given in
+		let view = ChainView::subset_of(&chain, &[6, 1, 5, 2]).unwrap();
+		assert_eq!(view.blocks(), &[(1, 0), (2, 1), (5, 1), (6, 5)]);
+		let partial = view.to_chain();
+		assert!(partial.knows_about_block(6));
+		assert!(!partial.knows_about_block(3));
+		assert_eq!(partial.leaves(), vec![2, 6]);
+
+		assert_eq!(ChainView::up_to(&chain, &[2, 6]).unwrap(), view);
+
+		// Gaps aren't allowed
+		assert_eq!(
+			ChainView::subset_of(&chain, &[1, 2, 4]),
+			Err(ChainError::UnknownParent {
+				block: 4,
+				parent: 3
+			})
+		);
+		assert_eq!(
+			ChainView::subset_of(&chain, &[1, 9]),
+			Err(ChainError::UnknownBlock(9))
+		);
+		assert_eq!(
+			ChainView::subset_of(&chain, &[1, 1]),
+			Err(ChainError::DuplicateBlock(1))
+		);
+	}
+
+	#[test]
+	fn chain_builder() {
+		let forks = ChainBuilder::new()
+			.chain([1, 2, 3, 4])
+			.named("a")
+			.fork_at(1, [5, 6, 7, 8])
+			.named("b")
+			.chain([9])
+			.build()
+			.unwrap();
+		assert_eq!(forks.chain.block_count(), 9);
+		assert!(forks.chain.blocks_conflict(4, 9));
+		assert!(forks.chain.is_descendent(9, 8));
+		assert_eq!(
+			forks.view("a").unwrap().blocks(),
+			&[(1, 0), (2, 1), (3, 2), (4, 3)]
+		);
+		assert_eq!(forks.views().len(), 2);
+		assert_eq!(
+			forks.all(),
+			ChainView::up_to(&forks.chain, &[4, 9]).unwrap()
+		);
+		assert_eq!(
+			forks.union(&["a", "b"]),
+			ChainView::up_to(&forks.chain, &[4, 8]).ok()
+		);
+		assert_eq!(forks.union(&["a", "c"]), None);
+
+		// Mistakes are reported when building
+		assert_eq!(
+			ChainBuilder::new()
+				.chain([1, 2])
+				.fork_at(1, [2])
+				.build()
+				.err(),
+			Some(ChainError::DuplicateBlock(2))
+		);
+		assert_eq!(
+			ChainBuilder::new().chain([1]).fork_at(3, [4]).build().err(),
+			Some(ChainError::UnknownParent {
+				block: 4,
+				parent: 3
+			})
+		);
+		assert_eq!(
+			ChainBuilder::new().chain([0]).build().err(),
+			Some(ChainError::DuplicateBlock(0))
 		);
 	}
 }
@@ -0,0 +1,873 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Configuration of an entire experiment in a single TOML file: the voters and what they know, how
+//! they misbehave, the network between them and the limits of the run.
+//!
+//! Unknown keys are rejected, and errors point to the offending key, so that a typo doesn't
+//! silently fall back to a default.
+//!
+//! ```toml
+//! [world]
+//! max_ticks = 200
+//!
+//! [network]
+//! latency = 2
+//! links = [{ between = ["Alice", "Bob"], latency = 5 }]
+//!
+//! [scenario]
+//! voter_set = ["Alice", "Bob", "Carol", "Dave"]
+//!
+//! [[scenario.histories]]
+//! name = "common"
+//! rounds = [{ round = 1, precommits = [[1, "Alice"], [1, "Bob"], [1, "Carol"]], finalizes = 1 }]
+//!
+//! [[scenario.voters]]
+//! id = "Alice"
+//! blocks = [[1, 0]]
+//! histories = ["common"]
+//! behaviour = { ignore_queries_from = ["Carol"] }
+//! behaviour_changes = [{ when = "at_tick", tick = 40, behaviour = "withhold_blocks" }]
+//! actions = [{ tick = 10, kind = "broadcast_commits" }]
+//!
+//! [expect]
+//! offenders = []
+//! guarantee = "no_conflict"
+//! ```
+
+use crate::{
+	action::Action,
+	behaviour::{Adversary, BehaviourSchedule},
+	block::BlockNumber,
+	execution::Execution,
+	network::{NetworkModel, Partition, PartitionSchedule},
+	policy::ServingPolicy,
+	protocol::ReplyKinds,
+	report::{AlternativeComparison, AlternativeOutcome},
+	scenario::{Expected, RoundSpec, ScenarioBuilder, ScenarioError},
+	voter::{QueryPlan, Voter, VoterId},
+	voting::{RoundNumber, SetId},
+	world::{StopCondition, World, WorldBuilder},
+};
+use serde::Deserialize;
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt::{Display, Formatter},
+	fs::File,
+	path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+	// The file couldn't be read.
+	Io(String),
+	// The file isn't valid TOML or doesn't match the schema. The message gives the location.
+	Parse(String),
+	// The file parsed, but the value at the key doesn't make sense.
+	Invalid { key: String, message: String },
+}
+
+impl ConfigError {
+	fn invalid(key: impl Into<String>, message: impl Display) -> Self {
+		ConfigError::Invalid {
+			key: key.into(),
+			message: message.to_string(),
+		}
+	}
+}
+
+impl Display for ConfigError {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			ConfigError::Io(message) | ConfigError::Parse(message) => write!(f, "{}", message),
+			ConfigError::Invalid { key, message } => write!(f, "{}: {}", key, message),
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+	#[serde(default)]
+	pub world: WorldConfig,
+	#[serde(default)]
+	pub network: NetworkConfig,
+	pub scenario: ScenarioConfig,
+	// The outcome the experiment is expected to come to, checked by the tests.
+	#[serde(default)]
+	pub expect: Expected,
+}
+
+/// Limits of the run and settings applying to all voters. Unset values keep the defaults of
+/// [`WorldBuilder`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldConfig {
+	pub max_ticks: Option<usize>,
+	pub seed: Option<u64>,
+	pub strict: Option<bool>,
+	pub message_budget: Option<usize>,
+	pub snapshot_interval: Option<usize>,
+	pub verification_failure_rate: Option<f64>,
+	pub deadline: Option<usize>,
+	// Messages each voter handles per tick, with overrides for individual voters.
+	pub processing_budget: Option<usize>,
+	#[serde(default)]
+	pub processing_budgets: BTreeMap<VoterId, usize>,
+	// The kinds of votes accepted as explanations: "precommits", "prevotes" or "either".
+	pub accepted_replies: Option<ReplyKinds>,
+	// How voters spread their queries: "broadcast_all" or "nearest_first".
+	pub query_plan: Option<QueryPlan>,
+	// Questions each voter has outstanding with peers at once, across all its protocol instances.
+	pub max_outstanding_queries: Option<usize>,
+	// When to stop short of settling: "settled", "accountability_reached" or "detected".
+	pub stop_condition: Option<StopCondition>,
+	// Hops gossiped commits and evidence are passed on for. Voters send them directly if not set.
+	pub gossip_hops: Option<u32>,
+	// If set, detectors of a conflict leave the queries to the one with the lowest id, and the
+	// others take over after this many ticks without a complete proof.
+	pub coordination_timeout: Option<usize>,
+	// Whether the first query also goes to the voters seen prevoting for the new block.
+	pub query_prevoters: Option<bool>,
+	// Whether voters explain estimates with the fewest votes that do, instead of all of them.
+	pub minimal_answers: Option<bool>,
+	// Rounds the voters vote in during the run, after the latest round of their histories.
+	pub active_rounds: Option<u64>,
+	// If set, voters send all the votes they have seen in their rounds at most once every this
+	// many ticks, instead of each vote as they cast it.
+	pub vote_batch_interval: Option<usize>,
+	// If set, voters finalize the block this many blocks below the one their rounds could
+	// finalize.
+	pub finalization_lag: Option<u32>,
+	// The behaviour every voter that has one takes on instead, to run the same scenario against
+	// different adversary strategies.
+	pub behaviour_override: Option<BehaviourConfig>,
+	// Whether to skip over ticks at which nothing is due, which is the default.
+	pub fast_forward: Option<bool>,
+	// Where voters run: "single_threaded" or "thread_per_voter".
+	pub execution: Option<Execution>,
+	// File to stream the event log to, as JSON Lines.
+	pub event_log: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+	pub latency: Option<usize>,
+	pub loss: Option<f64>,
+	#[serde(default)]
+	pub partitions: Vec<PartitionConfig>,
+	// Latencies between pairs of voters, overriding the latency of the network.
+	#[serde(default)]
+	pub links: Vec<LinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PartitionConfig {
+	pub start: usize,
+	pub end: usize,
+	pub groups: Vec<Vec<VoterId>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkConfig {
+	pub between: [VoterId; 2],
+	pub latency: usize,
+}
+
+/// The input to a [`ScenarioBuilder`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioConfig {
+	// The first voter set.
+	pub voter_set: Vec<VoterId>,
+	// Later voter sets, if the set changes.
+	#[serde(default)]
+	pub voter_sets: Vec<VoterSetConfig>,
+	#[serde(default)]
+	pub histories: Vec<HistoryConfig>,
+	#[serde(default)]
+	pub voters: Vec<VoterConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VoterSetConfig {
+	pub set_id: SetId,
+	pub voters: Vec<VoterId>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryConfig {
+	pub name: String,
+	pub rounds: Vec<RoundConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundConfig {
+	pub round: RoundNumber,
+	#[serde(default)]
+	pub set_id: SetId,
+	#[serde(default)]
+	pub prevotes: Vec<(BlockNumber, VoterId)>,
+	#[serde(default)]
+	pub precommits: Vec<(BlockNumber, VoterId)>,
+	pub finalizes: Option<BlockNumber>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VoterConfig {
+	pub id: VoterId,
+	// Blocks as `[number, parent]` pairs, in the order they are added.
+	#[serde(default)]
+	pub blocks: Vec<(BlockNumber, BlockNumber)>,
+	#[serde(default)]
+	pub histories: Vec<String>,
+	pub behaviour: Option<BehaviourConfig>,
+	// Changes to the behaviour during the run, in the order they are scheduled.
+	#[serde(default)]
+	pub behaviour_changes: Vec<BehaviourChangeConfig>,
+	#[serde(default)]
+	pub actions: Vec<ActionConfig>,
+	// Follows the chain without voting, so has neither histories nor a behaviour.
+	#[serde(default)]
+	pub observer: bool,
+	// Who the voter serves each kind of request to: "anyone", "members" or "nobody".
+	#[serde(default)]
+	pub policy: ServingPolicy,
+}
+
+/// The adversary strategies of [`Adversary`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviourConfig {
+	ReturnPrecommits,
+	ReturnPrevotes,
+	WithholdBlocks,
+	IgnoreQueriesFrom(Vec<VoterId>),
+	TruncateResponses,
+	IgnoreMessageBudget,
+	ReplayEarlierVotes,
+	FabricateOnQuery(Vec<VoterId>),
+	DoctorBlocks,
+	FabricateAccusations,
+}
+
+impl BehaviourConfig {
+	fn to_behaviour(&self) -> Adversary {
+		match self {
+			BehaviourConfig::ReturnPrecommits => Adversary::ReturnPrecommits,
+			BehaviourConfig::ReturnPrevotes => Adversary::ReturnPrevotes,
+			BehaviourConfig::WithholdBlocks => Adversary::WithholdBlocks,
+			BehaviourConfig::IgnoreQueriesFrom(voters) => {
+				Adversary::IgnoreQueriesFrom(voters.clone())
+			}
+			BehaviourConfig::TruncateResponses => Adversary::TruncateResponses,
+			BehaviourConfig::IgnoreMessageBudget => Adversary::IgnoreMessageBudget,
+			BehaviourConfig::ReplayEarlierVotes => Adversary::ReplayEarlierVotes,
+			BehaviourConfig::FabricateOnQuery(voters) => {
+				Adversary::FabricateOnQuery(voters.clone())
+			}
+			BehaviourConfig::DoctorBlocks => Adversary::DoctorBlocks,
+			BehaviourConfig::FabricateAccusations => Adversary::FabricateAccusations,
+		}
+	}
+}
+
+/// A change of behaviour of a [`BehaviourSchedule`], keyed by `when` it takes effect.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "when", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BehaviourChangeConfig {
+	AtTick {
+		tick: usize,
+		behaviour: BehaviourConfig,
+	},
+	OnceAccused {
+		behaviour: BehaviourConfig,
+	},
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ActionConfig {
+	BroadcastCommits {
+		tick: usize,
+	},
+	// Send our commit for the block, as we have it, to the voter.
+	SendCommit {
+		tick: usize,
+		to: VoterId,
+		block: BlockNumber,
+	},
+}
+
+impl ExperimentConfig {
+	pub fn from_toml(input: &str) -> Result<Self, ConfigError> {
+		toml::from_str(input).map_err(|error| ConfigError::Parse(error.to_string()))
+	}
+
+	pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+		let path = path.as_ref();
+		let input = std::fs::read_to_string(path)
+			.map_err(|error| ConfigError::Io(format!("{}: {}", path.display(), error)))?;
+		Self::from_toml(&input)
+	}
+
+	/// Run the scenario twice until settled, with the adversaries answering queries with precommits
+	/// and then with prevotes, overriding the behaviours configured.
+	pub fn compare_alternatives(&self) -> Result<AlternativeComparison, ConfigError> {
+		let run = |behaviour| -> Result<AlternativeOutcome, ConfigError> {
+			let mut config = self.clone();
+			config.world.behaviour_override = Some(behaviour);
+			// Both runs can't stream to the same event log.
+			config.world.event_log = None;
+			let summary = config.build()?.run_until_settled();
+			Ok(AlternativeOutcome::new(&summary))
+		};
+		Ok(AlternativeComparison {
+			precommits: run(BehaviourConfig::ReturnPrecommits)?,
+			prevotes: run(BehaviourConfig::ReturnPrevotes)?,
+		})
+	}
+
+	/// Set up the world the configuration describes, ready to run.
+	pub fn build(&self) -> Result<World, ConfigError> {
+		let mut builder = WorldBuilder::new().with_voters(self.scenario.build()?);
+
+		let world = &self.world;
+		if let Some(max_ticks) = world.max_ticks {
+			builder = builder.with_max_ticks(max_ticks);
+		}
+		if let Some(seed) = world.seed {
+			builder = builder.with_seed(seed);
+		}
+		if let Some(strict) = world.strict {
+			builder = builder.with_strict(strict);
+		}
+		if let Some(max_votes) = world.message_budget {
+			if max_votes == 0 {
+				return Err(ConfigError::invalid(
+					"world.message_budget",
+					"messages must have room for at least one vote",
+				));
+			}
+			builder = builder.with_message_budget(max_votes);
+		}
+		if let Some(interval) = world.snapshot_interval {
+			if interval == 0 {
+				return Err(ConfigError::invalid(
+					"world.snapshot_interval",
+					"snapshots must be at least one tick apart",
+				));
+			}
+			builder = builder.with_snapshot_interval(interval);
+		}
+		if let Some(failure_rate) = world.verification_failure_rate {
+			check_probability("world.verification_failure_rate", failure_rate)?;
+			builder = builder.with_verification_failure_rate(failure_rate);
+		}
+		if let Some(ticks) = world.deadline {
+			builder = builder.with_deadline(ticks);
+		}
+		if let Some(messages) = world.processing_budget {
+			check_processing_budget("world.processing_budget".to_string(), messages)?;
+			builder = builder.with_processing_budget(messages);
+		}
+		let is_known = |voter: &VoterId| {
+			self.scenario
+				.voters
+				.iter()
+				.any(|config| &config.id == voter)
+		};
+		for (voter, &messages) in &world.processing_budgets {
+			let key = format!("world.processing_budgets.{}", voter);
+			if !is_known(voter) {
+				return Err(ConfigError::invalid(key, "unknown voter"));
+			}
+			check_processing_budget(key, messages)?;
+			builder = builder.with_voter_processing_budget(voter, messages);
+		}
+		if let Some(accepted_replies) = world.accepted_replies {
+			builder = builder.with_accepted_replies(accepted_replies);
+		}
+		if let Some(query_plan) = world.query_plan {
+			builder = builder.with_query_plan(query_plan);
+		}
+		if let Some(max_queries) = world.max_outstanding_queries {
+			if max_queries == 0 {
+				return Err(ConfigError::invalid(
+					"world.max_outstanding_queries",
+					"voters must be able to ask at least one question at a time",
+				));
+			}
+			builder = builder.with_max_outstanding_queries(max_queries);
+		}
+		if let Some(stop_condition) = world.stop_condition {
+			builder = builder.with_stop_condition(stop_condition);
+		}
+		if let Some(max_hops) = world.gossip_hops {
+			builder = builder.with_gossip_hops(max_hops);
+		}
+		if let Some(timeout) = world.coordination_timeout {
+			builder = builder.with_verifier_coordination(timeout);
+		}
+		if let Some(query_prevoters) = world.query_prevoters {
+			builder = builder.with_prevoter_queries(query_prevoters);
+		}
+		if let Some(minimal_answers) = world.minimal_answers {
+			builder = builder.with_minimal_answers(minimal_answers);
+		}
+		if let Some(rounds) = world.active_rounds {
+			builder = builder.with_active_voting(rounds);
+		}
+		if let Some(interval) = world.vote_batch_interval {
+			if interval == 0 {
+				return Err(ConfigError::invalid(
+					"world.vote_batch_interval",
+					"batches must be at least one tick apart",
+				));
+			}
+			builder = builder.with_vote_batching(interval);
+		}
+		if let Some(lag) = world.finalization_lag {
+			builder = builder.with_finalization_lag(lag);
+		}
+		if let Some(behaviour) = &world.behaviour_override {
+			builder = builder.with_behaviour_override(behaviour.to_behaviour());
+		}
+		if let Some(fast_forward) = world.fast_forward {
+			builder = builder.with_fast_forward(fast_forward);
+		}
+		if let Some(execution) = world.execution {
+			builder = builder.with_execution(execution);
+		}
+		if let Some(path) = &world.event_log {
+			let file = File::create(path)
+				.map_err(|error| ConfigError::invalid("world.event_log", error))?;
+			builder = builder.with_event_sink(Box::new(file));
+		}
+
+		let network = &self.network;
+		let default = NetworkModel::default();
+		let loss = network.loss.unwrap_or(default.loss);
+		check_probability("network.loss", loss)?;
+		builder = builder.with_network(network.latency.unwrap_or(default.latency), loss);
+		let mut partitions = Vec::new();
+		for (index, partition) in network.partitions.iter().enumerate() {
+			if partition.start > partition.end {
+				return Err(ConfigError::invalid(
+					format!("network.partitions[{}].end", index),
+					format!(
+						"ends at {} before it starts at {}",
+						partition.end, partition.start
+					),
+				));
+			}
+			partitions.push(Partition::new(
+				partition.start,
+				partition.end,
+				partition.groups.clone(),
+			));
+		}
+		builder = builder.with_partition_schedule(PartitionSchedule::new(partitions));
+		for (index, link) in network.links.iter().enumerate() {
+			let key = format!("network.links[{}].between", index);
+			let [a, b] = &link.between;
+			if let Some(voter) = link.between.iter().find(|voter| !is_known(voter)) {
+				return Err(ConfigError::invalid(
+					key,
+					format!("unknown voter {}", voter),
+				));
+			}
+			if a == b {
+				return Err(ConfigError::invalid(
+					key,
+					"a link joins two different voters",
+				));
+			}
+			builder = builder.with_link_latency(a, b, link.latency);
+		}
+
+		Ok(builder.build())
+	}
+}
+
+fn check_processing_budget(key: String, messages: usize) -> Result<(), ConfigError> {
+	if messages > 0 {
+		Ok(())
+	} else {
+		Err(ConfigError::invalid(
+			key,
+			"voters must handle at least one message per tick",
+		))
+	}
+}
+
+fn check_probability(key: &str, value: f64) -> Result<(), ConfigError> {
+	if (0.0..=1.0).contains(&value) {
+		Ok(())
+	} else {
+		Err(ConfigError::invalid(
+			key,
+			format!("{} is not in the range [0, 1]", value),
+		))
+	}
+}
+
+impl ScenarioConfig {
+	fn build(&self) -> Result<BTreeMap<VoterId, Voter>, ConfigError> {
+		let mut scenario = ScenarioBuilder::new(&self.voter_set);
+		for voter_set in &self.voter_sets {
+			scenario = scenario.with_voter_set(voter_set.set_id, &voter_set.voters);
+		}
+		for history in &self.histories {
+			let rounds = history
+				.rounds
+				.iter()
+				.map(|round| {
+					let mut spec = RoundSpec::new(round.round).in_set(round.set_id);
+					spec.prevotes = round.prevotes.clone();
+					spec.precommits = round.precommits.clone();
+					spec.finalizes = round.finalizes;
+					spec
+				})
+				.collect();
+			scenario = scenario.with_history(&history.name, rounds);
+		}
+		for (index, voter) in self.voters.iter().enumerate() {
+			if voter.observer {
+				let key = format!("scenario.voters[{}]", index);
+				if !voter.histories.is_empty() {
+					let message = "observers don't vote, so have no histories";
+					return Err(ConfigError::invalid(format!("{}.histories", key), message));
+				}
+				if voter.behaviour.is_some() {
+					let message = "observers don't answer queries, so have no behaviour";
+					return Err(ConfigError::invalid(format!("{}.behaviour", key), message));
+				}
+				if !voter.behaviour_changes.is_empty() {
+					let message = "observers don't answer queries, so have no behaviour to change";
+					return Err(ConfigError::invalid(
+						format!("{}.behaviour_changes", key),
+						message,
+					));
+				}
+				scenario = scenario.with_observer(&voter.id, &voter.blocks);
+			} else {
+				let histories: Vec<_> = voter.histories.iter().map(String::as_str).collect();
+				let behaviour = voter.behaviour.as_ref().map(BehaviourConfig::to_behaviour);
+				scenario = scenario.with_voter(&voter.id, &voter.blocks, &histories, behaviour);
+				if !voter.behaviour_changes.is_empty() {
+					let schedule = voter.behaviour_changes.iter().fold(
+						BehaviourSchedule::new(),
+						|schedule, change| match change {
+							BehaviourChangeConfig::AtTick { tick, behaviour } => {
+								schedule.at_tick(*tick, behaviour.to_behaviour())
+							}
+							BehaviourChangeConfig::OnceAccused { behaviour } => {
+								schedule.once_accused(behaviour.to_behaviour())
+							}
+						},
+					);
+					scenario = scenario.with_behaviour_schedule(&voter.id, schedule);
+				}
+			}
+			for action in &voter.actions {
+				// Commits are only known once the scenario is built.
+				if let ActionConfig::BroadcastCommits { tick } = action {
+					scenario = scenario.with_action(&voter.id, *tick, Action::BroadcastCommits);
+				}
+			}
+		}
+		let mut voters = scenario.build().map_err(|error| self.locate(&error))?;
+
+		for (voter_index, config) in self.voters.iter().enumerate() {
+			voters.get_mut(&config.id).unwrap().policy = config.policy;
+			for (action_index, action) in config.actions.iter().enumerate() {
+				if let ActionConfig::SendCommit { tick, to, block } = action {
+					let voter = voters.get_mut(&config.id).unwrap();
+					let key = format!("scenario.voters[{}].actions[{}]", voter_index, action_index);
+					let commit = voter.commits().get(block).cloned().ok_or_else(|| {
+						ConfigError::invalid(
+							format!("{}.block", key),
+							format!("{} has no commit for block {}", config.id, block),
+						)
+					})?;
+					let round = *voter.chain.finalized_round(*block).unwrap();
+					voter.add_actions(vec![(*tick, Action::SendCommit(to.clone(), round, commit))]);
+				}
+			}
+		}
+		Ok(voters)
+	}
+
+	// The key of the part of the configuration the scenario error is about.
+	fn locate(&self, error: &ScenarioError) -> ConfigError {
+		let voter_index = |id: &str| self.voters.iter().position(|voter| voter.id == id);
+		let round_key = |history: &str, round: RoundNumber| {
+			let history_index = self.histories.iter().position(|h| h.name == history)?;
+			let round_index = self.histories[history_index]
+				.rounds
+				.iter()
+				.position(|spec| spec.round == round)?;
+			Some(format!(
+				"scenario.histories[{}].rounds[{}]",
+				history_index, round_index
+			))
+		};
+		let key = match error {
+			ScenarioError::VoterSet(_) => {
+				let has_duplicates =
+					|voters: &[VoterId]| voters.iter().collect::<HashSet<_>>().len() < voters.len();
+				if has_duplicates(&self.voter_set) {
+					Some("scenario.voter_set".to_string())
+				} else {
+					self.voter_sets
+						.iter()
+						.position(|set| has_duplicates(&set.voters))
+						.map(|index| format!("scenario.voter_sets[{}].voters", index))
+				}
+			}
+			ScenarioError::UnknownVoter(voter) => {
+				voter_index(voter).map(|index| format!("scenario.voters[{}].id", index))
+			}
+			ScenarioError::ObserverInVoterSet(voter) => {
+				voter_index(voter).map(|index| format!("scenario.voters[{}].observer", index))
+			}
+			ScenarioError::UnknownHistory { voter, .. } => {
+				voter_index(voter).map(|index| format!("scenario.voters[{}].histories", index))
+			}
+			ScenarioError::InvalidBlock { voter, .. } => {
+				voter_index(voter).map(|index| format!("scenario.voters[{}].blocks", index))
+			}
+			ScenarioError::NonMemberVote { history, round, .. } => round_key(history, *round),
+			ScenarioError::UnknownVoterSet { history, round, .. } => {
+				round_key(history, *round).map(|key| format!("{}.set_id", key))
+			}
+			ScenarioError::InvalidFinalization { history, round, .. } => {
+				round_key(history, *round).map(|key| format!("{}.finalizes", key))
+			}
+		};
+		ConfigError::invalid(key.unwrap_or_else(|| "scenario".to_string()), error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::policy::Audience;
+
+	const VOTERS: &str = r#"
+		[scenario]
+		voter_set = ["Alice", "Bob"]
+
+		[[scenario.histories]]
+		name = "common"
+		rounds = [{ round = 1, precommits = [[1, "Alice"], [1, "Bob"]], finalizes = 1 }]
+
+		[[scenario.voters]]
+		id = "Alice"
+		blocks = [[1, 0]]
+		histories = ["common"]
+		behaviour = { ignore_queries_from = ["Bob"] }
+		actions = [{ tick = 10, kind = "broadcast_commits" }]
+
+		[[scenario.voters]]
+		id = "Bob"
+		blocks = [[1, 0]]
+		histories = ["common"]
+	"#;
+
+	fn error(input: &str) -> ConfigError {
+		ExperimentConfig::from_toml(input)
+			.and_then(|config| config.build())
+			.err()
+			.unwrap()
+	}
+
+	#[test]
+	fn build_from_toml() {
+		let input = format!(
+			"[world]\nmax_ticks = 50\n[network]\nlatency = 2\n{}",
+			VOTERS
+		);
+		let mut world = ExperimentConfig::from_toml(&input)
+			.unwrap()
+			.build()
+			.unwrap();
+		let summary = world.run_until_settled();
+		assert!(summary.settled);
+		assert!(summary.offenders.is_empty());
+		assert!(world.voters()["Alice"].chain.knows_about_block(1));
+	}
+
+	#[test]
+	fn voter_policies() {
+		let input = VOTERS.replacen(
+			"histories = [\"common\"]\n",
+			"histories = [\"common\"]\n\t\tpolicy = { prevotes = \"members\", blocks = \"nobody\" }\n",
+			2,
+		);
+		let world = ExperimentConfig::from_toml(&input)
+			.unwrap()
+			.build()
+			.unwrap();
+		let policy = world.voters()["Bob"].policy;
+		assert_eq!(policy.prevotes, Audience::Members);
+		assert_eq!(policy.blocks, Audience::Nobody);
+		assert_eq!(policy.estimates, Audience::Anyone);
+
+		let input = VOTERS.replace(
+			"id = \"Bob\"",
+			"id = \"Bob\"\n\t\tpolicy = { blocks = \"friends\" }",
+		);
+		let message = error(&input).to_string();
+		assert!(message.contains("unknown variant `friends`"), "{}", message);
+	}
+
+	#[test]
+	fn behaviour_changes() {
+		let input = VOTERS.replace(
+			"id = \"Bob\"",
+			"id = \"Bob\"\n\t\tbehaviour_changes = [\n\t\t\t{ when = \"at_tick\", tick = 20, behaviour = \"withhold_blocks\" },\n\t\t\t{ when = \"once_accused\", behaviour = { ignore_queries_from = [\"Alice\"] } },\n\t\t]",
+		);
+		let world = ExperimentConfig::from_toml(&input)
+			.unwrap()
+			.build()
+			.unwrap();
+		assert!(world.voters()["Alice"].behaviour_schedule.is_empty());
+		assert!(!world.voters()["Bob"].behaviour_schedule.is_empty());
+
+		let input = VOTERS.replace(
+			"id = \"Bob\"",
+			"id = \"Bob\"\n\t\tbehaviour_changes = [{ when = \"never\", behaviour = \"withhold_blocks\" }]",
+		);
+		let message = error(&input).to_string();
+		assert!(message.contains("unknown variant `never`"), "{}", message);
+	}
+
+	#[test]
+	fn unknown_key() {
+		let input = format!("[network]\nlatncy = 2\n{}", VOTERS);
+		let message = error(&input).to_string();
+		assert!(message.contains("line 2"), "{}", message);
+		assert!(message.contains("unknown field `latncy`"), "{}", message);
+	}
+
+	#[test]
+	fn invalid_values_point_to_key() {
+		let input = format!("[network]\nloss = 1.5\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid("network.loss", "1.5 is not in the range [0, 1]")
+		);
+
+		let input = VOTERS.replacen(r#"histories = ["common"]"#, r#"histories = ["other"]"#, 1);
+		assert_eq!(
+			error(&input).to_string(),
+			"scenario.voters[0].histories: Alice: unknown voting history 'other'"
+		);
+
+		let input = VOTERS.replace(r#"[1, "Bob"]], finalizes"#, r#"[1, "Eve"]], finalizes"#);
+		assert_eq!(
+			error(&input).to_string(),
+			"scenario.histories[0].rounds[0]: history 'common', round 1: vote from Eve who is not \
+			 in the voter set"
+		);
+
+		let input = VOTERS.replace(
+			r#"kind = "broadcast_commits" }"#,
+			r#"kind = "send_commit", to = "Bob", block = 2 }"#,
+		);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"scenario.voters[0].actions[0].block",
+				"Alice has no commit for block 2"
+			)
+		);
+
+		let input = format!("[world]\nprocessing_budgets = {{ Carol = 1 }}\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid("world.processing_budgets.Carol", "unknown voter")
+		);
+
+		let input = format!(
+			"[[network.links]]\nbetween = [\"Alice\", \"Carol\"]\nlatency = 5\n{}",
+			VOTERS
+		);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid("network.links[0].between", "unknown voter Carol")
+		);
+
+		let input = format!(
+			"[[network.links]]\nbetween = [\"Bob\", \"Bob\"]\nlatency = 5\n{}",
+			VOTERS
+		);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"network.links[0].between",
+				"a link joins two different voters"
+			)
+		);
+
+		let input = format!("[world]\nprocessing_budget = 0\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"world.processing_budget",
+				"voters must handle at least one message per tick"
+			)
+		);
+
+		let input = format!("[world]\nmessage_budget = 0\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"world.message_budget",
+				"messages must have room for at least one vote"
+			)
+		);
+
+		let input = format!("[world]\nsnapshot_interval = 0\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"world.snapshot_interval",
+				"snapshots must be at least one tick apart"
+			)
+		);
+
+		let input = format!("[world]\nmax_outstanding_queries = 0\n{}", VOTERS);
+		assert_eq!(
+			error(&input),
+			ConfigError::invalid(
+				"world.max_outstanding_queries",
+				"voters must be able to ask at least one question at a time"
+			)
+		);
+	}
+}
@@ -0,0 +1,234 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Records which branches of the protocol and of the voter logic a run went through, so that it's
+//! easy to see which parts of the code no scenario exercises.
+
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Branch {
+	ProtocolStarted,
+	NoConflict,
+	// A query reply was invalid, or inconsistent with the responder's own precommit.
+	InvalidResponse,
+	// Walking back one round, asking the voters in the previous reply.
+	WalkBack,
+	// Alternative 1: the reply for the round after the block was finalized consists of precommits,
+	// which are cross-checked with the commit.
+	PrecommitCrossCheck,
+	// Alternative 2: the reply consists of prevotes, so the precommitters are asked which prevotes
+	// they have seen (Step 3).
+	PrevoteQuery,
+	PrevoteCrossCheck,
+	InconsistentTestimony,
+	ChunkedResponse,
+	// A chunked response wasn't completed in time.
+	PartialResponse,
+	// A commit for an unknown block was requeued until the block was known.
+	RequeueRequest,
+	// The peer we asked for a block didn't serve it, so we asked another.
+	BlockRequestFallback,
+	BlockSync,
+	BlocksWithheld,
+	QueryIgnored,
+	EvidenceGossip,
+	// A commit was rejected since its target number and hash don't match our chain.
+	CommitTargetMismatch,
+	// A response contained votes from another round than the one asked about.
+	ReplayedVotes,
+	// A protocol instance got its answer from a question another instance already asked.
+	SharedResponse,
+	// The two conflicting commits alone had enough common signers to prove the safety violation.
+	CommonSigners,
+	// A Byzantine voter made up votes to answer a query its stored history couldn't explain.
+	FabricatedVotes,
+	// Blocks served by a peer were inconsistent with the commit for them or with our chain.
+	UntrustedAncestry,
+	// The nearest voters asked didn't answer validly in time, so the query went further out.
+	QueryExpanded,
+	// Evidence gossiped by another detector held an accusation that doesn't hold up.
+	FabricationRejected,
+	// A responder's precommit didn't include the estimate the votes it claimed to have seen give.
+	EstimateInconsistency,
+	// A voter behind on finality asked its peers for the commits on forks conflicting with a commit
+	// it received.
+	CommitCatchUp,
+	// Some voters signed both conflicting commits in the same round.
+	CommitEquivocation,
+	// A responder sent more votes in a single message than the message budget allows.
+	OversizedResponse,
+	// A responder refused a query from a sender without standing, or one not backed by a valid
+	// statement of the conflict.
+	QueryRejected,
+	// A queried voter answered that it wasn't a member of the voter set of the round.
+	NonMemberResponse,
+	// Gossip was dropped for having been seen before.
+	DuplicateSuppressed,
+	// A response had votes for a block the verifier doesn't know.
+	UnknownBlockInResponse,
+	// A voter refused to serve a request, as its policy doesn't allow it for the sender.
+	PolicyRefusal,
+	// A received commit didn't hold up once its target block was known.
+	InvalidCommit,
+	// A verifier standing by for another took over after hearing nothing conclusive in time.
+	VerifierTakeover,
+	// A responder revealed votes for a round that differ from those it committed to.
+	BrokenCommitment,
+	// The first query also went to voters that only prevoted for the new block.
+	PrevoterQuery,
+	// A voter answered that it had lost its votes for the round.
+	LostHistory,
+	// An equivocator picked which of its histories to answer a prevote query from.
+	PrevoteHistorySelected,
+	// A response presented votes of another round or voter set as votes of the round.
+	MisplacedVotes,
+	// A voter switched to another behaviour on its schedule.
+	BehaviourSwitched,
+	// An answer arrived to a query we never sent.
+	UnsolicitedAnswer,
+	// A queried voter didn't answer in time.
+	QueryExpired,
+}
+
+impl Branch {
+	pub const ALL: &'static [Branch] = &[
+		Branch::ProtocolStarted,
+		Branch::NoConflict,
+		Branch::InvalidResponse,
+		Branch::WalkBack,
+		Branch::PrecommitCrossCheck,
+		Branch::PrevoteQuery,
+		Branch::PrevoteCrossCheck,
+		Branch::InconsistentTestimony,
+		Branch::ChunkedResponse,
+		Branch::PartialResponse,
+		Branch::RequeueRequest,
+		Branch::BlockRequestFallback,
+		Branch::BlockSync,
+		Branch::BlocksWithheld,
+		Branch::QueryIgnored,
+		Branch::EvidenceGossip,
+		Branch::CommitTargetMismatch,
+		Branch::ReplayedVotes,
+		Branch::SharedResponse,
+		Branch::CommonSigners,
+		Branch::FabricatedVotes,
+		Branch::UntrustedAncestry,
+		Branch::QueryExpanded,
+		Branch::FabricationRejected,
+		Branch::EstimateInconsistency,
+		Branch::CommitCatchUp,
+		Branch::CommitEquivocation,
+		Branch::OversizedResponse,
+		Branch::QueryRejected,
+		Branch::NonMemberResponse,
+		Branch::DuplicateSuppressed,
+		Branch::UnknownBlockInResponse,
+		Branch::PolicyRefusal,
+		Branch::InvalidCommit,
+		Branch::VerifierTakeover,
+		Branch::BrokenCommitment,
+		Branch::PrevoterQuery,
+		Branch::LostHistory,
+		Branch::PrevoteHistorySelected,
+		Branch::MisplacedVotes,
+		Branch::BehaviourSwitched,
+		Branch::UnsolicitedAnswer,
+		Branch::QueryExpired,
+	];
+
+	fn bit(self) -> u64 {
+		1 << Branch::ALL.iter().position(|b| *b == self).unwrap()
+	}
+}
+
+/// Bitmap of the branches hit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Coverage(u64);
+
+impl Coverage {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	pub fn hit(&mut self, branch: Branch) {
+		self.0 |= branch.bit();
+	}
+
+	pub fn contains(&self, branch: Branch) -> bool {
+		self.0 & branch.bit() != 0
+	}
+
+	pub fn merge(&mut self, other: Coverage) {
+		self.0 |= other.0;
+	}
+
+	pub fn bitmap(&self) -> u64 {
+		self.0
+	}
+
+	pub fn covered(&self) -> Vec<Branch> {
+		Branch::ALL
+			.iter()
+			.cloned()
+			.filter(|branch| self.contains(*branch))
+			.collect()
+	}
+
+	pub fn missing(&self) -> Vec<Branch> {
+		Branch::ALL
+			.iter()
+			.cloned()
+			.filter(|branch| !self.contains(*branch))
+			.collect()
+	}
+}
+
+impl Display for Coverage {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		for branch in Branch::ALL {
+			let mark = if self.contains(*branch) { 'x' } else { ' ' };
+			writeln!(f, "[{}] {:?}", mark, branch)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn merge_and_report() {
+		let mut a = Coverage::new();
+		a.hit(Branch::ProtocolStarted);
+		let mut b = Coverage::new();
+		b.hit(Branch::BlockSync);
+		b.hit(Branch::BlockSync);
+		a.merge(b);
+
+		assert_eq!(
+			a.covered(),
+			vec![Branch::ProtocolStarted, Branch::BlockSync]
+		);
+		assert_eq!(a.missing().len(), Branch::ALL.len() - 2);
+		assert_eq!(a.bitmap(), 0b1_0000_0000_0001);
+		assert!(a
+			.to_string()
+			.starts_with("[x] ProtocolStarted\n[ ] NoConflict\n"));
+	}
+}
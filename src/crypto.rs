@@ -0,0 +1,267 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Simulated signatures.
+//!
+//! Signatures are Schnorr signatures in the multiplicative group modulo the Mersenne prime
+//! 2^61 - 1. The parameters are far too small to be secure against a determined attacker, but the
+//! scheme has the shape that matters for the simulation: signing needs the secret key, which is
+//! never derived from anything public, and checking a signature needs only the public key in the
+//! [`KeyRegistry`]. So a voter can only produce signatures in its own name.
+//!
+//! Commitments to votes are made with [`siphash`], whose output, unlike that of the hashers of the
+//! standard library, doesn't depend on the toolchain.
+
+use crate::{network::Rng, voter::VoterId};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{hash_map::DefaultHasher, hash_map::RandomState, HashMap},
+	convert::TryInto,
+	hash::{BuildHasher, Hash, Hasher},
+};
+
+// The modulus of the group, and a generator of it.
+const MODULUS: u64 = (1 << 61) - 1;
+const GENERATOR: u64 = 37;
+// Exponents are taken modulo the order of the group.
+const ORDER: u64 = MODULUS - 1;
+
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+	((a as u128 * b as u128) % modulus as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64) -> u64 {
+	let mut result = 1;
+	base %= MODULUS;
+	while exponent > 0 {
+		if exponent & 1 == 1 {
+			result = mul_mod(result, base, MODULUS);
+		}
+		base = mul_mod(base, base, MODULUS);
+		exponent >>= 1;
+	}
+	result
+}
+
+/// SipHash-2-4 of the data under the key. Unlike the standard library's hashers, its output is
+/// fixed by its specification, so that values hashed by one voter can be checked by any other.
+pub fn siphash(key: [u64; 2], data: &[u8]) -> u64 {
+	let mut v = [
+		key[0] ^ 0x736f_6d65_7073_6575,
+		key[1] ^ 0x646f_7261_6e64_6f6d,
+		key[0] ^ 0x6c79_6765_6e65_7261,
+		key[1] ^ 0x7465_6462_7974_6573,
+	];
+	fn sip_round(v: &mut [u64; 4]) {
+		v[0] = v[0].wrapping_add(v[1]);
+		v[1] = v[1].rotate_left(13) ^ v[0];
+		v[0] = v[0].rotate_left(32);
+		v[2] = v[2].wrapping_add(v[3]);
+		v[3] = v[3].rotate_left(16) ^ v[2];
+		v[0] = v[0].wrapping_add(v[3]);
+		v[3] = v[3].rotate_left(21) ^ v[0];
+		v[2] = v[2].wrapping_add(v[1]);
+		v[1] = v[1].rotate_left(17) ^ v[2];
+		v[2] = v[2].rotate_left(32);
+	}
+	let mut compress = |word: u64| {
+		v[3] ^= word;
+		sip_round(&mut v);
+		sip_round(&mut v);
+		v[0] ^= word;
+	};
+
+	let mut words = data.chunks_exact(8);
+	for word in &mut words {
+		compress(u64::from_le_bytes(word.try_into().unwrap()));
+	}
+	// The last word holds the bytes left over, and the length of the data in its top byte.
+	let mut last = [0; 8];
+	last[..words.remainder().len()].copy_from_slice(words.remainder());
+	last[7] = data.len() as u8;
+	compress(u64::from_le_bytes(last));
+
+	v[2] ^= 0xff;
+	for _ in 0..4 {
+		sip_round(&mut v);
+	}
+	v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn hash_to_exponent<T: Hash>(value: &T) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	value.hash(&mut hasher);
+	hasher.finish() % ORDER
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretKey(u64);
+
+impl SecretKey {
+	pub fn new(secret: u64) -> Self {
+		Self(secret % ORDER)
+	}
+
+	/// A key drawn from the generator, so that the keys of a world are reproducible from its seed.
+	pub fn generate(rng: &mut Rng) -> Self {
+		Self::new(rng.next_u64())
+	}
+
+	/// A key nobody else can reproduce, for voters created outside of a world.
+	pub fn random() -> Self {
+		Self::new(RandomState::new().build_hasher().finish())
+	}
+
+	/// A secret for the context, which only the holder of the key can derive, and always derives
+	/// the same way.
+	pub fn nonce(&self, context: &[u8]) -> u64 {
+		siphash([self.0, 0], context)
+	}
+
+	pub fn public(&self) -> PublicKey {
+		PublicKey(pow_mod(GENERATOR, self.0))
+	}
+
+	pub fn sign<M: Hash>(&self, signer: &VoterId, message: &M) -> Signature {
+		// The nonce is derived from the key and the message, so that signing is deterministic.
+		let nonce = hash_to_exponent(&(self.0, signer, message));
+		let commitment = pow_mod(GENERATOR, nonce);
+		let challenge = hash_to_exponent(&(commitment, signer, message));
+		let response = (nonce as u128 + mul_mod(challenge, self.0, ORDER) as u128) % ORDER as u128;
+		Signature {
+			signer: signer.clone(),
+			commitment,
+			response: response as u64,
+		}
+	}
+}
+
+/// The public counterpart of a [`SecretKey`], which is all that is needed to check signatures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey(u64);
+
+impl PublicKey {
+	pub fn verify<M: Hash>(&self, signature: &Signature, message: &M) -> bool {
+		let challenge = hash_to_exponent(&(signature.commitment, &signature.signer, message));
+		pow_mod(GENERATOR, signature.response)
+			== mul_mod(signature.commitment, pow_mod(self.0, challenge), MODULUS)
+	}
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+	pub signer: VoterId,
+	commitment: u64,
+	response: u64,
+}
+
+/// The public keys of all participants, used to check signatures.
+#[derive(Clone, Debug, Default)]
+pub struct KeyRegistry(HashMap<VoterId, PublicKey>);
+
+impl KeyRegistry {
+	pub fn new() -> Self {
+		Self(HashMap::new())
+	}
+
+	pub fn register(&mut self, id: VoterId, key: PublicKey) {
+		self.0.insert(id, key);
+	}
+
+	pub fn verify<M: Hash>(&self, signature: &Signature, message: &M) -> bool {
+		self.0
+			.get(&signature.signer)
+			.is_some_and(|key| key.verify(signature, message))
+	}
+}
+
+/// Makes a fraction of signature checks fail regardless of the signature, to simulate key
+/// mismatches or corrupted messages.
+#[derive(Clone, Debug)]
+pub struct VerificationFaults {
+	failure_rate: f64,
+	rng: Rng,
+}
+
+impl VerificationFaults {
+	pub fn new(failure_rate: f64, seed: u64) -> Self {
+		Self {
+			failure_rate,
+			rng: Rng::new(seed),
+		}
+	}
+
+	pub fn verify<M: Hash>(
+		&mut self,
+		registry: &KeyRegistry,
+		signature: &Signature,
+		message: &M,
+	) -> bool {
+		if self.failure_rate > 0.0 && self.rng.next_f64() < self.failure_rate {
+			return false;
+		}
+		registry.verify(signature, message)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn signatures_only_verify_for_the_signer() {
+		let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+		let (alice_key, bob_key) = (SecretKey::random(), SecretKey::random());
+		let mut registry = KeyRegistry::new();
+		registry.register(alice.clone(), alice_key.public());
+		registry.register(bob.clone(), bob_key.public());
+
+		let signature = alice_key.sign(&alice, &"message");
+		assert!(registry.verify(&signature, &"message"));
+		assert!(!registry.verify(&signature, &"other message"));
+
+		// Bob can't sign in Alice's name
+		let forged = bob_key.sign(&alice, &"message");
+		assert!(!registry.verify(&forged, &"message"));
+
+		// Signatures from unknown signers are never valid
+		let unknown = "Eve".to_string();
+		let signature = SecretKey::random().sign(&unknown, &"message");
+		assert!(!registry.verify(&signature, &"message"));
+	}
+
+	#[test]
+	fn siphash_matches_the_reference_vectors() {
+		// The key and messages of the test vectors in the appendix of the SipHash paper.
+		let key = [0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908];
+		let message: Vec<u8> = (0..64).collect();
+		assert_eq!(siphash(key, &[]), 0x726f_db47_dd0e_0e31);
+		assert_eq!(siphash(key, &message[..15]), 0xa129_ca61_49be_45e5);
+		assert_eq!(siphash(key, &message[..63]), 0x958a_324c_eb06_4572);
+	}
+
+	#[test]
+	fn keys_are_reproducible_from_the_seed_only() {
+		let keys = |seed| {
+			let mut rng = Rng::new(seed);
+			(SecretKey::generate(&mut rng), SecretKey::generate(&mut rng))
+		};
+		assert_eq!(keys(7), keys(7));
+		assert_ne!(keys(7), keys(8));
+		let (first, second) = keys(7);
+		assert_ne!(first.public(), second.public());
+	}
+}
@@ -0,0 +1,344 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Structured log of what happened during a simulation run.
+//!
+//! The log can be streamed as JSON Lines, one event per line, for analysis with external tools, and
+//! read back with [`EventLog::read_jsonl`].
+//!
+//! Each event carries the hash of the event before it, so that a trace shared alongside a proof is
+//! tamper-evident: altering, dropping or reordering events breaks the chain, which
+//! [`EventLog::verify_integrity`] detects. Only the last event can be altered unnoticed, so the
+//! hash of the log, [`EventLog::head`], should be shared along with it.
+
+use crate::{
+	protocol::{ConflictId, EquivocationDetected},
+	voter::{QueryKind, VoterId},
+	voting::RoundNumber,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::hash_map::DefaultHasher,
+	fmt::{Display, Formatter},
+	hash::{Hash, Hasher},
+	io::{self, BufRead, Write},
+};
+
+/// Bumped whenever the serialized form of [`Event`] changes.
+pub const EVENT_SCHEMA_VERSION: u32 = 4;
+
+/// The position of an event in its log, by which other records refer to it.
+pub type EventId = usize;
+
+/// The previous hash of the first event in a log.
+pub const GENESIS_HASH: u64 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+	MessageSent,
+	MessageDelivered,
+	MessageDropped,
+	ProtocolStarted,
+	EquivocationDetected,
+	InvariantViolated,
+	ProtocolBug,
+	DeadlineMissed,
+	// Something a voter noted about what it was doing, for whoever reads the log.
+	Note,
+	// A received commit reached the next stage on its way to starting protocol instances.
+	CommitStage,
+}
+
+/// What subscribers to a world, see [`crate::world::World::subscribe`], are told about the moment
+/// it happens, rather than reading it off the event log after the run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionEvent {
+	// The detector started a protocol instance for the conflicting blocks.
+	ConflictDetected {
+		tick: usize,
+		detector: VoterId,
+		conflict: ConflictId,
+	},
+	QuerySent {
+		tick: usize,
+		verifier: VoterId,
+		responder: VoterId,
+		round: RoundNumber,
+		kind: QueryKind,
+	},
+	OffenceProven {
+		tick: usize,
+		detector: VoterId,
+		finding: EquivocationDetected,
+	},
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+	pub schema_version: u32,
+	pub tick: usize,
+	pub voter: VoterId,
+	pub kind: EventKind,
+	pub summary: String,
+	// The hash of the event before this one in the log. Set when the event is recorded.
+	pub prev_hash: u64,
+}
+
+impl Event {
+	pub fn new(tick: usize, voter: VoterId, kind: EventKind, summary: String) -> Self {
+		Self {
+			schema_version: EVENT_SCHEMA_VERSION,
+			tick,
+			voter,
+			kind,
+			summary,
+			prev_hash: GENESIS_HASH,
+		}
+	}
+
+	/// The hash of the event as serialized, which the next event in the log refers to. Fields are
+	/// serialized in declaration order, so this is deterministic.
+	pub fn hash(&self) -> u64 {
+		let serialized = serde_json::to_vec(self).expect("events always serialize");
+		let mut hasher = DefaultHasher::new();
+		serialized.hash(&mut hasher);
+		hasher.finish()
+	}
+}
+
+/// The event at the index doesn't refer to the hash of the one before it, so the log was altered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BrokenChain {
+	pub index: usize,
+}
+
+impl Display for BrokenChain {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"event {} doesn't follow the event before it, the log was altered",
+			self.index
+		)
+	}
+}
+
+#[derive(Default)]
+pub struct EventLog {
+	events: Vec<Event>,
+	// The hash of the last event recorded.
+	head: u64,
+	// If set, every event is also written out as JSON Lines as soon as it's recorded.
+	sink: Option<Box<dyn Write>>,
+	// The error the sink failed with, after which nothing more was written to it.
+	sink_error: Option<io::Error>,
+}
+
+impl EventLog {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	pub fn with_sink(sink: Box<dyn Write>) -> Self {
+		Self {
+			sink: Some(sink),
+			..Default::default()
+		}
+	}
+
+	pub fn record(&mut self, mut event: Event) -> EventId {
+		event.prev_hash = self.head;
+		self.head = event.hash();
+		if let Some(sink) = &mut self.sink {
+			// The log is a diagnostic aid, so failing to write it out shouldn't stop the run, but
+			// what was written out is incomplete, which is left to the caller to report.
+			if let Err(error) = write_event(sink, &event) {
+				self.sink = None;
+				self.sink_error = Some(error);
+			}
+		}
+		self.events.push(event);
+		self.events.len() - 1
+	}
+
+	pub fn events(&self) -> &[Event] {
+		&self.events
+	}
+
+	/// Why writing the events out to the sink stopped, if it did. The events recorded since are
+	/// still kept in the log.
+	pub fn sink_error(&self) -> Option<&io::Error> {
+		self.sink_error.as_ref()
+	}
+
+	pub fn get(&self, id: EventId) -> Option<&Event> {
+		self.events.get(id)
+	}
+
+	/// The hash of the last event recorded, which commits to the whole log.
+	pub fn head(&self) -> u64 {
+		self.head
+	}
+
+	/// Check that every event refers to the hash of the one before it.
+	pub fn verify_integrity(&self) -> Result<(), BrokenChain> {
+		Self::verify_events(&self.events)
+	}
+
+	/// Check the chain of hashes of events read back with [`EventLog::read_jsonl`].
+	pub fn verify_events(events: &[Event]) -> Result<(), BrokenChain> {
+		let mut prev_hash = GENESIS_HASH;
+		for (index, event) in events.iter().enumerate() {
+			if event.prev_hash != prev_hash {
+				return Err(BrokenChain { index });
+			}
+			prev_hash = event.hash();
+		}
+		Ok(())
+	}
+
+	pub fn write_jsonl(&self, mut writer: impl Write) -> io::Result<()> {
+		for event in &self.events {
+			write_event(&mut writer, event)?;
+		}
+		Ok(())
+	}
+
+	pub fn read_jsonl(reader: impl BufRead) -> io::Result<Vec<Event>> {
+		reader
+			.lines()
+			.filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+			.map(|line| {
+				let event: Event = serde_json::from_str(&line?)?;
+				if event.schema_version != EVENT_SCHEMA_VERSION {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("unsupported schema version {}", event.schema_version),
+					));
+				}
+				Ok(event)
+			})
+			.collect()
+	}
+}
+
+fn write_event(writer: &mut impl Write, event: &Event) -> io::Result<()> {
+	serde_json::to_writer(&mut *writer, event)?;
+	writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn jsonl_round_trip() {
+		let mut log = EventLog::new();
+		log.record(Event::new(
+			10,
+			"Dave".to_string(),
+			EventKind::MessageSent,
+			"HereIsCommit(4, 8) to Carol".to_string(),
+		));
+		log.record(Event::new(
+			40,
+			"Carol".to_string(),
+			EventKind::EquivocationDetected,
+			"Alice, Bob".to_string(),
+		));
+
+		let mut output = Vec::new();
+		log.write_jsonl(&mut output).unwrap();
+		let output = String::from_utf8(output).unwrap();
+		assert_eq!(output.lines().count(), 2);
+		assert!(output
+			.starts_with(r#"{"schema_version":4,"tick":10,"voter":"Dave","kind":"message_sent","#));
+
+		let events = EventLog::read_jsonl(output.as_bytes()).unwrap();
+		assert_eq!(events, log.events());
+		assert_eq!(EventLog::verify_events(&events), Ok(()));
+	}
+
+	#[test]
+	fn tampering_breaks_the_chain() {
+		let mut log = EventLog::new();
+		for (tick, voter) in &[(10, "Dave"), (20, "Carol"), (30, "Carol")] {
+			log.record(Event::new(
+				*tick,
+				voter.to_string(),
+				EventKind::MessageSent,
+				String::new(),
+			));
+		}
+		assert_eq!(log.verify_integrity(), Ok(()));
+		assert_eq!(log.events()[0].prev_hash, GENESIS_HASH);
+		assert_eq!(log.head(), log.events()[2].hash());
+
+		let mut altered = log.events().to_vec();
+		altered[1].voter = "Alice".to_string();
+		assert_eq!(
+			EventLog::verify_events(&altered),
+			Err(BrokenChain { index: 2 })
+		);
+
+		let mut dropped = log.events().to_vec();
+		dropped.remove(1);
+		assert_eq!(
+			EventLog::verify_events(&dropped),
+			Err(BrokenChain { index: 1 })
+		);
+
+		let mut reordered = log.events().to_vec();
+		reordered.swap(1, 2);
+		assert_eq!(
+			EventLog::verify_events(&reordered),
+			Err(BrokenChain { index: 1 })
+		);
+	}
+
+	#[test]
+	fn sink_errors_are_kept() {
+		struct Full;
+		impl Write for Full {
+			fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+				Err(io::Error::other("disk full"))
+			}
+			fn flush(&mut self) -> io::Result<()> {
+				Ok(())
+			}
+		}
+
+		let mut log = EventLog::with_sink(Box::new(Full));
+		assert!(log.sink_error().is_none());
+		for tick in 0..2 {
+			log.record(Event::new(
+				tick,
+				"Dave".to_string(),
+				EventKind::MessageSent,
+				String::new(),
+			));
+		}
+		assert_eq!(log.sink_error().unwrap().to_string(), "disk full");
+		assert_eq!(log.events().len(), 2);
+	}
+
+	#[test]
+	fn reject_unknown_schema_version() {
+		let line = r#"{"schema_version":0,"tick":1,"voter":"Dave","kind":"message_sent","summary":"","prev_hash":0}"#;
+		assert!(EventLog::read_jsonl(line.as_bytes()).is_err());
+	}
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! How the world runs its voters: all on the thread running the world, or each on a thread of its
+//! own.
+//!
+//! When each voter has its own thread, the world is only left routing messages and keeping time.
+//! In each phase of a tick it hands the voters with work to their threads over channels, together
+//! with the work, and takes them back as they finish. Between phases the world holds all the
+//! voters again, so everything it checks and reports works the same either way. Voters finish in
+//! whatever order the scheduler lets them, but what they hand back is put in the order of their
+//! ids, so that a seed gives the same run each time.
+
+use crate::{
+	message::{Message, Payload},
+	protocol::{ConflictId, EquivocationDetected},
+	voter::{Voter, VoterId},
+};
+use crossbeam_channel::{unbounded, Receiver, Select, Sender};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	panic,
+	thread::{self, JoinHandle},
+};
+
+/// Where the world runs its voters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Execution {
+	// All voters on the thread running the world, one after the other.
+	#[default]
+	SingleThreaded,
+	// Each voter on a thread of its own, exchanging messages with the world over channels.
+	ThreadPerVoter,
+}
+
+/// Work handed to a voter for one phase of a tick.
+pub(crate) enum Job {
+	ProcessActions,
+	HandleRequests(Vec<Message>),
+	HandleResponses(Vec<Message>),
+}
+
+/// What came of a [`Job`].
+pub(crate) enum JobResult {
+	Sent(Vec<Message>),
+	Requests(Vec<HandledRequest>),
+	// The equivocations newly detected by each response.
+	Responses(Vec<Vec<EquivocationDetected>>),
+}
+
+pub(crate) struct HandledRequest {
+	pub summary: String,
+	pub conflicts_started: Vec<ConflictId>,
+	pub responses: Vec<Message>,
+}
+
+impl Job {
+	pub(crate) fn run(self, voter: &mut Voter, tick: usize) -> JobResult {
+		match self {
+			Job::ProcessActions => JobResult::Sent(voter.process_actions(tick)),
+			Job::HandleRequests(requests) => JobResult::Requests(
+				requests
+					.into_iter()
+					.map(|message| handle_request(voter, message, tick))
+					.collect(),
+			),
+			Job::HandleResponses(responses) => JobResult::Responses(
+				responses
+					.into_iter()
+					.map(|message| handle_response(voter, message, tick))
+					.collect(),
+			),
+		}
+	}
+}
+
+fn handle_request(voter: &mut Voter, message: Message, tick: usize) -> HandledRequest {
+	let Message {
+		sender,
+		receiver,
+		content,
+	} = message;
+	let request = content.request().unwrap();
+	let instances_before: BTreeSet<ConflictId> = voter.instances().map(|(id, _)| *id).collect();
+	let responses = voter
+		.handle_request((sender, request.clone()), tick)
+		.into_iter()
+		.map(|(response_receiver, res)| Message {
+			receiver: response_receiver,
+			sender: receiver.clone(),
+			content: Payload::Response(res),
+		})
+		.collect();
+	HandledRequest {
+		summary: content.summary(),
+		conflicts_started: voter
+			.instances()
+			.map(|(id, _)| *id)
+			.filter(|id| !instances_before.contains(id))
+			.collect(),
+		responses,
+	}
+}
+
+fn handle_response(voter: &mut Voter, message: Message, tick: usize) -> Vec<EquivocationDetected> {
+	let Message {
+		sender, content, ..
+	} = message;
+	let response = content.response().unwrap();
+	let detected_before = voter.equivocations_detected().len();
+	voter.handle_response((sender, response.clone()), tick);
+	voter
+		.equivocations_detected()
+		.into_iter()
+		.skip(detected_before)
+		.collect()
+}
+
+struct VoterThread {
+	jobs: Sender<(Voter, Job, usize)>,
+	done: Receiver<(Voter, JobResult)>,
+	handle: Option<JoinHandle<()>>,
+}
+
+/// A long-lived thread for each voter, which the voter is moved to whenever it has work.
+pub(crate) struct VoterThreads {
+	threads: BTreeMap<VoterId, VoterThread>,
+}
+
+impl VoterThreads {
+	pub(crate) fn spawn<'a>(ids: impl Iterator<Item = &'a VoterId>) -> Self {
+		let threads = ids
+			.map(|id| {
+				let (jobs, job_receiver) = unbounded::<(Voter, Job, usize)>();
+				let (done_sender, done) = unbounded();
+				let handle = thread::Builder::new()
+					.name(id.clone())
+					.spawn(move || {
+						for (mut voter, job, tick) in job_receiver {
+							let result = job.run(&mut voter, tick);
+							if done_sender.send((voter, result)).is_err() {
+								break;
+							}
+						}
+					})
+					.expect("spawning a voter thread");
+				let thread = VoterThread {
+					jobs,
+					done,
+					handle: Some(handle),
+				};
+				(id.clone(), thread)
+			})
+			.collect();
+		Self { threads }
+	}
+
+	/// Run each job on the thread of its voter, and hand back the voters together with what came
+	/// of their jobs, in the order of their ids. A panic on a voter thread is resumed here.
+	pub(crate) fn run(&mut self, work: Vec<(Voter, Job)>, tick: usize) -> Vec<(Voter, JobResult)> {
+		let mut waiting_on = Vec::new();
+		for (voter, job) in work {
+			let id = voter.id.clone();
+			self.threads[&id]
+				.jobs
+				.send((voter, job, tick))
+				.expect("voter threads live as long as the world");
+			waiting_on.push(id);
+		}
+
+		let mut select = Select::new();
+		for id in &waiting_on {
+			select.recv(&self.threads[id].done);
+		}
+		let mut finished = Vec::with_capacity(waiting_on.len());
+		while finished.len() < waiting_on.len() {
+			let operation = select.select();
+			let index = operation.index();
+			let id = &waiting_on[index];
+			match operation.recv(&self.threads[id].done) {
+				Ok(done) => {
+					select.remove(index);
+					finished.push(done);
+				}
+				// The thread only hangs up if the voter panicked.
+				Err(_) => {
+					let handle = self.threads.get_mut(id).unwrap().handle.take();
+					if let Some(Err(payload)) = handle.map(JoinHandle::join) {
+						panic::resume_unwind(payload);
+					}
+					panic!("thread of voter {} stopped", id);
+				}
+			}
+		}
+		finished.sort_by(|(a, _), (b, _)| a.id.cmp(&b.id));
+		finished
+	}
+}
+
+impl Drop for VoterThreads {
+	fn drop(&mut self) {
+		// Hanging up on the threads lets them run out, after which they can be joined.
+		let handles: Vec<_> = std::mem::take(&mut self.threads)
+			.into_values()
+			.filter_map(|thread| thread.handle)
+			.collect();
+		for handle in handles {
+			// A thread that panicked has already been reported by the world, or is being.
+			let _ = handle.join();
+		}
+	}
+}
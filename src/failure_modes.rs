@@ -0,0 +1,495 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Catalogue of the inputs that used to bring the simulation down with a panic, each fed through
+//! the public API to check that it now comes out as an error or in a report instead.
+//!
+//! Each input is fed through both in strict mode and in the default mode. Inputs from peers must
+//! never panic, while internal inconsistencies only panic outside of strict mode.
+
+use crate::{
+	action::Action,
+	behaviour::Adversary,
+	fixtures,
+	message::{Payload, Request, Response},
+	protocol::{
+		AccountableSafety, QueryResponse, ReplyKinds, SignedConflictStatement, VoteCommitment,
+	},
+	report::Verdict,
+	scenario::{RoundSpec, ScenarioBuilder},
+	voter::{Voter, VoterId},
+	voting::{check_query_reply_is_valid, Commit, Precommit, VoterSet, VotingRounds},
+	world::WorldBuilder,
+};
+use std::{any::Any, panic};
+
+/// An input that used to panic, and how to feed it through the public API.
+pub struct FailureMode {
+	pub name: &'static str,
+	pub description: &'static str,
+	// Whether the input can only come from a bug of our own, which is fatal outside of strict mode.
+	pub internal: bool,
+	// Feeds the input through, in strict mode or not, and describes the error or report it came
+	// out as, if any.
+	trigger: fn(bool) -> Option<String>,
+}
+
+/// What came of feeding the input of a failure mode through the public API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Handling {
+	// Came out as the error or report described.
+	Reported(String),
+	// Neither panicked nor came out anywhere.
+	Unreported,
+	Panicked(String),
+}
+
+impl FailureMode {
+	pub fn run(&self, strict: bool) -> Handling {
+		match panic::catch_unwind(|| (self.trigger)(strict)) {
+			Ok(Some(report)) => Handling::Reported(report),
+			Ok(None) => Handling::Unreported,
+			Err(payload) => Handling::Panicked(panic_message(payload.as_ref())),
+		}
+	}
+
+	// Whether the input was handled the way it should be in the mode.
+	fn handled(&self, strict: bool, handling: &Handling) -> bool {
+		match handling {
+			Handling::Reported(_) => true,
+			Handling::Panicked(_) => self.internal && !strict,
+			Handling::Unreported => false,
+		}
+	}
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+	payload
+		.downcast_ref::<&str>()
+		.map(|message| message.to_string())
+		.or_else(|| payload.downcast_ref::<String>().cloned())
+		.unwrap_or_default()
+}
+
+/// Every failure mode known.
+pub fn catalogue() -> Vec<FailureMode> {
+	vec![
+		FailureMode {
+			name: "unknown_receiver",
+			description: "the world routes a message to a voter that doesn't exist",
+			internal: true,
+			trigger: unknown_receiver,
+		},
+		FailureMode {
+			name: "missing_round_data",
+			description: "a voter is asked about prevotes in a round it has no history for",
+			internal: false,
+			trigger: missing_round_data,
+		},
+		FailureMode {
+			name: "duplicate_finalization",
+			description: "a block is finalized again with a different commit",
+			internal: false,
+			trigger: duplicate_finalization,
+		},
+		FailureMode {
+			name: "unknown_block_in_ancestry",
+			description: "blocks are checked for conflict when one of them is unknown",
+			internal: false,
+			trigger: unknown_block_in_ancestry,
+		},
+		FailureMode {
+			name: "votes_for_unknown_block",
+			description: "a verifier is sent an explanation with votes for a block it doesn't know",
+			internal: false,
+			trigger: votes_for_unknown_block,
+		},
+		FailureMode {
+			name: "equivocation_within_response",
+			description: "a response has more than one vote from the same voter",
+			internal: false,
+			trigger: equivocation_within_response,
+		},
+		FailureMode {
+			name: "no_valid_explanation",
+			description: "a responder has no history that explains the estimate it is asked about",
+			internal: false,
+			trigger: no_valid_explanation,
+		},
+		FailureMode {
+			name: "several_valid_explanations",
+			description: "a responder has more than one history that explains the estimate",
+			internal: false,
+			trigger: several_valid_explanations,
+		},
+		FailureMode {
+			name: "unsolicited_answer",
+			description: "a voter is sent an answer to a query it never sent",
+			internal: false,
+			trigger: unsolicited_answer,
+		},
+		FailureMode {
+			name: "wrong_kind_reply",
+			description: "a prevote query is answered with precommits",
+			internal: false,
+			trigger: wrong_kind_reply,
+		},
+	]
+}
+
+/// Run every failure mode in strict mode or not, and return those that weren't handled
+/// gracefully, with what came of them.
+pub fn unhandled(strict: bool) -> Vec<(&'static str, Handling)> {
+	catalogue()
+		.into_iter()
+		.map(|mode| (mode.run(strict), mode))
+		.filter(|(handling, mode)| !mode.handled(strict, handling))
+		.map(|(handling, mode)| (mode.name, handling))
+		.collect()
+}
+
+// A voter of the paper example, that knows the keys of the others.
+fn paper_voter(id: &str, strict: bool) -> Voter {
+	let mut voter = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove(id)
+		.expect("a voter of the paper example");
+	voter.strict = strict;
+	voter.key_registry = fixtures::paper_key_registry();
+	voter
+}
+
+// What the voter made of the input: a protocol bug if it found one, or else its last note.
+fn voter_report(voter: &mut Voter) -> Option<String> {
+	match voter.protocol_bugs.first() {
+		Some(bug) => Some(bug.to_string()),
+		None => voter.take_notes().pop(),
+	}
+}
+
+// A request from Dave, backed by his statement of the conflict in the paper example.
+fn request_from_dave(request: fn(SignedConflictStatement) -> Request) -> (VoterId, Request) {
+	let statement = fixtures::paper_conflict_statement("Dave").sign(&fixtures::paper_key("Dave"));
+	("Dave".to_string(), request(statement))
+}
+
+fn unknown_receiver(strict: bool) -> Option<String> {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters
+		.get_mut("Dave")?
+		.add_actions(vec![(5, Action::SendBlock("Eve".to_string(), 1))]);
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_strict(strict)
+		.with_max_ticks(100)
+		.build();
+	world.run_until_settled();
+	let bug = world.protocol_bugs().first()?.summary.clone();
+	Some(bug)
+}
+
+fn missing_round_data(strict: bool) -> Option<String> {
+	let mut carol = paper_voter("Carol", strict);
+	carol.voting_rounds = VotingRounds::new();
+	carol.handle_request(
+		request_from_dave(|statement| Request::WhichPrevotesSeenInRound(2, statement)),
+		0,
+	);
+	voter_report(&mut carol)
+}
+
+fn duplicate_finalization(_strict: bool) -> Option<String> {
+	let commit = |voters: [&str; 3]| {
+		Commit::new(
+			2,
+			voters
+				.iter()
+				.map(|voter| Precommit::new(2, 2, *voter))
+				.collect(),
+		)
+	};
+	let mut chain = fixtures::paper_chain();
+	chain
+		.try_finalize_block(2, 2, commit(["Alice", "Bob", "Carol"]))
+		.ok()?;
+	chain
+		.try_finalize_block(2, 2, commit(["Alice", "Bob", "Dave"]))
+		.err()
+		.map(|error| error.to_string())
+}
+
+fn unknown_block_in_ancestry(_strict: bool) -> Option<String> {
+	fixtures::paper_chain()
+		.try_blocks_conflict(2, 42)
+		.err()
+		.map(|error| error.to_string())
+}
+
+fn votes_for_unknown_block(_strict: bool) -> Option<String> {
+	let chain = fixtures::paper_chain();
+	let statement = fixtures::paper_conflict_statement("Carol");
+	let mut instance =
+		AccountableSafety::start(2, 2, statement.commit_for_block_not_included, 8, 4, &chain)
+			.ok()?;
+	let signers = ["Alice", "Bob", "Dave"];
+	instance.start_query_round(4, signers.iter().map(|voter| voter.to_string()).collect());
+	let precommits: Vec<_> = signers
+		.iter()
+		.map(|voter| Precommit::new(3, 42, *voter))
+		.collect();
+	let commitment = VoteCommitment::new("Alice".to_string(), 3, 0, 0, &[], &precommits)
+		.sign(&fixtures::paper_key("Alice"));
+	let answer = QueryResponse::Precommits(precommits);
+	instance
+		.add_response(
+			4,
+			"Alice".to_string(),
+			answer.clone(),
+			commitment.revealing(0, &answer),
+			&chain,
+		)
+		.ok()?;
+	instance
+		.verdicts()
+		.into_iter()
+		.flat_map(|round| round.verdicts)
+		.find(|(voter, verdict)| voter == "Alice" && *verdict != Verdict::Missing)
+		.map(|(voter, verdict)| format!("{}: {}", voter, verdict))
+}
+
+fn equivocation_within_response(_strict: bool) -> Option<String> {
+	let voters = VoterSet::new(fixtures::PAPER_EXAMPLE_VOTERS).voter_ids();
+	let response = QueryResponse::Precommits(vec![
+		Precommit::new(2, 1, "Alice"),
+		Precommit::new(2, 5, "Alice"),
+		Precommit::new(2, 1, "Bob"),
+	]);
+	check_query_reply_is_valid(&response, 2, &voters, &fixtures::paper_chain())
+		.ok()?
+		.map(|_| "response rejected as invalid".to_string())
+}
+
+fn no_valid_explanation(strict: bool) -> Option<String> {
+	// Carol only has the history in which block 2 was finalized, so she can't explain why the
+	// estimate of round 3 wouldn't include it.
+	let mut carol = paper_voter("Carol", strict);
+	carol.handle_request(
+		request_from_dave(|statement| {
+			Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement)
+		}),
+		0,
+	);
+	voter_report(&mut carol)
+}
+
+fn several_valid_explanations(strict: bool) -> Option<String> {
+	// Alice has two histories of round 2, in both of which block 1 has all the precommits.
+	let blocks = [
+		(1, 0),
+		(2, 1),
+		(3, 2),
+		(4, 3),
+		(5, 1),
+		(6, 5),
+		(7, 6),
+		(8, 7),
+	];
+	let round = |votes: &[(u32, &str)], precommits: &[(u32, &str)]| {
+		vec![RoundSpec::new(2).prevote(votes).precommit(precommits)]
+	};
+	let mut voters = ScenarioBuilder::new(fixtures::PAPER_EXAMPLE_VOTERS)
+		.with_history(
+			"x",
+			round(
+				&[(1, "Alice"), (1, "Bob"), (1, "Carol")],
+				&[(1, "Alice"), (1, "Bob"), (1, "Carol")],
+			),
+		)
+		.with_history(
+			"y",
+			round(
+				&[(1, "Alice"), (5, "Bob"), (5, "Dave")],
+				&[(1, "Alice"), (1, "Bob"), (1, "Dave")],
+			),
+		)
+		.with_voter(
+			"Alice",
+			&blocks,
+			&["x", "y"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.build()
+		.ok()?;
+	let mut alice = voters.remove("Alice")?;
+	alice.strict = strict;
+	alice.key_registry = fixtures::paper_key_registry();
+	let (_, response) = alice
+		.handle_request(
+			request_from_dave(|statement| {
+				Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement)
+			}),
+			0,
+		)
+		.pop()?;
+	Some(Payload::Response(response).summary())
+}
+
+fn unsolicited_answer(strict: bool) -> Option<String> {
+	let mut carol = paper_voter("Carol", strict);
+	carol.handle_response(("Alice".to_string(), Response::DontKnowRound(3)), 0);
+	voter_report(&mut carol)
+}
+
+fn wrong_kind_reply(_strict: bool) -> Option<String> {
+	let chain = fixtures::paper_chain();
+	let statement = fixtures::paper_conflict_statement("Carol");
+	let mut instance =
+		AccountableSafety::start(2, 2, statement.commit_for_block_not_included, 8, 4, &chain)
+			.ok()?;
+	instance.start_prevote_query(2, vec!["Dave".to_string()]);
+	let precommits = vec![Precommit::new(2, 1, "Dave")];
+	let commitment = VoteCommitment::new("Dave".to_string(), 2, 0, 0, &[], &precommits)
+		.sign(&fixtures::paper_key("Dave"));
+	let answer = QueryResponse::Precommits(precommits);
+	instance
+		.add_prevote_response(
+			2,
+			"Dave".to_string(),
+			answer.clone(),
+			commitment.revealing(0, &answer),
+		)
+		.ok()?;
+	instance
+		.verdicts()
+		.into_iter()
+		.flat_map(|round| round.verdicts)
+		.find(|(voter, verdict)| voter == "Dave" && *verdict != Verdict::Missing)
+		.map(|(voter, verdict)| format!("{}: {}", voter, verdict))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(name: &str, strict: bool) -> Handling {
+		catalogue()
+			.into_iter()
+			.find(|mode| mode.name == name)
+			.unwrap()
+			.run(strict)
+	}
+
+	// Inputs from peers are handled the same whether in strict mode or not.
+	fn run_in_both_modes(name: &str) -> Handling {
+		let handling = run(name, true);
+		assert_eq!(run(name, false), handling, "{}", name);
+		handling
+	}
+
+	fn reported(report: &str) -> Handling {
+		Handling::Reported(report.to_string())
+	}
+
+	#[test]
+	fn unknown_receiver() {
+		let bug = "HereAreBlocks(1 blocks) from Dave to unknown voter Eve";
+		assert_eq!(run("unknown_receiver", true), reported(bug));
+		assert_eq!(
+			run("unknown_receiver", false),
+			Handling::Panicked(bug.to_string())
+		);
+	}
+
+	#[test]
+	fn missing_round_data() {
+		assert_eq!(
+			run_in_both_modes("missing_round_data"),
+			reported("ignoring query about prevotes in round 2, which we have no votes for")
+		);
+	}
+
+	#[test]
+	fn duplicate_finalization() {
+		assert_eq!(
+			run_in_both_modes("duplicate_finalization"),
+			reported("block 2 is already finalized by another commit")
+		);
+	}
+
+	#[test]
+	fn unknown_block_in_ancestry() {
+		assert_eq!(
+			run_in_both_modes("unknown_block_in_ancestry"),
+			reported("unknown block 42")
+		);
+	}
+
+	#[test]
+	fn votes_for_unknown_block() {
+		assert_eq!(
+			run_in_both_modes("votes_for_unknown_block"),
+			reported("Alice: invalid (votes for unknown block 42)")
+		);
+	}
+
+	#[test]
+	fn equivocation_within_response() {
+		assert_eq!(
+			run_in_both_modes("equivocation_within_response"),
+			reported("response rejected as invalid")
+		);
+	}
+
+	#[test]
+	fn no_valid_explanation() {
+		assert_eq!(
+			run_in_both_modes("no_valid_explanation"),
+			reported("no valid explanation for round 3 not including block 2, not answering")
+		);
+	}
+
+	#[test]
+	fn several_valid_explanations() {
+		// The first history that explains the estimate is the one given.
+		assert_eq!(
+			run_in_both_modes("several_valid_explanations"),
+			reported("ExplainEstimate(round: 3, block: 2, 3 votes)")
+		);
+	}
+
+	#[test]
+	fn unsolicited_answer() {
+		assert_eq!(
+			run_in_both_modes("unsolicited_answer"),
+			reported(
+				"ignoring answer from Alice about round 3 that no protocol instance asked for"
+			)
+		);
+	}
+
+	#[test]
+	fn wrong_kind_reply() {
+		assert_eq!(
+			run_in_both_modes("wrong_kind_reply"),
+			reported("Dave: invalid (wrong kind of votes)")
+		);
+	}
+
+	#[test]
+	fn every_failure_mode_is_handled() {
+		assert_eq!(unhandled(true), vec![]);
+		assert_eq!(unhandled(false), vec![]);
+	}
+}
@@ -0,0 +1,580 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Ready-made scenarios, for tests and for experimenting with the protocol outside of them.
+//!
+//! Each constructor returns the voters of the scenario, with their behaviour when queried set to
+//! the one given, and with the actions needed to kick off the simulation already scheduled.
+
+use crate::{
+	action::Action,
+	behaviour::Adversary,
+	block::BlockNumber,
+	chain::Chain,
+	crypto::{KeyRegistry, SecretKey},
+	network::Rng,
+	protocol::ConflictStatement,
+	scenario::{Expected, RoundSpec, ScenarioBuilder},
+	schema::{GuaranteeState, OffenceKind},
+	supermajority::{self, Weight},
+	voter::{Voter, VoterId},
+	voting::{Commit, Precommit},
+};
+use std::collections::BTreeMap;
+
+/// The voters of the example in the paper.
+pub const PAPER_EXAMPLE_VOTERS: &[&str] = &["Alice", "Bob", "Carol", "Dave"];
+
+const PAPER_KEY_HOLDERS: &[&str] = &["Alice", "Bob", "Carol", "Dave", "Mallory"];
+const PAPER_KEY_SEED: u64 = 0x0070_6170_6572;
+
+const COMMON_BLOCKS: &[(BlockNumber, BlockNumber)] = &[(1, 0)];
+const FORK_A_BLOCKS: &[(BlockNumber, BlockNumber)] = &[(2, 1), (3, 2), (4, 3)];
+const FORK_B_BLOCKS: &[(BlockNumber, BlockNumber)] = &[(5, 1), (6, 5), (7, 6), (8, 7)];
+const FORK_C_BLOCKS: &[(BlockNumber, BlockNumber)] = &[(9, 1), (10, 9)];
+
+/// The chain of the paper example, with all the blocks on both forks.
+pub fn paper_chain() -> Chain {
+	Chain::new_from(&[COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS].concat())
+}
+
+/// The conflict between the commits of the paper example, as stated by the detector, before
+/// signing.
+pub fn paper_conflict_statement(detector: &str) -> ConflictStatement {
+	let commit = |round, block, voters: [&str; 3]| {
+		Commit::new(
+			block,
+			voters
+				.iter()
+				.map(|voter| Precommit::new(round, block, *voter))
+				.collect(),
+		)
+	};
+	ConflictStatement {
+		detector: detector.to_string(),
+		round_for_block_not_included: 2,
+		commit_for_block_not_included: commit(2, 2, ["Alice", "Bob", "Carol"]),
+		round_for_new_block: 4,
+		commit_for_new_block: commit(4, 8, ["Alice", "Bob", "Dave"]),
+	}
+}
+
+/// The secret key of a voter of the paper example, or of Mallory who isn't one, for tests that
+/// sign in their name outside of a world. Worlds give voters keys of their own.
+pub fn paper_key(voter: &str) -> SecretKey {
+	let index = PAPER_KEY_HOLDERS
+		.iter()
+		.position(|holder| *holder == voter)
+		.expect("a voter of the paper example, or Mallory");
+	let mut rng = Rng::new(PAPER_KEY_SEED);
+	(0..index).for_each(|_| {
+		rng.next_u64();
+	});
+	SecretKey::generate(&mut rng)
+}
+
+/// The public keys of the voters of the paper example, and of Mallory who isn't one.
+pub fn paper_key_registry() -> KeyRegistry {
+	let mut key_registry = KeyRegistry::new();
+	for voter in PAPER_KEY_HOLDERS {
+		key_registry.register(voter.to_string(), paper_key(voter).public());
+	}
+	key_registry
+}
+
+// Give the voters the keys of `paper_key`, so that they match `paper_key_registry`.
+fn with_paper_keys(mut voters: BTreeMap<VoterId, Voter>) -> BTreeMap<VoterId, Voter> {
+	for (id, voter) in voters.iter_mut() {
+		voter.secret_key = paper_key(id);
+	}
+	voters
+}
+
+/// The example from the paper: Alice and Bob vote on both forks, so that block 2 is finalized with
+/// Carol in round 2 and block 8 with Dave in round 4. Dave broadcasts his commits at tick 10.
+pub fn paper_example(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	with_paper_keys(
+		paper_example_with_histories(behaviour, paper_history_a(), paper_history_b())
+			.build()
+			.expect("the paper example is a valid scenario"),
+	)
+}
+
+/// Same forks as the paper example, but Alice and Bob only equivocate on their prevotes.
+pub fn paper_example_with_prevote_equivocations(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	let history_a = paper_history_a().into_iter().take(1).collect();
+	let mut history_b = paper_history_b();
+	history_b[0] = RoundSpec::new(2)
+		.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+		.precommit(&[(2, "Alice"), (2, "Bob"), (1, "Dave")]);
+	paper_example_with_histories(behaviour, history_a, history_b)
+		.build()
+		.expect("the paper example is a valid scenario")
+}
+
+/// Alice votes on both forks, but with only a single equivocator the second fork can't gather a
+/// supermajority and nothing is finalized on it.
+pub fn single_equivocator(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	paper_example_with_histories(
+		behaviour,
+		single_equivocator_history_a(),
+		single_equivocator_history_b(),
+	)
+	.build()
+	.expect("the paper example is a valid scenario")
+}
+
+/// A control with no misbehaviour: everyone knows about both forks of the paper example, but only
+/// the first is ever voted for, with block 2 finalized by all four voters in round 2. Dave
+/// broadcasts his commits at tick 10, which agree with everyone else's.
+pub fn one_sided_fork(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	one_sided_fork_scenario(behaviour)
+		.build()
+		.expect("a one sided fork is a valid scenario")
+}
+
+/// A control with no misbehaviour: everyone finalizes the same blocks, and the commits broadcast by
+/// Dave at tick 10 are broadcast again by Alice at tick 40 and Carol at tick 60, long after everyone
+/// has them.
+pub fn duplicate_commits(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	duplicate_commits_scenario(behaviour)
+		.build()
+		.expect("duplicate commits are a valid scenario")
+}
+
+/// Seven voters where Alice, Bob, Carol and Dave vote on three forks: block 2 is finalized with Eve
+/// in round 2, block 8 with Ferdie in round 4 and block 10 with Grace in round 6. Ferdie and Grace
+/// broadcast their commits at tick 10.
+///
+/// Everyone knows about the blocks on all three forks, since the estimates explained in queries
+/// can be on any of them.
+pub fn three_way_fork(behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	three_way_fork_scenario(behaviour)
+		.build()
+		.expect("the three way fork is a valid scenario")
+}
+
+fn three_way_fork_scenario(behaviour: Adversary) -> ScenarioBuilder {
+	let byzantine = ["Alice", "Bob", "Carol", "Dave"];
+	let names = ["Alice", "Bob", "Carol", "Dave", "Eve", "Ferdie", "Grace"];
+	// Each fork has a history running up to the last round in which anything is finalized, where
+	// the byzantine voters vote along with the honest voter of the fork.
+	let history = |honest: &str, head: BlockNumber, target: BlockNumber, finalized_in| {
+		(2..=6)
+			.map(|round| {
+				let block = if round < finalized_in { 1 } else { target };
+				let spec = RoundSpec::new(round)
+					.prevote(&votes(&byzantine, block, &[honest], block.max(head)))
+					.precommit(&votes(&byzantine, block, &[honest], block));
+				if round == finalized_in {
+					spec.finalize(target)
+				} else {
+					spec
+				}
+			})
+			.collect::<Vec<_>>()
+	};
+
+	let all_blocks = [COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS, FORK_C_BLOCKS].concat();
+	let mut builder = ScenarioBuilder::new(&names)
+		.with_history("common", common_history(&names))
+		.with_history("a", history("Eve", 2, 2, 2))
+		.with_history("b", history("Ferdie", 5, 8, 4))
+		.with_history("c", history("Grace", 9, 10, 6));
+	for voter in &byzantine {
+		builder = builder.with_voter(
+			voter,
+			&all_blocks,
+			&["common", "a", "b", "c"],
+			Some(behaviour.clone()),
+		);
+	}
+	builder
+		.with_voter(
+			"Eve",
+			&all_blocks,
+			&["common", "a"],
+			Some(behaviour.clone()),
+		)
+		.with_voter(
+			"Ferdie",
+			&all_blocks,
+			&["common", "b"],
+			Some(behaviour.clone()),
+		)
+		.with_voter(
+			"Grace",
+			&all_blocks,
+			&["common", "c"],
+			Some(behaviour.clone()),
+		)
+		.with_action("Ferdie", 10, Action::BroadcastCommits)
+		.with_action("Grace", 10, Action::BroadcastCommits)
+}
+
+/// Seven voters where Alice, Bob, Carol and Dave vote on both forks: block 2 is finalized with Eve
+/// in round 2 and block 8 with Ferdie in round 4, when Grace prevotes for block 8 but her precommit
+/// doesn't make it into the commit. Eve sends her commit for block 2 to Grace at tick 10, and only
+/// Grace detects the conflict.
+///
+/// Everyone who precommitted for block 8 ignores Grace's queries, so she only gets an answer if she
+/// also asks the voters she saw prevote for it.
+pub fn unresponsive_precommitters() -> BTreeMap<VoterId, Voter> {
+	let byzantine = ["Alice", "Bob", "Carol", "Dave"];
+	let names = ["Alice", "Bob", "Carol", "Dave", "Eve", "Ferdie", "Grace"];
+	let history_a = (2..=4)
+		.map(|round| {
+			let spec = RoundSpec::new(round)
+				.prevote(&votes(&byzantine, 2, &["Eve"], 2))
+				.precommit(&votes(&byzantine, 2, &["Eve"], 2));
+			if round == 2 {
+				spec.finalize(2)
+			} else {
+				spec
+			}
+		})
+		.collect();
+	let history_b = vec![
+		RoundSpec::new(2)
+			.prevote(&votes(&byzantine, 1, &["Ferdie", "Grace"], 5))
+			.precommit(&votes(&byzantine, 1, &["Ferdie", "Grace"], 1)),
+		RoundSpec::new(3)
+			.prevote(&votes(&byzantine, 1, &["Ferdie", "Grace"], 5))
+			.precommit(&votes(&byzantine, 1, &["Ferdie", "Grace"], 1)),
+		RoundSpec::new(4)
+			.prevote(&votes(&byzantine, 8, &["Ferdie", "Grace"], 8))
+			.precommit(&votes(&byzantine, 8, &["Ferdie"], 8))
+			.finalize(8),
+	];
+	let commit_for_block_2 = Commit::new(
+		2,
+		votes(&byzantine, 2, &["Eve"], 2)
+			.into_iter()
+			.map(|(block, voter)| Precommit::new(2, block, voter))
+			.collect(),
+	);
+
+	let all_blocks = [COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS].concat();
+	let unresponsive = Adversary::IgnoreQueriesFrom(vec!["Grace".into()]);
+	let mut builder = ScenarioBuilder::new(&names)
+		.with_history("common", common_history(&names))
+		.with_history("a", history_a)
+		.with_history("b", history_b);
+	for voter in &byzantine {
+		builder = builder.with_voter(
+			voter,
+			&all_blocks,
+			&["common", "a", "b"],
+			Some(unresponsive.clone()),
+		);
+	}
+	builder
+		.with_voter(
+			"Eve",
+			&all_blocks,
+			&["common", "a"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.with_voter(
+			"Ferdie",
+			&all_blocks,
+			&["common", "b"],
+			Some(unresponsive.clone()),
+		)
+		.with_voter(
+			"Grace",
+			&all_blocks,
+			&["common", "b"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.with_action(
+			"Eve",
+			10,
+			Action::SendCommit("Grace".to_string(), 2, commit_for_block_2),
+		)
+		.build()
+		.expect("unresponsive precommitters is a valid scenario")
+}
+
+/// The paper example scaled up to `num_voters` voters, named `voter0`, `voter1` and so on. Just
+/// enough of them to finalize both forks equivocate, and the honest voters are split between the
+/// forks. The last honest voter broadcasts their commits at tick 10.
+///
+/// Needs at least four voters for there to be an honest voter on each fork.
+pub fn large_voterset(num_voters: usize, behaviour: Adversary) -> BTreeMap<VoterId, Voter> {
+	assert!(num_voters >= 4, "need at least four voters to fork");
+	let names: Vec<String> = (0..num_voters).map(|i| format!("voter{}", i)).collect();
+	let threshold = supermajority::threshold(num_voters as Weight) as usize;
+	let num_byzantine = 2 * threshold - num_voters;
+	let (byzantine, honest) = names.split_at(num_byzantine);
+	let (honest_a, honest_b) = honest.split_at(honest.len() / 2);
+
+	let history_a = (2..=4)
+		.map(|round| {
+			let spec = RoundSpec::new(round)
+				.prevote(&votes(byzantine, 4, honest_a, 2))
+				.precommit(&votes(byzantine, 2, honest_a, 2));
+			if round == 2 {
+				spec.finalize(2)
+			} else {
+				spec
+			}
+		})
+		.collect();
+	let history_b = vec![
+		RoundSpec::new(2)
+			.prevote(&votes(byzantine, 1, honest_b, 5))
+			.precommit(&votes(byzantine, 1, honest_b, 1)),
+		RoundSpec::new(3)
+			.prevote(&votes(byzantine, 1, honest_b, 5))
+			.precommit(&votes(byzantine, 1, honest_b, 1)),
+		RoundSpec::new(4)
+			.prevote(&votes(byzantine, 8, honest_b, 8))
+			.precommit(&votes(byzantine, 8, honest_b, 8))
+			.finalize(8),
+	];
+
+	let all_blocks = [COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS].concat();
+	let blocks_a = [COMMON_BLOCKS, FORK_A_BLOCKS].concat();
+	let blocks_b = [COMMON_BLOCKS, FORK_B_BLOCKS].concat();
+	let mut builder = ScenarioBuilder::new(&names)
+		.with_history("common", common_history(&names))
+		.with_history("a", history_a)
+		.with_history("b", history_b);
+	for voter in byzantine {
+		builder = builder.with_voter(
+			voter,
+			&all_blocks,
+			&["common", "a", "b"],
+			Some(behaviour.clone()),
+		);
+	}
+	for voter in honest_a {
+		builder = builder.with_voter(voter, &blocks_a, &["common", "a"], Some(behaviour.clone()));
+	}
+	for voter in honest_b {
+		builder = builder.with_voter(voter, &blocks_b, &["common", "b"], Some(behaviour.clone()));
+	}
+	builder
+		.with_action(&names[num_voters - 1], 10, Action::BroadcastCommits)
+		.build()
+		.expect("a large voter set is a valid scenario")
+}
+
+fn one_sided_fork_scenario(behaviour: Adversary) -> ScenarioBuilder {
+	let all_blocks = [COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS].concat();
+	let mut builder = ScenarioBuilder::new(PAPER_EXAMPLE_VOTERS)
+		.with_history("common", common_history(PAPER_EXAMPLE_VOTERS))
+		.with_history("a", unanimous_history());
+	for name in PAPER_EXAMPLE_VOTERS {
+		builder = builder.with_voter(name, &all_blocks, &["common", "a"], Some(behaviour.clone()));
+	}
+	builder.with_action("Dave", 10, Action::BroadcastCommits)
+}
+
+fn duplicate_commits_scenario(behaviour: Adversary) -> ScenarioBuilder {
+	let blocks = [COMMON_BLOCKS, FORK_A_BLOCKS].concat();
+	let mut builder = ScenarioBuilder::new(PAPER_EXAMPLE_VOTERS)
+		.with_history("common", common_history(PAPER_EXAMPLE_VOTERS))
+		.with_history("a", unanimous_history());
+	for name in PAPER_EXAMPLE_VOTERS {
+		builder = builder.with_voter(name, &blocks, &["common", "a"], Some(behaviour.clone()));
+	}
+	builder
+		.with_action("Dave", 10, Action::BroadcastCommits)
+		.with_action("Alice", 40, Action::BroadcastCommits)
+		.with_action("Carol", 60, Action::BroadcastCommits)
+}
+
+fn paper_example_with_histories(
+	behaviour: Adversary,
+	history_a: Vec<RoundSpec>,
+	history_b: Vec<RoundSpec>,
+) -> ScenarioBuilder {
+	let all_blocks = [COMMON_BLOCKS, FORK_A_BLOCKS, FORK_B_BLOCKS].concat();
+	ScenarioBuilder::new(PAPER_EXAMPLE_VOTERS)
+		.with_history("common", common_history(PAPER_EXAMPLE_VOTERS))
+		.with_history("a", history_a)
+		.with_history("b", history_b)
+		.with_voter(
+			"Alice",
+			&all_blocks,
+			&["common", "a", "b"],
+			Some(behaviour.clone()),
+		)
+		.with_voter(
+			"Bob",
+			&all_blocks,
+			&["common", "a", "b"],
+			Some(behaviour.clone()),
+		)
+		.with_voter(
+			"Carol",
+			&[COMMON_BLOCKS, FORK_A_BLOCKS].concat(),
+			&["common", "a"],
+			Some(behaviour.clone()),
+		)
+		.with_voter(
+			"Dave",
+			&[COMMON_BLOCKS, FORK_B_BLOCKS].concat(),
+			&["common", "b"],
+			Some(behaviour.clone()),
+		)
+		// Kick off the simulation by having one voter broadcast all their commits, revealing the
+		// conflicting finalized blocks to the other (honest) voters.
+		.with_action("Dave", 10, Action::BroadcastCommits)
+}
+
+/// The ready-made scenarios annotated with the outcome they are expected to come to, by name, for
+/// checking them all in one go.
+pub fn registered() -> Vec<(&'static str, ScenarioBuilder)> {
+	vec![
+		(
+			"paper_example",
+			paper_example_with_histories(
+				Adversary::ReturnPrecommits,
+				paper_history_a(),
+				paper_history_b(),
+			)
+			.with_expected(
+				Expected::default()
+					.offenders(&["Alice", "Bob"])
+					.offences(&[
+						OffenceKind::PrecommitEquivocation,
+						OffenceKind::InconsistentTestimony,
+					])
+					.guarantee(GuaranteeState::Reached)
+					.settles_within(300),
+			),
+		),
+		(
+			"single_equivocator",
+			paper_example_with_histories(
+				Adversary::ReturnPrecommits,
+				single_equivocator_history_a(),
+				single_equivocator_history_b(),
+			)
+			.with_expected(
+				Expected::default()
+					.offenders(&[])
+					.guarantee(GuaranteeState::NoConflict)
+					.settles_within(50),
+			),
+		),
+		(
+			"one_sided_fork",
+			one_sided_fork_scenario(Adversary::ReturnPrecommits)
+				.with_expected(Expected::no_misbehaviour().settles_within(50)),
+		),
+		(
+			"duplicate_commits",
+			duplicate_commits_scenario(Adversary::ReturnPrecommits)
+				.with_expected(Expected::no_misbehaviour().settles_within(100)),
+		),
+		(
+			"three_way_fork",
+			three_way_fork_scenario(Adversary::ReturnPrecommits).with_expected(
+				Expected::default()
+					.offenders(&["Alice", "Bob", "Carol", "Dave"])
+					.offences(&[
+						OffenceKind::PrecommitEquivocation,
+						OffenceKind::InvalidResponse,
+						OffenceKind::InconsistentTestimony,
+						OffenceKind::EstimateInconsistency,
+					])
+					.guarantee(GuaranteeState::Exceeded)
+					.settles_within(300),
+			),
+		),
+	]
+}
+
+// The byzantine voters vote for one block, and the honest ones for another.
+fn votes<'a, S: AsRef<str>>(
+	byzantine: &'a [S],
+	byzantine_block: BlockNumber,
+	honest: &'a [S],
+	honest_block: BlockNumber,
+) -> Vec<(BlockNumber, &'a str)> {
+	byzantine
+		.iter()
+		.map(|voter| (byzantine_block, voter.as_ref()))
+		.chain(honest.iter().map(|voter| (honest_block, voter.as_ref())))
+		.collect()
+}
+
+// Everyone votes for and finalizes block 1 in the first round.
+fn common_history<S: AsRef<str>>(names: &[S]) -> Vec<RoundSpec> {
+	let votes: Vec<_> = names.iter().map(|name| (1, name.as_ref())).collect();
+	vec![RoundSpec::new(1)
+		.prevote(&votes)
+		.precommit(&votes)
+		.finalize(1)]
+}
+
+// Everyone votes for and finalizes block 2 in the second round.
+fn unanimous_history() -> Vec<RoundSpec> {
+	let votes: Vec<_> = PAPER_EXAMPLE_VOTERS.iter().map(|name| (2, *name)).collect();
+	vec![RoundSpec::new(2)
+		.prevote(&votes)
+		.precommit(&votes)
+		.finalize(2)]
+}
+
+fn single_equivocator_history_a() -> Vec<RoundSpec> {
+	vec![RoundSpec::new(2)
+		.prevote(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+		.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+		.finalize(2)]
+}
+
+fn single_equivocator_history_b() -> Vec<RoundSpec> {
+	vec![RoundSpec::new(2)
+		.prevote(&[(5, "Alice"), (5, "Dave")])
+		.precommit(&[(5, "Alice"), (5, "Dave")])]
+}
+
+// Sequence of voting rounds leading to finalizing block 2 on the first fork.
+fn paper_history_a() -> Vec<RoundSpec> {
+	(2..=4)
+		.map(|round| {
+			let spec = RoundSpec::new(round)
+				.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+				.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
+			if round == 2 {
+				spec.finalize(2)
+			} else {
+				spec
+			}
+		})
+		.collect()
+}
+
+// Sequence of voting rounds leading to finalizing block 8 on the second fork.
+fn paper_history_b() -> Vec<RoundSpec> {
+	vec![
+		RoundSpec::new(2)
+			.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+		RoundSpec::new(3)
+			.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+		RoundSpec::new(4)
+			.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+			.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+			.finalize(8),
+	]
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Chains of inference from each proven offence back to the events of the run it rests on: the
+//! protocol instance that was started, the queries that were put, the responses to them and the
+//! cross-check that gave the offender away.
+//!
+//! Events are referred to by their position in the run's event log, see [`EventLog::get`], so that
+//! a report can be followed from a verdict back to the messages that were exchanged.
+//!
+//! [`EventLog::get`]: crate::event::EventLog::get
+
+use crate::{
+	event::EventId,
+	protocol::EquivocationDetected,
+	voter::{QueryKind, VoterId},
+	voting::{RoundNumber, SetId},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+};
+
+// The verifier, the responder, the round asked about and what was asked.
+type QueryKey = (VoterId, VoterId, RoundNumber, QueryKind);
+
+/// A query a detector put to a responder, and what came back.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InferenceStep {
+	pub responder: VoterId,
+	pub round: RoundNumber,
+	pub kind: QueryKind,
+	// The query as sent, unless the response came back without one having been recorded.
+	pub query: Option<EventId>,
+	// The responses as delivered, more than one for a chunked response.
+	pub responses: Vec<EventId>,
+}
+
+/// How a detector came to prove an offender to have misbehaved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceChain {
+	pub offender: VoterId,
+	pub set_id: SetId,
+	pub detector: VoterId,
+	// The detector starting its latest protocol instance before the offence was found.
+	pub started: Option<EventId>,
+	// The queries answered by the offender, or whose answers revealed its votes, in the order they
+	// were answered.
+	pub steps: Vec<InferenceStep>,
+	// The cross-check that found the offence.
+	pub detected: EventId,
+	// The finding, with the votes that prove it.
+	pub finding: EquivocationDetected,
+}
+
+impl EvidenceChain {
+	/// Every event the chain refers to, in the order they were recorded.
+	pub fn events(&self) -> Vec<EventId> {
+		let mut events: Vec<_> = self
+			.started
+			.iter()
+			.copied()
+			.chain(self.steps.iter().flat_map(|step| {
+				step.query
+					.iter()
+					.copied()
+					.chain(step.responses.iter().copied())
+			}))
+			.chain(std::iter::once(self.detected))
+			.collect();
+		events.sort_unstable();
+		events.dedup();
+		events
+	}
+}
+
+impl Display for EvidenceChain {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		writeln!(
+			f,
+			"{} (set {}) proven by {} at event {}",
+			self.offender, self.set_id, self.detector, self.detected
+		)?;
+		if let Some(started) = self.started {
+			writeln!(f, "  protocol started at event {}", started)?;
+		}
+		for step in &self.steps {
+			let query = step
+				.query
+				.map_or_else(|| "unrecorded".to_string(), |query| query.to_string());
+			writeln!(
+				f,
+				"  {:?} query to {} about round {}: sent at event {}, answered at events {:?}",
+				step.kind, step.responder, step.round, query, step.responses
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// Links the queries and responses of a run as they are sent and delivered, and builds the
+/// evidence chain of every offence as it's found.
+#[derive(Debug, Default)]
+pub struct Transcript {
+	// The latest protocol instance each detector started.
+	started: BTreeMap<VoterId, EventId>,
+	// The latest of each query sent, retries replacing earlier attempts.
+	queries: BTreeMap<QueryKey, EventId>,
+	// The responses delivered for each query, with the voters whose votes each revealed.
+	responses: BTreeMap<QueryKey, Vec<(EventId, BTreeSet<VoterId>)>>,
+	chains: Vec<EvidenceChain>,
+}
+
+impl Transcript {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	pub fn protocol_started(&mut self, detector: &VoterId, event: EventId) {
+		self.started.insert(detector.clone(), event);
+	}
+
+	pub fn query_sent(
+		&mut self,
+		verifier: &VoterId,
+		responder: &VoterId,
+		round: RoundNumber,
+		kind: QueryKind,
+		event: EventId,
+	) {
+		self.queries
+			.insert((verifier.clone(), responder.clone(), round, kind), event);
+	}
+
+	pub fn response_delivered(
+		&mut self,
+		verifier: &VoterId,
+		responder: &VoterId,
+		round: RoundNumber,
+		kind: QueryKind,
+		revealed: BTreeSet<VoterId>,
+		event: EventId,
+	) {
+		self.responses
+			.entry((verifier.clone(), responder.clone(), round, kind))
+			.or_default()
+			.push((event, revealed));
+	}
+
+	/// Record the evidence chain of every offence in a finding of the detector.
+	pub fn offence_found(
+		&mut self,
+		detector: &VoterId,
+		finding: &EquivocationDetected,
+		event: EventId,
+	) {
+		for (set_id, offender) in finding.offences() {
+			let mut steps: Vec<_> = self
+				.responses
+				.iter()
+				.filter(|((verifier, ..), _)| verifier == detector)
+				.filter_map(|(key, responses)| {
+					let (_, responder, round, kind) = key;
+					let responses: Vec<_> = responses
+						.iter()
+						.filter(|(_, revealed)| {
+							responder == &offender || revealed.contains(&offender)
+						})
+						.map(|(event, _)| *event)
+						.collect();
+					(!responses.is_empty()).then(|| InferenceStep {
+						responder: responder.clone(),
+						round: *round,
+						kind: *kind,
+						query: self.queries.get(key).copied(),
+						responses,
+					})
+				})
+				.collect();
+			steps.sort_by_key(|step| step.responses[0]);
+			self.chains.push(EvidenceChain {
+				offender,
+				set_id,
+				detector: detector.clone(),
+				started: self.started.get(detector).copied(),
+				steps,
+				detected: event,
+				finding: finding.clone(),
+			});
+		}
+	}
+
+	/// The evidence chains of every offence found so far, in the order they were found.
+	pub fn chains(&self) -> &[EvidenceChain] {
+		&self.chains
+	}
+}
@@ -93,13 +93,36 @@
 //!    Take the union with S and find the equivocators.
 //!
 
-mod action;
-mod block;
-mod chain;
-mod message;
-mod protocol;
-mod voter;
-mod voting;
+pub mod action;
+pub mod archive;
+pub mod behaviour;
+pub mod block;
+pub mod chain;
+pub mod config;
+pub mod coverage;
+pub mod crypto;
+pub mod event;
+pub mod execution;
+pub mod failure_modes;
+pub mod fixtures;
+pub mod inference;
+pub mod memory;
+pub mod message;
+pub mod network;
+#[cfg(feature = "libp2p")]
+pub mod p2p;
+pub mod policy;
+pub mod prelude;
+pub mod protocol;
+pub mod report;
+pub mod scenario;
+pub mod schema;
+pub mod snapshot;
+pub mod storage;
+pub mod supermajority;
+pub mod verification;
+pub mod voter;
+pub mod voting;
 pub mod world;
 
 #[cfg(test)]
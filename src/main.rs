@@ -0,0 +1,125 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs an experiment described by a configuration file, see [`accountable_safety::config`].
+//!
+//! ```text
+//! accountable-safety --config run.toml
+//! ```
+//!
+//! With `--plan`, the run stops as soon as a conflict is detected and prints the rounds each
+//! detector would ask about, and whom, instead of exchanging the queries. With `--json`, the
+//! summary of the run is printed as JSON. With `--report`, the outcome of the run is printed in the
+//! versioned format of [`accountable_safety::schema`] instead, for tooling to consume. With
+//! `--compare`, the scenario is run twice, with the adversaries answering queries with precommits
+//! and then with prevotes, and the outcomes are compared.
+
+use accountable_safety::{config::ExperimentConfig, schema::ReportV1, world::StopCondition};
+use std::process::exit;
+
+fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let (path, flags) = match args.as_slice() {
+		[flag, path, flags @ ..]
+			if flag == "--config"
+				&& flags.iter().all(|flag| {
+					flag == "--plan"
+						|| flag == "--json"
+						|| flag == "--report"
+						|| flag == "--compare"
+				}) =>
+		{
+			(path, flags)
+		}
+		_ => {
+			eprintln!(
+				"usage: accountable-safety --config <file> [--plan] [--json] [--report] [--compare]"
+			);
+			exit(2);
+		}
+	};
+	let plan = flags.iter().any(|flag| flag == "--plan");
+	let json = flags.iter().any(|flag| flag == "--json");
+	let report = flags.iter().any(|flag| flag == "--report");
+	let compare = flags.iter().any(|flag| flag == "--compare");
+
+	let config = ExperimentConfig::from_file(path).map(|mut config| {
+		if plan {
+			config.world.stop_condition = Some(StopCondition::Detected);
+		}
+		config
+	});
+	if compare {
+		let comparison = match config.and_then(|config| config.compare_alternatives()) {
+			Ok(comparison) => comparison,
+			Err(error) => {
+				eprintln!("{}: {}", path, error);
+				exit(1);
+			}
+		};
+		if json {
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&comparison).expect("comparisons always serialize")
+			);
+		} else {
+			print!("{}", comparison);
+		}
+		if comparison.precommits.protocol_bugs > 0 || comparison.prevotes.protocol_bugs > 0 {
+			exit(1);
+		}
+		return;
+	}
+	let mut world = match config.and_then(|config| config.build()) {
+		Ok(world) => world,
+		Err(error) => {
+			eprintln!("{}: {}", path, error);
+			exit(1);
+		}
+	};
+	let summary = world.run_until_settled();
+	if let Some(error) = world.event_log().sink_error() {
+		eprintln!("{}: failed to write the event log: {}", path, error);
+		exit(1);
+	}
+	if plan {
+		for (detector, plans) in world.query_plans() {
+			for (conflict, rounds) in plans {
+				println!(
+					"{} investigating blocks {} and {}:",
+					detector, conflict.block_not_included, conflict.new_block
+				);
+				for round in rounds {
+					println!("  {}", round);
+				}
+			}
+		}
+		return;
+	}
+	if report {
+		println!("{}", ReportV1::new(&world).to_json());
+	} else if json {
+		println!(
+			"{}",
+			serde_json::to_string_pretty(&summary).expect("summaries always serialize")
+		);
+	} else {
+		print!("{}", summary);
+	}
+	if summary.protocol_bugs > 0 || summary.invariant_violations > 0 {
+		exit(1);
+	}
+}
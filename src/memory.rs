@@ -0,0 +1,131 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Heap usage of the simulation, to measure how much memory long runs take.
+//!
+//! With the `alloc-tracking` feature a counting allocator, wrapping the system allocator, is
+//! installed as the global allocator. Without it nothing is tracked and the functions here return
+//! `None`. The counts are for the whole process, so runs on other threads are included.
+
+#[cfg(feature = "alloc-tracking")]
+mod counting {
+	use std::{
+		alloc::{GlobalAlloc, Layout, System},
+		sync::atomic::{AtomicUsize, Ordering},
+	};
+
+	pub(super) static CURRENT: AtomicUsize = AtomicUsize::new(0);
+	pub(super) static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+	struct CountingAllocator;
+
+	fn allocated(size: usize) {
+		let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+		PEAK.fetch_max(current, Ordering::Relaxed);
+	}
+
+	fn freed(size: usize) {
+		CURRENT.fetch_sub(size, Ordering::Relaxed);
+	}
+
+	unsafe impl GlobalAlloc for CountingAllocator {
+		unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+			let ptr = System.alloc(layout);
+			if !ptr.is_null() {
+				allocated(layout.size());
+			}
+			ptr
+		}
+
+		unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+			let ptr = System.alloc_zeroed(layout);
+			if !ptr.is_null() {
+				allocated(layout.size());
+			}
+			ptr
+		}
+
+		unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+			System.dealloc(ptr, layout);
+			freed(layout.size());
+		}
+
+		unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+			let new_ptr = System.realloc(ptr, layout, new_size);
+			if !new_ptr.is_null() {
+				if new_size > layout.size() {
+					allocated(new_size - layout.size());
+				} else {
+					freed(layout.size() - new_size);
+				}
+			}
+			new_ptr
+		}
+	}
+
+	#[global_allocator]
+	static ALLOCATOR: CountingAllocator = CountingAllocator;
+}
+
+/// Bytes currently allocated on the heap.
+pub fn current_bytes() -> Option<usize> {
+	#[cfg(feature = "alloc-tracking")]
+	return Some(counting::CURRENT.load(std::sync::atomic::Ordering::Relaxed));
+	#[cfg(not(feature = "alloc-tracking"))]
+	None
+}
+
+/// The most bytes allocated at once since the start, or since the last [`reset_peak`].
+pub fn peak_bytes() -> Option<usize> {
+	#[cfg(feature = "alloc-tracking")]
+	return Some(counting::PEAK.load(std::sync::atomic::Ordering::Relaxed));
+	#[cfg(not(feature = "alloc-tracking"))]
+	None
+}
+
+/// Start tracking the peak again from the current usage, so that it covers a single run.
+pub fn reset_peak() {
+	#[cfg(feature = "alloc-tracking")]
+	{
+		use std::sync::atomic::Ordering;
+		let current = counting::CURRENT.load(Ordering::Relaxed);
+		counting::PEAK.store(current, Ordering::Relaxed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(feature = "alloc-tracking")]
+	#[test]
+	fn peak_covers_freed_allocations() {
+		reset_peak();
+		let before = peak_bytes().unwrap();
+		let buffer = vec![0u8; 1 << 20];
+		drop(buffer);
+		assert!(peak_bytes().unwrap() >= before + (1 << 20));
+		assert!(current_bytes().unwrap() <= peak_bytes().unwrap());
+	}
+
+	#[cfg(not(feature = "alloc-tracking"))]
+	#[test]
+	fn untracked_without_feature() {
+		reset_peak();
+		assert_eq!(current_bytes(), None);
+		assert_eq!(peak_bytes(), None);
+	}
+}
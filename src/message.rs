@@ -16,27 +16,117 @@
 
 use crate::{
 	block::{Block, BlockNumber},
-	protocol::QueryResponse,
+	policy::Refusal,
+	protocol::{
+		ConflictId, QueryResponse, ReplyKinds, ResponseChunk, SignedConflictStatement,
+		SignedEvidenceBundle, SignedVoteCommitment,
+	},
 	voter::VoterId,
-	voting::{Commit, RoundNumber},
+	voting::{Commit, Precommit, Prevote, RoundNumber, VoteBatch},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::hash_map::DefaultHasher,
+	hash::{Hash, Hasher},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
 	HereIsCommit(RoundNumber, Commit),
 	HereAreBlocks(Vec<Block>),
-	WhyDidEstimateForRoundNotIncludeBlock(RoundNumber, BlockNumber),
-	WhichPrevotesSeenInRound(RoundNumber),
+	// Queries name the kinds of votes the verifier accepts as an explanation. Both kinds of query
+	// carry the verifier's signed statement of the conflict it is investigating, which is what
+	// identifies the verifier to the responder.
+	WhyDidEstimateForRoundNotIncludeBlock(
+		RoundNumber,
+		BlockNumber,
+		ReplyKinds,
+		SignedConflictStatement,
+	),
+	WhichPrevotesSeenInRound(RoundNumber, SignedConflictStatement),
+	// Ask a peer other than the sender of a commit for the block it targets.
+	PleaseSendBlock(BlockNumber),
+	// Ask a peer for the commit finalizing the block, or failing that its latest finalized ancestor.
+	RequestCommitFor(BlockNumber),
+	HereIsEvidence(SignedEvidenceBundle),
+	// A request passed on from voter to voter on behalf of the voter that first sent it.
+	Gossip(Gossip),
+	// We detected the conflict and are in line to verify it, when verifiers coordinate.
+	ClaimConflict(ConflictId),
+	// Our votes in the rounds we vote in during the run.
+	Prevote(Prevote),
+	Precommit(Precommit),
+	// All the votes we have seen in each of the rounds we vote in, sent instead of our votes one by
+	// one when votes are batched.
+	RoundVotes(Vec<VoteBatch>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gossip {
+	pub originator: VoterId,
+	// How many times the payload was passed on before reaching us.
+	pub hops: u32,
+	pub payload: Box<Request>,
+}
+
+impl Gossip {
+	pub fn new(originator: VoterId, payload: Request) -> Self {
+		Self {
+			originator,
+			hops: 0,
+			payload: Box::new(payload),
+		}
+	}
+
+	/// Identifies the payload together with its originator, however it got to us.
+	pub fn id(&self) -> u64 {
+		let serialized = serde_json::to_vec(&(&self.originator, &self.payload))
+			.expect("requests always serialize");
+		let mut hasher = DefaultHasher::new();
+		serialized.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	/// The gossip as passed on by us.
+	pub fn relayed(&self) -> Self {
+		Self {
+			hops: self.hops + 1,
+			..self.clone()
+		}
+	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Response {
 	RequestBlock(BlockNumber),
-	ExplainEstimate(RoundNumber, QueryResponse),
-	PrevotesSeen(RoundNumber, QueryResponse),
+	// Explanations name the block the query was about, so that they reach the protocol instances
+	// that asked. Answers carry the responder's commitment to its votes in the round they reveal.
+	ExplainEstimate(
+		RoundNumber,
+		BlockNumber,
+		QueryResponse,
+		SignedVoteCommitment,
+	),
+	// An explanation too large for a single message, sent in several parts, each with the
+	// commitment.
+	ExplainEstimateChunk(
+		RoundNumber,
+		BlockNumber,
+		ResponseChunk,
+		SignedVoteCommitment,
+	),
+	PrevotesSeen(RoundNumber, QueryResponse, SignedVoteCommitment),
+	// We have no history for the round before the one asked about, not having been a member of its
+	// voter set.
+	NotAMemberAtRound(RoundNumber),
+	// We were a member of the voter set of the round before the one asked about, but no longer have
+	// our votes for it.
+	DontKnowRound(RoundNumber),
+	// Our policy is not to serve the request to the sender.
+	PolicyRefusal(Refusal),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Payload {
 	Request(Request),
 	Response(Response),
@@ -56,6 +146,101 @@ impl Payload {
 			Payload::Response(response) => Some(response),
 		}
 	}
+
+	// Short description of the payload, leaving out the votes themselves.
+	pub fn summary(&self) -> String {
+		match self {
+			Payload::Request(Request::HereIsCommit(round, commit)) => {
+				format!(
+					"HereIsCommit(round: {}, block: {})",
+					round, commit.target_number
+				)
+			}
+			Payload::Request(Request::HereAreBlocks(blocks)) => {
+				format!("HereAreBlocks({} blocks)", blocks.len())
+			}
+			Payload::Request(Request::WhyDidEstimateForRoundNotIncludeBlock(round, block, ..)) => {
+				format!(
+					"WhyDidEstimateForRoundNotIncludeBlock(round: {}, block: {})",
+					round, block
+				)
+			}
+			Payload::Request(Request::WhichPrevotesSeenInRound(round, _)) => {
+				format!("WhichPrevotesSeenInRound(round: {})", round)
+			}
+			Payload::Request(Request::PleaseSendBlock(block)) => {
+				format!("PleaseSendBlock(block: {})", block)
+			}
+			Payload::Request(Request::RequestCommitFor(block)) => {
+				format!("RequestCommitFor(block: {})", block)
+			}
+			Payload::Request(Request::ClaimConflict(conflict)) => format!(
+				"ClaimConflict(blocks: {} and {})",
+				conflict.block_not_included, conflict.new_block
+			),
+			Payload::Request(Request::Prevote(prevote)) => format!(
+				"Prevote(round: {}, block: {}, voter: {})",
+				prevote.round, prevote.target_number, prevote.id
+			),
+			Payload::Request(Request::Precommit(precommit)) => format!(
+				"Precommit(round: {}, block: {}, voter: {})",
+				precommit.round, precommit.target_number, precommit.id
+			),
+			Payload::Request(Request::RoundVotes(batches)) => format!(
+				"RoundVotes(rounds: {}, {} votes)",
+				batches
+					.iter()
+					.map(|batch| batch.round.to_string())
+					.collect::<Vec<_>>()
+					.join(", "),
+				batches.iter().map(VoteBatch::len).sum::<usize>()
+			),
+			Payload::Request(Request::HereIsEvidence(evidence)) => {
+				format!("HereIsEvidence(detector: {})", evidence.bundle.detector)
+			}
+			Payload::Request(Request::Gossip(gossip)) => {
+				format!(
+					"Gossip(originator: {}, hops: {}, {})",
+					gossip.originator,
+					gossip.hops,
+					Payload::Request((*gossip.payload).clone()).summary()
+				)
+			}
+			Payload::Response(Response::RequestBlock(block)) => {
+				format!("RequestBlock(block: {})", block)
+			}
+			Payload::Response(Response::NotAMemberAtRound(round)) => {
+				format!("NotAMemberAtRound(round: {})", round)
+			}
+			Payload::Response(Response::DontKnowRound(round)) => {
+				format!("DontKnowRound(round: {})", round)
+			}
+			Payload::Response(Response::PolicyRefusal(refusal)) => {
+				format!("PolicyRefusal({})", refusal)
+			}
+			Payload::Response(Response::ExplainEstimate(round, block, response, _)) => {
+				format!(
+					"ExplainEstimate(round: {}, block: {}, {} votes)",
+					round,
+					block,
+					response.len()
+				)
+			}
+			Payload::Response(Response::ExplainEstimateChunk(round, block, chunk, _)) => {
+				format!(
+					"ExplainEstimateChunk(round: {}, block: {}, chunk: {}/{}, {} votes)",
+					round,
+					block,
+					chunk.index + 1,
+					chunk.total,
+					chunk.votes.len()
+				)
+			}
+			Payload::Response(Response::PrevotesSeen(round, response, _)) => {
+				format!("PrevotesSeen(round: {}, {} votes)", round, response.len())
+			}
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -0,0 +1,176 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::voter::VoterId;
+use std::collections::BTreeMap;
+
+/// Properties of the simulated network that all messages are sent over.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkModel {
+	/// Number of ticks between a message being sent and it being delivered.
+	pub latency: usize,
+	/// Probability in the range [0, 1] that a message is lost in transit.
+	pub loss: f64,
+	/// Latencies of individual links, overriding `latency` for messages from the first voter to the
+	/// second.
+	pub links: BTreeMap<(VoterId, VoterId), usize>,
+}
+
+impl NetworkModel {
+	pub fn new(latency: usize, loss: f64) -> Self {
+		assert!((0.0..=1.0).contains(&loss));
+		Self {
+			latency,
+			loss,
+			links: Default::default(),
+		}
+	}
+
+	/// Set the latency of the link between the two voters, in both directions.
+	pub fn with_link(mut self, a: &str, b: &str, latency: usize) -> Self {
+		self.links.insert((a.to_string(), b.to_string()), latency);
+		self.links.insert((b.to_string(), a.to_string()), latency);
+		self
+	}
+
+	/// Number of ticks a message from the sender takes to reach the receiver.
+	pub fn latency_between(&self, sender: &VoterId, receiver: &VoterId) -> usize {
+		self.links
+			.get(&(sender.clone(), receiver.clone()))
+			.copied()
+			.unwrap_or(self.latency)
+	}
+}
+
+/// A network partition active for the ticks in `start..end`. Voters in different groups can't
+/// reach each other, while voters not listed in any group are unaffected.
+#[derive(Debug, Clone)]
+pub struct Partition {
+	pub start: usize,
+	pub end: usize,
+	pub groups: Vec<Vec<VoterId>>,
+}
+
+impl Partition {
+	pub fn new(start: usize, end: usize, groups: Vec<Vec<VoterId>>) -> Self {
+		Self { start, end, groups }
+	}
+
+	fn group_of(&self, voter: &VoterId) -> Option<usize> {
+		self.groups.iter().position(|group| group.contains(voter))
+	}
+
+	pub fn separates(&self, tick: usize, sender: &VoterId, receiver: &VoterId) -> bool {
+		if !(self.start..self.end).contains(&tick) {
+			return false;
+		}
+		match (self.group_of(sender), self.group_of(receiver)) {
+			(Some(a), Some(b)) => a != b,
+			_ => false,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PartitionSchedule(pub Vec<Partition>);
+
+impl PartitionSchedule {
+	pub fn new(partitions: Vec<Partition>) -> Self {
+		Self(partitions)
+	}
+
+	pub fn separates(&self, tick: usize, sender: &VoterId, receiver: &VoterId) -> bool {
+		self.0
+			.iter()
+			.any(|partition| partition.separates(tick, sender, receiver))
+	}
+}
+
+/// Small deterministic pseudo random number generator (SplitMix64), so that runs are reproducible
+/// from their seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+	state: u64,
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		Self { state: seed }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Returns a float in the range [0, 1).
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn partition_separates_groups_only_while_active() {
+		let partition = Partition::new(
+			10,
+			20,
+			vec![
+				vec!["Alice".to_string(), "Bob".to_string()],
+				vec!["Carol".to_string()],
+			],
+		);
+		let (alice, bob, carol, dave) = (
+			"Alice".to_string(),
+			"Bob".to_string(),
+			"Carol".to_string(),
+			"Dave".to_string(),
+		);
+
+		assert!(!partition.separates(9, &alice, &carol));
+		assert!(partition.separates(10, &alice, &carol));
+		assert!(partition.separates(19, &carol, &bob));
+		assert!(!partition.separates(20, &alice, &carol));
+
+		assert!(!partition.separates(15, &alice, &bob));
+		assert!(!partition.separates(15, &alice, &dave));
+	}
+
+	#[test]
+	fn link_latencies_override_default() {
+		let network = NetworkModel::new(2, 0.0).with_link("Alice", "Carol", 20);
+		let (alice, bob, carol) = ("Alice".to_string(), "Bob".to_string(), "Carol".to_string());
+		assert_eq!(network.latency_between(&alice, &carol), 20);
+		assert_eq!(network.latency_between(&carol, &alice), 20);
+		assert_eq!(network.latency_between(&alice, &bob), 2);
+	}
+
+	#[test]
+	fn rng_is_deterministic() {
+		let mut a = Rng::new(42);
+		let mut b = Rng::new(42);
+		for _ in 0..10 {
+			assert_eq!(a.next_u64(), b.next_u64());
+		}
+		assert!((0..100).all(|_| (0.0..1.0).contains(&a.next_f64())));
+	}
+}
@@ -0,0 +1,364 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Adapter running a voter over libp2p's request-response protocol, so that the protocol can be
+//! tried out between separate processes and not only in the simulated world.
+//!
+//! Every message a voter sends, be it a request or a response, goes out as a libp2p request
+//! encoded as JSON, since responses aren't always replies to a request from the same peer. The
+//! responses to a request that are for the requester are sent back on the libp2p response. The
+//! application owns the swarm, and drives the node by passing on the events of the behaviour and
+//! calling [`VoterNode::tick`] at a regular interval. Both hand back what went wrong with the
+//! messages on the way, as [`NodeError`]s, for the application to act on.
+
+use crate::{
+	message::{Message, Payload, Response},
+	voter::{Voter, VoterId},
+};
+use libp2p::{
+	request_response::{self, InboundFailure, OutboundFailure, ProtocolSupport},
+	PeerId, StreamProtocol,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	fmt::{Display, Formatter},
+	io,
+};
+
+pub const PROTOCOL_NAME: StreamProtocol = StreamProtocol::new("/grandpa/accountable-safety/1");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireMessage {
+	pub sender: VoterId,
+	pub payload: Payload,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireResponse {
+	pub sender: VoterId,
+	pub responses: Vec<Response>,
+}
+
+pub type Behaviour = request_response::json::Behaviour<WireMessage, WireResponse>;
+pub type Event = request_response::Event<WireMessage, WireResponse>;
+
+pub fn new_behaviour() -> Behaviour {
+	Behaviour::new(
+		[(PROTOCOL_NAME, ProtocolSupport::Full)],
+		request_response::Config::default(),
+	)
+}
+
+/// A message that didn't make it between a node and its peers.
+#[derive(Debug)]
+pub enum NodeError {
+	// The peer sent something that isn't a message of the protocol.
+	Undecodable {
+		peer: PeerId,
+		error: io::Error,
+	},
+	// The peer sent a message in the name of a voter other than the one it's known as.
+	Impersonation {
+		peer: PeerId,
+		claimed: VoterId,
+	},
+	// The connection to the voter closed before the responses to its request went out.
+	ResponseNotSent {
+		receiver: VoterId,
+	},
+	SendFailed {
+		peer: PeerId,
+		error: OutboundFailure,
+	},
+	ReceiveFailed {
+		peer: PeerId,
+		error: InboundFailure,
+	},
+	// There is no known peer for the voter, so the message to it was dropped.
+	UnknownPeer {
+		receiver: VoterId,
+		summary: String,
+	},
+}
+
+impl NodeError {
+	fn inbound(peer: PeerId, error: InboundFailure) -> Self {
+		match error {
+			// The codec fails decoding with the error of the JSON parser.
+			InboundFailure::Io(error) if error.kind() == io::ErrorKind::InvalidData => {
+				NodeError::Undecodable { peer, error }
+			}
+			error => NodeError::ReceiveFailed { peer, error },
+		}
+	}
+
+	fn outbound(peer: PeerId, error: OutboundFailure) -> Self {
+		match error {
+			OutboundFailure::Io(error) if error.kind() == io::ErrorKind::InvalidData => {
+				NodeError::Undecodable { peer, error }
+			}
+			error => NodeError::SendFailed { peer, error },
+		}
+	}
+}
+
+impl Display for NodeError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			NodeError::Undecodable { peer, error } => {
+				write!(f, "failed to decode message from {}: {}", peer, error)
+			}
+			NodeError::Impersonation { peer, claimed } => {
+				write!(
+					f,
+					"dropping message from {} claiming to be {}",
+					peer, claimed
+				)
+			}
+			NodeError::ResponseNotSent { receiver } => {
+				write!(f, "connection to {} closed before responding", receiver)
+			}
+			NodeError::SendFailed { peer, error } => {
+				write!(f, "failed to send to {}: {}", peer, error)
+			}
+			NodeError::ReceiveFailed { peer, error } => {
+				write!(f, "failed to receive from {}: {}", peer, error)
+			}
+			NodeError::UnknownPeer { receiver, summary } => {
+				write!(f, "no known peer for {}, dropping {}", receiver, summary)
+			}
+		}
+	}
+}
+
+impl std::error::Error for NodeError {}
+
+/// A voter connected to its peers over libp2p.
+pub struct VoterNode {
+	voter: Voter,
+	peers: BTreeMap<VoterId, PeerId>,
+	current_tick: usize,
+	// Requests the voter requeued for itself, handled on the next tick.
+	requeued: Vec<Message>,
+	// What went wrong since the errors were last handed back.
+	errors: Vec<NodeError>,
+}
+
+impl VoterNode {
+	pub fn new(voter: Voter) -> Self {
+		Self {
+			voter,
+			peers: Default::default(),
+			current_tick: 0,
+			requeued: Default::default(),
+			errors: Default::default(),
+		}
+	}
+
+	pub fn voter(&self) -> &Voter {
+		&self.voter
+	}
+
+	pub fn current_tick(&self) -> usize {
+		self.current_tick
+	}
+
+	pub fn add_peer(&mut self, id: VoterId, peer: PeerId) {
+		self.peers.insert(id, peer);
+	}
+
+	/// Act on the actions of the voter that are due, and move on to the next tick. Returns the
+	/// messages that couldn't be sent.
+	pub fn tick(&mut self, behaviour: &mut Behaviour) -> Vec<NodeError> {
+		for message in std::mem::take(&mut self.requeued) {
+			let sender = message.sender.clone();
+			self.handle_payload(behaviour, sender, message.content);
+		}
+
+		for message in self.voter.process_actions(self.current_tick) {
+			// Requeued requests keep their original sender, and never go out over the network.
+			if message.sender != self.voter.id {
+				self.requeued.push(message);
+			} else {
+				self.send(behaviour, &message.receiver, message.content);
+			}
+		}
+		self.current_tick += 1;
+		std::mem::take(&mut self.errors)
+	}
+
+	/// Handle an event emitted by the behaviour. Returns what went wrong with the messages it was
+	/// about, and with any sent in turn.
+	pub fn handle_event(&mut self, behaviour: &mut Behaviour, event: Event) -> Vec<NodeError> {
+		match event {
+			request_response::Event::Message { peer, message } => match message {
+				request_response::Message::Request {
+					request, channel, ..
+				} => {
+					if !self.is_peer(&request.sender, &peer) {
+						self.errors.push(NodeError::Impersonation {
+							peer,
+							claimed: request.sender,
+						});
+						return std::mem::take(&mut self.errors);
+					}
+					let sender = request.sender.clone();
+					let responses = self.handle_payload(behaviour, sender, request.payload);
+					let response = WireResponse {
+						sender: self.voter.id.clone(),
+						responses,
+					};
+					if behaviour.send_response(channel, response).is_err() {
+						self.errors.push(NodeError::ResponseNotSent {
+							receiver: request.sender,
+						});
+					}
+				}
+				request_response::Message::Response { response, .. } => {
+					if !self.is_peer(&response.sender, &peer) {
+						self.errors.push(NodeError::Impersonation {
+							peer,
+							claimed: response.sender,
+						});
+						return std::mem::take(&mut self.errors);
+					}
+					for r in response.responses {
+						self.voter
+							.handle_response((response.sender.clone(), r), self.current_tick);
+					}
+				}
+			},
+			request_response::Event::OutboundFailure { peer, error, .. } => {
+				self.errors.push(NodeError::outbound(peer, error));
+			}
+			request_response::Event::InboundFailure { peer, error, .. } => {
+				self.errors.push(NodeError::inbound(peer, error));
+			}
+			request_response::Event::ResponseSent { .. } => {}
+		}
+		std::mem::take(&mut self.errors)
+	}
+
+	fn is_peer(&self, id: &VoterId, peer: &PeerId) -> bool {
+		self.peers.get(id) == Some(peer)
+	}
+
+	fn send(&mut self, behaviour: &mut Behaviour, receiver: &VoterId, payload: Payload) {
+		match self.peers.get(receiver) {
+			Some(peer) => {
+				behaviour.send_request(
+					peer,
+					WireMessage {
+						sender: self.voter.id.clone(),
+						payload,
+					},
+				);
+			}
+			None => self.errors.push(NodeError::UnknownPeer {
+				receiver: receiver.clone(),
+				summary: payload.summary(),
+			}),
+		}
+	}
+
+	// Pass the payload on to the voter, and return the responses for the sender. Responses for
+	// anyone else are sent separately.
+	fn handle_payload(
+		&mut self,
+		behaviour: &mut Behaviour,
+		sender: VoterId,
+		payload: Payload,
+	) -> Vec<Response> {
+		match payload {
+			Payload::Request(request) => {
+				let mut responses = Vec::new();
+				for (receiver, response) in self
+					.voter
+					.handle_request((sender.clone(), request), self.current_tick)
+				{
+					if receiver == sender {
+						responses.push(response);
+					} else {
+						self.send(behaviour, &receiver, Payload::Response(response));
+					}
+				}
+				responses
+			}
+			Payload::Response(response) => {
+				self.voter
+					.handle_response((sender, response), self.current_tick);
+				Vec::new()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		message::Request,
+		voting::{Commit, Precommit},
+	};
+
+	#[test]
+	fn wire_message_round_trip() {
+		let commit = Commit::new(
+			2,
+			vec![Precommit::new(3, 2, "Alice"), Precommit::new(3, 2, "Bob")],
+		)
+		.with_target_hash(42);
+		let message = WireMessage {
+			sender: "Alice".to_string(),
+			payload: Payload::Request(Request::HereIsCommit(3, commit.clone())),
+		};
+
+		let encoded = serde_json::to_vec(&message).unwrap();
+		let decoded: WireMessage = serde_json::from_slice(&encoded).unwrap();
+		assert_eq!(decoded.sender, "Alice");
+		match decoded.payload {
+			Payload::Request(Request::HereIsCommit(round, decoded_commit)) => {
+				assert_eq!(round, 3);
+				assert_eq!(decoded_commit, commit);
+			}
+			payload => panic!("unexpected payload {:?}", payload),
+		}
+	}
+
+	#[test]
+	fn undecodable_messages_are_reported() {
+		let peer = PeerId::random();
+		// The codec turns the error of the JSON parser into an IO error.
+		let garbled = serde_json::from_slice::<WireMessage>(b"{\"sender\": 4}").unwrap_err();
+		let error = NodeError::inbound(peer, InboundFailure::Io(garbled.into()));
+		assert!(matches!(error, NodeError::Undecodable { peer: from, .. } if from == peer));
+		assert!(error
+			.to_string()
+			.starts_with(&format!("failed to decode message from {}", peer)));
+
+		let garbled = serde_json::from_slice::<WireResponse>(b"[]").unwrap_err();
+		let error = NodeError::outbound(peer, OutboundFailure::Io(garbled.into()));
+		assert!(matches!(error, NodeError::Undecodable { .. }));
+
+		// Failing to get a message across at all is something else.
+		let error = NodeError::inbound(peer, InboundFailure::Timeout);
+		assert!(matches!(error, NodeError::ReceiveFailed { .. }));
+		let closed = io::Error::from(io::ErrorKind::UnexpectedEof);
+		let error = NodeError::outbound(peer, OutboundFailure::Io(closed));
+		assert!(matches!(error, NodeError::SendFailed { .. }));
+	}
+}
@@ -0,0 +1,143 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Which requests a voter serves, and to whom.
+//!
+//! A privacy-conscious voter might keep the votes it has seen to the members of the voter set, or
+//! not reveal them at all, while still serving blocks to anyone. Requests it won't serve are
+//! answered with a [`Refusal`], so that the sender can tell a voter keeping to its policy from one
+//! evading the question.
+
+use crate::{block::BlockNumber, message::Request, voting::RoundNumber};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Who a kind of request is served to.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+	#[default]
+	Anyone,
+	// Members of any voter set we know of. Observers are not members.
+	Members,
+	Nobody,
+}
+
+impl Audience {
+	fn admits(self, member: bool) -> bool {
+		match self {
+			Audience::Anyone => true,
+			Audience::Members => member,
+			Audience::Nobody => false,
+		}
+	}
+}
+
+/// The requests a voter serves to each audience. By default everything is served to anyone.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServingPolicy {
+	// Queries about why the estimate of a round didn't include a block.
+	pub estimates: Audience,
+	// Queries about the prevotes seen in a round.
+	pub prevotes: Audience,
+	pub blocks: Audience,
+	pub commits: Audience,
+}
+
+impl ServingPolicy {
+	/// Serve queries about votes only to members of a voter set, and blocks and commits to anyone.
+	pub fn members_only_votes() -> Self {
+		Self {
+			estimates: Audience::Members,
+			prevotes: Audience::Members,
+			..Default::default()
+		}
+	}
+
+	/// The refusal to send, if the request isn't served to the sender. Requests that only hand us
+	/// something, rather than ask for anything, are always accepted.
+	pub fn refusal(&self, request: &Request, sender_is_member: bool) -> Option<Refusal> {
+		let (audience, refusal) = match request {
+			Request::WhyDidEstimateForRoundNotIncludeBlock(round, block, ..) => {
+				(self.estimates, Refusal::Estimate(*round, *block))
+			}
+			Request::WhichPrevotesSeenInRound(round, _) => {
+				(self.prevotes, Refusal::Prevotes(*round))
+			}
+			Request::PleaseSendBlock(block) => (self.blocks, Refusal::Block(*block)),
+			Request::RequestCommitFor(block) => (self.commits, Refusal::Commit(*block)),
+			Request::HereIsCommit(..)
+			| Request::HereAreBlocks(..)
+			| Request::HereIsEvidence(..)
+			| Request::ClaimConflict(..)
+			| Request::Gossip(..)
+			| Request::Prevote(..)
+			| Request::Precommit(..)
+			| Request::RoundVotes(..) => return None,
+		};
+		(!audience.admits(sender_is_member)).then_some(refusal)
+	}
+}
+
+/// A request the responder's policy doesn't let it serve to the sender.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Refusal {
+	Estimate(RoundNumber, BlockNumber),
+	Prevotes(RoundNumber),
+	Block(BlockNumber),
+	Commit(BlockNumber),
+}
+
+impl Display for Refusal {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			Refusal::Estimate(round, block) => {
+				write!(f, "estimate for round {} and block {}", round, block)
+			}
+			Refusal::Prevotes(round) => write!(f, "prevotes seen in round {}", round),
+			Refusal::Block(block) => write!(f, "block {}", block),
+			Refusal::Commit(block) => write!(f, "commit for block {}", block),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fixtures;
+
+	#[test]
+	fn members_only_votes() {
+		let policy = ServingPolicy::members_only_votes();
+		let statement =
+			fixtures::paper_conflict_statement("Carol").sign(&fixtures::paper_key("Carol"));
+		let prevotes = Request::WhichPrevotesSeenInRound(2, statement);
+		assert_eq!(policy.refusal(&prevotes, true), None);
+		assert_eq!(policy.refusal(&prevotes, false), Some(Refusal::Prevotes(2)));
+		assert_eq!(policy.refusal(&Request::PleaseSendBlock(8), false), None);
+
+		let policy = ServingPolicy {
+			blocks: Audience::Nobody,
+			..Default::default()
+		};
+		assert_eq!(
+			policy.refusal(&Request::PleaseSendBlock(8), true),
+			Some(Refusal::Block(8))
+		);
+		assert_eq!(policy.refusal(&Request::HereAreBlocks(vec![]), false), None);
+	}
+}
@@ -0,0 +1,72 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The types needed to set up and run a simulation and inspect its outcome, for glob importing.
+//!
+//! ```
+//! use accountable_safety::prelude::*;
+//!
+//! let names = ["Alice", "Bob", "Carol", "Dave"];
+//! let votes: Vec<_> = names.iter().map(|name| (1, *name)).collect();
+//! let mut scenario = ScenarioBuilder::new(&names).with_history(
+//!     "common",
+//!     vec![RoundSpec::new(1).prevote(&votes).precommit(&votes).finalize(1)],
+//! );
+//! for name in &names {
+//!     scenario = scenario.with_voter(name, &[(1, 0)], &["common"], None);
+//! }
+//! let scenario = scenario.with_action("Alice", 10, Action::BroadcastCommits);
+//!
+//! let mut world = WorldBuilder::new()
+//!     .with_voters(scenario.build().unwrap())
+//!     .with_max_ticks(100)
+//!     .build();
+//! assert!(world.run_until_settled().offenders.is_empty());
+//! ```
+
+pub use crate::{
+	action::Action,
+	behaviour::{
+		Adversary, Behaviour, BehaviourSchedule, BehaviourTrigger, EstimateQuestion,
+		PrevoteQuestion,
+	},
+	block::{Block, BlockNumber},
+	chain::{Chain, ChainBuilder, ChainView, CommitProvenance, Finalization, Forks},
+	event::{DetectionEvent, Event, EventId, EventKind, EventLog},
+	execution::Execution,
+	inference::{EvidenceChain, InferenceStep},
+	network::{NetworkModel, Partition, PartitionSchedule},
+	policy::{Audience, Refusal, ServingPolicy},
+	protocol::{
+		CommitEquivocation, ConflictId, ConflictStatement, Equivocation, EquivocationDetected,
+		EvidenceBundle, InconsistentTestimony, PlannedRound, QueryRejection, ReplyKinds, Revealed,
+		Salts, SignedConflictStatement, SignedEvidenceBundle, SignedVoteCommitment, VoteCommitment,
+	},
+	report::{
+		AlternativeComparison, AlternativeOutcome, CacheStats, CommitReport, Exoneration,
+		GuaranteeStatus, InstanceVerdicts, InvalidReason, OffenderAnalysis, RoundVerdicts,
+		RunSummary, SimulationReport, Termination, UnjustifiedCommitParticipation, Verdict,
+	},
+	scenario::{Expected, RoundSpec, ScenarioBuilder, ScenarioError},
+	schema::{ReportV1, REPORT_SCHEMA_VERSION},
+	verification::{verify_equivocation_proof, HeaderProvider, ProofError},
+	voter::{
+		CommitProgress, CommitStage, OutstandingQuery, OwnVote, QueryKind, Role, ServedQuery,
+		Voter, VoterId,
+	},
+	voting::{Commit, Precommit, Prevote, RoundNumber, SetId, VoterSet, VotingRound, VotingRounds},
+	world::{StopCondition, World, WorldBuilder},
+};
@@ -16,14 +16,20 @@
 
 use crate::{
 	block::BlockNumber,
-	chain::Chain,
+	chain::{Chain, ChainError, CommitProvenance},
+	coverage::{Branch, Coverage},
+	crypto::{siphash, KeyRegistry, SecretKey, Signature, VerificationFaults},
+	report::{Exoneration, InvalidReason, RoundVerdicts, UnjustifiedCommitParticipation, Verdict},
 	voter::{VoterId, VoterName},
 	voting::{
-		check_query_reply_is_valid, cross_check_votes, Commit, Precommit, Prevote, RoundNumber,
+		check_query_reply_is_valid, cross_check_votes_in_round, estimate_lower_bound,
+		query_reply_is_consistent_with_commit, Commit, MisplacedVote, Precommit, Prevote,
+		RoundNumber, SetId, VoterSet,
 	},
 };
-use itertools::Itertools;
-use std::collections::BTreeMap;
+use itertools::{Either, Itertools};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
 // State of the accountable safety protocol
 #[derive(Debug)]
@@ -31,8 +37,25 @@ pub struct AccountableSafety {
 	block_not_included: BlockNumber,
 	round_for_block_not_included: RoundNumber,
 	commit_for_block_not_included: Commit,
+	new_block: BlockNumber,
+	round_for_new_block: RoundNumber,
+	// The voters whose precommits are in the commit for the new block, if we were told.
+	new_commit_signers: BTreeSet<VoterId>,
 	querying_rounds: BTreeMap<RoundNumber, QueryState>,
 	prevote_queries: BTreeMap<RoundNumber, QueryState>,
+	// The voter set in effect from each round on, as far as the detector knows.
+	sessions: BTreeMap<RoundNumber, SetId>,
+	// The members of each voter set the detector knows, to check claims of non-membership against.
+	voter_sets: BTreeMap<SetId, VoterSet>,
+	// Equivocations found in the two conflicting commits themselves, without asking anyone.
+	commit_equivocations: Vec<EquivocationDetected>,
+	// The kinds of votes we accept to explain an estimate with.
+	accepted_replies: ReplyKinds,
+	// The most votes a responder may put in a single message, if limited.
+	message_budget: Option<usize>,
+	// Signers of the conflicting commits that can't account for their votes.
+	unjustified: Vec<UnjustifiedCommitParticipation>,
+	coverage: Coverage,
 }
 
 // The state of the querying about a specific round.
@@ -43,13 +66,59 @@ struct QueryState {
 	round: RoundNumber,
 	voters: Vec<VoterId>,
 	responses: BTreeMap<VoterId, QueryResponse>,
+	// Responses that are being delivered in chunks and haven't been completed yet.
+	partial_responses: BTreeMap<VoterId, PartialResponse>,
 	equivocations: Vec<EquivocationDetected>,
+	// What came of each response so far.
+	verdicts: BTreeMap<VoterId, Verdict>,
+	// What each responder that answered committed to for the round its votes are from.
+	commitments: BTreeMap<VoterId, SignedVoteCommitment>,
+}
+
+#[derive(Debug)]
+struct PartialResponse {
+	total: usize,
+	chunks: BTreeMap<usize, QueryResponse>,
 }
 
 impl QueryState {
-	fn add_response(&mut self, voter: VoterId, query_response: QueryResponse) {
+	fn add_response(
+		&mut self,
+		voter: VoterId,
+		query_response: QueryResponse,
+		commitment: SignedVoteCommitment,
+	) {
+		self.verdicts.insert(voter.clone(), Verdict::Valid);
+		self.commitments.insert(voter.clone(), commitment);
 		self.responses.insert(voter, query_response);
 	}
+
+	fn reject(&mut self, voter: &VoterId, reason: InvalidReason) {
+		self.verdicts
+			.insert(voter.clone(), Verdict::Invalid(reason));
+	}
+
+	// Responses that were found invalid stay so.
+	fn revealed_equivocations(&mut self, voter: &VoterId) {
+		if let Some(verdict @ Verdict::Valid) = self.verdicts.get_mut(voter) {
+			*verdict = Verdict::RevealedEquivocations;
+		}
+	}
+
+	fn round_verdicts(&self, prevotes: bool) -> RoundVerdicts {
+		RoundVerdicts {
+			round: self.round,
+			prevotes,
+			verdicts: self
+				.voters
+				.iter()
+				.map(|voter| {
+					let verdict = self.verdicts.get(voter).cloned();
+					(voter.clone(), verdict.unwrap_or(Verdict::Missing))
+				})
+				.collect(),
+		}
+	}
 }
 
 pub enum NextQuery {
@@ -57,39 +126,299 @@ pub enum NextQuery {
 	PrevotesForRound(PrevoteQuery),
 }
 
+impl NextQuery {
+	/// The same query, put to the given receivers instead.
+	pub fn with_receivers(&self, receivers: Vec<VoterId>) -> Self {
+		match self {
+			NextQuery::AskAboutRound(query) => NextQuery::AskAboutRound(Query {
+				receivers,
+				..query.clone()
+			}),
+			NextQuery::PrevotesForRound(query) => NextQuery::PrevotesForRound(PrevoteQuery {
+				receivers,
+				..query.clone()
+			}),
+		}
+	}
+
+	/// The conflict investigated by the instance that asks.
+	pub fn conflict(&self) -> ConflictId {
+		match self {
+			NextQuery::AskAboutRound(query) => query.conflict,
+			NextQuery::PrevotesForRound(query) => query.conflict,
+		}
+	}
+}
+
+/// Identifies an instance of the protocol by the two conflicting blocks it investigates, the one
+/// finalized first coming first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ConflictId {
+	pub block_not_included: BlockNumber,
+	pub new_block: BlockNumber,
+}
+
+impl ConflictId {
+	pub fn new(block_not_included: BlockNumber, new_block: BlockNumber) -> Self {
+		Self {
+			block_not_included,
+			new_block,
+		}
+	}
+
+	/// The same two blocks, in the other order.
+	pub fn reversed(self) -> Self {
+		Self::new(self.new_block, self.block_not_included)
+	}
+}
+
 // Query sent to the voters for a specific round
 #[derive(Debug, Clone)]
 pub struct Query {
 	pub round: RoundNumber,
 	pub receivers: Vec<VoterId>,
-	pub block_not_included: BlockNumber,
+	// The instance asking, which also gives the block the estimate should have included.
+	pub conflict: ConflictId,
+	pub accepted_replies: ReplyKinds,
 }
 
 #[derive(Debug, Clone)]
 pub struct PrevoteQuery {
 	pub round: RoundNumber,
 	pub receivers: Vec<VoterId>,
+	pub conflict: ConflictId,
 }
 
-#[derive(Debug, Clone)]
+/// A round the protocol instance expects to ask about, with the voters it would ask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRound {
+	pub round: RoundNumber,
+	pub voters: Vec<VoterId>,
+	// Whether the query for the round was already started, rather than only anticipated.
+	pub started: bool,
+}
+
+impl std::fmt::Display for PlannedRound {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"round {}: {} ({})",
+			self.round,
+			self.voters.join(", "),
+			if self.started { "started" } else { "planned" }
+		)
+	}
+}
+
+/// The kinds of votes a verifier accepts to explain an estimate with. Step 1 of the protocol allows
+/// either, but a verifier can insist on one of them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyKinds {
+	Precommits,
+	Prevotes,
+	#[default]
+	Either,
+}
+
+impl ReplyKinds {
+	pub fn allows_precommits(self) -> bool {
+		self != ReplyKinds::Prevotes
+	}
+
+	pub fn allows_prevotes(self) -> bool {
+		self != ReplyKinds::Precommits
+	}
+
+	pub fn accepts(self, response: &QueryResponse) -> bool {
+		match response {
+			QueryResponse::Precommits(_) => self.allows_precommits(),
+			QueryResponse::Prevotes(_) => self.allows_prevotes(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QueryResponse {
 	Prevotes(Vec<Prevote>),
 	Precommits(Vec<Precommit>),
 }
 
+/// A responder's commitment to all the votes it has for a round, in one of its tagged histories. It
+/// goes with every answer about the round, so that an answer revealing other votes, or a second
+/// commitment to other votes for the same round, gives the responder away. Votes are committed to
+/// one by one, so that an answer can reveal only some of them.
+///
+/// Each vote is committed to with a salt of its own, derived from the vote and a nonce the
+/// responder keeps secret, so that the votes an answer leaves out can't be found by trying all
+/// the votes there could have been. The salts of the votes an answer reveals go with it.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoteCommitment {
+	pub responder: VoterId,
+	pub round: RoundNumber,
+	// The history the votes come from.
+	pub tag: u32,
+	// The prevotes and precommits are committed to separately, as answers only reveal one kind.
+	pub prevotes: BTreeSet<u64>,
+	pub precommits: BTreeSet<u64>,
+}
+
+impl VoteCommitment {
+	pub fn new(
+		responder: VoterId,
+		round: RoundNumber,
+		tag: u32,
+		nonce: u64,
+		prevotes: &[Prevote],
+		precommits: &[Precommit],
+	) -> Self {
+		let digests = |votes: Vec<Vec<u8>>| {
+			votes
+				.iter()
+				.map(|vote| salted_digest(salt(nonce, vote), vote))
+				.collect()
+		};
+		Self {
+			responder,
+			round,
+			tag,
+			prevotes: digests(prevotes.iter().map(encode).collect()),
+			precommits: digests(precommits.iter().map(encode).collect()),
+		}
+	}
+
+	/// Every vote of the answer is one of those committed to, with the salt revealed for it.
+	pub fn matches(&self, response: &QueryResponse, salts: &Salts) -> bool {
+		let committed = |digests: &BTreeSet<u64>, vote: Vec<u8>| {
+			salts
+				.digest(&vote)
+				.is_some_and(|digest| digests.contains(&digest))
+		};
+		match response {
+			QueryResponse::Prevotes(prevotes) => prevotes
+				.iter()
+				.all(|prevote| committed(&self.prevotes, encode(prevote))),
+			QueryResponse::Precommits(precommits) => precommits
+				.iter()
+				.all(|precommit| committed(&self.precommits, encode(precommit))),
+		}
+	}
+
+	/// Commits to the same votes as the other, whichever history they were said to come from.
+	pub fn same_votes(&self, other: &VoteCommitment) -> bool {
+		(&self.prevotes, &self.precommits) == (&other.prevotes, &other.precommits)
+	}
+
+	pub fn sign(self, key: &SecretKey) -> SignedVoteCommitment {
+		let signature = key.sign(&self.responder, &self);
+		SignedVoteCommitment {
+			commitment: self,
+			signature,
+			salts: Default::default(),
+		}
+	}
+}
+
+// Votes are hashed in their JSON encoding, which unlike their `Hash` implementation is the same
+// whatever the toolchain.
+fn encode<T: Serialize>(vote: &T) -> Vec<u8> {
+	serde_json::to_vec(vote).expect("votes always encode")
+}
+
+fn salt(nonce: u64, vote: &[u8]) -> u64 {
+	siphash([nonce, 0], vote)
+}
+
+fn salted_digest(salt: u64, vote: &[u8]) -> u64 {
+	siphash([salt, 1], vote)
+}
+
+/// The salts that the votes of an answer were committed to with, by the unsalted digest of each
+/// vote.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Salts(BTreeMap<u64, u64>);
+
+impl Salts {
+	fn reveal(nonce: u64, answer: &QueryResponse) -> Self {
+		let votes = match answer {
+			QueryResponse::Prevotes(prevotes) => prevotes.iter().map(encode).collect::<Vec<_>>(),
+			QueryResponse::Precommits(precommits) => precommits.iter().map(encode).collect(),
+		};
+		Self(
+			votes
+				.iter()
+				.map(|vote| (siphash([0, 0], vote), salt(nonce, vote)))
+				.collect(),
+		)
+	}
+
+	fn digest(&self, vote: &[u8]) -> Option<u64> {
+		self.0
+			.get(&siphash([0, 0], vote))
+			.map(|salt| salted_digest(*salt, vote))
+	}
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedVoteCommitment {
+	pub commitment: VoteCommitment,
+	pub signature: Signature,
+	// The salts of the votes revealed by the answer the commitment goes with, which aren't part of
+	// what was signed.
+	pub salts: Salts,
+}
+
+impl SignedVoteCommitment {
+	pub fn verify(&self, registry: &KeyRegistry) -> bool {
+		self.signature.signer == self.commitment.responder
+			&& registry.verify(&self.signature, &self.commitment)
+	}
+
+	/// The commitment to go with the answer, with the salts of its votes for the nonce they were
+	/// committed to with.
+	pub fn revealing(self, nonce: u64, answer: &QueryResponse) -> Self {
+		Self {
+			salts: Salts::reveal(nonce, answer),
+			..self
+		}
+	}
+
+	/// Every vote of the answer is one of those committed to, with the salts that came with the
+	/// commitment.
+	pub fn matches(&self, response: &QueryResponse) -> bool {
+		self.commitment.matches(response, &self.salts)
+	}
+}
+
+/// What a responder revealed that contradicts a commitment it signed.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Revealed {
+	Votes(QueryResponse),
+	Commitment(SignedVoteCommitment),
+}
+
+/// Part of a response that is too large to fit in a single message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseChunk {
+	pub index: usize,
+	pub total: usize,
+	pub votes: QueryResponse,
+}
+
 impl QueryResponse {
-	pub fn names(&self) -> Vec<VoterName> {
+	pub fn len(&self) -> usize {
 		match self {
-			QueryResponse::Prevotes(prevotes) => {
-				prevotes.iter().map(|prevote| prevote.id).collect()
-			}
-			QueryResponse::Precommits(precommits) => {
-				precommits.iter().map(|precommit| precommit.id).collect()
-			}
+			QueryResponse::Prevotes(prevotes) => prevotes.len(),
+			QueryResponse::Precommits(precommits) => precommits.len(),
 		}
 	}
 
-	pub fn ids(&self) -> Vec<VoterId> {
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// The voters whose votes are included.
+	pub fn voters(&self) -> BTreeSet<VoterId> {
 		match self {
 			QueryResponse::Prevotes(prevotes) => prevotes
 				.iter()
@@ -102,46 +431,580 @@ impl QueryResponse {
 		}
 	}
 
-	pub fn target_numbers(&self) -> Vec<BlockNumber> {
-		match self {
+	/// Split into chunks of at most `max_votes` votes each.
+	pub fn into_chunks(self, max_votes: usize) -> Vec<ResponseChunk> {
+		let parts: Vec<_> = match self {
 			QueryResponse::Prevotes(prevotes) => prevotes
-				.iter()
-				.map(|prevote| prevote.target_number)
+				.chunks(max_votes)
+				.map(|chunk| QueryResponse::Prevotes(chunk.to_vec()))
 				.collect(),
+			QueryResponse::Precommits(precommits) => precommits
+				.chunks(max_votes)
+				.map(|chunk| QueryResponse::Precommits(chunk.to_vec()))
+				.collect(),
+		};
+		let total = parts.len();
+		parts
+			.into_iter()
+			.enumerate()
+			.map(|(index, votes)| ResponseChunk {
+				index,
+				total,
+				votes,
+			})
+			.collect()
+	}
+
+	// Append the votes of another response of the same kind. Returns false if the kinds differ.
+	fn append(&mut self, other: QueryResponse) -> bool {
+		match (self, other) {
+			(QueryResponse::Prevotes(prevotes), QueryResponse::Prevotes(mut other)) => {
+				prevotes.append(&mut other);
+				true
+			}
+			(QueryResponse::Precommits(precommits), QueryResponse::Precommits(mut other)) => {
+				precommits.append(&mut other);
+				true
+			}
+			_ => false,
+		}
+	}
+
+	pub fn names(&self) -> Vec<VoterName> {
+		match self {
+			QueryResponse::Prevotes(prevotes) => {
+				prevotes.iter().map(|prevote| prevote.id.clone()).collect()
+			}
 			QueryResponse::Precommits(precommits) => precommits
 				.iter()
-				.map(|precommit| precommit.target_number)
+				.map(|precommit| precommit.id.clone())
 				.collect(),
 		}
 	}
+
+	/// The rounds the votes were cast in, other than the given one.
+	pub fn rounds_other_than(&self, round: RoundNumber) -> Vec<RoundNumber> {
+		let rounds: Vec<_> = match self {
+			QueryResponse::Prevotes(prevotes) => prevotes.iter().map(|vote| vote.round).collect(),
+			QueryResponse::Precommits(precommits) => {
+				precommits.iter().map(|vote| vote.round).collect()
+			}
+		};
+		rounds
+			.into_iter()
+			.filter(|vote_round| *vote_round != round)
+			.unique()
+			.sorted()
+			.collect()
+	}
+
+	pub fn ids(&self) -> impl Iterator<Item = &str> + '_ {
+		match self {
+			QueryResponse::Prevotes(prevotes) => {
+				Either::Left(prevotes.iter().map(|prevote| prevote.id.as_str()))
+			}
+			QueryResponse::Precommits(precommits) => {
+				Either::Right(precommits.iter().map(|precommit| precommit.id.as_str()))
+			}
+		}
+	}
+
+	pub fn target_numbers(&self) -> impl Iterator<Item = BlockNumber> + '_ {
+		match self {
+			QueryResponse::Prevotes(prevotes) => {
+				Either::Left(prevotes.iter().map(|prevote| prevote.target_number))
+			}
+			QueryResponse::Precommits(precommits) => {
+				Either::Right(precommits.iter().map(|precommit| precommit.target_number))
+			}
+		}
+	}
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EquivocationDetected {
 	Prevote(Vec<Equivocation>),
 	Precommit(Vec<Equivocation>),
-	InvalidResponse(VoterId),
+	// Found in the two conflicting commits themselves, without asking anyone.
+	CommitEquivocation(Vec<CommitEquivocation>),
+	// Offences that aren't about signed votes carry the voter set of the round in which the
+	// responder cast the vote it was asked to explain, so that they can be attributed to the right
+	// session.
+	InvalidResponse {
+		voter: VoterId,
+		set_id: SetId,
+	},
+	InconsistentTestimony(InconsistentTestimony),
+	// The responder started sending a chunked response but never completed it.
+	PartialResponse {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		received: usize,
+		total: usize,
+	},
+	// The responder passed off votes from other rounds as votes from the round it was asked about.
+	ReplayedVotes {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		replayed_from: Vec<RoundNumber>,
+	},
+	// The responder presented votes signed for another round or voter set alongside those of the
+	// round, which cross-checking would otherwise have taken as votes of a different context.
+	MisplacedVotes {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		votes: Vec<MisplacedVote>,
+	},
+	// The responder sent more votes in a single message than the message budget allows.
+	OversizedResponse {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		votes: usize,
+		limit: usize,
+	},
+	// The responder claimed to have no history for the round, not being a member of its voter set,
+	// while it is.
+	FalseNonMembership {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+	},
+	// The responder's precommit in the round after contradicts the estimate that the precommits it
+	// claimed to have seen in the round give.
+	EstimateInconsistency {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		estimate: BlockNumber,
+		precommit: Precommit,
+	},
+	// The responder revealed votes for the round other than those it committed to, in an answer or
+	// in a second commitment.
+	BrokenCommitment {
+		voter: VoterId,
+		set_id: SetId,
+		round: RoundNumber,
+		committed: Box<SignedVoteCommitment>,
+		revealed: Box<Revealed>,
+	},
+	// The detector published an accusation that the accusation itself shows to be false.
+	FabricatedAccusation {
+		voter: VoterId,
+		set_id: SetId,
+		accusation: Box<EquivocationDetected>,
+	},
 }
 
+/// An internal inconsistency in the state of the protocol, such as a response to a query that was
+/// never sent. These point to bugs in the implementation rather than misbehaving voters.
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolBug(pub String);
+
+impl std::fmt::Display for ProtocolBug {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+// Responses are only checked against the chain once every block they vote for is known to trace
+// back to genesis, so failing to walk the ancestry after that is a bug of our own.
+fn ancestry_bug(error: ChainError) -> ProtocolBug {
+	ProtocolBug(format!("checking a response against the chain: {}", error))
+}
+
+/// How an instance of the protocol concluded, when it didn't run to the detection of equivocations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+	/// The two blocks are on the same chain, so there is no safety violation to investigate.
+	NoConflict {
+		block_not_included: BlockNumber,
+		new_block: BlockNumber,
+	},
+	/// Every peer served ancestry for the block of a commit that disagreed with the commit or with
+	/// our chain, so there was no telling whether it conflicts with the blocks we finalized.
+	UntrustedAncestry {
+		block: BlockNumber,
+		suppliers: Vec<VoterId>,
+	},
+	/// The ancestry of one of the two finalized blocks couldn't be walked in our chain, so there
+	/// was no telling whether they conflict.
+	BrokenAncestry(ChainError),
+}
+
+impl EquivocationDetected {
+	/// The finding against a detector that published the accusation. The fabrication counts as an
+	/// offence in the voter set of the accused, or the first voter set if it accuses nobody.
+	pub fn fabricated(detector: VoterId, accusation: EquivocationDetected) -> Self {
+		let set_id = accusation
+			.offences()
+			.first()
+			.map_or(0, |(set_id, _)| *set_id);
+		EquivocationDetected::FabricatedAccusation {
+			voter: detector,
+			set_id,
+			accusation: Box::new(accusation),
+		}
+	}
+
+	/// The voters proven to have misbehaved.
+	pub fn offenders(&self) -> Vec<VoterId> {
+		self.offences()
+			.into_iter()
+			.map(|(_, voter)| voter)
+			.collect()
+	}
+
+	/// The voters proven to have misbehaved, each with the voter set it misbehaved as a member of.
+	pub fn offences(&self) -> Vec<(SetId, VoterId)> {
+		match self {
+			EquivocationDetected::Prevote(equivocations)
+			| EquivocationDetected::Precommit(equivocations) => equivocations
+				.iter()
+				.map(|equivocation| (equivocation.set_id, equivocation.voter.clone()))
+				.collect(),
+			EquivocationDetected::CommitEquivocation(equivocations) => equivocations
+				.iter()
+				.map(|equivocation| (equivocation.set_id, equivocation.voter.clone()))
+				.collect(),
+			EquivocationDetected::InvalidResponse { voter, set_id }
+			| EquivocationDetected::PartialResponse { voter, set_id, .. }
+			| EquivocationDetected::ReplayedVotes { voter, set_id, .. }
+			| EquivocationDetected::MisplacedVotes { voter, set_id, .. }
+			| EquivocationDetected::OversizedResponse { voter, set_id, .. }
+			| EquivocationDetected::FalseNonMembership { voter, set_id, .. }
+			| EquivocationDetected::EstimateInconsistency { voter, set_id, .. }
+			| EquivocationDetected::BrokenCommitment { voter, set_id, .. }
+			| EquivocationDetected::FabricatedAccusation { voter, set_id, .. } => {
+				vec![(*set_id, voter.clone())]
+			}
+			EquivocationDetected::InconsistentTestimony(testimony) => {
+				vec![(testimony.signed.set_id, testimony.voter.clone())]
+			}
+		}
+	}
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Equivocation {
 	pub voter: VoterId,
+	// The voter set the conflicting votes were cast in, which is the session to slash in.
+	pub set_id: SetId,
 	pub blocks: Vec<BlockNumber>,
 }
 
+/// A voter signed both of two commits for blocks on different forks, with precommits from the same
+/// round.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitEquivocation {
+	pub voter: VoterId,
+	pub set_id: SetId,
+	pub round: RoundNumber,
+	// The blocks the voter precommitted for, in the commit finalized first and then the other.
+	pub targets: [BlockNumber; 2],
+}
+
+impl CommitEquivocation {
+	/// The voters that signed both commits with precommits from the same round of the same voter
+	/// set. Precommits from different rounds for conflicting blocks aren't an offence by
+	/// themselves.
+	pub fn between(first: &Commit, second: &Commit) -> Vec<Self> {
+		first
+			.precommits
+			.iter()
+			.cartesian_product(&second.precommits)
+			.filter(|(a, b)| a.id == b.id && a.set_id == b.set_id && a.round == b.round)
+			.filter(|(a, b)| a.target_number != b.target_number)
+			.map(|(a, b)| CommitEquivocation {
+				voter: a.id.to_string(),
+				set_id: a.set_id,
+				round: a.round,
+				targets: [a.target_number, b.target_number],
+			})
+			.sorted_by(|a, b| a.voter.cmp(&b.voter))
+			.dedup_by(|a, b| a.voter == b.voter)
+			.collect()
+	}
+}
+
+/// A responder explained the estimate with a set of precommits that contradicts its own signed
+/// precommit in the commit for the block that wasn't included.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InconsistentTestimony {
+	pub voter: VoterId,
+	// The round the testimony is about, where the block was finalized.
+	pub round: RoundNumber,
+	// The block finalized by the commit, which the responder's precommit must include.
+	pub finalized: BlockNumber,
+	// The responder's precommit, as part of the commit.
+	pub signed: Precommit,
+	// The precommits the responder claimed to have seen in the same round.
+	pub claimed: Vec<Precommit>,
+}
+
+/// Everything a voter found out by running the protocol, as reported by that voter.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceBundle {
+	pub detector: VoterId,
+	pub equivocations: Vec<EquivocationDetected>,
+	// Who sent the detector the conflicting commits, for the blocks it didn't finalize itself.
+	pub commit_provenance: BTreeMap<BlockNumber, CommitProvenance>,
+}
+
+impl EvidenceBundle {
+	pub fn sign(self, key: &SecretKey) -> SignedEvidenceBundle {
+		let signature = key.sign(&self.detector, &self);
+		SignedEvidenceBundle {
+			bundle: self,
+			signature,
+		}
+	}
+}
+
+/// An evidence bundle signed by the voter that produced it, so that reports can't be attributed
+/// to someone else.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedEvidenceBundle {
+	pub bundle: EvidenceBundle,
+	pub signature: Signature,
+}
+
+impl SignedEvidenceBundle {
+	pub fn verify(&self, registry: &KeyRegistry) -> bool {
+		self.signature.signer == self.bundle.detector
+			&& registry.verify(&self.signature, &self.bundle)
+	}
+
+	/// Verify, subject to injected verification failures.
+	pub fn verify_with_faults(
+		&self,
+		registry: &KeyRegistry,
+		faults: &mut VerificationFaults,
+	) -> bool {
+		self.signature.signer == self.bundle.detector
+			&& faults.verify(registry, &self.signature, &self.bundle)
+	}
+}
+
+/// What a verifier presents to back its queries: the two conflicting commits it is investigating,
+/// with the round each was finalized in.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictStatement {
+	pub detector: VoterId,
+	pub round_for_block_not_included: RoundNumber,
+	pub commit_for_block_not_included: Commit,
+	pub round_for_new_block: RoundNumber,
+	pub commit_for_new_block: Commit,
+}
+
+impl ConflictStatement {
+	pub fn sign(self, key: &SecretKey) -> SignedConflictStatement {
+		let signature = key.sign(&self.detector, &self);
+		SignedConflictStatement {
+			statement: self,
+			signature,
+		}
+	}
+}
+
+/// A conflict statement signed by the verifier, sent along with each of its queries so that the
+/// responder can check the verifier's standing before revealing any votes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedConflictStatement {
+	pub statement: ConflictStatement,
+	pub signature: Signature,
+}
+
+impl SignedConflictStatement {
+	pub fn verify(&self, registry: &KeyRegistry) -> bool {
+		self.signature.signer == self.statement.detector
+			&& registry.verify(&self.signature, &self.statement)
+	}
+
+	pub fn conflict(&self) -> ConflictId {
+		ConflictId::new(
+			self.statement.commit_for_block_not_included.target_number,
+			self.statement.commit_for_new_block.target_number,
+		)
+	}
+
+	// The estimate queries the conflict gives grounds for: those about the block not included, in
+	// the rounds the protocol walks back through.
+	pub fn covers_estimate_query(&self, round: RoundNumber, block: BlockNumber) -> bool {
+		block == self.statement.commit_for_block_not_included.target_number
+			&& round > self.statement.round_for_block_not_included
+			&& round <= self.statement.round_for_new_block
+	}
+
+	// Prevotes are only asked about in the round the block not included was finalized in.
+	pub fn covers_prevote_query(&self, round: RoundNumber) -> bool {
+		round == self.statement.round_for_block_not_included
+	}
+}
+
+/// Why a responder refused to answer a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryRejection {
+	// The sender is neither in the voter set nor a designated observer.
+	NoStanding,
+	// The conflict statement wasn't signed by the sender.
+	BadSignature,
+	// One of the commits isn't signed by a supermajority of the voter set in its round.
+	InvalidCommit(BlockNumber),
+	// The commits finalize blocks on the same branch.
+	NoConflict(BlockNumber, BlockNumber),
+	// The query asks about a round or block the conflict gives no grounds for.
+	OutsideConflict(RoundNumber),
+}
+
+impl std::fmt::Display for QueryRejection {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			QueryRejection::NoStanding => write!(f, "sender has no standing"),
+			QueryRejection::BadSignature => write!(f, "conflict statement not signed by sender"),
+			QueryRejection::InvalidCommit(block) => {
+				write!(f, "commit for block {} lacks a supermajority", block)
+			}
+			QueryRejection::NoConflict(block, other) => {
+				write!(f, "blocks {} and {} don't conflict", block, other)
+			}
+			QueryRejection::OutsideConflict(round) => {
+				write!(f, "round {} is outside the conflict", round)
+			}
+		}
+	}
+}
+
 impl AccountableSafety {
 	pub fn start(
 		block_not_included: BlockNumber,
 		round_for_block_not_included: RoundNumber,
 		commit_for_block_not_included: Commit,
-	) -> Self {
-		Self {
+		new_block: BlockNumber,
+		round_for_new_block: RoundNumber,
+		chain: &Chain,
+	) -> Result<Self, Outcome> {
+		// There is only something to investigate if the two finalized blocks are on different
+		// branches.
+		let conflict = chain
+			.try_blocks_conflict(block_not_included, new_block)
+			.map_err(Outcome::BrokenAncestry)?;
+		if !conflict {
+			return Err(Outcome::NoConflict {
+				block_not_included,
+				new_block,
+			});
+		}
+
+		Ok(Self {
 			block_not_included,
 			round_for_block_not_included,
 			commit_for_block_not_included,
+			new_block,
+			round_for_new_block,
+			new_commit_signers: Default::default(),
 			querying_rounds: Default::default(),
 			prevote_queries: Default::default(),
+			sessions: Default::default(),
+			voter_sets: Default::default(),
+			commit_equivocations: Default::default(),
+			accepted_replies: Default::default(),
+			message_budget: None,
+			unjustified: Default::default(),
+			coverage: Default::default(),
+		})
+	}
+
+	/// Only accept estimates explained with the given kinds of votes, in every round.
+	pub fn with_accepted_replies(mut self, accepted_replies: ReplyKinds) -> Self {
+		self.accepted_replies = accepted_replies;
+		self
+	}
+
+	/// Limit the number of votes in a single message of an explanation, if given. Responders must
+	/// send larger explanations in chunks, and a message over the limit counts against the
+	/// responder.
+	pub fn with_message_budget(mut self, max_votes: Option<usize>) -> Self {
+		self.message_budget = max_votes;
+		self
+	}
+
+	/// The commit for the new block, to tell which of the voters we ask signed it.
+	pub fn with_commit_for_new_block(mut self, commit: &Commit) -> Self {
+		self.new_commit_signers = commit.ids().collect();
+		self
+	}
+
+	/// The voter set in effect from each round on. Without this, every round is taken to be in the
+	/// voter set of the commit for the block not included.
+	pub fn with_sessions(mut self, sessions: BTreeMap<RoundNumber, SetId>) -> Self {
+		self.sessions = sessions;
+		self
+	}
+
+	/// The members of the voter sets we know, to tell voters that legitimately have no history for a
+	/// round from members evading the query.
+	pub fn with_voter_sets(mut self, voter_sets: BTreeMap<SetId, VoterSet>) -> Self {
+		self.voter_sets = voter_sets;
+		self
+	}
+
+	// The voter set the round was voted in.
+	fn set_id_for(&self, round: RoundNumber) -> SetId {
+		self.sessions
+			.range(..=round)
+			.next_back()
+			.map_or(self.commit_for_block_not_included.set_id, |(_, set_id)| {
+				*set_id
+			})
+	}
+
+	/// The conflict this instance is investigating.
+	pub fn conflict(&self) -> ConflictId {
+		ConflictId::new(self.block_not_included, self.new_block)
+	}
+
+	/// Whether we asked the voter to explain the estimate for the round.
+	pub fn asked_about_round(&self, round: RoundNumber, voter: &VoterId) -> bool {
+		self.querying_rounds
+			.get(&round)
+			.is_some_and(|state| state.voters.contains(voter))
+	}
+
+	/// Whether any voter explained the estimate for the round with a valid response.
+	pub fn has_valid_response(&self, round: RoundNumber) -> bool {
+		self.querying_rounds
+			.get(&round)
+			.is_some_and(|state| !state.responses.is_empty())
+	}
+
+	/// Whether we asked the voter which prevotes it saw in the round.
+	pub fn asked_about_prevotes(&self, round: RoundNumber, voter: &VoterId) -> bool {
+		self.prevote_queries
+			.get(&round)
+			.is_some_and(|state| state.voters.contains(voter))
+	}
+
+	/// Record the voters that signed both conflicting commits in the same round, and check whether
+	/// there are more than `max_faulty` of them, in which case there's no need to query anyone.
+	pub fn check_common_signers(&mut self, new_commit: &Commit, max_faulty: usize) -> bool {
+		let equivocations =
+			CommitEquivocation::between(&self.commit_for_block_not_included, new_commit);
+		if equivocations.is_empty() {
+			return false;
+		}
+		let offenders = equivocations.len();
+		self.commit_equivocations
+			.push(EquivocationDetected::CommitEquivocation(equivocations));
+		self.coverage.hit(Branch::CommitEquivocation);
+		if offenders <= max_faulty {
+			return false;
 		}
+		self.coverage.hit(Branch::CommonSigners);
+		true
 	}
 
 	// Ask the question why the estimate for the previous round didn't include the earlier block
@@ -151,16 +1014,21 @@ impl AccountableSafety {
 			round,
 			QueryState {
 				round,
-				voters: voters.clone(),
+				// Sorted, as needed to check the responses.
+				voters: voters.iter().cloned().sorted().dedup().collect(),
 				responses: Default::default(),
+				partial_responses: Default::default(),
 				equivocations: Default::default(),
+				verdicts: Default::default(),
+				commitments: Default::default(),
 			},
 		);
 
 		Query {
 			round,
 			receivers: voters,
-			block_not_included: self.block_not_included,
+			conflict: self.conflict(),
+			accepted_replies: self.accepted_replies,
 		}
 	}
 
@@ -176,37 +1044,141 @@ impl AccountableSafety {
 				round,
 				voters: voters.clone(),
 				responses: Default::default(),
+				partial_responses: Default::default(),
 				equivocations: Default::default(),
+				verdicts: Default::default(),
+				commitments: Default::default(),
 			},
 		);
 
 		PrevoteQuery {
 			round,
 			receivers: voters,
+			conflict: self.conflict(),
 		}
 	}
 
+	/// Add a response sent in a single message.
 	pub fn add_response(
 		&mut self,
 		round: RoundNumber,
 		voter: VoterId,
 		query_response: QueryResponse,
+		commitment: SignedVoteCommitment,
 		chain: &Chain,
-	) -> Option<NextQuery> {
+	) -> Result<Option<NextQuery>, ProtocolBug> {
+		if self.exceeds_message_budget(round, &voter, query_response.len()) {
+			return Ok(None);
+		}
+		self.handle_response(round, voter, query_response, commitment, chain)
+	}
+
+	fn handle_response(
+		&mut self,
+		round: RoundNumber,
+		voter: VoterId,
+		query_response: QueryResponse,
+		commitment: SignedVoteCommitment,
+		chain: &Chain,
+	) -> Result<Option<NextQuery>, ProtocolBug> {
+		// The responder answers for its vote in the round, so that's the session it's accountable in.
+		let set_id = self.set_id_for(round);
+		let broken = self.broken_commitment(&voter, &query_response, &commitment);
 		// Add response to the right QueryState in querying_rounds.
 		{
-			let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+			let querying_state = self.querying_rounds.get_mut(&round).ok_or_else(|| {
+				ProtocolBug(format!(
+					"response from {} about round {} which was never queried",
+					voter, round
+				))
+			})?;
 			let voters = querying_state.voters.clone();
-			if let Some(invalid_response) = check_query_reply_is_valid(
-				&query_response,
-				self.block_not_included,
-				&voters,
-				&chain,
-			) {
-				querying_state.equivocations.push(invalid_response);
-				return None;
+			// The estimate for the round is explained with the votes of the round before.
+			let replayed_from = query_response.rounds_other_than(round - 1);
+			if !replayed_from.is_empty() {
+				querying_state.reject(&voter, InvalidReason::ReplayedVotes);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::ReplayedVotes {
+						voter,
+						set_id,
+						round: round - 1,
+						replayed_from,
+					});
+				self.coverage.hit(Branch::ReplayedVotes);
+				return Ok(None);
+			}
+			// We can't tell what votes for blocks we don't know, or can't trace back to genesis,
+			// include, so they explain nothing. Nor are they proof of anything against the
+			// responder. Past this, walking the ancestry of the blocks voted for can't fail.
+			if let Some(block) = query_response
+				.target_numbers()
+				.find(|target| chain.try_block_height(*target).is_err())
+			{
+				querying_state.reject(&voter, InvalidReason::UnknownBlock(block));
+				self.coverage.hit(Branch::UnknownBlockInResponse);
+				return Ok(None);
+			}
+			let explains_estimate = self.accepted_replies.accepts(&query_response)
+				&& check_query_reply_is_valid(
+					&query_response,
+					self.block_not_included,
+					&voters,
+					chain,
+				)
+				.map_err(ancestry_bug)?
+				.is_none();
+			if !explains_estimate {
+				querying_state.reject(&voter, InvalidReason::DoesNotExplainEstimate);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::InvalidResponse { voter, set_id });
+				self.coverage.hit(Branch::InvalidResponse);
+				return Ok(None);
+			} else if querying_state.round == self.round_for_new_block
+				&& !query_reply_is_consistent_with_commit(
+					&query_response,
+					self.new_block,
+					&voters,
+					chain,
+				)
+				.map_err(ancestry_bug)?
+			{
+				// The responder precommitted for the new block, so the votes it claims to have
+				// seen in the preceding round must not rule that block out.
+				querying_state.reject(&voter, InvalidReason::ContradictsCommit);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::InvalidResponse { voter, set_id });
+				self.coverage.hit(Branch::InvalidResponse);
+				return Ok(None);
+			} else if let Some((committed, revealed)) = broken {
+				querying_state.reject(&voter, InvalidReason::BrokenCommitment);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::BrokenCommitment {
+						voter,
+						set_id,
+						round: round - 1,
+						committed: Box::new(committed),
+						revealed: Box::new(revealed),
+					});
+				self.coverage.hit(Branch::BrokenCommitment);
+				return Ok(None);
 			} else {
-				querying_state.add_response(voter, query_response.clone());
+				querying_state.add_response(voter.clone(), query_response.clone(), commitment);
+			}
+		}
+
+		if let QueryResponse::Precommits(precommits) = &query_response {
+			if let Some(inconsistency) = self
+				.audit_estimate(round, &voter, precommits, chain)
+				.map_err(ancestry_bug)?
+			{
+				self.coverage.hit(Branch::EstimateInconsistency);
+				let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+				querying_state.reject(&voter, InvalidReason::EstimateInconsistency);
+				querying_state.equivocations.push(inconsistency);
 			}
 		}
 
@@ -215,20 +1187,37 @@ impl AccountableSafety {
 		if round == self.round_for_block_not_included + 1 {
 			match query_response {
 				QueryResponse::Precommits(precommits) => {
-					if let Some(equivocations) = cross_check_votes(
+					self.coverage.hit(Branch::PrecommitCrossCheck);
+					if let Some(testimony) = self
+						.check_testimony(&voter, &precommits, chain)
+						.map_err(ancestry_bug)?
+					{
+						self.coverage.hit(Branch::InconsistentTestimony);
+						let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+						querying_state.reject(&voter, InvalidReason::InconsistentTestimony);
+						querying_state
+							.equivocations
+							.push(EquivocationDetected::InconsistentTestimony(testimony));
+					}
+					let cross_check = cross_check_votes_in_round(
+						self.set_id_for(round - 1),
+						round - 1,
 						precommits,
 						self.commit_for_block_not_included.precommits.clone(),
-					) {
-						self.querying_rounds
-							.get_mut(&round)
-							.unwrap()
+					);
+					let misplaced = self.misplaced_votes(round, &voter, cross_check.misplaced);
+					if !cross_check.equivocations.is_empty() {
+						let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+						querying_state.revealed_equivocations(&voter);
+						querying_state
 							.equivocations
-							.push(EquivocationDetected::Precommit(equivocations));
-					} else {
-						panic!(
-							"Reaching the end of the accountable safety protocol without \
-							finding any equivocators!"
-						);
+							.push(EquivocationDetected::Precommit(cross_check.equivocations));
+					} else if !misplaced {
+						return Err(ProtocolBug(
+							"reached the end of the accountable safety protocol without finding \
+							any equivocators"
+								.to_string(),
+						));
 					}
 				}
 				QueryResponse::Prevotes(_) => {
@@ -242,10 +1231,12 @@ impl AccountableSafety {
 					{
 						let voters_in_commit: Vec<VoterId> =
 							self.commit_for_block_not_included.ids().collect();
+						self.coverage.hit(Branch::PrevoteQuery);
 
-						return Some(NextQuery::PrevotesForRound(
-							self.start_prevote_query(next_round_to_investigate, voters_in_commit),
-						));
+						return Ok(Some(NextQuery::PrevotesForRound(self.start_prevote_query(
+							next_round_to_investigate,
+							voters_in_commit,
+						))));
 					}
 				}
 			}
@@ -258,14 +1249,379 @@ impl AccountableSafety {
 				.querying_rounds
 				.contains_key(&next_round_to_investigate)
 			{
-				let voters_in_precommits = query_response.ids().into_iter().unique().collect();
-				return Some(NextQuery::AskAboutRound(
-					self.start_query_round(next_round_to_investigate, voters_in_precommits),
-				));
+				let voters_in_precommits =
+					query_response.ids().unique().map(String::from).collect();
+				self.coverage.hit(Branch::WalkBack);
+				return Ok(Some(NextQuery::AskAboutRound(self.start_query_round(
+					next_round_to_investigate,
+					voters_in_precommits,
+				))));
 			}
 		}
 
-		None
+		Ok(None)
+	}
+
+	/// Handle a voter answering that it has no history for the round before, since it wasn't a
+	/// member of the voter set then. Members of that voter set must explain their votes, so the
+	/// claim counts against them. Claims we can't check, not knowing the voter set, are taken at
+	/// their word.
+	pub fn add_non_member_response(
+		&mut self,
+		round: RoundNumber,
+		voter: VoterId,
+	) -> Result<(), ProtocolBug> {
+		let set_id = self.set_id_for(round - 1);
+		let member = self
+			.voter_sets
+			.get(&set_id)
+			.is_some_and(|voter_set| voter_set.is_member(&voter));
+		let querying_state = self.querying_rounds.get_mut(&round).ok_or_else(|| {
+			ProtocolBug(format!(
+				"non-membership claim from {} about round {} which was never queried",
+				voter, round
+			))
+		})?;
+		if querying_state.responses.contains_key(&voter) {
+			return Ok(());
+		}
+		self.coverage.hit(Branch::NonMemberResponse);
+		if member {
+			querying_state.reject(&voter, InvalidReason::FalseNonMembership);
+			querying_state
+				.equivocations
+				.push(EquivocationDetected::FalseNonMembership {
+					voter,
+					set_id,
+					round: round - 1,
+				});
+		} else {
+			querying_state.verdicts.insert(voter, Verdict::NotAMember);
+		}
+		Ok(())
+	}
+
+	/// Handle a voter answering that it has lost its votes for the round before. That's no proof of
+	/// anything, but if its precommit is in one of the conflicting commits, the precommit goes
+	/// unjustified, which is recorded.
+	pub fn add_lost_history_response(
+		&mut self,
+		round: RoundNumber,
+		voter: VoterId,
+	) -> Result<(), ProtocolBug> {
+		let set_id = self.set_id_for(round - 1);
+		let commit = if self
+			.commit_for_block_not_included
+			.ids()
+			.any(|id| id == voter)
+		{
+			Some(self.block_not_included)
+		} else if round == self.round_for_new_block && self.new_commit_signers.contains(&voter) {
+			Some(self.new_block)
+		} else {
+			None
+		};
+		let querying_state = self.querying_rounds.get_mut(&round).ok_or_else(|| {
+			ProtocolBug(format!(
+				"lost history claim from {} about round {} which was never queried",
+				voter, round
+			))
+		})?;
+		if querying_state.responses.contains_key(&voter) {
+			return Ok(());
+		}
+		self.coverage.hit(Branch::LostHistory);
+		querying_state
+			.verdicts
+			.insert(voter.clone(), Verdict::LostHistory);
+		if let Some(commit) = commit {
+			let participation = UnjustifiedCommitParticipation {
+				voter,
+				set_id,
+				round: round - 1,
+				commit,
+			};
+			if !self.unjustified.contains(&participation) {
+				self.unjustified.push(participation);
+			}
+		}
+		Ok(())
+	}
+
+	/// Signers of the conflicting commits that answered they had lost their votes.
+	pub fn unjustified_participations(&self) -> &[UnjustifiedCommitParticipation] {
+		&self.unjustified
+	}
+
+	/// Handle a voter refusing to answer our query, its policy being not to serve it to us. It isn't
+	/// evading the question, so isn't held to account for it, but it explains nothing either.
+	pub fn add_policy_refusal(
+		&mut self,
+		round: RoundNumber,
+		voter: VoterId,
+		prevotes: bool,
+	) -> Result<(), ProtocolBug> {
+		let queries = if prevotes {
+			&mut self.prevote_queries
+		} else {
+			&mut self.querying_rounds
+		};
+		let querying_state = queries.get_mut(&round).ok_or_else(|| {
+			ProtocolBug(format!(
+				"refusal from {} about round {} which was never queried",
+				voter, round
+			))
+		})?;
+		if querying_state.responses.contains_key(&voter) {
+			return Ok(());
+		}
+		self.coverage.hit(Branch::PolicyRefusal);
+		querying_state.verdicts.insert(voter, Verdict::Refused);
+		Ok(())
+	}
+
+	/// Add one chunk of a response. Chunks are checked as they come in, and once all of them have
+	/// arrived the combined response is handled like any other.
+	pub fn add_response_chunk(
+		&mut self,
+		round: RoundNumber,
+		voter: VoterId,
+		chunk: ResponseChunk,
+		commitment: SignedVoteCommitment,
+		chain: &Chain,
+	) -> Result<Option<NextQuery>, ProtocolBug> {
+		let set_id = self.set_id_for(round);
+		let querying_state = self.querying_rounds.get_mut(&round).ok_or_else(|| {
+			ProtocolBug(format!(
+				"response chunk from {} about round {} which was never queried",
+				voter, round
+			))
+		})?;
+		if querying_state.responses.contains_key(&voter) {
+			return Ok(None);
+		}
+		self.coverage.hit(Branch::ChunkedResponse);
+		if self.exceeds_message_budget(round, &voter, chunk.votes.len()) {
+			self.querying_rounds
+				.get_mut(&round)
+				.unwrap()
+				.partial_responses
+				.remove(&voter);
+			return Ok(None);
+		}
+		let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+
+		let partial = querying_state
+			.partial_responses
+			.entry(voter.clone())
+			.or_insert_with(|| PartialResponse {
+				total: chunk.total,
+				chunks: Default::default(),
+			});
+		let same_kind = partial.chunks.values().next().is_none_or(|first| {
+			std::mem::discriminant(first) == std::mem::discriminant(&chunk.votes)
+		});
+		let well_formed = chunk.index < chunk.total
+			&& chunk.total == partial.total
+			&& !partial.chunks.contains_key(&chunk.index)
+			&& same_kind;
+		if !well_formed {
+			querying_state.partial_responses.remove(&voter);
+			querying_state.reject(&voter, InvalidReason::MalformedChunk);
+			querying_state
+				.equivocations
+				.push(EquivocationDetected::InvalidResponse { voter, set_id });
+			self.coverage.hit(Branch::InvalidResponse);
+			return Ok(None);
+		}
+
+		partial.chunks.insert(chunk.index, chunk.votes);
+		if partial.chunks.len() < partial.total {
+			return Ok(None);
+		}
+
+		let partial = querying_state.partial_responses.remove(&voter).unwrap();
+		let mut chunks = partial.chunks.into_values();
+		let mut response = chunks.next().unwrap();
+		for chunk in chunks {
+			response.append(chunk);
+		}
+		self.handle_response(round, voter, response, commitment, chain)
+	}
+
+	pub fn has_partial_response(&self, round: RoundNumber, voter: &VoterId) -> bool {
+		self.querying_rounds
+			.get(&round)
+			.is_some_and(|state| state.partial_responses.contains_key(voter))
+	}
+
+	/// Give up on a chunked response that hasn't been completed, which counts against the
+	/// responder.
+	pub fn expire_partial_response(&mut self, round: RoundNumber, voter: &VoterId) {
+		let set_id = self.set_id_for(round);
+		let querying_state = match self.querying_rounds.get_mut(&round) {
+			Some(querying_state) => querying_state,
+			None => return,
+		};
+		if let Some(partial) = querying_state.partial_responses.remove(voter) {
+			querying_state.reject(voter, InvalidReason::Incomplete);
+			querying_state
+				.equivocations
+				.push(EquivocationDetected::PartialResponse {
+					voter: voter.clone(),
+					set_id,
+					round,
+					received: partial.chunks.len(),
+					total: partial.total,
+				});
+			self.coverage.hit(Branch::PartialResponse);
+		}
+	}
+
+	/// Give up waiting for the voter to answer our query about the round. Silence proves nothing,
+	/// but the query no longer holds up the instance from concluding.
+	pub fn expire_query(&mut self, round: RoundNumber, voter: &VoterId, prevotes: bool) {
+		let queries = if prevotes {
+			&mut self.prevote_queries
+		} else {
+			&mut self.querying_rounds
+		};
+		let querying_state = match queries.get_mut(&round) {
+			Some(querying_state) => querying_state,
+			None => return,
+		};
+		if querying_state.voters.contains(voter) && !querying_state.verdicts.contains_key(voter) {
+			querying_state
+				.verdicts
+				.insert(voter.clone(), Verdict::Unresponsive);
+			self.coverage.hit(Branch::QueryExpired);
+		}
+	}
+
+	// Record an offence if a single message from the responder has more votes than allowed.
+	fn exceeds_message_budget(
+		&mut self,
+		round: RoundNumber,
+		voter: &VoterId,
+		votes: usize,
+	) -> bool {
+		let limit = match self.message_budget {
+			Some(limit) if votes > limit => limit,
+			_ => return false,
+		};
+		let set_id = self.set_id_for(round);
+		let querying_state = match self.querying_rounds.get_mut(&round) {
+			Some(querying_state) => querying_state,
+			// Reported when handling the response.
+			None => return false,
+		};
+		querying_state.reject(voter, InvalidReason::Oversized);
+		querying_state
+			.equivocations
+			.push(EquivocationDetected::OversizedResponse {
+				voter: voter.clone(),
+				set_id,
+				round,
+				votes,
+				limit,
+			});
+		self.coverage.hit(Branch::OversizedResponse);
+		true
+	}
+
+	// Hold the responder to its commitments: the votes it answers with must be those it committed
+	// to, and it mustn't have committed to other votes for the same round in an earlier answer.
+	fn broken_commitment(
+		&self,
+		voter: &VoterId,
+		answer: &QueryResponse,
+		commitment: &SignedVoteCommitment,
+	) -> Option<(SignedVoteCommitment, Revealed)> {
+		if !commitment.matches(answer) {
+			return Some((commitment.clone(), Revealed::Votes(answer.clone())));
+		}
+		self.querying_rounds
+			.values()
+			.chain(self.prevote_queries.values())
+			.filter_map(|state| state.commitments.get(voter))
+			.find(|earlier| {
+				earlier.commitment.round == commitment.commitment.round
+					&& !earlier.commitment.same_votes(&commitment.commitment)
+			})
+			.map(|earlier| (earlier.clone(), Revealed::Commitment(commitment.clone())))
+	}
+
+	// A responder that signed the commit for the block not included must have seen its own
+	// precommit for that block, so leaving it out or claiming a different one is a lie.
+	fn check_testimony(
+		&self,
+		voter: &str,
+		claimed: &[Precommit],
+		chain: &Chain,
+	) -> Result<Option<InconsistentTestimony>, ChainError> {
+		let Some(signed) = self
+			.commit_for_block_not_included
+			.precommits
+			.iter()
+			.find(|precommit| precommit.id == voter)
+		else {
+			return Ok(None);
+		};
+		for precommit in claimed.iter().filter(|precommit| precommit.id == voter) {
+			if chain.try_block_includes(precommit.target_number, self.block_not_included)? {
+				return Ok(None);
+			}
+		}
+		Ok(Some(InconsistentTestimony {
+			voter: voter.to_string(),
+			round: self.round_for_block_not_included,
+			finalized: self.block_not_included,
+			signed: signed.clone(),
+			claimed: claimed.to_vec(),
+		}))
+	}
+
+	// Recompute the lowest the estimate of the round before could have been for the responder, given
+	// the precommits it explained it with, and check that its own precommit in the round, as the
+	// responders for the round after claimed to have seen it, includes that.
+	fn audit_estimate(
+		&self,
+		round: RoundNumber,
+		voter: &VoterId,
+		claimed: &[Precommit],
+		chain: &Chain,
+	) -> Result<Option<EquivocationDetected>, ChainError> {
+		let (Some(querying_state), Some(next_state)) = (
+			self.querying_rounds.get(&round),
+			self.querying_rounds.get(&(round + 1)),
+		) else {
+			return Ok(None);
+		};
+		let Some(estimate) = estimate_lower_bound(claimed, voter, &querying_state.voters, chain)?
+		else {
+			return Ok(None);
+		};
+		let precommits = next_state
+			.responses
+			.values()
+			.filter_map(|response| match response {
+				QueryResponse::Precommits(precommits) => Some(precommits),
+				QueryResponse::Prevotes(_) => None,
+			})
+			.flatten()
+			.filter(|precommit| precommit.id == voter.as_str() && precommit.round == round);
+		for precommit in precommits {
+			if !chain.try_block_includes(precommit.target_number, estimate)? {
+				return Ok(Some(EquivocationDetected::EstimateInconsistency {
+					voter: voter.clone(),
+					set_id: self.set_id_for(round),
+					round: round - 1,
+					estimate,
+					precommit: precommit.clone(),
+				}));
+			}
+		}
+		Ok(None)
 	}
 
 	pub fn add_prevote_response(
@@ -273,53 +1629,156 @@ impl AccountableSafety {
 		round: RoundNumber,
 		voter: VoterId,
 		query_response: QueryResponse,
-	) -> Option<NextQuery> {
+		commitment: SignedVoteCommitment,
+	) -> Result<Option<NextQuery>, ProtocolBug> {
+		// The prevotes explain the responder's precommit in the round after.
+		let set_id = self.set_id_for(round + 1);
+		let broken = self.broken_commitment(&voter, &query_response, &commitment);
 		// Add the response first
 		{
-			let querying_state = self.prevote_queries.get_mut(&round).unwrap();
-			querying_state.add_response(voter, query_response.clone());
+			let querying_state = self.prevote_queries.get_mut(&round).ok_or_else(|| {
+				ProtocolBug(format!(
+					"prevotes from {} for round {} which was never queried",
+					voter, round
+				))
+			})?;
+			let replayed_from = query_response.rounds_other_than(round);
+			if !replayed_from.is_empty() {
+				querying_state.reject(&voter, InvalidReason::ReplayedVotes);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::ReplayedVotes {
+						voter,
+						set_id,
+						round,
+						replayed_from,
+					});
+				self.coverage.hit(Branch::ReplayedVotes);
+				return Ok(None);
+			}
+			if let Some((committed, revealed)) = broken {
+				querying_state.reject(&voter, InvalidReason::BrokenCommitment);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::BrokenCommitment {
+						voter,
+						set_id,
+						round,
+						committed: Box::new(committed),
+						revealed: Box::new(revealed),
+					});
+				self.coverage.hit(Branch::BrokenCommitment);
+				return Ok(None);
+			}
+			// Precommits say nothing about the prevotes seen, so they don't answer the query.
+			if let QueryResponse::Precommits(_) = query_response {
+				querying_state.reject(&voter, InvalidReason::WrongKind);
+				querying_state
+					.equivocations
+					.push(EquivocationDetected::InvalidResponse { voter, set_id });
+				self.coverage.hit(Branch::InvalidResponse);
+				return Ok(None);
+			}
+			querying_state.add_response(voter.clone(), query_response.clone(), commitment);
 		}
 
 		match query_response {
 			QueryResponse::Prevotes(prevotes) => {
 				let previous_round = round + 1;
-				let previous_responses = self.querying_rounds.get(&previous_round).unwrap();
+				let previous_responses =
+					self.querying_rounds.get(&previous_round).ok_or_else(|| {
+						ProtocolBug(format!(
+							"prevote query for round {} without a query for round {}",
+							round, previous_round
+						))
+					})?;
 				let previous_prevote_replies = previous_responses
 					.responses
 					.iter()
-					.flat_map(|response| match response.1 {
-						QueryResponse::Precommits(_) => panic!(),
-						QueryResponse::Prevotes(prevotes) => prevotes,
+					.filter_map(|response| match response.1 {
+						// Responders that explained with precommits are not part of the prevote
+						// cross-check.
+						QueryResponse::Precommits(_) => None,
+						QueryResponse::Prevotes(prevotes) => Some(prevotes),
 					})
+					.flatten()
 					.cloned()
 					.collect();
 
-				if let Some(equivocations) = cross_check_votes(prevotes, previous_prevote_replies) {
-					self.prevote_queries
-						.get_mut(&round)
-						.unwrap()
+				self.coverage.hit(Branch::PrevoteCrossCheck);
+				let cross_check = cross_check_votes_in_round(
+					self.set_id_for(round),
+					round,
+					prevotes,
+					previous_prevote_replies,
+				);
+				if !cross_check.misplaced.is_empty() {
+					let querying_state = self.prevote_queries.get_mut(&round).unwrap();
+					querying_state.reject(&voter, InvalidReason::MisplacedVotes);
+					querying_state
 						.equivocations
-						.push(EquivocationDetected::Prevote(equivocations));
-				} else {
-					panic!(
-						"Reaching the end of the accountable safety protocol without \
-						finding any equivocators!"
-					);
+						.push(EquivocationDetected::MisplacedVotes {
+							voter: voter.clone(),
+							set_id,
+							round,
+							votes: cross_check.misplaced,
+						});
+					self.coverage.hit(Branch::MisplacedVotes);
 				}
+				if !cross_check.equivocations.is_empty() {
+					let querying_state = self.prevote_queries.get_mut(&round).unwrap();
+					querying_state.revealed_equivocations(&voter);
+					querying_state
+						.equivocations
+						.push(EquivocationDetected::Prevote(cross_check.equivocations));
+				}
+				// An equivocator can answer with the prevotes it already revealed, which proves
+				// nothing. Honest responders are left to reveal the equivocations.
 			}
-			QueryResponse::Precommits(_) => {
-				panic!("This is an invalid response! Malicious voter?")
-			}
+			// Rejected above.
+			QueryResponse::Precommits(_) => {}
 		}
-		None
+		Ok(None)
+	}
+
+	// Reject the response for presenting votes of other contexts as votes of the round before the
+	// one asked about, if it did. Returns whether it did.
+	fn misplaced_votes(
+		&mut self,
+		round: RoundNumber,
+		voter: &VoterId,
+		misplaced: Vec<MisplacedVote>,
+	) -> bool {
+		if misplaced.is_empty() {
+			return false;
+		}
+		self.coverage.hit(Branch::MisplacedVotes);
+		let set_id = self.set_id_for(round - 1);
+		let querying_state = self.querying_rounds.get_mut(&round).unwrap();
+		querying_state.reject(voter, InvalidReason::MisplacedVotes);
+		querying_state
+			.equivocations
+			.push(EquivocationDetected::MisplacedVotes {
+				voter: voter.clone(),
+				set_id,
+				round,
+				votes: misplaced,
+			});
+		true
+	}
+
+	pub fn coverage(&self) -> Coverage {
+		self.coverage
 	}
 
 	pub fn equivocations_detected(&self) -> Vec<EquivocationDetected> {
-		let mut equivocations: Vec<_> = self
+		let mut equivocations: Vec<_> = self.commit_equivocations.clone();
+		let mut query_equivocations = self
 			.querying_rounds
 			.values()
 			.flat_map(|query_state| query_state.equivocations.clone())
 			.collect();
+		equivocations.append(&mut query_equivocations);
 
 		let mut prevote_equivocations = self
 			.prevote_queries
@@ -330,4 +1789,100 @@ impl AccountableSafety {
 		equivocations.append(&mut prevote_equivocations);
 		equivocations
 	}
+
+	/// The rounds to walk back through, down to the one right after the block not included was
+	/// finalized, and whom we would ask about each, without asking anyone. Rounds already queried
+	/// list the voters asked. Each round after that lists the voters named in the responses so far
+	/// for the round before it in the walk, or failing those, the voters asked about it. Empty if
+	/// the commits alone were proof enough.
+	pub fn plan(&self) -> Vec<PlannedRound> {
+		let mut plan = Vec::new();
+		let mut expected: Option<Vec<VoterId>> = None;
+		for round in (self.round_for_block_not_included + 1..=self.round_for_new_block).rev() {
+			let planned = match (self.querying_rounds.get(&round), expected.take()) {
+				(Some(state), _) => PlannedRound {
+					round,
+					voters: state.voters.clone(),
+					started: true,
+				},
+				(None, Some(voters)) => PlannedRound {
+					round,
+					voters,
+					started: false,
+				},
+				(None, None) => break,
+			};
+			let named: Vec<VoterId> = self
+				.querying_rounds
+				.get(&round)
+				.into_iter()
+				.flat_map(|state| state.responses.values())
+				.flat_map(|response| response.ids())
+				.map(String::from)
+				.sorted()
+				.dedup()
+				.collect();
+			expected = Some(if named.is_empty() {
+				planned.voters.clone()
+			} else {
+				named
+			});
+			plan.push(planned);
+		}
+		plan
+	}
+
+	/// Whether the instance has run its course, with a verdict on every voter it asked, even if
+	/// it already proved someone to have misbehaved.
+	pub fn concluded(&self) -> bool {
+		self.querying_rounds
+			.values()
+			.chain(self.prevote_queries.values())
+			.all(|query_state| {
+				query_state
+					.voters
+					.iter()
+					.all(|voter| query_state.verdicts.contains_key(voter))
+			})
+	}
+
+	/// The verdict on every voter asked about each round, first the rounds whose estimates were
+	/// asked about and then those whose prevotes were.
+	pub fn verdicts(&self) -> Vec<RoundVerdicts> {
+		let estimate_queries = self.querying_rounds.values().map(|query| (false, query));
+		let prevote_queries = self.prevote_queries.values().map(|query| (true, query));
+		estimate_queries
+			.chain(prevote_queries)
+			.map(|(prevotes, query_state)| query_state.round_verdicts(prevotes))
+			.collect()
+	}
+
+	/// The voters we queried that weren't found to have misbehaved, with the responses they gave.
+	pub fn exonerations(&self) -> Vec<Exoneration> {
+		let offenders: BTreeSet<_> = self
+			.equivocations_detected()
+			.iter()
+			.flat_map(|equivocation| equivocation.offenders())
+			.collect();
+		let mut exonerations: BTreeMap<VoterId, Exoneration> = BTreeMap::new();
+		let estimate_queries = self.querying_rounds.iter().map(|query| (false, query));
+		let prevote_queries = self.prevote_queries.iter().map(|query| (true, query));
+		for (prevotes, (round, query_state)) in estimate_queries.chain(prevote_queries) {
+			for (voter, response) in &query_state.responses {
+				if offenders.contains(voter) {
+					continue;
+				}
+				let exoneration = exonerations
+					.entry(voter.clone())
+					.or_insert_with(|| Exoneration::new(voter.clone()));
+				let responses = if prevotes {
+					&mut exoneration.prevotes_seen
+				} else {
+					&mut exoneration.explanations
+				};
+				responses.insert(*round, response.clone());
+			}
+		}
+		exonerations.into_values().collect()
+	}
 }
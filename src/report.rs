@@ -0,0 +1,931 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Reports on the state of voters, for printing and for asserting on in tests.
+
+use crate::{
+	block::BlockNumber,
+	inference::EvidenceChain,
+	protocol::{EquivocationDetected, QueryResponse},
+	voter::VoterId,
+	voting::{Commit, RoundNumber, SetId},
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+	cmp::Ordering,
+	collections::{BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+};
+
+/// A block finalized by a voter, together with the round and the precommits that finalized it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalizedBlock {
+	pub block: BlockNumber,
+	pub round: Option<RoundNumber>,
+	pub signers: Vec<VoterId>,
+}
+
+impl FinalizedBlock {
+	pub fn new(commit: &Commit, round: Option<RoundNumber>) -> Self {
+		Self {
+			block: commit.target_number,
+			round,
+			signers: commit.ids().collect(),
+		}
+	}
+}
+
+impl Display for FinalizedBlock {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(f, "block {} finalized ", self.block)?;
+		if let Some(round) = self.round {
+			write!(f, "in round {} ", round)?;
+		}
+		write!(f, "by {{ {} }}", self.signers.iter().format(", "))
+	}
+}
+
+/// The commits known to a voter, ordered by block number.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitReport {
+	pub voter: VoterId,
+	pub finalized: Vec<FinalizedBlock>,
+}
+
+impl CommitReport {
+	pub fn finalized_blocks(&self) -> Vec<BlockNumber> {
+		self.finalized.iter().map(|f| f.block).collect()
+	}
+}
+
+impl Display for CommitReport {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		writeln!(f, "{}:", self.voter)?;
+		for finalized in &self.finalized {
+			writeln!(f, "  {}", finalized)?;
+		}
+		Ok(())
+	}
+}
+
+/// Whether the honest voters all had a complete proof within the deadline after the first
+/// detected conflict. A proof is complete once it names at least f + 1 offenders.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadlineReport {
+	pub deadline: usize,
+	// The tick at which the first voter detected conflicting commits.
+	pub first_detection: Option<usize>,
+	// The tick at which each honest voter first had a complete proof.
+	pub completed: BTreeMap<VoterId, Option<usize>>,
+}
+
+impl DeadlineReport {
+	/// Honest voters without a complete proof by the deadline.
+	pub fn violations(&self) -> Vec<&VoterId> {
+		let first_detection = match self.first_detection {
+			Some(tick) => tick,
+			None => return Vec::new(),
+		};
+		self.completed
+			.iter()
+			.filter(|(_, completed)| {
+				completed.is_none_or(|tick| tick > first_detection + self.deadline)
+			})
+			.map(|(voter, _)| voter)
+			.collect()
+	}
+
+	pub fn met(&self) -> bool {
+		self.violations().is_empty()
+	}
+
+	/// Ticks from the first detection until the last honest voter had a complete proof.
+	pub fn latency(&self) -> Option<usize> {
+		let first_detection = self.first_detection?;
+		self.completed
+			.values()
+			.map(|completed| completed.map(|tick| tick - first_detection))
+			.collect::<Option<Vec<_>>>()?
+			.into_iter()
+			.max()
+	}
+}
+
+impl Display for DeadlineReport {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match (self.first_detection, self.met()) {
+			(None, _) => write!(f, "no conflict detected"),
+			(Some(_), true) => write!(
+				f,
+				"deadline of {} ticks met, latency {} ticks",
+				self.deadline,
+				self.latency().unwrap_or_default()
+			),
+			(Some(_), false) => write!(
+				f,
+				"deadline of {} ticks missed by {{ {} }}",
+				self.deadline,
+				self.violations().iter().format(", ")
+			),
+		}
+	}
+}
+
+/// Why a run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+	// Nothing was left to do, and every protocol instance started had run its course.
+	Concluded,
+	// Nothing was left to do, but some protocol instances were still waiting for answers.
+	Stalled,
+	// The stop condition of the run was met before it settled.
+	StopCondition,
+	// The tick limit was reached, which is only a safety net.
+	TickLimit,
+}
+
+impl Display for Termination {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		let termination = match self {
+			Termination::Concluded => "settled",
+			Termination::Stalled => "stalled",
+			Termination::StopCondition => "stopped",
+			Termination::TickLimit => "hit the tick limit",
+		};
+		write!(f, "{}", termination)
+	}
+}
+
+/// Outcome of a run, as returned by [`crate::world::World::run_until_settled`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSummary {
+	pub ticks: usize,
+	// Whether the run stopped since there was nothing left to do, rather than hitting the limit.
+	pub settled: bool,
+	// Why the run ended, if it did.
+	pub termination: Option<Termination>,
+	pub offenders: BTreeSet<VoterId>,
+	// Whether the offenders proven by the honest voters reach the f + 1 the paper guarantees.
+	pub guarantee: GuaranteeStatus,
+	// Voters that were queried, but only ever gave responses that held up.
+	pub exonerated: BTreeSet<VoterId>,
+	pub protocol_bugs: usize,
+	pub invariant_violations: usize,
+	// Messages sent over the network, including those that were lost.
+	pub messages: usize,
+	// Ticks from the first detected conflict until every honest voter had a complete proof.
+	pub proof_latency: Option<usize>,
+	// Ticks from the first detected conflict until the honest voters between them had proven more
+	// than a third of a voter set to have misbehaved.
+	pub accountability_latency: Option<usize>,
+	pub deadline: Option<DeadlineReport>,
+	// Peak heap usage in bytes during the run, with the `alloc-tracking` feature.
+	pub peak_memory: Option<usize>,
+	// The most questions any voter had put to peers and not had answered at once.
+	pub peak_outstanding_queries: usize,
+	// Queries about estimates answered from, and added to, the voters' explanation caches.
+	pub explanation_cache: CacheStats,
+	// Gossip dropped by voters that had seen it before.
+	pub duplicates_suppressed: usize,
+	// The verdict on every responder in every round each protocol instance queried.
+	pub verdicts: Vec<InstanceVerdicts>,
+	// How each offence was proven, referring to the events of the run.
+	pub evidence_chains: Vec<EvidenceChain>,
+}
+
+impl RunSummary {
+	/// Whether a configured deadline was missed.
+	pub fn deadline_missed(&self) -> bool {
+		self.deadline
+			.as_ref()
+			.is_some_and(|deadline| !deadline.met())
+	}
+}
+
+impl Display for RunSummary {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self.termination {
+			Some(termination) => writeln!(f, "{} after {} ticks", termination, self.ticks)?,
+			None => writeln!(f, "running after {} ticks", self.ticks)?,
+		}
+		writeln!(
+			f,
+			"  offenders: {{ {} }}",
+			self.offenders.iter().format(", ")
+		)?;
+		writeln!(f, "  guarantee: {}", self.guarantee)?;
+		writeln!(
+			f,
+			"  exonerated: {{ {} }}",
+			self.exonerated.iter().format(", ")
+		)?;
+		writeln!(f, "  protocol bugs: {}", self.protocol_bugs)?;
+		writeln!(f, "  invariant violations: {}", self.invariant_violations)?;
+		writeln!(f, "  messages: {}", self.messages)?;
+		writeln!(
+			f,
+			"  peak outstanding queries: {}",
+			self.peak_outstanding_queries
+		)?;
+		writeln!(f, "  explanation cache: {}", self.explanation_cache)?;
+		writeln!(f, "  duplicates suppressed: {}", self.duplicates_suppressed)?;
+		if let Some(latency) = self.proof_latency {
+			writeln!(f, "  proof latency: {} ticks", latency)?;
+		}
+		if let Some(latency) = self.accountability_latency {
+			writeln!(f, "  accountability latency: {} ticks", latency)?;
+		}
+		if let Some(deadline) = &self.deadline {
+			writeln!(f, "  {}", deadline)?;
+		}
+		if let Some(peak_memory) = self.peak_memory {
+			writeln!(f, "  peak memory: {} bytes", peak_memory)?;
+		}
+		for verdicts in &self.verdicts {
+			for line in verdicts.to_string().lines() {
+				writeln!(f, "  {}", line)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// What a run came to, as returned by [`crate::world::World::run`], for asserting on without
+/// going through the voters.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationReport {
+	pub ticks: usize,
+	pub termination: Option<Termination>,
+	// Messages sent over the network, including those that were lost.
+	pub messages: usize,
+	// Queries about estimates and prevotes sent by any voter.
+	pub queries_sent: usize,
+	// The protocol instances started by all the voters together.
+	pub instances_started: usize,
+	// The equivocations detected by each voter, with an entry for every voter.
+	pub equivocations: BTreeMap<VoterId, Vec<EquivocationDetected>>,
+}
+
+impl SimulationReport {
+	/// The voters that detected any equivocations.
+	pub fn detectors(&self) -> BTreeSet<&VoterId> {
+		self.equivocations
+			.iter()
+			.filter(|(_, equivocations)| !equivocations.is_empty())
+			.map(|(voter, _)| voter)
+			.collect()
+	}
+}
+
+impl Display for SimulationReport {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self.termination {
+			Some(termination) => writeln!(f, "{} after {} ticks", termination, self.ticks)?,
+			None => writeln!(f, "running after {} ticks", self.ticks)?,
+		}
+		writeln!(f, "messages: {}", self.messages)?;
+		writeln!(f, "queries sent: {}", self.queries_sent)?;
+		writeln!(f, "protocol instances started: {}", self.instances_started)?;
+		for (voter, equivocations) in &self.equivocations {
+			writeln!(
+				f,
+				"{}: {} equivocations detected",
+				voter,
+				equivocations.len()
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// What a run came to, for comparing runs of the same scenario with the adversaries answering
+/// queries in different ways.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlternativeOutcome {
+	pub offenders: BTreeSet<VoterId>,
+	pub guarantee: GuaranteeStatus,
+	pub messages: usize,
+	// The rounds asked to explain their estimates, and those asked which prevotes were seen, by
+	// any protocol instance.
+	pub rounds_queried: BTreeSet<RoundNumber>,
+	pub prevote_rounds_queried: BTreeSet<RoundNumber>,
+	pub protocol_bugs: usize,
+}
+
+impl AlternativeOutcome {
+	pub fn new(summary: &RunSummary) -> Self {
+		let rounds = |prevotes| {
+			summary
+				.verdicts
+				.iter()
+				.flat_map(|instance| &instance.rounds)
+				.filter(|round| round.prevotes == prevotes)
+				.map(|round| round.round)
+				.collect()
+		};
+		Self {
+			offenders: summary.offenders.clone(),
+			guarantee: summary.guarantee,
+			messages: summary.messages,
+			rounds_queried: rounds(false),
+			prevote_rounds_queried: rounds(true),
+			protocol_bugs: summary.protocol_bugs,
+		}
+	}
+}
+
+impl Display for AlternativeOutcome {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		writeln!(
+			f,
+			"  offenders: {{ {} }}",
+			self.offenders.iter().format(", ")
+		)?;
+		writeln!(f, "  guarantee: {}", self.guarantee)?;
+		writeln!(f, "  messages: {}", self.messages)?;
+		writeln!(
+			f,
+			"  rounds queried: {{ {} }}",
+			self.rounds_queried.iter().format(", ")
+		)?;
+		writeln!(
+			f,
+			"  prevote rounds queried: {{ {} }}",
+			self.prevote_rounds_queried.iter().format(", ")
+		)?;
+		writeln!(f, "  protocol bugs: {}", self.protocol_bugs)
+	}
+}
+
+/// The same scenario run twice, once with the adversaries explaining estimates with precommits as
+/// in Alternative 1 of the protocol, and once with prevotes as in Alternative 2.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlternativeComparison {
+	pub precommits: AlternativeOutcome,
+	pub prevotes: AlternativeOutcome,
+}
+
+impl AlternativeComparison {
+	/// The voters found to have misbehaved in one run but not the other.
+	pub fn offenders_found_in_one(&self) -> BTreeSet<VoterId> {
+		self.precommits
+			.offenders
+			.symmetric_difference(&self.prevotes.offenders)
+			.cloned()
+			.collect()
+	}
+}
+
+impl Display for AlternativeComparison {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		writeln!(f, "adversaries answering with precommits:")?;
+		write!(f, "{}", self.precommits)?;
+		writeln!(f, "adversaries answering with prevotes:")?;
+		write!(f, "{}", self.prevotes)?;
+		writeln!(
+			f,
+			"offenders found in only one: {{ {} }}",
+			self.offenders_found_in_one().iter().format(", ")
+		)
+	}
+}
+
+/// Hits and misses of a cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+	pub hits: usize,
+	pub misses: usize,
+}
+
+impl std::ops::Add for CacheStats {
+	type Output = Self;
+
+	fn add(self, other: Self) -> Self {
+		Self {
+			hits: self.hits + other.hits,
+			misses: self.misses + other.misses,
+		}
+	}
+}
+
+impl Display for CacheStats {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(f, "{} hits, {} misses", self.hits, self.misses)
+	}
+}
+
+/// A voter with a precommit in one of the conflicting commits that answered it no longer has its
+/// votes for the round before, as after losing its voting history. Silence proves no equivocation,
+/// so this is no offence, but nothing justifies the precommit either.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnjustifiedCommitParticipation {
+	pub voter: VoterId,
+	pub set_id: SetId,
+	// The round the voter has lost its votes for.
+	pub round: RoundNumber,
+	// The block of the commit the voter's precommit is in.
+	pub commit: BlockNumber,
+}
+
+impl Display for UnjustifiedCommitParticipation {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"{} signed the commit for block {} but can't account for its votes in round {}",
+			self.voter, self.commit, self.round
+		)
+	}
+}
+
+/// A voter queried by a protocol instance that wasn't found to have misbehaved, with the responses
+/// it gave, which held up against every cross-check.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exoneration {
+	pub voter: VoterId,
+	// The explanation of the estimate for each round the voter was asked about.
+	pub explanations: BTreeMap<RoundNumber, QueryResponse>,
+	// The prevotes the voter claimed to have seen, for each round it was asked about.
+	pub prevotes_seen: BTreeMap<RoundNumber, QueryResponse>,
+}
+
+impl Exoneration {
+	pub fn new(voter: VoterId) -> Self {
+		Self {
+			voter,
+			explanations: Default::default(),
+			prevotes_seen: Default::default(),
+		}
+	}
+}
+
+impl Display for Exoneration {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"{} explained rounds {{ {} }}",
+			self.voter,
+			self.explanations.keys().format(", ")
+		)?;
+		if !self.prevotes_seen.is_empty() {
+			write!(
+				f,
+				" and the prevotes seen in rounds {{ {} }}",
+				self.prevotes_seen.keys().format(", ")
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// What came of asking a voter about a round.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+	// The response held up against every check.
+	Valid,
+	// The response held up, and cross-checking it revealed equivocations by others.
+	RevealedEquivocations,
+	Invalid(InvalidReason),
+	// The voter had no history for the round, not being a member of its voter set.
+	NotAMember,
+	// The voter was a member, but had lost its votes for the round.
+	LostHistory,
+	// The voter's policy is not to answer us. Unlike a missing response, this is no sign of
+	// evasion, but it explains nothing either.
+	Refused,
+	// No response arrived before we stopped waiting for one.
+	Unresponsive,
+	// No complete response arrived.
+	Missing,
+}
+
+impl Display for Verdict {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			Verdict::Valid => write!(f, "valid"),
+			Verdict::RevealedEquivocations => write!(f, "revealed equivocations"),
+			Verdict::Invalid(reason) => write!(f, "invalid ({})", reason),
+			Verdict::NotAMember => write!(f, "not a member"),
+			Verdict::LostHistory => write!(f, "lost its history"),
+			Verdict::Refused => write!(f, "refused by policy"),
+			Verdict::Unresponsive => write!(f, "unresponsive"),
+			Verdict::Missing => write!(f, "missing"),
+		}
+	}
+}
+
+/// Why a response was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvalidReason {
+	// The response had votes from other rounds than the one asked about.
+	ReplayedVotes,
+	// The votes don't rule out the block not included, or aren't of a kind that is accepted.
+	DoesNotExplainEstimate,
+	// The votes rule out the new block, which the responder precommitted for.
+	ContradictsCommit,
+	// The votes contradict the responder's own precommit in the commit for the block not included.
+	InconsistentTestimony,
+	// The responder's precommit in the round after doesn't include the estimate the votes give.
+	EstimateInconsistency,
+	// A single message had more votes than the message budget allows.
+	Oversized,
+	MalformedChunk,
+	// Only some of the chunks arrived in time.
+	Incomplete,
+	// A member of the voter set of the round claimed not to be one.
+	FalseNonMembership,
+	// The response had votes for a block we don't know, so we can't tell what they include.
+	UnknownBlock(BlockNumber),
+	// The votes differ from those the responder committed to for the round.
+	BrokenCommitment,
+	// The response had votes signed for another round or voter set among those of the round.
+	MisplacedVotes,
+	// The response had precommits where prevotes were asked for.
+	WrongKind,
+}
+
+impl Display for InvalidReason {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		let reason = match self {
+			InvalidReason::ReplayedVotes => "replayed votes",
+			InvalidReason::DoesNotExplainEstimate => "does not explain the estimate",
+			InvalidReason::ContradictsCommit => "contradicts the commit",
+			InvalidReason::InconsistentTestimony => "inconsistent testimony",
+			InvalidReason::EstimateInconsistency => "inconsistent estimate",
+			InvalidReason::Oversized => "oversized",
+			InvalidReason::MalformedChunk => "malformed chunk",
+			InvalidReason::Incomplete => "incomplete",
+			InvalidReason::FalseNonMembership => "false claim of non-membership",
+			InvalidReason::BrokenCommitment => "broken commitment",
+			InvalidReason::MisplacedVotes => "misplaced votes",
+			InvalidReason::WrongKind => "wrong kind of votes",
+			InvalidReason::UnknownBlock(block) => {
+				return write!(f, "votes for unknown block {}", block)
+			}
+		};
+		write!(f, "{}", reason)
+	}
+}
+
+/// The verdict on every voter asked about a round by a protocol instance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundVerdicts {
+	pub round: RoundNumber,
+	// Whether the voters were asked which prevotes they saw, rather than to explain the estimate.
+	pub prevotes: bool,
+	pub verdicts: BTreeMap<VoterId, Verdict>,
+}
+
+impl Display for RoundVerdicts {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"round {}{}: {}",
+			self.round,
+			if self.prevotes { " prevotes" } else { "" },
+			self.verdicts
+				.iter()
+				.format_with(", ", |(voter, verdict), f| f(&format_args!(
+					"{} {}",
+					voter, verdict
+				)))
+		)
+	}
+}
+
+/// The verdicts of a single protocol instance, for the summary of a run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceVerdicts {
+	pub detector: VoterId,
+	pub block_not_included: BlockNumber,
+	pub new_block: BlockNumber,
+	pub rounds: Vec<RoundVerdicts>,
+}
+
+impl Display for InstanceVerdicts {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		writeln!(
+			f,
+			"{} investigating blocks {} and {}:",
+			self.detector, self.block_not_included, self.new_block
+		)?;
+		for round in &self.rounds {
+			writeln!(f, "  {}", round)?;
+		}
+		Ok(())
+	}
+}
+
+// Above this many offenders the minimal set is approximated greedily rather than searched for.
+const MAX_OFFENDERS_FOR_EXACT_SEARCH: usize = 16;
+
+/// The offenders found by an instance of the protocol, and the smallest set of them that accounts
+/// for every finding. When the two differ, some of the offenders weren't needed to explain the
+/// conflict.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffenderAnalysis {
+	pub offenders: BTreeSet<VoterId>,
+	// The smallest set of voters such that every finding names at least one of them.
+	pub minimal: BTreeSet<VoterId>,
+	// f + 1, the fewest offenders there can be for a conflicting finalization.
+	pub threshold: usize,
+}
+
+impl OffenderAnalysis {
+	pub fn new(findings: &[EquivocationDetected], max_faulty: usize) -> Self {
+		let findings: Vec<BTreeSet<VoterId>> = findings
+			.iter()
+			.map(|finding| finding.offenders().into_iter().collect())
+			.filter(|offenders: &BTreeSet<_>| !offenders.is_empty())
+			.collect();
+		let offenders: BTreeSet<_> = findings.iter().flatten().cloned().collect();
+
+		let minimal = if offenders.len() <= MAX_OFFENDERS_FOR_EXACT_SEARCH {
+			(0..=offenders.len())
+				.flat_map(|size| offenders.iter().combinations(size))
+				.map(|candidate| candidate.into_iter().cloned().collect::<BTreeSet<_>>())
+				.find(|candidate| covers(candidate, &findings))
+				.unwrap_or_default()
+		} else {
+			greedy_cover(&findings)
+		};
+
+		Self {
+			offenders,
+			minimal,
+			threshold: max_faulty + 1,
+		}
+	}
+
+	/// Every offender found was needed to account for the findings.
+	pub fn all_necessary(&self) -> bool {
+		self.minimal == self.offenders
+	}
+
+	/// Enough offenders were found to account for a conflicting finalization.
+	pub fn sufficient(&self) -> bool {
+		self.offenders.len() >= self.threshold
+	}
+
+	pub fn guarantee(&self) -> GuaranteeStatus {
+		GuaranteeStatus::new(self.offenders.len(), self.threshold - 1)
+	}
+}
+
+impl Display for OffenderAnalysis {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"offenders {{ {} }}, minimal {{ {} }}, threshold {}",
+			self.offenders.iter().format(", "),
+			self.minimal.iter().format(", "),
+			self.threshold
+		)
+	}
+}
+
+/// How the offenders proven measure up to the guarantee of the paper: that for a conflicting
+/// finalization, at least f + 1 voters of a set of n = 3f + 1 can be proven to have misbehaved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuaranteeStatus {
+	// Nothing conflicting was finalized, so there was nobody to find.
+	NoConflict,
+	FellShort { proven: usize, required: usize },
+	Reached { proven: usize },
+	// More than f + 1 offenders, which is allowed but more than the protocol promises.
+	Exceeded { proven: usize, required: usize },
+}
+
+impl GuaranteeStatus {
+	pub fn new(proven: usize, max_faulty: usize) -> Self {
+		let required = max_faulty + 1;
+		match proven.cmp(&required) {
+			Ordering::Less => Self::FellShort { proven, required },
+			Ordering::Equal => Self::Reached { proven },
+			Ordering::Greater => Self::Exceeded { proven, required },
+		}
+	}
+
+	/// At least f + 1 offenders were proven.
+	pub fn is_met(&self) -> bool {
+		matches!(self, Self::Reached { .. } | Self::Exceeded { .. })
+	}
+}
+
+impl Display for GuaranteeStatus {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			Self::NoConflict => write!(f, "no conflict"),
+			Self::FellShort { proven, required } => {
+				write!(f, "fell short, {} of {} offenders proven", proven, required)
+			}
+			Self::Reached { proven } => write!(f, "reached, {} offenders proven", proven),
+			Self::Exceeded { proven, required } => write!(
+				f,
+				"exceeded, {} offenders proven where {} are guaranteed",
+				proven, required
+			),
+		}
+	}
+}
+
+fn covers(candidate: &BTreeSet<VoterId>, findings: &[BTreeSet<VoterId>]) -> bool {
+	findings
+		.iter()
+		.all(|finding| !finding.is_disjoint(candidate))
+}
+
+// Repeatedly pick the voter named in the most findings not yet accounted for.
+fn greedy_cover(findings: &[BTreeSet<VoterId>]) -> BTreeSet<VoterId> {
+	let mut cover = BTreeSet::new();
+	let mut remaining: Vec<_> = findings.iter().collect();
+	while !remaining.is_empty() {
+		let counts = remaining.iter().flat_map(|finding| finding.iter()).counts();
+		let best = counts
+			.into_iter()
+			.max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+			.map(|(voter, _)| voter.clone())
+			.unwrap();
+		remaining.retain(|finding| !finding.contains(&best));
+		cover.insert(best);
+	}
+	cover
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{protocol::Equivocation, voting::Precommit};
+
+	#[test]
+	fn display_commit_report() {
+		let commit = Commit::new(
+			2,
+			vec![Precommit::new(3, 2, "Alice"), Precommit::new(3, 2, "Bob")],
+		);
+		let report = CommitReport {
+			voter: "Carol".to_string(),
+			finalized: vec![
+				FinalizedBlock::new(&commit, Some(3)),
+				FinalizedBlock::new(&commit, None),
+			],
+		};
+		assert_eq!(
+			report.to_string(),
+			"Carol:\n  block 2 finalized in round 3 by { Alice, Bob }\n  block 2 finalized by { Alice, Bob }\n"
+		);
+	}
+
+	#[test]
+	fn display_exoneration() {
+		let mut exoneration = Exoneration::new("Dave".to_string());
+		for round in &[4, 3] {
+			exoneration.explanations.insert(
+				*round,
+				QueryResponse::Precommits(vec![Precommit::new(*round - 1, 1, "Dave")]),
+			);
+		}
+		assert_eq!(exoneration.to_string(), "Dave explained rounds { 3, 4 }");
+
+		exoneration
+			.prevotes_seen
+			.insert(3, QueryResponse::Prevotes(Vec::new()));
+		assert_eq!(
+			exoneration.to_string(),
+			"Dave explained rounds { 3, 4 } and the prevotes seen in rounds { 3 }"
+		);
+	}
+
+	#[test]
+	fn display_round_verdicts() {
+		let verdicts = RoundVerdicts {
+			round: 3,
+			prevotes: false,
+			verdicts: vec![
+				("Alice", Verdict::Invalid(InvalidReason::Oversized)),
+				("Bob", Verdict::Missing),
+				("Dave", Verdict::RevealedEquivocations),
+			]
+			.into_iter()
+			.map(|(voter, verdict)| (voter.to_string(), verdict))
+			.collect(),
+		};
+		assert_eq!(
+			verdicts.to_string(),
+			"round 3: Alice invalid (oversized), Bob missing, Dave revealed equivocations"
+		);
+	}
+
+	#[test]
+	fn deadline_violations() {
+		let mut report = DeadlineReport {
+			deadline: 50,
+			first_detection: Some(100),
+			completed: vec![
+				("Carol".to_string(), Some(120)),
+				("Dave".to_string(), Some(150)),
+			]
+			.into_iter()
+			.collect(),
+		};
+		assert!(report.met());
+		assert_eq!(report.latency(), Some(50));
+		assert_eq!(
+			report.to_string(),
+			"deadline of 50 ticks met, latency 50 ticks"
+		);
+
+		report.completed.insert("Dave".to_string(), Some(151));
+		report.completed.insert("Eve".to_string(), None);
+		assert_eq!(report.violations(), vec!["Dave", "Eve"]);
+		assert_eq!(report.latency(), None);
+		assert_eq!(
+			report.to_string(),
+			"deadline of 50 ticks missed by { Dave, Eve }"
+		);
+
+		report.first_detection = None;
+		assert!(report.met());
+	}
+
+	#[test]
+	fn minimal_offender_set() {
+		let equivocation = |voter: &str| Equivocation {
+			voter: voter.to_string(),
+			set_id: 0,
+			blocks: vec![1, 2],
+		};
+		let findings = vec![
+			EquivocationDetected::Precommit(vec![equivocation("Alice"), equivocation("Bob")]),
+			EquivocationDetected::Prevote(vec![equivocation("Bob"), equivocation("Carol")]),
+			EquivocationDetected::InvalidResponse {
+				voter: "Bob".to_string(),
+				set_id: 0,
+			},
+		];
+		let analysis = OffenderAnalysis::new(&findings, 1);
+		assert_eq!(
+			analysis.to_string(),
+			"offenders { Alice, Bob, Carol }, minimal { Bob }, threshold 2"
+		);
+		assert!(!analysis.all_necessary());
+		assert!(analysis.sufficient());
+		assert_eq!(
+			analysis.guarantee(),
+			GuaranteeStatus::Exceeded {
+				proven: 3,
+				required: 2
+			}
+		);
+
+		let findings: Vec<_> = findings
+			.iter()
+			.map(|finding| finding.offenders().into_iter().collect::<BTreeSet<_>>())
+			.collect();
+		assert_eq!(greedy_cover(&findings), analysis.minimal);
+
+		let analysis = OffenderAnalysis::new(&[], 1);
+		assert!(analysis.minimal.is_empty());
+		assert!(!analysis.sufficient());
+		assert_eq!(
+			analysis.guarantee(),
+			GuaranteeStatus::FellShort {
+				proven: 0,
+				required: 2
+			}
+		);
+	}
+
+	#[test]
+	fn guarantee_status() {
+		// With n = 7, f = 2.
+		let status = GuaranteeStatus::new(2, 2);
+		assert!(!status.is_met());
+		assert_eq!(status.to_string(), "fell short, 2 of 3 offenders proven");
+		let status = GuaranteeStatus::new(3, 2);
+		assert!(status.is_met());
+		assert_eq!(status.to_string(), "reached, 3 offenders proven");
+		let status = GuaranteeStatus::new(5, 2);
+		assert!(status.is_met());
+		assert_eq!(
+			status.to_string(),
+			"exceeded, 5 offenders proven where 3 are guaranteed"
+		);
+		assert!(!GuaranteeStatus::NoConflict.is_met());
+	}
+}
@@ -0,0 +1,792 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Declarative construction of the voters taking part in a simulation, with validation of the
+//! input so that malformed scenarios are reported instead of panicking halfway through setup.
+
+use crate::{
+	action::{Action, TriggerAtTick},
+	behaviour::{Adversary, Behaviour, BehaviourSchedule},
+	block::{Block, BlockNumber},
+	chain::{Chain, ChainError},
+	report::RunSummary,
+	schema::{GuaranteeState, GuaranteeV1, OffenceKind, ReportV1},
+	voter::{Voter, VoterId},
+	voting::{Commit, RoundNumber, SetId, VoterSet, VoterSetError, VotingRound, VotingRounds},
+	world::World,
+};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+	sync::Arc,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioError {
+	VoterSet(VoterSetError),
+	UnknownVoter(VoterId),
+	ObserverInVoterSet(VoterId),
+	UnknownHistory {
+		voter: VoterId,
+		history: String,
+	},
+	InvalidBlock {
+		voter: VoterId,
+		error: ChainError,
+	},
+	NonMemberVote {
+		history: String,
+		round: RoundNumber,
+		voter: VoterId,
+	},
+	UnknownVoterSet {
+		history: String,
+		round: RoundNumber,
+		set_id: SetId,
+	},
+	InvalidFinalization {
+		voter: VoterId,
+		history: String,
+		round: RoundNumber,
+		error: ChainError,
+	},
+}
+
+impl Display for ScenarioError {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			ScenarioError::VoterSet(VoterSetError::DuplicateVoter(voter)) => {
+				write!(f, "voter {} appears more than once in the voter set", voter)
+			}
+			ScenarioError::UnknownVoter(voter) => {
+				write!(f, "{} is not in any voter set", voter)
+			}
+			ScenarioError::ObserverInVoterSet(observer) => {
+				write!(f, "observer {} is a member of a voter set", observer)
+			}
+			ScenarioError::UnknownHistory { voter, history } => {
+				write!(f, "{}: unknown voting history '{}'", voter, history)
+			}
+			ScenarioError::InvalidBlock { voter, error } => write!(f, "{}: {}", voter, error),
+			ScenarioError::NonMemberVote {
+				history,
+				round,
+				voter,
+			} => write!(
+				f,
+				"history '{}', round {}: vote from {} who is not in the voter set",
+				history, round, voter
+			),
+			ScenarioError::UnknownVoterSet {
+				history,
+				round,
+				set_id,
+			} => write!(
+				f,
+				"history '{}', round {}: unknown voter set {}",
+				history, round, set_id
+			),
+			ScenarioError::InvalidFinalization {
+				voter,
+				history,
+				round,
+				error,
+			} => write!(
+				f,
+				"{}: history '{}', round {}: {}",
+				voter, history, round, error
+			),
+		}
+	}
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<VoterSetError> for ScenarioError {
+	fn from(error: VoterSetError) -> Self {
+		ScenarioError::VoterSet(error)
+	}
+}
+
+/// The votes of a single voting round, as part of a named history.
+#[derive(Debug, Clone)]
+pub struct RoundSpec {
+	pub round: RoundNumber,
+	pub set_id: SetId,
+	pub prevotes: Vec<(BlockNumber, VoterId)>,
+	pub precommits: Vec<(BlockNumber, VoterId)>,
+	// Block finalized by the precommits of this round, if any.
+	pub finalizes: Option<BlockNumber>,
+}
+
+impl RoundSpec {
+	pub fn new(round: RoundNumber) -> Self {
+		Self {
+			round,
+			set_id: 0,
+			prevotes: Default::default(),
+			precommits: Default::default(),
+			finalizes: None,
+		}
+	}
+
+	/// The round is voted in the given voter set, added with [`ScenarioBuilder::with_voter_set`],
+	/// rather than the first one.
+	pub fn in_set(mut self, set_id: SetId) -> Self {
+		self.set_id = set_id;
+		self
+	}
+
+	pub fn prevote(mut self, votes: &[(BlockNumber, &str)]) -> Self {
+		self.prevotes
+			.extend(votes.iter().map(|(n, id)| (*n, id.to_string())));
+		self
+	}
+
+	pub fn precommit(mut self, votes: &[(BlockNumber, &str)]) -> Self {
+		self.precommits
+			.extend(votes.iter().map(|(n, id)| (*n, id.to_string())));
+		self
+	}
+
+	pub fn finalize(mut self, block: BlockNumber) -> Self {
+		self.finalizes = Some(block);
+		self
+	}
+}
+
+/// What running a scenario until it settles is expected to come to. Only the outcomes that are set
+/// are checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expected {
+	pub offenders: Option<BTreeSet<VoterId>>,
+	// The kinds of offence proven, whoever committed them.
+	pub offences: Option<BTreeSet<OffenceKind>>,
+	pub guarantee: Option<GuaranteeState>,
+	// The run settles at or before this tick.
+	pub settles_within: Option<usize>,
+	// The protocol instances started and the queries sent, by all the voters together.
+	pub instances_started: Option<usize>,
+	pub queries_sent: Option<usize>,
+}
+
+impl Expected {
+	pub fn offenders(mut self, offenders: &[&str]) -> Self {
+		self.offenders = Some(offenders.iter().map(|id| id.to_string()).collect());
+		self
+	}
+
+	pub fn offences(mut self, offences: &[OffenceKind]) -> Self {
+		self.offences = Some(offences.iter().copied().collect());
+		self
+	}
+
+	pub fn guarantee(mut self, guarantee: GuaranteeState) -> Self {
+		self.guarantee = Some(guarantee);
+		self
+	}
+
+	pub fn settles_within(mut self, ticks: usize) -> Self {
+		self.settles_within = Some(ticks);
+		self
+	}
+
+	pub fn instances_started(mut self, instances: usize) -> Self {
+		self.instances_started = Some(instances);
+		self
+	}
+
+	pub fn queries_sent(mut self, queries: usize) -> Self {
+		self.queries_sent = Some(queries);
+		self
+	}
+
+	/// A run with nothing to account for: no protocol instance started, no one queried and no
+	/// offence proven.
+	pub fn no_misbehaviour() -> Self {
+		Self::default()
+			.offenders(&[])
+			.offences(&[])
+			.guarantee(GuaranteeState::NoConflict)
+			.instances_started(0)
+			.queries_sent(0)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self == &Self::default()
+	}
+
+	/// Compare the outcome of a run with what was expected, returning every difference.
+	pub fn check(&self, world: &World, summary: &RunSummary) -> Vec<Mismatch> {
+		let mut mismatches = Vec::new();
+		let mut compare = |outcome, expected: Option<String>, actual: String| {
+			if let Some(expected) = expected {
+				if expected != actual {
+					mismatches.push(Mismatch {
+						outcome,
+						expected,
+						actual,
+					});
+				}
+			}
+		};
+		let set = |items: &mut dyn Iterator<Item = String>| format!("{{{}}}", items.join(", "));
+		compare(
+			"offenders",
+			self.offenders
+				.as_ref()
+				.map(|offenders| set(&mut offenders.iter().cloned())),
+			set(&mut summary.offenders.iter().cloned()),
+		);
+		let offences: BTreeSet<_> = ReportV1::new(world)
+			.offences
+			.iter()
+			.map(|offence| offence.kind)
+			.collect();
+		let kinds = |offences: &BTreeSet<OffenceKind>| {
+			set(&mut offences.iter().map(|kind| format!("{:?}", kind)))
+		};
+		compare(
+			"offences",
+			self.offences.as_ref().map(kinds),
+			kinds(&offences),
+		);
+		compare(
+			"guarantee",
+			self.guarantee.map(|guarantee| format!("{:?}", guarantee)),
+			format!("{:?}", GuaranteeV1::from(summary.guarantee).status),
+		);
+		compare(
+			"instances started",
+			self.instances_started
+				.map(|instances| instances.to_string()),
+			world.instances_started().to_string(),
+		);
+		compare(
+			"queries sent",
+			self.queries_sent.map(|queries| queries.to_string()),
+			world.queries_sent().to_string(),
+		);
+		if let Some(ticks) = self.settles_within {
+			if !summary.settled || summary.ticks > ticks {
+				let actual = if summary.settled {
+					format!("at tick {}", summary.ticks)
+				} else {
+					"not at all".to_string()
+				};
+				mismatches.push(Mismatch {
+					outcome: "settled",
+					expected: format!("by tick {}", ticks),
+					actual,
+				});
+			}
+		}
+		mismatches
+	}
+}
+
+/// An outcome of a run that differs from what the scenario expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+	pub outcome: &'static str,
+	pub expected: String,
+	pub actual: String,
+}
+
+impl Display for Mismatch {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: expected {}, got {}",
+			self.outcome, self.expected, self.actual
+		)
+	}
+}
+
+#[derive(Debug, Clone)]
+struct History {
+	tag: u32,
+	rounds: Vec<RoundSpec>,
+}
+
+#[derive(Clone)]
+struct VoterSpec {
+	blocks: Vec<(BlockNumber, BlockNumber)>,
+	histories: Vec<String>,
+	behaviour: Option<Arc<dyn Behaviour>>,
+	behaviour_schedule: BehaviourSchedule,
+	actions: Vec<(TriggerAtTick, Action)>,
+	observer: bool,
+}
+
+/// Builds the voters for a scenario from the blocks and voting histories each of them knows about.
+///
+/// Histories are named sequences of voting rounds. Voters that know about several histories
+/// covering the same round will have seen different votes in that round, which is how equivocation
+/// is modelled. Each history gets its own tag, in the order they are added.
+///
+/// The voters passed to [`ScenarioBuilder::new`] form the first voter set, with id 0. Later sets
+/// can be added to model changes of the voter set, and each voter keeps the latest set it is a
+/// member of as its own.
+pub struct ScenarioBuilder {
+	voter_sets: BTreeMap<SetId, Vec<VoterId>>,
+	histories: BTreeMap<String, History>,
+	voters: BTreeMap<VoterId, VoterSpec>,
+	expected: Expected,
+}
+
+impl ScenarioBuilder {
+	pub fn new<S: AsRef<str>>(names: &[S]) -> Self {
+		Self {
+			voter_sets: Default::default(),
+			histories: Default::default(),
+			voters: Default::default(),
+			expected: Default::default(),
+		}
+		.with_voter_set(0, names)
+	}
+
+	/// Annotate the scenario with the outcome it is expected to come to, so that it can check
+	/// itself when run.
+	pub fn with_expected(mut self, expected: Expected) -> Self {
+		self.expected = expected;
+		self
+	}
+
+	pub fn expected(&self) -> &Expected {
+		&self.expected
+	}
+
+	pub fn with_voter_set<S: AsRef<str>>(mut self, set_id: SetId, names: &[S]) -> Self {
+		self.voter_sets.insert(
+			set_id,
+			names.iter().map(|name| name.as_ref().to_string()).collect(),
+		);
+		self
+	}
+
+	pub fn with_history(mut self, name: &str, rounds: Vec<RoundSpec>) -> Self {
+		let tag = self.histories.len() as u32;
+		self.histories
+			.insert(name.to_string(), History { tag, rounds });
+		self
+	}
+
+	/// Add a voter that knows about the given blocks, as `(number, parent)` pairs in the order they
+	/// should be added, and the named histories.
+	pub fn with_voter(
+		mut self,
+		id: &str,
+		blocks: &[(BlockNumber, BlockNumber)],
+		histories: &[&str],
+		behaviour: Option<Adversary>,
+	) -> Self {
+		self.voters.insert(
+			id.to_string(),
+			VoterSpec {
+				blocks: blocks.to_vec(),
+				histories: histories.iter().map(|h| h.to_string()).collect(),
+				behaviour: behaviour.map(|behaviour| Arc::new(behaviour) as Arc<dyn Behaviour>),
+				behaviour_schedule: Default::default(),
+				actions: Default::default(),
+				observer: false,
+			},
+		);
+		self
+	}
+
+	/// Add an observer that knows about the given blocks. Observers follow the latest voter set
+	/// without being a member of any, so have no voting histories.
+	pub fn with_observer(mut self, id: &str, blocks: &[(BlockNumber, BlockNumber)]) -> Self {
+		self.voters.insert(
+			id.to_string(),
+			VoterSpec {
+				blocks: blocks.to_vec(),
+				histories: Default::default(),
+				behaviour: None,
+				behaviour_schedule: Default::default(),
+				actions: Default::default(),
+				observer: true,
+			},
+		);
+		self
+	}
+
+	/// Give a voter added with `with_voter` a behaviour other than the built-in ones. Unknown voters
+	/// are reported when building.
+	pub fn with_behaviour(mut self, id: &str, behaviour: impl Behaviour + 'static) -> Self {
+		let entry = self.voters.entry(id.to_string()).or_insert(VoterSpec {
+			blocks: Default::default(),
+			histories: Default::default(),
+			behaviour: None,
+			behaviour_schedule: Default::default(),
+			actions: Default::default(),
+			observer: false,
+		});
+		entry.behaviour = Some(Arc::new(behaviour));
+		self
+	}
+
+	/// Have a voter added with `with_voter` change its behaviour during the run. Unknown voters are
+	/// reported when building.
+	pub fn with_behaviour_schedule(mut self, id: &str, schedule: BehaviourSchedule) -> Self {
+		let entry = self.voters.entry(id.to_string()).or_insert(VoterSpec {
+			blocks: Default::default(),
+			histories: Default::default(),
+			behaviour: None,
+			behaviour_schedule: Default::default(),
+			actions: Default::default(),
+			observer: false,
+		});
+		entry.behaviour_schedule = schedule;
+		self
+	}
+
+	/// Schedule an action for a voter added with `with_voter`. Unknown voters are reported when
+	/// building.
+	pub fn with_action(mut self, id: &str, tick: TriggerAtTick, action: Action) -> Self {
+		let entry = self.voters.entry(id.to_string()).or_insert(VoterSpec {
+			blocks: Default::default(),
+			histories: Default::default(),
+			behaviour: None,
+			behaviour_schedule: Default::default(),
+			actions: Default::default(),
+			observer: false,
+		});
+		entry.actions.push((tick, action));
+		self
+	}
+
+	pub fn build(self) -> Result<BTreeMap<VoterId, Voter>, ScenarioError> {
+		let mut voter_sets = BTreeMap::new();
+		for (set_id, names) in &self.voter_sets {
+			voter_sets.insert(*set_id, VoterSet::try_new(names)?.with_set_id(*set_id));
+		}
+
+		for (name, history) in &self.histories {
+			for round in &history.rounds {
+				let voter_set = voter_sets.get(&round.set_id).ok_or_else(|| {
+					ScenarioError::UnknownVoterSet {
+						history: name.clone(),
+						round: round.round,
+						set_id: round.set_id,
+					}
+				})?;
+				let non_member = round
+					.prevotes
+					.iter()
+					.chain(round.precommits.iter())
+					.find(|(_, voter)| !voter_set.is_member(voter));
+				if let Some((_, voter)) = non_member {
+					return Err(ScenarioError::NonMemberVote {
+						history: name.clone(),
+						round: round.round,
+						voter: voter.clone(),
+					});
+				}
+			}
+		}
+
+		let mut voters = BTreeMap::new();
+		for (id, spec) in self.voters {
+			let member_of = voter_sets.values().rev().find(|set| set.is_member(&id));
+			let voter_set = match (member_of, spec.observer) {
+				(Some(voter_set), false) => voter_set.clone(),
+				(None, false) => return Err(ScenarioError::UnknownVoter(id)),
+				(Some(_), true) => return Err(ScenarioError::ObserverInVoterSet(id)),
+				// There is always the first voter set.
+				(None, true) => voter_sets.values().next_back().unwrap().clone(),
+			};
+
+			let mut chain = Chain::new();
+			for (number, parent) in &spec.blocks {
+				chain
+					.try_add_block(Block::new(*number, *parent))
+					.map_err(|error| ScenarioError::InvalidBlock {
+						voter: id.clone(),
+						error,
+					})?;
+			}
+
+			let mut voting_rounds = VotingRounds::new();
+			for history_name in &spec.histories {
+				let history = self.histories.get(history_name).ok_or_else(|| {
+					ScenarioError::UnknownHistory {
+						voter: id.clone(),
+						history: history_name.clone(),
+					}
+				})?;
+				for round_spec in &history.rounds {
+					// Checked above.
+					let round_set = voter_sets[&round_spec.set_id].clone();
+					let mut round =
+						VotingRound::new_with_tag(round_spec.round, round_set, history.tag);
+					round.prevote(&round_spec.prevotes);
+					round.precommit(&round_spec.precommits);
+					if let Some(block) = round_spec.finalizes {
+						let commit = Commit::new(block, round.precommits.clone());
+						chain
+							.try_finalize_block(block, round_spec.round, commit)
+							.map_err(|error| ScenarioError::InvalidFinalization {
+								voter: id.clone(),
+								history: history_name.clone(),
+								round: round_spec.round,
+								error,
+							})?;
+					}
+					voting_rounds.add(round);
+				}
+			}
+
+			let mut voter = if spec.observer {
+				Voter::new_observer(id.clone(), chain, voter_set)
+			} else {
+				Voter::new(id.clone(), chain, voter_set, voting_rounds, spec.behaviour)
+			};
+			voter.behaviour_schedule = spec.behaviour_schedule;
+			voter.add_actions(spec.actions);
+			voters.insert(id, voter);
+		}
+
+		Ok(voters)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NAMES: &[&str] = &["Alice", "Bob", "Carol", "Dave"];
+
+	fn history() -> Vec<RoundSpec> {
+		vec![RoundSpec::new(1)
+			.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol")])
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol")])
+			.finalize(1)]
+	}
+
+	#[test]
+	fn build_valid_scenario() {
+		let voters = ScenarioBuilder::new(NAMES)
+			.with_history("common", history())
+			.with_voter("Alice", &[(1, 0), (2, 1)], &["common"], None)
+			.with_action("Alice", 10, Action::BroadcastCommits)
+			.build()
+			.unwrap();
+
+		let alice = &voters["Alice"];
+		assert!(alice.chain.knows_about_block(2));
+		assert_eq!(alice.chain.finalized_round(1), Some(&1));
+		assert_eq!(alice.pending_actions().count(), 1);
+	}
+
+	#[test]
+	fn expected_outcome_mismatches() {
+		let mut scenario = ScenarioBuilder::new(NAMES).with_history("common", history());
+		for name in NAMES {
+			scenario = scenario.with_voter(name, &[(1, 0)], &["common"], None);
+		}
+		let scenario = scenario
+			.with_action("Alice", 10, Action::BroadcastCommits)
+			.with_expected(
+				Expected::default()
+					.offenders(&["Alice"])
+					.guarantee(GuaranteeState::NoConflict)
+					.settles_within(5),
+			);
+		let expected = scenario.expected().clone();
+		let mut world = World::new(scenario.build().unwrap());
+		let summary = world.run_until_settled();
+
+		let mismatches: Vec<_> = expected
+			.check(&world, &summary)
+			.iter()
+			.map(Mismatch::to_string)
+			.collect();
+		assert_eq!(
+			mismatches,
+			vec![
+				"offenders: expected {Alice}, got {}".to_string(),
+				format!("settled: expected by tick 5, got at tick {}", summary.ticks),
+			]
+		);
+		assert!(Expected::default().check(&world, &summary).is_empty());
+	}
+
+	#[test]
+	fn duplicate_voter_in_set() {
+		let error = ScenarioBuilder::new(&["Alice", "Alice"])
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::VoterSet(VoterSetError::DuplicateVoter("Alice".to_string()))
+		);
+	}
+
+	#[test]
+	fn unknown_parent_block() {
+		let error = ScenarioBuilder::new(NAMES)
+			.with_voter("Bob", &[(1, 0), (3, 2)], &[], None)
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::InvalidBlock {
+				voter: "Bob".to_string(),
+				error: ChainError::UnknownParent {
+					block: 3,
+					parent: 2
+				},
+			}
+		);
+		assert_eq!(error.to_string(), "Bob: block 3 has unknown parent 2");
+	}
+
+	#[test]
+	fn vote_from_non_member() {
+		let error = ScenarioBuilder::new(NAMES)
+			.with_history("a", vec![RoundSpec::new(2).prevote(&[(1, "Eve")])])
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::NonMemberVote {
+				history: "a".to_string(),
+				round: 2,
+				voter: "Eve".to_string(),
+			}
+		);
+	}
+
+	#[test]
+	fn double_finalization() {
+		// Knowing the same finalization twice is fine.
+		assert!(ScenarioBuilder::new(NAMES)
+			.with_history("a", history())
+			.with_history("b", history())
+			.with_voter("Carol", &[(1, 0)], &["a", "b"], None)
+			.build()
+			.is_ok());
+
+		let other = vec![RoundSpec::new(1)
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")])
+			.finalize(1)];
+		let error = ScenarioBuilder::new(NAMES)
+			.with_history("a", history())
+			.with_history("b", other)
+			.with_voter("Carol", &[(1, 0)], &["a", "b"], None)
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::InvalidFinalization {
+				voter: "Carol".to_string(),
+				history: "b".to_string(),
+				round: 1,
+				error: ChainError::AlreadyFinalized(1),
+			}
+		);
+	}
+
+	#[test]
+	fn unknown_voter_and_history() {
+		let error = ScenarioBuilder::new(NAMES)
+			.with_action("Eve", 10, Action::BroadcastCommits)
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(error, ScenarioError::UnknownVoter("Eve".to_string()));
+
+		let error = ScenarioBuilder::new(NAMES)
+			.with_voter("Dave", &[], &["missing"], None)
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::UnknownHistory {
+				voter: "Dave".to_string(),
+				history: "missing".to_string(),
+			}
+		);
+	}
+
+	#[test]
+	fn observers_are_not_members() {
+		let voters = ScenarioBuilder::new(NAMES)
+			.with_observer("Olive", &[(1, 0)])
+			.build()
+			.unwrap();
+		assert!(voters["Olive"].is_observer());
+		assert!(voters["Olive"].rounds().next().is_none());
+
+		let error = ScenarioBuilder::new(NAMES)
+			.with_observer("Dave", &[(1, 0)])
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(error, ScenarioError::ObserverInVoterSet("Dave".to_string()));
+	}
+
+	#[test]
+	fn voter_set_change() {
+		let voters = ScenarioBuilder::new(NAMES)
+			.with_voter_set(1, &["Bob", "Carol", "Dave", "Eve"])
+			.with_history(
+				"a",
+				vec![
+					RoundSpec::new(1).precommit(&[(1, "Alice"), (1, "Bob")]),
+					RoundSpec::new(2)
+						.in_set(1)
+						.precommit(&[(1, "Bob"), (1, "Eve")]),
+				],
+			)
+			.with_voter("Alice", &[(1, 0)], &["a"], None)
+			.with_voter("Eve", &[(1, 0)], &["a"], None)
+			.build()
+			.unwrap();
+
+		// Voters keep the latest set they are a member of.
+		assert_eq!(voters["Alice"].voter_set.set_id, 0);
+		assert_eq!(voters["Eve"].voter_set.set_id, 1);
+
+		let eve = &voters["Eve"];
+		assert_eq!(eve.round(1, 0).unwrap().precommits[0].set_id, 0);
+		assert_eq!(eve.round(2, 0).unwrap().precommits[0].set_id, 1);
+
+		let error = ScenarioBuilder::new(NAMES)
+			.with_history("a", vec![RoundSpec::new(3).in_set(2)])
+			.build()
+			.err()
+			.unwrap();
+		assert_eq!(
+			error,
+			ScenarioError::UnknownVoterSet {
+				history: "a".to_string(),
+				round: 3,
+				set_id: 2,
+			}
+		);
+	}
+}
@@ -0,0 +1,336 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A versioned, machine-readable report of a run, for tooling such as dashboards or slashing bots
+//! that needs a stable contract rather than the formatting of [`crate::report`].
+//!
+//! The types here are kept separate from those the simulation uses internally, so that those can
+//! change without breaking consumers. Any change to the serialized shape of [`ReportV1`] comes
+//! with a bump of [`REPORT_SCHEMA_VERSION`], which the golden tests check.
+
+use crate::{
+	block::BlockNumber, protocol::EquivocationDetected, report::GuaranteeStatus, voter::VoterId,
+	voting::SetId, world::World,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+	hash::{Hash, Hasher},
+};
+
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// The outcome of a run, in version 1 of the report schema.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportV1 {
+	pub schema_version: u32,
+	pub run: RunMetadata,
+	pub conflicts: Vec<ConflictV1>,
+	// Everyone proven to have misbehaved by any of the voters.
+	pub offenders: Vec<VoterId>,
+	pub offences: Vec<OffenceV1>,
+	pub evidence: Vec<EvidenceRef>,
+	pub guarantee: GuaranteeV1,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunMetadata {
+	pub ticks: usize,
+	pub settled: bool,
+	pub messages: usize,
+	pub protocol_bugs: usize,
+	pub invariant_violations: usize,
+	pub proof_latency: Option<usize>,
+	pub accountability_latency: Option<usize>,
+}
+
+/// A pair of conflicting blocks that was investigated, with the voters that did so and the
+/// offenders they found.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictV1 {
+	pub block_not_included: BlockNumber,
+	pub new_block: BlockNumber,
+	pub detectors: Vec<VoterId>,
+	pub offenders: Vec<VoterId>,
+}
+
+/// A voter proven to have committed an offence as a member of a voter set, and the voters that
+/// proved it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffenceV1 {
+	pub voter: VoterId,
+	pub set_id: SetId,
+	pub kind: OffenceKind,
+	pub detectors: Vec<VoterId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffenceKind {
+	PrevoteEquivocation,
+	PrecommitEquivocation,
+	CommitEquivocation,
+	InvalidResponse,
+	InconsistentTestimony,
+	PartialResponse,
+	ReplayedVotes,
+	OversizedResponse,
+	FalseNonMembership,
+	EstimateInconsistency,
+	FabricatedAccusation,
+}
+
+impl OffenceKind {
+	pub const ALL: [OffenceKind; 11] = [
+		OffenceKind::PrevoteEquivocation,
+		OffenceKind::PrecommitEquivocation,
+		OffenceKind::CommitEquivocation,
+		OffenceKind::InvalidResponse,
+		OffenceKind::InconsistentTestimony,
+		OffenceKind::PartialResponse,
+		OffenceKind::ReplayedVotes,
+		OffenceKind::OversizedResponse,
+		OffenceKind::FalseNonMembership,
+		OffenceKind::EstimateInconsistency,
+		OffenceKind::FabricatedAccusation,
+	];
+
+	pub fn of(finding: &EquivocationDetected) -> Self {
+		match finding {
+			EquivocationDetected::Prevote(_) => OffenceKind::PrevoteEquivocation,
+			EquivocationDetected::Precommit(_) => OffenceKind::PrecommitEquivocation,
+			EquivocationDetected::CommitEquivocation(_) => OffenceKind::CommitEquivocation,
+			EquivocationDetected::InvalidResponse { .. } => OffenceKind::InvalidResponse,
+			EquivocationDetected::InconsistentTestimony(_) => OffenceKind::InconsistentTestimony,
+			EquivocationDetected::PartialResponse { .. } => OffenceKind::PartialResponse,
+			EquivocationDetected::ReplayedVotes { .. } => OffenceKind::ReplayedVotes,
+			EquivocationDetected::OversizedResponse { .. } => OffenceKind::OversizedResponse,
+			EquivocationDetected::FalseNonMembership { .. } => OffenceKind::FalseNonMembership,
+			EquivocationDetected::EstimateInconsistency { .. } => {
+				OffenceKind::EstimateInconsistency
+			}
+			EquivocationDetected::FabricatedAccusation { .. } => OffenceKind::FabricatedAccusation,
+			// Version 1 of the schema predates commitments, and a response that broke one is an
+			// invalid response to readers of it.
+			EquivocationDetected::BrokenCommitment { .. } => OffenceKind::InvalidResponse,
+			// Nor does it tell votes of other voter sets apart from votes of other rounds.
+			EquivocationDetected::MisplacedVotes { .. } => OffenceKind::ReplayedVotes,
+		}
+	}
+}
+
+/// Refers to the signed evidence bundle a detector published, by the hash of the bundle as
+/// serialized, rather than including it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceRef {
+	pub detector: VoterId,
+	// Hex encoded.
+	pub digest: String,
+	pub findings: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuaranteeV1 {
+	pub status: GuaranteeState,
+	pub proven: usize,
+	// The f + 1 offenders guaranteed to be found, unless nothing conflicting was finalized.
+	pub required: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuaranteeState {
+	NoConflict,
+	FellShort,
+	Reached,
+	Exceeded,
+}
+
+impl From<GuaranteeStatus> for GuaranteeV1 {
+	fn from(status: GuaranteeStatus) -> Self {
+		let (status, proven, required) = match status {
+			GuaranteeStatus::NoConflict => (GuaranteeState::NoConflict, 0, None),
+			GuaranteeStatus::FellShort { proven, required } => {
+				(GuaranteeState::FellShort, proven, Some(required))
+			}
+			GuaranteeStatus::Reached { proven } => (GuaranteeState::Reached, proven, Some(proven)),
+			GuaranteeStatus::Exceeded { proven, required } => {
+				(GuaranteeState::Exceeded, proven, Some(required))
+			}
+		};
+		Self {
+			status,
+			proven,
+			required,
+		}
+	}
+}
+
+/// Why a report couldn't be read.
+#[derive(Debug)]
+pub enum SchemaError {
+	Malformed(serde_json::Error),
+	UnsupportedVersion(u32),
+}
+
+impl Display for SchemaError {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			SchemaError::Malformed(error) => write!(f, "malformed report: {}", error),
+			SchemaError::UnsupportedVersion(version) => {
+				write!(f, "unsupported report schema version {}", version)
+			}
+		}
+	}
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<serde_json::Error> for SchemaError {
+	fn from(error: serde_json::Error) -> Self {
+		SchemaError::Malformed(error)
+	}
+}
+
+// Just enough of a report to tell which version of the schema to read the rest with.
+#[derive(Deserialize)]
+struct Versioned {
+	schema_version: u32,
+}
+
+impl ReportV1 {
+	pub fn new(world: &World) -> Self {
+		let summary = world.summary();
+
+		let mut conflicts: BTreeMap<_, (BTreeSet<_>, BTreeSet<_>)> = BTreeMap::new();
+		let mut offences: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+		for (id, voter) in world.voters() {
+			for (conflict, offenders) in voter.offenders_per_conflict() {
+				let (detectors, found) = conflicts.entry(conflict).or_default();
+				detectors.insert(id.clone());
+				found.extend(offenders);
+			}
+			for finding in voter.equivocations_detected() {
+				for (set_id, offender) in finding.offences() {
+					offences
+						.entry((offender, set_id, OffenceKind::of(&finding)))
+						.or_default()
+						.insert(id.clone());
+				}
+			}
+		}
+
+		Self {
+			schema_version: REPORT_SCHEMA_VERSION,
+			run: RunMetadata {
+				ticks: summary.ticks,
+				settled: summary.settled,
+				messages: summary.messages,
+				protocol_bugs: summary.protocol_bugs,
+				invariant_violations: summary.invariant_violations,
+				proof_latency: summary.proof_latency,
+				accountability_latency: summary.accountability_latency,
+			},
+			conflicts: conflicts
+				.into_iter()
+				.map(|(conflict, (detectors, offenders))| ConflictV1 {
+					block_not_included: conflict.block_not_included,
+					new_block: conflict.new_block,
+					detectors: detectors.into_iter().collect(),
+					offenders: offenders.into_iter().collect(),
+				})
+				.collect(),
+			offenders: summary.offenders.into_iter().collect(),
+			offences: offences
+				.into_iter()
+				.map(|((voter, set_id, kind), detectors)| OffenceV1 {
+					voter,
+					set_id,
+					kind,
+					detectors: detectors.into_iter().collect(),
+				})
+				.collect(),
+			evidence: world
+				.evidence_bundles()
+				.iter()
+				.map(|bundle| {
+					let serialized =
+						serde_json::to_vec(bundle).expect("evidence bundles always serialize");
+					let mut hasher = DefaultHasher::new();
+					serialized.hash(&mut hasher);
+					EvidenceRef {
+						detector: bundle.bundle.detector.clone(),
+						digest: format!("{:016x}", hasher.finish()),
+						findings: bundle.bundle.equivocations.len(),
+					}
+				})
+				.collect(),
+			guarantee: summary.guarantee.into(),
+		}
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("reports always serialize")
+	}
+
+	/// Read a report, refusing any written with a different version of the schema.
+	pub fn from_json(input: &str) -> Result<Self, SchemaError> {
+		let Versioned { schema_version } = serde_json::from_str(input)?;
+		if schema_version != REPORT_SCHEMA_VERSION {
+			return Err(SchemaError::UnsupportedVersion(schema_version));
+		}
+		Ok(serde_json::from_str(input)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{behaviour::Adversary, fixtures};
+
+	#[test]
+	fn report_round_trips() {
+		let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+		world.run_until_settled();
+		let report = ReportV1::new(&world);
+		assert_eq!(report.schema_version, REPORT_SCHEMA_VERSION);
+		assert_eq!(report.guarantee.status, GuaranteeState::Reached);
+		assert!(!report.conflicts.is_empty());
+		assert!(!report.evidence.is_empty());
+		assert!(report
+			.offences
+			.iter()
+			.all(|offence| report.offenders.contains(&offence.voter)));
+		assert_eq!(ReportV1::from_json(&report.to_json()).unwrap(), report);
+	}
+
+	#[test]
+	fn reject_other_schema_versions() {
+		let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+		world.run_until_settled();
+		let mut report = ReportV1::new(&world);
+		report.schema_version = REPORT_SCHEMA_VERSION + 1;
+		assert!(matches!(
+			ReportV1::from_json(&report.to_json()),
+			Err(SchemaError::UnsupportedVersion(version)) if version == REPORT_SCHEMA_VERSION + 1
+		));
+		assert!(matches!(
+			ReportV1::from_json("{}"),
+			Err(SchemaError::Malformed(_))
+		));
+	}
+}
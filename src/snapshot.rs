@@ -0,0 +1,125 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Periodic snapshots of the state of the voters during a run, so that tests can assert on how the
+//! state evolved over time without re-running the simulation.
+
+use crate::{
+	block::BlockNumber,
+	protocol::EquivocationDetected,
+	voter::{Voter, VoterId},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a single voter knew at the time of the snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoterSnapshot {
+	pub known_blocks: usize,
+	pub finalized: BTreeSet<BlockNumber>,
+	pub protocol_instances: usize,
+	pub equivocations: Vec<EquivocationDetected>,
+	pub known_offenders: BTreeSet<VoterId>,
+	// The detectors whose evidence we hold.
+	pub proofs_from: BTreeSet<VoterId>,
+}
+
+impl VoterSnapshot {
+	pub fn new(voter: &Voter) -> Self {
+		Self {
+			known_blocks: voter.chain.block_count(),
+			finalized: voter.chain.commits().keys().cloned().collect(),
+			protocol_instances: voter.instances().len(),
+			equivocations: voter.equivocations_detected(),
+			known_offenders: voter.known_offenders(),
+			proofs_from: voter.proof_store.keys().cloned().collect(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+	pub tick: usize,
+	pub voters: BTreeMap<VoterId, VoterSnapshot>,
+}
+
+impl Snapshot {
+	pub fn new(tick: usize, voters: &BTreeMap<VoterId, Voter>) -> Self {
+		Self {
+			tick,
+			voters: voters
+				.iter()
+				.map(|(id, voter)| (id.clone(), VoterSnapshot::new(voter)))
+				.collect(),
+		}
+	}
+
+	pub fn protocol_started(&self) -> bool {
+		self.voters
+			.values()
+			.any(|voter| voter.protocol_instances > 0)
+	}
+
+	pub fn any_accusations(&self) -> bool {
+		self.voters
+			.values()
+			.any(|voter| !voter.known_offenders.is_empty())
+	}
+}
+
+/// Snapshots taken every `interval` ticks.
+#[derive(Clone, Debug)]
+pub struct SnapshotHistory {
+	interval: usize,
+	snapshots: Vec<Snapshot>,
+}
+
+impl SnapshotHistory {
+	pub fn new(interval: usize) -> Self {
+		assert!(interval > 0, "snapshot interval must be positive");
+		Self {
+			interval,
+			snapshots: Default::default(),
+		}
+	}
+
+	pub fn is_due(&self, tick: usize) -> bool {
+		tick.is_multiple_of(self.interval)
+	}
+
+	pub fn push(&mut self, snapshot: Snapshot) {
+		self.snapshots.push(snapshot);
+	}
+
+	pub fn snapshots(&self) -> &[Snapshot] {
+		&self.snapshots
+	}
+
+	/// The state as of the given tick, which is the latest snapshot taken at or before it.
+	pub fn state_at(&self, tick: usize) -> Option<&Snapshot> {
+		self.snapshots
+			.iter()
+			.take_while(|snapshot| snapshot.tick <= tick)
+			.last()
+	}
+
+	/// The tick of the first snapshot satisfying the predicate.
+	pub fn first_tick_where(&self, predicate: impl Fn(&Snapshot) -> bool) -> Option<usize> {
+		self.snapshots
+			.iter()
+			.find(|snapshot| predicate(snapshot))
+			.map(|snapshot| snapshot.tick)
+	}
+}
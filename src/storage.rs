@@ -0,0 +1,387 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where voters keep the voting rounds they took part in. By default that's a map in memory, but
+//! very long histories can be kept on disk instead, one file per round, so that only the rounds
+//! being looked at are loaded.
+
+use crate::{
+	block::BlockNumber,
+	voting::{Precommit, Prevote, RoundNumber, SetId, VoterSet, VotingRound},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	fmt::Debug,
+	fs, io,
+	ops::{Bound, RangeBounds},
+	path::{Path, PathBuf},
+};
+
+/// Storage for the voting rounds of a voter, each round holding one history per tag.
+pub trait RoundStorage: Debug + Send + Sync {
+	/// All our histories of the round, if we have any.
+	fn get(&self, round: RoundNumber) -> io::Result<Option<Vec<VotingRound>>>;
+
+	fn add(&mut self, voting_round: VotingRound) -> io::Result<()>;
+
+	/// The histories of the rounds within the bounds, ordered by round and then by tag. The rounds
+	/// are read as the iterator gets to them, so errors come with the round they occurred for.
+	fn range(
+		&self,
+		start: Bound<RoundNumber>,
+		end: Bound<RoundNumber>,
+	) -> Box<dyn Iterator<Item = io::Result<VotingRound>> + '_>;
+
+	/// The latest round we have votes for.
+	fn latest_round(&self) -> io::Result<Option<RoundNumber>>;
+
+	/// Remove and return the histories of all rounds before the given round. Nothing is removed
+	/// unless all of them could be read.
+	fn remove_before(&mut self, round: RoundNumber) -> io::Result<Vec<VotingRound>>;
+
+	fn boxed_clone(&self) -> Box<dyn RoundStorage>;
+}
+
+/// The voting rounds kept in memory, which is the default.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryRounds(BTreeMap<RoundNumber, Vec<VotingRound>>);
+
+impl RoundStorage for InMemoryRounds {
+	fn get(&self, round: RoundNumber) -> io::Result<Option<Vec<VotingRound>>> {
+		Ok(self.0.get(&round).cloned())
+	}
+
+	fn add(&mut self, voting_round: VotingRound) -> io::Result<()> {
+		let histories = self.0.entry(voting_round.round_number).or_default();
+		histories.push(voting_round);
+		histories.sort_by_key(|voting_round| voting_round.tag);
+		Ok(())
+	}
+
+	fn range(
+		&self,
+		start: Bound<RoundNumber>,
+		end: Bound<RoundNumber>,
+	) -> Box<dyn Iterator<Item = io::Result<VotingRound>> + '_> {
+		Box::new(
+			self.0
+				.range((start, end))
+				.flat_map(|(_, histories)| histories.iter().cloned().map(Ok)),
+		)
+	}
+
+	fn latest_round(&self) -> io::Result<Option<RoundNumber>> {
+		Ok(self.0.keys().next_back().copied())
+	}
+
+	fn remove_before(&mut self, round: RoundNumber) -> io::Result<Vec<VotingRound>> {
+		let kept = self.0.split_off(&round);
+		Ok(std::mem::replace(&mut self.0, kept)
+			.into_values()
+			.flatten()
+			.collect())
+	}
+
+	fn boxed_clone(&self) -> Box<dyn RoundStorage> {
+		Box::new(self.clone())
+	}
+}
+
+// Serialized form of a history of a round, with the voter set it was cast in.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredRound {
+	round: RoundNumber,
+	tag: u32,
+	set_id: SetId,
+	// The voters of the set by id, with their display names.
+	voters: Vec<(String, String)>,
+	prevotes: Vec<Prevote>,
+	precommits: Vec<Precommit>,
+	finalized: Option<BlockNumber>,
+}
+
+impl StoredRound {
+	fn new(voting_round: &VotingRound) -> Self {
+		let voter_set = &voting_round.voter_set;
+		let voters = voter_set
+			.voter_ids()
+			.into_iter()
+			.map(|id| {
+				let display_name = voter_set.display_name(&id).unwrap_or(&id).to_string();
+				(id, display_name)
+			})
+			.collect();
+		Self {
+			round: voting_round.round_number,
+			tag: voting_round.tag,
+			set_id: voter_set.set_id,
+			voters,
+			prevotes: voting_round.prevotes.clone(),
+			precommits: voting_round.precommits.clone(),
+			finalized: voting_round.finalized,
+		}
+	}
+
+	fn into_voting_round(self) -> Option<VotingRound> {
+		let voters: Vec<_> = self
+			.voters
+			.iter()
+			.map(|(id, name)| (id.as_str(), name.as_str()))
+			.collect();
+		let voter_set = VoterSet::with_display_names(&voters)
+			.ok()?
+			.with_set_id(self.set_id);
+		let mut voting_round = VotingRound::new_with_tag(self.round, voter_set, self.tag);
+		voting_round.prevotes = self.prevotes;
+		voting_round.precommits = self.precommits;
+		voting_round.finalized = self.finalized;
+		Some(voting_round)
+	}
+}
+
+/// The voting rounds kept on disk, as a JSON file per round in a directory. Each lookup reads the
+/// file of the round, so only the rounds looked at are ever in memory.
+///
+/// Clones refer to the same directory, so they see each other's changes.
+#[derive(Clone, Debug)]
+pub struct FileRounds {
+	dir: PathBuf,
+}
+
+impl FileRounds {
+	/// Keep the rounds in the directory, creating it if needed. Rounds already in it are kept.
+	pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+		fs::create_dir_all(dir.as_ref())?;
+		Ok(Self {
+			dir: dir.as_ref().to_path_buf(),
+		})
+	}
+
+	fn path(&self, round: RoundNumber) -> PathBuf {
+		self.dir.join(format!("round-{}.json", round))
+	}
+
+	// The rounds with a file in the directory, in ascending order.
+	fn stored_rounds(&self) -> io::Result<Vec<RoundNumber>> {
+		let mut rounds = Vec::new();
+		for entry in fs::read_dir(&self.dir)? {
+			let name = entry?.file_name();
+			let round: Option<RoundNumber> = name
+				.to_str()
+				.and_then(|name| name.strip_prefix("round-"))
+				.and_then(|name| name.strip_suffix(".json"))
+				.and_then(|round| round.parse().ok());
+			rounds.extend(round);
+		}
+		rounds.sort_unstable();
+		Ok(rounds)
+	}
+
+	// The stored histories of the round, none if it has no file. A file that can't be read or
+	// parsed is an error, so that it isn't taken for an empty round and overwritten.
+	fn load(&self, round: RoundNumber) -> io::Result<Vec<StoredRound>> {
+		let path = self.path(round);
+		let input = match fs::read(&path) {
+			Ok(input) => input,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(err) => return Err(with_path(err, &path)),
+		};
+		serde_json::from_slice(&input).map_err(|err| with_path(err.into(), &path))
+	}
+
+	fn store(&self, round: RoundNumber, stored: &[StoredRound]) -> io::Result<()> {
+		let path = self.path(round);
+		let output = serde_json::to_vec(stored)?;
+		fs::write(&path, output).map_err(|err| with_path(err, &path))
+	}
+}
+
+fn with_path(err: io::Error, path: &Path) -> io::Error {
+	io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+impl RoundStorage for FileRounds {
+	fn get(&self, round: RoundNumber) -> io::Result<Option<Vec<VotingRound>>> {
+		let voting_rounds: Vec<_> = self
+			.load(round)?
+			.into_iter()
+			.filter_map(StoredRound::into_voting_round)
+			.collect();
+		if voting_rounds.is_empty() {
+			Ok(None)
+		} else {
+			Ok(Some(voting_rounds))
+		}
+	}
+
+	fn add(&mut self, voting_round: VotingRound) -> io::Result<()> {
+		let round = voting_round.round_number;
+		let mut stored = self.load(round)?;
+		stored.push(StoredRound::new(&voting_round));
+		stored.sort_by_key(|stored| stored.tag);
+		self.store(round, &stored)
+	}
+
+	fn range(
+		&self,
+		start: Bound<RoundNumber>,
+		end: Bound<RoundNumber>,
+	) -> Box<dyn Iterator<Item = io::Result<VotingRound>> + '_> {
+		let rounds = match self.stored_rounds() {
+			Ok(rounds) => rounds,
+			Err(err) => return Box::new(std::iter::once(Err(err))),
+		};
+		let rounds = rounds
+			.into_iter()
+			.filter(move |round| (start, end).contains(round));
+		Box::new(rounds.flat_map(move |round| {
+			match self.get(round) {
+				Ok(voting_rounds) => voting_rounds
+					.unwrap_or_default()
+					.into_iter()
+					.map(Ok)
+					.collect(),
+				Err(err) => vec![Err(err)],
+			}
+		}))
+	}
+
+	fn latest_round(&self) -> io::Result<Option<RoundNumber>> {
+		Ok(self.stored_rounds()?.last().copied())
+	}
+
+	fn remove_before(&mut self, round: RoundNumber) -> io::Result<Vec<VotingRound>> {
+		let rounds: Vec<_> = self
+			.stored_rounds()?
+			.into_iter()
+			.take_while(|stored_round| *stored_round < round)
+			.collect();
+		let mut removed = Vec::new();
+		for stored_round in &rounds {
+			removed.extend(self.get(*stored_round)?.unwrap_or_default());
+		}
+		for stored_round in rounds {
+			fs::remove_file(self.path(stored_round))?;
+		}
+		Ok(removed)
+	}
+
+	fn boxed_clone(&self) -> Box<dyn RoundStorage> {
+		Box::new(self.clone())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn check_storage(mut storage: impl RoundStorage) {
+		let voter_set = VoterSet::with_display_names(&[("alice-1", "Alice"), ("bob", "Bob")])
+			.unwrap()
+			.with_set_id(1);
+		for (round, tag) in [(3, 1), (1, 0), (3, 0), (2, 0)] {
+			let mut voting_round = VotingRound::new_with_tag(round, voter_set.clone(), tag);
+			voting_round.prevote(&[(5, "alice-1"), (5, "bob")]);
+			voting_round.precommit(&[(5, "bob")]);
+			storage.add(voting_round).unwrap();
+		}
+		let rounds = |storage: &dyn RoundStorage, start, end| -> Vec<_> {
+			storage
+				.range(start, end)
+				.map(|voting_round| {
+					let voting_round = voting_round.unwrap();
+					(voting_round.round_number, voting_round.tag)
+				})
+				.collect()
+		};
+
+		assert_eq!(storage.latest_round().unwrap(), Some(3));
+		assert_eq!(
+			rounds(&storage, Bound::Unbounded, Bound::Unbounded),
+			vec![(1, 0), (2, 0), (3, 0), (3, 1)]
+		);
+		assert_eq!(
+			rounds(&storage, Bound::Excluded(1), Bound::Included(2)),
+			vec![(2, 0)]
+		);
+
+		let round = &storage.get(3).unwrap().unwrap()[1];
+		assert_eq!(round.tag, 1);
+		assert_eq!(round.voter_set.set_id, 1);
+		assert_eq!(round.voter_set.display_name("alice-1"), Some("Alice"));
+		assert_eq!(
+			round.prevotes,
+			vec![
+				Prevote::new(3, 5, "alice-1").in_set(1),
+				Prevote::new(3, 5, "bob").in_set(1)
+			]
+		);
+		assert_eq!(
+			round.precommits,
+			vec![Precommit::new(3, 5, "bob").in_set(1)]
+		);
+		assert!(storage.get(4).unwrap().is_none());
+
+		let removed: Vec<_> = storage
+			.remove_before(3)
+			.unwrap()
+			.into_iter()
+			.map(|voting_round| voting_round.round_number)
+			.collect();
+		assert_eq!(removed, vec![1, 2]);
+		assert_eq!(
+			rounds(&storage, Bound::Unbounded, Bound::Unbounded),
+			vec![(3, 0), (3, 1)]
+		);
+	}
+
+	#[test]
+	fn in_memory_rounds() {
+		check_storage(InMemoryRounds::default());
+	}
+
+	#[test]
+	fn file_rounds() {
+		let dir =
+			std::env::temp_dir().join(format!("accountable-safety-rounds-{}", std::process::id()));
+		check_storage(FileRounds::open(&dir).unwrap());
+
+		// The rounds outlive the storage they were added through.
+		let reopened = FileRounds::open(&dir).unwrap();
+		assert_eq!(reopened.latest_round().unwrap(), Some(3));
+		assert_eq!(reopened.get(3).unwrap().unwrap().len(), 2);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+	#[test]
+	fn unreadable_rounds_are_not_overwritten() {
+		let dir = std::env::temp_dir().join(format!(
+			"accountable-safety-corrupt-rounds-{}",
+			std::process::id()
+		));
+		let mut storage = FileRounds::open(&dir).unwrap();
+		fs::write(storage.path(2), "not json").unwrap();
+
+		let voter_set = VoterSet::new(&["Alice", "Bob"]);
+		assert!(storage.get(2).is_err());
+		assert!(storage.add(VotingRound::new(2, voter_set)).is_err());
+		assert!(storage.remove_before(3).is_err());
+		assert_eq!(fs::read_to_string(storage.path(2)).unwrap(), "not json");
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,132 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The threshold arithmetic of GRANDPA, as pure functions of vote weights.
+//!
+//! Voters all weigh one in the simulation, so a weight is a number of distinct voters, but the
+//! functions hold for any weights. Products are taken in `u128`, so that they don't overflow for
+//! weights anywhere in the range of `u64`.
+
+/// The weight of a set of voters: how many of them there are, when all voters weigh one.
+pub type Weight = u64;
+
+/// Whether votes of the given weight are a supermajority, more than two thirds, of the total.
+pub fn is_supermajority(votes: Weight, total: Weight) -> bool {
+	3 * votes as u128 > 2 * total as u128
+}
+
+/// Whether a block can still get a supermajority, with votes of weight `supporting` including it
+/// and voters of weight `absent` yet to vote, who might all vote for it.
+pub fn supermajority_possible(supporting: Weight, absent: Weight, total: Weight) -> bool {
+	is_supermajority(supporting.saturating_add(absent), total)
+}
+
+/// The impossibility condition a response to a query has to show: even if every absent voter
+/// voted for the block, the votes including it would be at most two thirds of the total.
+pub fn cannot_have_supermajority(supporting: Weight, absent: Weight, total: Weight) -> bool {
+	!supermajority_possible(supporting, absent, total)
+}
+
+/// The least weight that is a supermajority of the total.
+pub fn threshold(total: Weight) -> Weight {
+	(2 * total as u128 / 3 + 1) as Weight
+}
+
+/// The most weight of faulty voters the protocol tolerates, f in n = 3f + 1.
+pub fn max_faulty(total: Weight) -> Weight {
+	total.saturating_sub(1) / 3
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn supermajority_at_exactly_two_thirds() {
+		// Exactly two thirds isn't more than two thirds.
+		for total in [3, 6, 9, 300] {
+			let two_thirds = 2 * total / 3;
+			assert!(!is_supermajority(two_thirds, total));
+			assert!(is_supermajority(two_thirds + 1, total));
+			assert_eq!(threshold(total), two_thirds + 1);
+		}
+	}
+
+	#[test]
+	fn impossibility_at_the_boundary() {
+		// With 3 * (includes + absent) == 2 * n, the block can't get a supermajority, and with
+		// one more voter either way it can.
+		for total in [3, 6, 9, 300] {
+			let boundary = 2 * total / 3;
+			for absent in 0..=boundary {
+				let includes = boundary - absent;
+				assert_eq!(3 * (includes + absent), 2 * total);
+				assert!(cannot_have_supermajority(includes, absent, total));
+				assert!(!cannot_have_supermajority(includes + 1, absent, total));
+				assert!(!cannot_have_supermajority(includes, absent + 1, total));
+			}
+		}
+	}
+
+	#[test]
+	fn thresholds_off_by_one_voter() {
+		// Adding a voter to a set of 3f + 1 leaves f unchanged until the set reaches 3f + 4, while
+		// the threshold moves up every time two thirds passes a whole voter.
+		let expected = [
+			(1, 0, 1),
+			(2, 0, 2),
+			(3, 0, 3),
+			(4, 1, 3),
+			(5, 1, 4),
+			(6, 1, 5),
+			(7, 2, 5),
+			(8, 2, 6),
+			(9, 2, 7),
+			(10, 3, 7),
+		];
+		for (total, faulty, supermajority) in expected {
+			assert_eq!(max_faulty(total), faulty, "{} voters", total);
+			assert_eq!(threshold(total), supermajority, "{} voters", total);
+			// The honest voters alone are always a supermajority.
+			assert!(is_supermajority(total - max_faulty(total), total));
+			assert!(!is_supermajority(threshold(total) - 1, total));
+		}
+		assert_eq!(max_faulty(0), 0);
+	}
+
+	#[test]
+	fn weighted_boundaries() {
+		// Voters of weights 5, 3, 2 and 2: 8 of 12 is exactly two thirds.
+		let total = 5 + 3 + 2 + 2;
+		assert!(cannot_have_supermajority(5, 3, total));
+		assert!(!cannot_have_supermajority(5 + 2, 2, total));
+		assert!(is_supermajority(5 + 3 + 2, total));
+		assert_eq!(max_faulty(total), 3);
+
+		// Weights near the top of the range don't overflow.
+		let total = Weight::MAX;
+		let two_thirds = Weight::MAX / 3 * 2;
+		assert!(!is_supermajority(two_thirds, total));
+		assert!(is_supermajority(two_thirds + 1, total));
+		assert_eq!(threshold(total), two_thirds + 1);
+		assert!(supermajority_possible(Weight::MAX, Weight::MAX, total));
+		assert!(cannot_have_supermajority(
+			two_thirds / 2,
+			two_thirds / 2,
+			total
+		));
+	}
+}
@@ -19,188 +19,328 @@
 
 use crate::{
 	action::Action,
-	chain::Chain,
-	protocol::{Equivocation, EquivocationDetected},
-	voter::{Behaviour, Voter, VoterId},
-	voting::{Commit, VoterSet, VotingRound, VotingRounds},
-	world::World,
+	archive::{ArchiveBackend, FileArchive, InMemoryArchive},
+	behaviour::{Adversary, Behaviour, BehaviourSchedule, EstimateQuestion},
+	block::BlockNumber,
+	chain::{ChainBuilder, ChainError, ChainView, Forks},
+	coverage::{Branch, Coverage},
+	crypto::SecretKey,
+	event::{DetectionEvent, EventKind, EventLog},
+	execution::Execution,
+	fixtures,
+	message::{Request, Response},
+	network::{Partition, PartitionSchedule},
+	policy::{Audience, ServingPolicy},
+	protocol::{
+		AccountableSafety, CommitEquivocation, ConflictId, ConflictStatement, Equivocation,
+		EquivocationDetected, EvidenceBundle, InconsistentTestimony, Outcome, PlannedRound,
+		QueryRejection, QueryResponse, ReplyKinds, Revealed, VoteCommitment,
+	},
+	report::{CacheStats, GuaranteeStatus, InvalidReason, RoundVerdicts, Termination, Verdict},
+	scenario::{RoundSpec, ScenarioBuilder, ScenarioError},
+	snapshot::Snapshot,
+	storage::FileRounds,
+	supermajority::{self, Weight},
+	verification::{verify_equivocation_proof, ProofError},
+	voter::{CommitStage, OutstandingQuery, QueryKind, QueryPlan, Voter, VoterId},
+	voting::{
+		Commit, CommitVariant, DivergentCommitVariants, InvalidCommit, Precommit, Prevote,
+		RoundNumber, VoterSet, VotingRound, VotingRounds,
+	},
+	world::{StopCondition, World, WorldBuilder},
+};
+use std::{
+	collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+	hash::{Hash, Hasher},
+	sync::Arc,
 };
-use std::collections::BTreeMap;
 
-fn setup_voters_with_two_finalized_forks(behaviour: Behaviour) -> BTreeMap<VoterId, Voter> {
-	let names = &["Alice", "Bob", "Carol", "Dave"];
-	let voter_set = VoterSet::new(names);
+#[test]
+fn basic_example_with_precommits() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
 
-	let mut voters = BTreeMap::new();
+	for report in world.commit_reports() {
+		print!("{}", report);
+	}
 
-	let chain_common = [(1, 0)];
-	let chain_a_fork = [(2, 1), (3, 2), (4, 3)];
-	let chain_b_fork = [(5, 1), (6, 5), (7, 6), (8, 7)];
-	let chain_all: Vec<_> = chain_common
-		.iter()
-		.chain(chain_a_fork.iter())
-		.chain(chain_b_fork.iter())
-		.cloned()
-		.collect();
-	let chain_a: Vec<_> = chain_common
-		.iter()
-		.chain(chain_a_fork.iter())
-		.cloned()
-		.collect();
-	let chain_b: Vec<_> = chain_common
+	let reports = world.commit_reports();
+	let finalized: Vec<_> = reports
 		.iter()
-		.chain(chain_b_fork.iter())
-		.cloned()
+		.map(|report| (report.voter.as_str(), report.finalized_blocks()))
 		.collect();
+	assert_eq!(
+		finalized,
+		vec![
+			("Alice", vec![1, 2, 8]),
+			("Bob", vec![1, 2, 8]),
+			("Carol", vec![1, 2]),
+			("Dave", vec![1, 8]),
+		]
+	);
+	assert_eq!(
+		reports[2].finalized[1].to_string(),
+		"block 2 finalized in round 2 by { Alice, Bob, Carol }"
+	);
 
-	// Setup the 4 voters and the voting history that they know about.
-	{
-		let mut chain = Chain::new_from(&chain_all);
-		let mut voting_rounds = create_common_voting_rounds(&voter_set, &mut chain);
-		append_voting_rounds_a(&mut voting_rounds, &voter_set, &mut chain);
-		append_voting_rounds_b(&mut voting_rounds, &voter_set, &mut chain);
-		let id = names[0].to_string();
-		voters.insert(
-			id.clone(),
-			Voter::new(
-				id,
-				chain.clone(),
-				voter_set.clone(),
-				voting_rounds,
-				Some(behaviour),
-			),
-		);
-	}
-	{
-		let mut chain = Chain::new_from(&chain_all);
-		let mut voting_rounds = create_common_voting_rounds(&voter_set, &mut chain);
-		append_voting_rounds_a(&mut voting_rounds, &voter_set, &mut chain);
-		append_voting_rounds_b(&mut voting_rounds, &voter_set, &mut chain);
-		let id = names[1].to_string();
-		voters.insert(
-			id.clone(),
-			Voter::new(id, chain, voter_set.clone(), voting_rounds, Some(behaviour)),
-		);
+	println!("\n*** Starting loop ***\n");
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
 	}
-	{
-		let mut chain = Chain::new_from(&chain_a);
-		let mut voting_rounds = create_common_voting_rounds(&voter_set, &mut chain);
-		append_voting_rounds_a(&mut voting_rounds, &voter_set, &mut chain);
-		let id = names[2].to_string();
-		voters.insert(
-			id.clone(),
-			Voter::new(
-				id.clone(),
-				chain,
-				voter_set.clone(),
-				voting_rounds,
-				Some(behaviour),
-			),
-		);
+
+	// We get three sets of equivocations, one for each response to the query about round 2. Alice
+	// and Bob also lied about their own precommits in round 2.
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+#[test]
+fn simulation_report() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let report = world.run();
+
+	assert_eq!(report.termination, Some(Termination::Concluded));
+	assert_eq!(report.ticks, world.current_tick());
+	assert_eq!(report.messages, world.messages_sent());
+	assert_eq!(report.instances_started, 1);
+	assert_eq!(report.detectors(), ["Carol".to_string()].iter().collect());
+	assert_eq!(report.equivocations["Carol"], detections_in_basic_example());
+	assert!(report.equivocations["Alice"].is_empty());
+}
+
+#[test]
+fn basic_example_with_prevotes() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrevotes));
+
+	for report in world.commit_reports() {
+		print!("{}", report);
 	}
-	{
-		let mut chain = Chain::new_from(&chain_b);
-		let mut voting_rounds = create_common_voting_rounds(&voter_set, &mut chain);
-		append_voting_rounds_b(&mut voting_rounds, &voter_set, &mut chain);
-		let id = names[3].to_string();
-		voters.insert(
-			id.clone(),
-			Voter::new(id, chain, voter_set, voting_rounds, Some(behaviour)),
-		);
+
+	println!("\n*** Starting loop ***\n");
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
 	}
 
-	// Kick off the simulation by having one voter broadcast all their commits, reveiling the conflicting
-	// finalized blocks to the other (honest) voters.
-	voters
-		.get_mut(&"Dave".to_string())
-		.map(|v| v.add_actions(vec![(10, Action::BroadcastCommits)]));
+	// The prevote reply for the round after the finalized block leads to the prevote query, whose
+	// replies are cross-checked against each other, with no shortcuts along the way.
+	assert!(world.protocol_bugs().is_empty());
+	let coverage = world.coverage();
+	assert!(coverage.contains(Branch::PrevoteQuery));
+	assert!(coverage.contains(Branch::PrevoteCrossCheck));
+	assert!(!coverage.contains(Branch::PrecommitCrossCheck));
+	assert!(!coverage.contains(Branch::InvalidResponse));
 
-	voters
+	assert_eq!(
+		world.equivocations_detected(),
+		&[EquivocationDetected::Prevote(vec![
+			Equivocation {
+				voter: "Alice".to_string(),
+				set_id: 0,
+				blocks: vec![1, 4],
+			},
+			Equivocation {
+				voter: "Bob".to_string(),
+				set_id: 0,
+				blocks: vec![1, 4],
+			}
+		]),],
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
 }
 
-fn create_common_voting_rounds(voter_set: &VoterSet, chain: &mut Chain) -> VotingRounds {
-	let mut voting_rounds = VotingRounds::new();
-	let voting_round_tag = 0;
-
-	{
-		let mut round = VotingRound::new_with_tag(1, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")]);
-		round.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")]);
-		let commit = Commit::new(1, round.precommits.clone());
-		chain.finalize_block(1, round.round_number, commit);
-		voting_rounds.add(round);
-	}
-
-	voting_rounds
-}
-
-// Sequence of voting rounds leading to finalizing block 2 on the first fork
-fn append_voting_rounds_a(
-	voting_rounds: &mut VotingRounds,
-	voter_set: &VoterSet,
-	chain: &mut Chain,
-) {
-	let voting_round_tag = 0;
-	{
-		let mut round = VotingRound::new_with_tag(2, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")]);
-		round.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
-		let commit = Commit::new(2, round.precommits.clone());
-		chain.finalize_block(2, round.round_number, commit);
-		voting_rounds.add(round);
-	}
-	{
-		let mut round = VotingRound::new_with_tag(3, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")]);
-		round.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
-		voting_rounds.add(round);
-	}
-	{
-		let mut round = VotingRound::new_with_tag(4, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")]);
-		round.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
-		voting_rounds.add(round);
-	}
-}
-
-// Sequence of voting rounds leading to finalizing block 8 on the second fork
-fn append_voting_rounds_b(
-	voting_rounds: &mut VotingRounds,
-	voter_set: &VoterSet,
-	chain: &mut Chain,
-) {
-	let voting_round_tag = 1;
-	{
-		let mut round = VotingRound::new_with_tag(2, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")]);
-		round.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]);
-		voting_rounds.add(round);
-	}
-	{
-		let mut round = VotingRound::new_with_tag(3, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")]);
-		round.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]);
-		voting_rounds.add(round);
-	}
-	{
-		let mut round = VotingRound::new_with_tag(4, voter_set.clone(), voting_round_tag);
-		round.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")]);
-		round.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")]);
-		let commit = Commit::new(8, round.precommits.clone());
-		chain.finalize_block(8, round.round_number, commit);
-		voting_rounds.add(round);
+// Answers estimate queries with prevotes, like `Adversary::ReturnPrevotes`, and never takes in
+// commits, so never detects anything.
+#[derive(Debug)]
+struct PrevotingRecluse;
+
+impl Behaviour for PrevotingRecluse {
+	fn answer_estimate_query(&self, question: &EstimateQuestion) -> Option<QueryResponse> {
+		question
+			.first_valid(question.prevotes())
+			.or_else(|| question.first_valid(question.precommits()))
+	}
+
+	fn on_commit(&self, _sender: &VoterId, _round: RoundNumber, _commit: &Commit) -> bool {
+		false
 	}
 }
 
 #[test]
-fn basic_example_with_precommits() {
-	let mut world = World::new(setup_voters_with_two_finalized_forks(
-		Behaviour::ReturnPrecommits,
-	));
+fn custom_behaviours_plug_into_voters() {
+	let scenario = |behaviour: Option<Arc<dyn Behaviour>>| {
+		let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+		for adversary in &["Alice", "Bob"] {
+			voters.get_mut(*adversary).unwrap().behaviour = behaviour.clone();
+		}
+		let mut world = World::new(voters);
+		world.run_until_settled();
+		world
+	};
+
+	// Answering like the built-in behaviour comes to the same, and ignoring commits makes no
+	// difference to voters that don't detect the conflict.
+	let custom = scenario(Some(Arc::new(PrevotingRecluse)));
+	let built_in = scenario(Some(Arc::new(Adversary::ReturnPrevotes)));
+	assert_eq!(
+		custom.equivocations_detected(),
+		built_in.equivocations_detected()
+	);
+	assert_eq!(custom.guarantee(), GuaranteeStatus::Reached { proven: 2 });
 
-	world.list_commits();
+	// Carol never takes in Dave's commit, so never detects the conflict.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Carol").unwrap().behaviour = Some(Arc::new(PrevotingRecluse));
+	let mut world = World::new(voters);
+	let report = world.run();
+	assert_eq!(report.instances_started, 0);
+	assert!(report.detectors().is_empty());
+}
 
-	println!("\n*** Starting loop ***\n");
+#[test]
+fn adversaries_switching_behaviour_mid_run() {
+	let scenario = |schedule: BehaviourSchedule| {
+		let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+		for adversary in &["Alice", "Bob"] {
+			voters.get_mut(*adversary).unwrap().behaviour_schedule = schedule.clone();
+		}
+		let mut world = World::new(voters);
+		let report = world.run();
+		(world, report)
+	};
+	let silent = || Adversary::IgnoreQueriesFrom(vec!["Carol".into()]);
+
+	// Alice and Bob answer the first query, about round 4, and then go silent before Carol walks
+	// back to round 3. Dave's answer alone still reveals their equivocation, and once Carol stops
+	// waiting for the questions they leave unanswered, she finds them unresponsive and concludes.
+	let (world, report) = scenario(BehaviourSchedule::new().at_tick(45, silent()));
+	assert_eq!(report.termination, Some(Termination::Concluded));
+	assert_eq!(
+		report.detectors().into_iter().collect::<Vec<_>>(),
+		vec!["Carol"]
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::BehaviourSwitched));
+	assert!(world.coverage().contains(Branch::QueryIgnored));
+	let verdicts = &world.verdicts()["Carol"][&ConflictId::new(2, 8)];
+	assert_eq!(verdicts[0].round, 3);
+	assert_eq!(verdicts[0].verdicts["Alice"], Verdict::Unresponsive);
+	assert_eq!(verdicts[0].verdicts["Dave"], Verdict::RevealedEquivocations);
+	assert_eq!(verdicts[1].verdicts["Alice"], Verdict::Valid);
+	assert!(world.voters()["Carol"].outstanding_queries().is_empty());
+	assert!(world
+		.summary()
+		.to_string()
+		.contains("round 3: Alice unresponsive, Bob unresponsive"));
+	assert!(world.coverage().contains(Branch::QueryExpired));
+
+	// Going silent once accused comes too late, as the evidence only goes out once Carol has
+	// everything she needs, so the outcome is that of staying honest.
+	let (world, report) = scenario(BehaviourSchedule::new().once_accused(silent()));
+	let (honest, _) = scenario(BehaviourSchedule::new());
+	assert_eq!(report.termination, Some(Termination::Concluded));
+	assert_eq!(
+		world.equivocations_detected(),
+		honest.equivocations_detected()
+	);
+	let evidence_delivered = world
+		.event_log()
+		.events()
+		.iter()
+		.find(|event| event.voter == "Alice" && event.summary.starts_with("HereIsEvidence"))
+		.map(|event| event.tick);
+	let switched = world
+		.event_log()
+		.events()
+		.iter()
+		.find(|event| event.voter == "Alice" && event.summary.starts_with("switching behaviour"))
+		.map(|event| event.tick);
+	assert!(switched.is_some());
+	assert_eq!(switched, evidence_delivered);
+	assert!(world.voters()["Dave"].behaviour_schedule.is_empty());
+}
+
+#[test]
+fn responders_choose_different_reply_kinds() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Bob").unwrap().behaviour = Some(Arc::new(Adversary::ReturnPrevotes));
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+
+	// Alice explains round 3 with precommits, which are cross-checked with the commit, and Bob with
+	// prevotes, which leads to asking the signers of the commit about the prevotes they saw.
+	let coverage = world.coverage();
+	assert!(coverage.contains(Branch::PrecommitCrossCheck));
+	assert!(coverage.contains(Branch::PrevoteQuery));
+	assert!(!coverage.contains(Branch::InvalidResponse));
+	let detections = world.voters()["Carol"].equivocations_detected();
+	assert!(detections
+		.iter()
+		.any(|detection| matches!(detection, EquivocationDetected::Precommit(..))));
+	assert!(detections
+		.iter()
+		.any(|detection| matches!(detection, EquivocationDetected::Prevote(..))));
+	assert_eq!(
+		world.offenders().into_iter().collect::<Vec<_>>(),
+		vec!["Alice", "Bob"]
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+#[test]
+fn verifiers_accepting_only_prevotes() {
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_accepted_replies(ReplyKinds::Prevotes)
+		.build();
+	run_to_completion(&mut world);
+
+	// Everyone explains with prevotes instead, as in the example where that's their preference.
+	assert!(!world.coverage().contains(Branch::PrecommitCrossCheck));
+	assert_eq!(
+		world.equivocations_detected(),
+		&[EquivocationDetected::Prevote(vec![
+			Equivocation {
+				voter: "Alice".to_string(),
+				set_id: 0,
+				blocks: vec![1, 4],
+			},
+			Equivocation {
+				voter: "Bob".to_string(),
+				set_id: 0,
+				blocks: vec![1, 4],
+			}
+		]),],
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+#[test]
+fn basic_example_with_network_latency_and_invariants() {
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_network(3, 0.0)
+		.with_seed(1)
+		.with_max_ticks(1000)
+		.with_invariants(vec![Box::new(|world: &World| {
+			if world.current_tick() < 10 && !world.equivocations_detected().is_empty() {
+				Some("equivocations detected before commits were broadcast".to_string())
+			} else {
+				None
+			}
+		})])
+		.build();
 
 	while !world.completed() {
 		let requests = world.process_actions();
@@ -209,53 +349,233 @@ fn basic_example_with_precommits() {
 		world.tick();
 	}
 
-	// We get three sets of equivocations, one coming from each voter
+	assert_eq!(world.termination(), Some(Termination::Concluded));
+	assert!(world.current_tick() < 1000);
+	assert!(world.invariant_violations().is_empty());
+	assert_eq!(world.equivocations_detected().len(), 5);
+}
+
+#[test]
+fn run_limit_scales_with_the_scenario() {
+	let world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let small = world.estimated_completion_bound();
+	assert_eq!(world.max_ticks(), 4 * small);
+
+	// Slow links stretch every query, so a run that would have been cut off by a fixed limit gets
+	// the time it needs, while the small one above isn't left looping for thousands of ticks.
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_network(1000, 0.0)
+		.build();
+	assert!(world.estimated_completion_bound() > small);
+	assert!(world.max_ticks() > 5000);
+	let summary = world.run_until_settled();
+	assert!(summary.settled);
+	assert!(summary.ticks > 5000);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+
+	// An explicit limit still wins.
+	let world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_max_ticks(100)
+		.build();
+	assert_eq!(world.max_ticks(), 100);
+}
+
+#[test]
+fn runs_end_once_the_protocol_concludes() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let summary = world.run_until_settled();
+	assert_eq!(summary.termination, Some(Termination::Concluded));
+	assert!(summary.ticks < world.max_ticks());
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+
+	// Cut short, the tick limit ends the run instead.
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_max_ticks(10)
+		.build();
+	let summary = world.run_until_settled();
+	assert_eq!(summary.termination, Some(Termination::TickLimit));
+	assert!(!summary.settled);
+	assert_eq!(summary.ticks, 10);
+}
+
+#[test]
+fn prevoters_answer_for_unresponsive_precommitters() {
+	let run = |query_prevoters| {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::unresponsive_precommitters())
+			.with_prevoter_queries(query_prevoters)
+			.build();
+		world.run_until_settled();
+		world
+	};
+
+	// Everyone who precommitted for block 8 ignores Grace, so asking only them gets her nowhere.
+	let world = run(false);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(!world.coverage().contains(Branch::PrevoterQuery));
+	assert!(world.offenders().is_empty());
 	assert_eq!(
-		world.equivocations_detected(),
-		&[
-			EquivocationDetected::Precommit(vec![
-				Equivocation {
-					voter: "Alice".to_string(),
-					blocks: vec![1, 2],
-				},
-				Equivocation {
-					voter: "Bob".to_string(),
-					blocks: vec![1, 2],
-				}
-			]),
-			EquivocationDetected::Precommit(vec![
-				Equivocation {
-					voter: "Alice".to_string(),
-					blocks: vec![1, 2],
-				},
-				Equivocation {
-					voter: "Bob".to_string(),
-					blocks: vec![1, 2],
-				}
-			]),
-			EquivocationDetected::Precommit(vec![
-				Equivocation {
-					voter: "Alice".to_string(),
-					blocks: vec![1, 2],
-				},
-				Equivocation {
-					voter: "Bob".to_string(),
-					blocks: vec![1, 2],
-				}
-			]),
-		],
+		world.guarantee(),
+		GuaranteeStatus::FellShort {
+			proven: 0,
+			required: 3
+		}
+	);
+
+	// Grace saw her own prevote for block 8, and her answer about the rounds before is enough to
+	// catch everyone who voted on both forks.
+	let world = run(true);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::PrevoterQuery));
+	assert_eq!(
+		world.offenders().into_iter().collect::<Vec<_>>(),
+		vec!["Alice", "Bob", "Carol", "Dave"]
+	);
+	assert_eq!(
+		world.guarantee(),
+		GuaranteeStatus::Exceeded {
+			proven: 4,
+			required: 3
+		}
 	);
 }
 
 #[test]
-fn basic_example_with_prevotes() {
-	let mut world = World::new(setup_voters_with_two_finalized_forks(
-		Behaviour::ReturnPrevotes,
-	));
+fn minimal_answers_reveal_fewer_votes_to_the_same_effect() {
+	let run = |minimal_answers| {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+			.with_minimal_answers(minimal_answers)
+			.build();
+		world.run_until_settled();
+		assert!(world.protocol_bugs().is_empty());
+		let revealed: usize = world
+			.voters()
+			.values()
+			.flat_map(|voter| &voter.served_queries)
+			.filter_map(|served| served.answer.as_ref())
+			.map(QueryResponse::len)
+			.sum();
+		(world, revealed)
+	};
 
-	world.list_commits();
+	let (full, revealed_in_full) = run(false);
+	let (minimal, revealed_minimally) = run(true);
+	assert!(revealed_minimally < revealed_in_full);
 
-	println!("\n*** Starting loop ***\n");
+	// Leaving out votes is no breach of a commitment to them, and what's left still explains the
+	// estimates and catches the equivocators.
+	assert!(!minimal.coverage().contains(Branch::BrokenCommitment));
+	assert!(!minimal.coverage().contains(Branch::InvalidResponse));
+	assert_eq!(minimal.offenders(), full.offenders());
+	assert_eq!(minimal.guarantee(), full.guarantee());
+}
+
+#[test]
+fn evidence_chains_lead_back_to_the_transcript() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let summary = world.run_until_settled();
+	assert_eq!(summary.evidence_chains, world.evidence_chains());
+
+	for offender in &["Alice", "Bob"] {
+		let chains = world.evidence_chains_against(offender);
+		assert!(!chains.is_empty());
+		for chain in chains {
+			assert_eq!(chain.detector, "Carol");
+			assert!(chain
+				.finding
+				.offences()
+				.contains(&(chain.set_id, offender.to_string())));
+
+			let event = |id| world.event(id).unwrap();
+			assert_eq!(event(chain.detected).kind, EventKind::EquivocationDetected);
+			assert_eq!(event(chain.detected).voter, "Carol");
+			let started = chain.started.unwrap();
+			assert_eq!(event(started).kind, EventKind::ProtocolStarted);
+
+			// Every query was sent by the detector, and answered by the offender or with its votes.
+			assert!(!chain.steps.is_empty());
+			for step in &chain.steps {
+				let query = event(step.query.unwrap());
+				assert_eq!(query.kind, EventKind::MessageSent);
+				assert_eq!(query.voter, "Carol");
+				assert!(query.summary.ends_with(&format!("to {}", step.responder)));
+				for response in &step.responses {
+					let response = event(*response);
+					assert_eq!(response.kind, EventKind::MessageDelivered);
+					assert_eq!(response.voter, "Carol");
+					assert!(response
+						.summary
+						.ends_with(&format!("from {}", step.responder)));
+				}
+			}
+
+			// Traversed from the verdict back, the events come in the order they happened.
+			let events = chain.events();
+			assert_eq!(events.first(), Some(&started));
+			assert_eq!(events.last(), Some(&chain.detected));
+		}
+	}
+	assert!(world.evidence_chains_against("Dave").is_empty());
+}
+
+#[test]
+fn control_scenarios_never_start_the_protocol() {
+	for voters in [
+		fixtures::one_sided_fork(Adversary::ReturnPrecommits),
+		fixtures::duplicate_commits(Adversary::ReturnPrecommits),
+	] {
+		let mut world = World::new(voters);
+		let report = world.run();
+
+		// Everyone heard the commits, but none of them conflict with what they had finalized.
+		for voter in world.voters().values() {
+			assert_eq!(voter.chain.finalized_round(2), Some(&2));
+		}
+		assert_eq!(report.termination, Some(Termination::Concluded));
+		assert_eq!(report.instances_started, 0);
+		assert_eq!(report.queries_sent, 0);
+		assert!(report.detectors().is_empty());
+		assert!(world.offenders().is_empty());
+		assert_eq!(world.guarantee(), GuaranteeStatus::NoConflict);
+		assert!(world.protocol_bugs().is_empty());
+	}
+}
+
+#[test]
+fn partitioned_broadcaster_triggers_nothing() {
+	let everyone_else = vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()];
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_partition_schedule(PartitionSchedule::new(vec![Partition::new(
+			0,
+			100,
+			vec![vec!["Dave".to_string()], everyone_else],
+		)]))
+		.with_max_ticks(100)
+		.build();
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	assert!(world.equivocations_detected().is_empty());
+	assert_eq!(world.guarantee(), GuaranteeStatus::NoConflict);
+}
+
+#[test]
+fn prevote_equivocations_only() {
+	// Even though the adversaries prefer to answer with precommits, the only valid explanation for
+	// round 2 are prevotes, so the protocol must go through the prevote query.
+	let mut world = World::new(fixtures::paper_example_with_prevote_equivocations(
+		Adversary::ReturnPrecommits,
+	));
 
 	while !world.completed() {
 		let requests = world.process_actions();
@@ -269,12 +589,3687 @@ fn basic_example_with_prevotes() {
 		&[EquivocationDetected::Prevote(vec![
 			Equivocation {
 				voter: "Alice".to_string(),
+				set_id: 0,
 				blocks: vec![1, 4],
 			},
 			Equivocation {
 				voter: "Bob".to_string(),
+				set_id: 0,
 				blocks: vec![1, 4],
 			}
 		]),],
 	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+
+	// Alice and Bob have two histories for round 2 and answer the prevote query with the one they
+	// already revealed, which proves nothing, but doesn't count against them either.
+	assert!(world.coverage().contains(Branch::PrevoteHistorySelected));
+	assert!(world.protocol_bugs().is_empty());
+	let verdicts = &world.verdicts()["Carol"][&ConflictId::new(2, 8)];
+	let prevote_verdicts = verdicts.iter().find(|round| round.prevotes).unwrap();
+	assert_eq!(prevote_verdicts.verdicts["Alice"], Verdict::Valid);
+	assert_eq!(prevote_verdicts.verdicts["Bob"], Verdict::Valid);
+	assert_eq!(
+		prevote_verdicts.verdicts["Carol"],
+		Verdict::RevealedEquivocations
+	);
+}
+
+#[test]
+fn threshold_sized_byzantine_set_never_triggers_protocol() {
+	let mut voters = fixtures::single_equivocator(Adversary::ReturnPrecommits);
+	// Have the other fork broadcast its commits as well, so that Dave syncs and receives them.
+	if let Some(v) = voters.get_mut("Carol") {
+		v.add_actions(vec![(10, Action::BroadcastCommits)]);
+	}
+	let mut world = World::new(voters);
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	assert!(world.equivocations_detected().is_empty());
+	assert!(world
+		.voters()
+		.values()
+		.all(|voter| voter.instances().len() == 0 && voter.outcomes.is_empty()));
+	assert!(world.voters()["Dave"].chain.knows_about_block(2));
+	assert_eq!(world.guarantee(), GuaranteeStatus::NoConflict);
+}
+
+#[test]
+fn force_triggered_protocol_without_conflict() {
+	let mut chain = ChainBuilder::new()
+		.chain([1, 2])
+		.fork_at(1, [5, 6])
+		.build()
+		.unwrap()
+		.chain;
+	let voter_set = VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]);
+	let mut round = VotingRound::new(1, voter_set);
+	round.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol")]);
+	let commit = Commit::new(1, round.precommits.clone());
+	chain.finalize_block(1, 1, commit.clone());
+
+	assert_eq!(
+		AccountableSafety::start(1, 1, commit.clone(), 6, 3, &chain).unwrap_err(),
+		Outcome::NoConflict {
+			block_not_included: 1,
+			new_block: 6,
+		},
+	);
+	assert!(AccountableSafety::start(2, 1, commit, 6, 3, &chain).is_ok());
+}
+
+#[test]
+fn invalid_responses_are_attributed_to_the_responder() {
+	let mut chain = ChainBuilder::new()
+		.chain([1, 2])
+		.fork_at(1, [5, 6])
+		.build()
+		.unwrap()
+		.chain;
+	let voter_set = VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]);
+	let mut round = VotingRound::new(1, voter_set.clone());
+	round.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
+	let commit = Commit::new(2, round.precommits.clone());
+	chain.finalize_block(2, 1, commit.clone());
+
+	// Alice explains the estimate of round 2 with precommits that could have finalized block 2,
+	// which rules out nothing. It is her answer, so she is the one on record for it.
+	let mut instance = AccountableSafety::start(2, 1, commit, 6, 3, &chain).unwrap();
+	instance.start_query_round(3, voter_set.voter_ids());
+	let mut round = VotingRound::new(2, voter_set);
+	round.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]);
+	let response = QueryResponse::Precommits(round.precommits.clone());
+	let commitment = VoteCommitment::new("Alice".to_string(), 2, 0, 0, &[], &round.precommits)
+		.sign(&fixtures::paper_key("Alice"))
+		.revealing(0, &response);
+	assert!(instance
+		.add_response(3, "Alice".to_string(), response, commitment, &chain)
+		.unwrap()
+		.is_none());
+	assert_eq!(
+		instance.equivocations_detected(),
+		vec![EquivocationDetected::InvalidResponse {
+			voter: "Alice".to_string(),
+			set_id: 0,
+		}]
+	);
+}
+
+#[test]
+fn commit_for_the_earlier_round_arriving_last() {
+	// Carol also broadcasts her commits, so Dave, who finalized block 8 in round 4, hears about
+	// block 2 finalized in round 2 after the fact. The protocol walks back from the later round
+	// whichever of the commits arrives first, so Dave finds the same equivocations as Carol.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters
+		.get_mut("Carol")
+		.unwrap()
+		.add_actions(vec![(10, Action::BroadcastCommits)]);
+	let mut world = World::new(voters);
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	let carol = world.voters()["Carol"].equivocations_detected();
+	let dave = world.voters()["Dave"].equivocations_detected();
+	assert!(!dave.is_empty());
+	assert_eq!(dave, carol);
+}
+
+#[test]
+fn broadcaster_withholding_blocks() {
+	// Dave never serves the blocks for the commit it broadcasts, so Carol has to fetch them from
+	// someone else before the protocol can start.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Dave").unwrap().behaviour = Some(Arc::new(Adversary::WithholdBlocks));
+	let mut world = World::new(voters);
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	let carol = &world.voters()["Carol"];
+	assert!(carol.chain.knows_about_block(8));
+	assert_eq!(
+		carol.block_requests[&8][..2],
+		["Dave".to_string(), "Alice".to_string()]
+	);
+	assert_eq!(world.equivocations_detected().len(), 5);
+}
+
+// Everyone but Carol knows the blocks of both forks, while Carol only knows the first one.
+fn voters_serving_doctored_blocks() -> ScenarioBuilder {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	basic_scenario(
+		&[
+			("Alice", all.clone()),
+			("Bob", all.clone()),
+			("Carol", ChainView::up_to(&chain, &[4]).unwrap()),
+			("Dave", all),
+		],
+		Adversary::DoctorBlocks,
+	)
+}
+
+#[test]
+fn doctored_ancestry_is_not_trusted() {
+	// Whoever Carol asks serves block 8 as a child of block 4, which would hide the conflict with
+	// block 2, but the block doesn't match the hash in Dave's commit.
+	let mut world = World::new(voters_serving_doctored_blocks().build().unwrap());
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::UntrustedAncestry));
+
+	let carol = &world.voters()["Carol"];
+	assert!(!carol.chain.knows_about_block(8));
+	assert_eq!(carol.instances().len(), 0);
+	assert_eq!(
+		carol.outcomes,
+		vec![Outcome::UntrustedAncestry {
+			block: 8,
+			suppliers: vec!["Alice".to_string(), "Bob".to_string(), "Dave".to_string()],
+		}]
+	);
+}
+
+#[test]
+fn honest_peer_serves_trusted_ancestry() {
+	// Alice's commit for block 8 reaches Carol first, but Carol only builds on the blocks once Dave
+	// serves them.
+	let mut voters = voters_serving_doctored_blocks()
+		.with_action("Alice", 5, Action::BroadcastCommits)
+		.build()
+		.unwrap();
+	voters.get_mut("Dave").unwrap().behaviour = Some(Arc::new(Adversary::ReturnPrecommits));
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::UntrustedAncestry));
+
+	let carol = &world.voters()["Carol"];
+	assert_eq!(
+		carol.chain.get_block(8),
+		fixtures::paper_chain().get_block(8)
+	);
+	assert!(carol.outcomes.is_empty());
+	assert_eq!(
+		carol.known_offenders(),
+		vec!["Alice".to_string(), "Bob".to_string()]
+			.into_iter()
+			.collect()
+	);
+}
+
+#[test]
+fn queried_voters_are_exonerated() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+
+	// Carol asks Alice, Bob and Dave about rounds 4 and 3, but only Dave's answers hold up.
+	let exonerations = &world.voters()["Carol"].exonerations()[&ConflictId::new(2, 8)];
+	assert_eq!(exonerations.len(), 1);
+	assert_eq!(
+		exonerations[0].to_string(),
+		"Dave explained rounds { 3, 4 }"
+	);
+	assert_eq!(
+		exonerations[0].explanations[&4],
+		QueryResponse::Precommits(vec![
+			Precommit::new(3, 1, "Alice"),
+			Precommit::new(3, 1, "Bob"),
+			Precommit::new(3, 1, "Dave"),
+		]),
+	);
+
+	let summary = world.summary();
+	assert_eq!(summary.exonerated, world.exonerated());
+	assert_eq!(
+		summary.exonerated.into_iter().collect::<Vec<_>>(),
+		vec!["Dave"]
+	);
+	assert!(summary.offenders.iter().all(|voter| voter != "Dave"));
+}
+
+#[test]
+fn voter_restarting_with_amnesia() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	// Dave restarts having lost his votes, but not the chain or the commits in it.
+	voters.get_mut("Dave").unwrap().lose_voting_history();
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+
+	// Not remembering is no proof of equivocating, so only Alice and Bob are found out.
+	assert!(world.protocol_bugs().is_empty());
+	assert_eq!(
+		world.summary().offenders.into_iter().collect::<Vec<_>>(),
+		vec!["Alice", "Bob"]
+	);
+	let verdicts = world.verdicts();
+	assert!(verdicts["Carol"][&ConflictId::new(2, 8)]
+		.iter()
+		.any(|round| round.verdicts.get("Dave") == Some(&Verdict::LostHistory)));
+
+	// But his precommit in the commit for block 8 goes unjustified.
+	let participations = &world.unjustified_participations()["Carol"][&ConflictId::new(2, 8)];
+	assert_eq!(participations.len(), 1);
+	assert_eq!(participations[0].voter, "Dave");
+	assert_eq!(participations[0].commit, 8);
+}
+
+#[test]
+fn evidence_bundles_are_signed_by_detector() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	// Carol is the only one that didn't already know about both commits
+	let mut bundles = world.evidence_bundles();
+	assert_eq!(bundles.len(), 1);
+	assert_eq!(bundles[0].bundle.detector, "Carol");
+	assert!(bundles
+		.iter()
+		.all(|bundle| bundle.verify(world.key_registry())));
+	let aggregated = world.aggregate_evidence(&bundles);
+	assert_eq!(aggregated, world.equivocations_detected());
+
+	// Alice, being Byzantine, tries to pass off a report accusing Carol as coming from Dave, signed
+	// with her own key or with a key worked out from Dave's id.
+	let accusation = EvidenceBundle {
+		detector: "Dave".to_string(),
+		equivocations: vec![EquivocationDetected::Precommit(vec![Equivocation {
+			voter: "Carol".to_string(),
+			set_id: 0,
+			blocks: vec![1, 2],
+		}])],
+		commit_provenance: Default::default(),
+	};
+	let derived_from_id = {
+		let mut hasher = DefaultHasher::new();
+		"secret".hash(&mut hasher);
+		"Dave".hash(&mut hasher);
+		SecretKey::new(hasher.finish())
+	};
+	let forged = accusation.clone().sign(&world.voters()["Alice"].secret_key);
+	assert!(!forged.verify(world.key_registry()));
+	let derived = accusation.sign(&derived_from_id);
+	assert!(!derived.verify(world.key_registry()));
+	assert!(!derived.verify(&world.voters()["Carol"].key_registry));
+
+	// Tampering with a genuine report invalidates it as well.
+	let mut tampered = bundles[0].clone();
+	tampered.bundle.equivocations.clear();
+	assert!(!tampered.verify(world.key_registry()));
+
+	let genuine = world.aggregate_evidence(&bundles);
+	bundles.push(forged);
+	bundles.push(derived);
+	bundles.push(tampered);
+	assert_eq!(world.aggregate_evidence(&bundles), genuine);
+	let rejections = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| event.voter == "world" && event.kind == EventKind::Note)
+		.filter(|event| event.summary.contains("with invalid signature"))
+		.count();
+	assert_eq!(rejections, 3);
+}
+
+#[test]
+fn commit_provenance_in_evidence() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+
+	// Dave spread the commit for block 8 at tick 10, while Carol finalized block 2 herself.
+	let carol = &world.voters()["Carol"];
+	assert_eq!(carol.commit_provenance(2), None);
+	let provenance = carol.commit_provenance(8).unwrap();
+	assert_eq!(provenance.first_received, 10);
+	assert_eq!(provenance.senders.iter().collect::<Vec<_>>(), vec!["Dave"]);
+
+	let bundles = world.evidence_bundles();
+	assert_eq!(bundles[0].bundle.detector, "Carol");
+	assert_eq!(
+		bundles[0].bundle.commit_provenance,
+		std::iter::once((8, provenance.clone())).collect()
+	);
+}
+
+#[test]
+fn fabricated_accusations_are_rejected() {
+	// Carol runs the protocol, but her evidence also accuses Dave, whose answers held up, of
+	// equivocating.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Carol").unwrap().behaviour = Some(Arc::new(Adversary::FabricateAccusations));
+	voters.get_mut("Dave").unwrap().behaviour = None;
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	let carol = world.voters()["Carol"].evidence_bundle();
+	assert!(carol
+		.bundle
+		.equivocations
+		.iter()
+		.any(|equivocation| equivocation.offenders() == vec!["Dave".to_string()]));
+
+	let dave = &world.voters()["Dave"];
+	assert!(!dave.proof_store.contains_key("Carol"));
+	assert!(dave.known_offenders().contains("Carol"));
+	assert!(!dave.known_offenders().contains("Dave"));
+	assert!(world.coverage().contains(Branch::FabricationRejected));
+
+	let offenders: BTreeSet<_> = world
+		.aggregate_evidence(&[carol])
+		.iter()
+		.flat_map(|equivocation| equivocation.offenders())
+		.collect();
+	let expected: BTreeSet<_> = vec!["Alice", "Bob", "Carol"]
+		.into_iter()
+		.map(String::from)
+		.collect();
+	assert_eq!(offenders, expected);
+	assert!(world
+		.event_log()
+		.events()
+		.iter()
+		.any(|event| event.kind == EventKind::Note
+			&& event.summary.starts_with("rejecting accusation by Carol")));
+}
+
+#[test]
+fn unverifiable_accusations_are_not_counted() {
+	// Alice, signing as herself, accuses Dave of answering her queries wrongly and of precommitting
+	// for a block Carol has never seen. Neither can be checked by Carol, nor shown to be false.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let carol = voters.get_mut("Carol").unwrap();
+	carol.key_registry = fixtures::paper_key_registry();
+	let claims = vec![
+		EquivocationDetected::InvalidResponse {
+			voter: "Dave".to_string(),
+			set_id: 0,
+		},
+		EquivocationDetected::Precommit(vec![Equivocation {
+			voter: "Dave".to_string(),
+			set_id: 0,
+			blocks: vec![1, 99],
+		}]),
+	];
+	let evidence = EvidenceBundle {
+		detector: "Alice".to_string(),
+		equivocations: claims.clone(),
+		commit_provenance: Default::default(),
+	}
+	.sign(&fixtures::paper_key("Alice"));
+	carol.handle_request(("Alice".into(), Request::HereIsEvidence(evidence)), 0);
+
+	assert_eq!(carol.unconfirmed_claims["Alice"], claims);
+	assert!(carol.proof_store["Alice"].is_empty());
+	assert!(carol.known_offenders().is_empty());
+	assert!(carol.known_offenders_by_set().is_empty());
+	assert!(!carol.coverage().contains(Branch::FabricationRejected));
+}
+
+#[test]
+fn stop_once_accountability_is_reached() {
+	let run = |stop_condition| {
+		WorldBuilder::new()
+			.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+			.with_stop_condition(stop_condition)
+			.with_max_ticks(1000)
+			.build()
+			.run_until_settled()
+	};
+	let settled = run(StopCondition::Settled);
+	let accountable = run(StopCondition::AccountabilityReached);
+
+	// Alice and Bob are more than a third of the four voters.
+	assert!(settled.settled);
+	assert!(!accountable.settled);
+	assert!(accountable.ticks < settled.ticks);
+	assert_eq!(accountable.offenders, settled.offenders);
+	assert!(accountable.accountability_latency.is_some());
+	assert_eq!(
+		accountable.accountability_latency,
+		settled.accountability_latency
+	);
+}
+
+#[test]
+fn query_plan_as_dry_run() {
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_stop_condition(StopCondition::Detected)
+		.build();
+	world.run_until_settled();
+
+	// Carol stops before sending the first query, knowing only whom she'll ask about round 4.
+	let queries_sent = |world: &World| {
+		world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| {
+				event.kind == EventKind::MessageSent
+					&& event
+						.summary
+						.starts_with("WhyDidEstimateForRoundNotIncludeBlock")
+			})
+			.count()
+	};
+	assert_eq!(queries_sent(&world), 0);
+	let signers: Vec<VoterId> = vec!["Alice".into(), "Bob".into(), "Dave".into()];
+	let planned = |round, started| PlannedRound {
+		round,
+		voters: signers.clone(),
+		started,
+	};
+	let plans = world.query_plans();
+	assert_eq!(plans.keys().collect::<Vec<_>>(), vec!["Carol"]);
+	assert_eq!(
+		plans["Carol"][&ConflictId::new(2, 8)],
+		vec![planned(4, true), planned(3, false)]
+	);
+
+	// The plan holds up once the queries are actually exchanged.
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	assert_eq!(queries_sent(&world), 6);
+	assert_eq!(
+		world.query_plans()["Carol"][&ConflictId::new(2, 8)],
+		vec![planned(4, true), planned(3, true)]
+	);
+}
+
+#[test]
+fn event_log_as_json_lines() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	let mut output = Vec::new();
+	world.event_log().write_jsonl(&mut output).unwrap();
+	let events = EventLog::read_jsonl(output.as_slice()).unwrap();
+	assert_eq!(events, world.event_log().events());
+	assert_eq!(EventLog::verify_events(&events), Ok(()));
+
+	// The same run serializes to the same log.
+	let mut again = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut again);
+	assert_eq!(again.event_log().head(), world.event_log().head());
+
+	let started: Vec<_> = events
+		.iter()
+		.filter(|event| event.kind == EventKind::ProtocolStarted)
+		.collect();
+	assert_eq!(started.len(), 1);
+	assert_eq!(started[0].voter, "Carol");
+
+	let detections = events
+		.iter()
+		.filter(|event| event.kind == EventKind::EquivocationDetected)
+		.count();
+	assert_eq!(detections, world.equivocations_detected().len());
+	assert!(events.iter().all(|event| event.tick >= 10));
+}
+
+#[test]
+fn adversaries_ignoring_a_verifier() {
+	// Alice and Bob refuse to answer Carol, and Carol is cut off from Dave while running the
+	// protocol. Dave, who also learns about the conflict from Carol's commits, gossips what he
+	// finds so that Carol ends up with the same offenders.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for adversary in &["Alice", "Bob"] {
+		voters.get_mut(*adversary).unwrap().behaviour =
+			Some(Arc::new(Adversary::IgnoreQueriesFrom(vec!["Carol".into()])));
+	}
+	let carol = voters.get_mut("Carol").unwrap();
+	carol.behaviour = None;
+	carol.add_actions(vec![(10, Action::BroadcastCommits)]);
+	voters.get_mut("Dave").unwrap().behaviour = None;
+
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_partition_schedule(PartitionSchedule::new(vec![Partition::new(
+			11,
+			200,
+			vec![vec!["Carol".to_string()], vec!["Dave".to_string()]],
+		)]))
+		.with_max_ticks(500)
+		.build();
+
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	let carol = &world.voters()["Carol"];
+	let dave = &world.voters()["Dave"];
+	assert!(carol.equivocations_detected().is_empty());
+	assert!(!dave.equivocations_detected().is_empty());
+
+	let expected_offenders = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	assert_eq!(dave.known_offenders(), expected_offenders);
+	assert_eq!(carol.known_offenders(), expected_offenders);
+	assert!(carol.proof_store.contains_key("Dave"));
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+#[test]
+fn nearest_first_expands_past_silent_voters() {
+	// Alice is the nearest voter to Carol, but ignores her queries. Carol moves on to the next
+	// nearest voter once an answer from Alice is overdue.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Alice").unwrap().behaviour =
+		Some(Arc::new(Adversary::IgnoreQueriesFrom(vec!["Carol".into()])));
+
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_query_plan(QueryPlan::NearestFirst)
+		.with_link_latency("Alice", "Carol", 1)
+		.with_network(3, 0.0)
+		.with_max_ticks(1000)
+		.build();
+	run_to_completion(&mut world);
+
+	assert!(world.coverage().contains(Branch::QueryExpanded));
+	let expected_offenders = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	assert_eq!(
+		world.voters()["Carol"].known_offenders(),
+		expected_offenders
+	);
+}
+
+#[test]
+fn queries_ignored_stay_outstanding_until_they_time_out() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for adversary in &["Alice", "Bob"] {
+		voters.get_mut(*adversary).unwrap().behaviour =
+			Some(Arc::new(Adversary::IgnoreQueriesFrom(vec!["Carol".into()])));
+	}
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_max_ticks(500)
+		.build();
+	while world.current_tick() < 100 {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+
+	// Dave answered Carol, but Alice and Bob haven't, and Carol is still waiting for them.
+	let outstanding = world.voters()["Carol"].outstanding_queries();
+	assert_eq!(
+		outstanding.first(),
+		Some(&OutstandingQuery {
+			round: 4,
+			kind: QueryKind::Estimate,
+			voter: "Alice".to_string(),
+			since: 30,
+			conflicts: vec![ConflictId::new(2, 8)],
+		})
+	);
+	let unanswered: BTreeSet<_> = outstanding
+		.iter()
+		.map(|query| query.voter.as_str())
+		.collect();
+	assert_eq!(unanswered, vec!["Alice", "Bob"].into_iter().collect());
+	assert!(outstanding
+		.iter()
+		.all(|query| query.conflicts == vec![ConflictId::new(2, 8)]));
+
+	// They never will. Carol stops waiting for them, so the run concludes rather than going on to
+	// the tick limit, and nothing is left outstanding.
+	run_to_completion(&mut world);
+	assert_eq!(world.termination(), Some(Termination::Concluded));
+	assert!(world.current_tick() < 500);
+	let carol = &world.voters()["Carol"];
+	assert_eq!(carol.pending_actions().count(), 0);
+	assert!(carol.outstanding_queries().is_empty());
+}
+
+// The same scenario as `fixtures::paper_example`, but with the blocks each voter
+// knows about given by the chain views.
+fn scenario_with_chain_views(
+	views: &[(&'static str, ChainView)],
+	behaviour: Adversary,
+) -> Result<BTreeMap<VoterId, Voter>, ScenarioError> {
+	basic_scenario(views, behaviour).build()
+}
+
+fn basic_scenario(views: &[(&'static str, ChainView)], behaviour: Adversary) -> ScenarioBuilder {
+	let mut scenario = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+					.finalize(2),
+				RoundSpec::new(3)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+				RoundSpec::new(4)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+			],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(3)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(4)
+					.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.finalize(8),
+			],
+		);
+	for (voter, view) in views {
+		let histories: &[&str] = match *voter {
+			"Carol" => &["common", "a"],
+			"Dave" => &["common", "b"],
+			_ => &["common", "a", "b"],
+		};
+		scenario = scenario.with_voter(voter, view.blocks(), histories, Some(behaviour.clone()));
+	}
+	scenario.with_action("Dave", 10, Action::BroadcastCommits)
+}
+
+#[test]
+fn honest_prevotes_for_a_third_fork_are_consistent_with_the_commit() {
+	// As in the paper example, except that in round 3 on fork b everyone prevoted for block 10 on a
+	// third fork. Their precommits stayed on block 1, so that was the estimate, and precommitting
+	// for block 8 in round 4 was fine. Dave, honest but answering with prevotes, shows the
+	// supermajority for block 10, which bounds the ghost but not the estimate.
+	let chain = ChainBuilder::new()
+		.chain([1, 2])
+		.fork_at(1, [5, 6, 7, 8])
+		.fork_at(1, [9, 10])
+		.build()
+		.unwrap()
+		.chain;
+	let all = ChainView::up_to(&chain, &[2, 8, 10]).unwrap();
+	let all_blocks = all.blocks();
+	let common = RoundSpec::new(1)
+		.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+		.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+		.finalize(1);
+	let fork_a = RoundSpec::new(2)
+		.prevote(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+		.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+		.finalize(2);
+	let fork_b = vec![
+		RoundSpec::new(2)
+			.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Dave")])
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+		RoundSpec::new(3)
+			.prevote(&[(10, "Alice"), (10, "Bob"), (10, "Dave")])
+			.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+		RoundSpec::new(4)
+			.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+			.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+			.finalize(8),
+	];
+	let voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history("common", vec![common])
+		.with_history("a", vec![fork_a])
+		.with_history("b", fork_b)
+		.with_voter("Alice", all_blocks, &["common", "a", "b"], None)
+		.with_voter("Bob", all_blocks, &["common", "a", "b"], None)
+		.with_voter("Carol", all_blocks, &["common", "a"], None)
+		.with_voter("Dave", all_blocks, &["common", "b"], None)
+		.with_behaviour("Dave", Adversary::ReturnPrevotes)
+		.with_action("Dave", 10, Action::BroadcastCommits)
+		.build()
+		.unwrap();
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+
+	let verdicts = &world.verdicts()["Carol"][&ConflictId::new(2, 8)];
+	let round_4 = verdicts
+		.iter()
+		.find(|verdicts| verdicts.round == 4)
+		.unwrap();
+	assert_eq!(round_4.verdicts["Dave"], Verdict::Valid);
+	assert!(!world.offenders().contains("Dave"));
+	assert!(world.protocol_bugs().is_empty());
+}
+
+// The two forks of the paper example, named "a" and "b".
+fn paper_forks() -> Forks {
+	ChainBuilder::new()
+		.chain([1, 2, 3, 4])
+		.named("a")
+		.fork_at(1, [5, 6, 7, 8])
+		.named("b")
+		.build()
+		.unwrap()
+}
+
+fn run_to_completion(world: &mut World) {
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		world.tick();
+	}
+}
+
+// What Carol finds out in the basic example when Alice and Bob explain with precommits.
+fn detections_in_basic_example() -> Vec<EquivocationDetected> {
+	let precommit_equivocations = EquivocationDetected::Precommit(vec![
+		Equivocation {
+			voter: "Alice".to_string(),
+			set_id: 0,
+			blocks: vec![1, 2],
+		},
+		Equivocation {
+			voter: "Bob".to_string(),
+			set_id: 0,
+			blocks: vec![1, 2],
+		},
+	]);
+	let claimed = vec![
+		Precommit::new(2, 1, "Alice"),
+		Precommit::new(2, 1, "Bob"),
+		Precommit::new(2, 1, "Dave"),
+	];
+	let inconsistent_testimony = |voter: &str| {
+		EquivocationDetected::InconsistentTestimony(InconsistentTestimony {
+			voter: voter.to_string(),
+			round: 2,
+			finalized: 2,
+			signed: Precommit::new(2, 2, voter),
+			claimed: claimed.clone(),
+		})
+	};
+	vec![
+		inconsistent_testimony("Alice"),
+		precommit_equivocations.clone(),
+		inconsistent_testimony("Bob"),
+		precommit_equivocations.clone(),
+		precommit_equivocations,
+	]
+}
+
+#[test]
+fn basic_example_from_scenario_builder() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let voters = scenario_with_chain_views(
+		&[
+			("Alice", all.clone()),
+			("Bob", all),
+			("Carol", ChainView::up_to(&chain, &[4]).unwrap()),
+			("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+		],
+		Adversary::ReturnPrecommits,
+	)
+	.expect("scenario is valid");
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+}
+
+#[test]
+fn diverging_chain_views_are_synced() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+
+	// Carol is missing different parts of the fork she finalized on, or already knows the start
+	// of the other fork. In all cases she has to fetch block 8 and its ancestors from Dave
+	// before she can act on his commit.
+	let carol_views = [
+		ChainView::subset_of(&chain, &[1, 2]).unwrap(),
+		ChainView::subset_of(&chain, &[1, 2, 3]).unwrap(),
+		ChainView::subset_of(&chain, &[1, 2, 5]).unwrap(),
+		ChainView::subset_of(&chain, &[1, 2, 3, 4, 5, 6]).unwrap(),
+	];
+	for carol_view in carol_views.iter() {
+		let voters = scenario_with_chain_views(
+			&[
+				("Alice", all.clone()),
+				("Bob", all.clone()),
+				("Carol", carol_view.clone()),
+				("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+			],
+			Adversary::ReturnPrecommits,
+		)
+		.expect("scenario is valid");
+		assert!(!voters["Carol"].chain.knows_about_block(8));
+
+		let mut world = World::new(voters);
+		run_to_completion(&mut world);
+
+		assert!(world.voters()["Carol"].chain.knows_about_block(8));
+		assert!(world
+			.event_log()
+			.events()
+			.iter()
+			.any(|event| event.voter == "Dave" && event.summary.starts_with("HereAreBlocks")));
+		assert_eq!(
+			world.voters()["Carol"].equivocations_detected(),
+			detections_in_basic_example()
+		);
+	}
+}
+
+#[test]
+fn observer_verifies_conflicting_commits() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let voters = basic_scenario(
+		&[
+			("Alice", all.clone()),
+			("Bob", all.clone()),
+			("Carol", ChainView::up_to(&chain, &[4]).unwrap()),
+			("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+		],
+		Adversary::ReturnPrecommits,
+	)
+	.with_observer("Olive", all.blocks())
+	.with_action("Carol", 10, Action::BroadcastCommits)
+	.build()
+	.unwrap();
+	assert!(voters["Olive"].is_observer());
+	assert!(!voters["Alice"].voter_set.is_member("Olive"));
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+
+	// Olive only learns of the commits from Carol and Dave, and finds the offenders by querying the
+	// voters without ever being queried herself.
+	let olive = &world.voters()["Olive"];
+	assert_eq!(olive.commit_report().finalized_blocks(), vec![1, 2, 8]);
+	assert_eq!(
+		olive.known_offenders(),
+		vec!["Alice".to_string(), "Bob".to_string()]
+			.into_iter()
+			.collect()
+	);
+	let queries: Vec<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| {
+			event.kind == EventKind::MessageDelivered
+				&& event
+					.summary
+					.starts_with("WhyDidEstimateForRoundNotIncludeBlock")
+		})
+		.collect();
+	assert!(queries
+		.iter()
+		.any(|event| event.summary.ends_with("from Olive")));
+	assert!(queries.iter().all(|event| event.voter != "Olive"));
+
+	// Her evidence reaches the voters too.
+	assert!(world.voters()["Carol"].proof_store.contains_key("Olive"));
+}
+
+#[test]
+fn chain_view_must_contain_finalized_blocks() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let error = scenario_with_chain_views(
+		&[
+			("Alice", all.clone()),
+			("Bob", all),
+			// Carol has seen the commit for block 2, so must know about it
+			("Carol", ChainView::subset_of(&chain, &[1]).unwrap()),
+		],
+		Adversary::ReturnPrecommits,
+	)
+	.err()
+	.unwrap();
+	assert_eq!(
+		error,
+		ScenarioError::InvalidFinalization {
+			voter: "Carol".to_string(),
+			history: "a".to_string(),
+			round: 2,
+			error: ChainError::UnknownBlock(2),
+		}
+	);
+}
+
+#[test]
+fn scenario_coverage_report() {
+	let mut withholding = fixtures::paper_example(Adversary::ReturnPrecommits);
+	withholding.get_mut("Dave").unwrap().behaviour = Some(Arc::new(Adversary::WithholdBlocks));
+	let mut ignoring = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for adversary in &["Alice", "Bob"] {
+		ignoring.get_mut(*adversary).unwrap().behaviour =
+			Some(Arc::new(Adversary::IgnoreQueriesFrom(vec!["Carol".into()])));
+	}
+
+	let scenarios = vec![
+		(
+			"precommits",
+			fixtures::paper_example(Adversary::ReturnPrecommits),
+		),
+		(
+			"prevotes",
+			fixtures::paper_example(Adversary::ReturnPrevotes),
+		),
+		(
+			"prevote equivocations",
+			fixtures::paper_example_with_prevote_equivocations(Adversary::ReturnPrecommits),
+		),
+		("withholding blocks", withholding),
+		("ignoring queries", ignoring),
+	];
+
+	let mut total = Coverage::new();
+	for (name, voters) in scenarios {
+		let mut world = WorldBuilder::new()
+			.with_voters(voters)
+			.with_max_ticks(500)
+			.build();
+		run_to_completion(&mut world);
+		let coverage = world.coverage();
+		println!("{}: {:#x}", name, coverage.bitmap());
+		total.merge(coverage);
+	}
+
+	println!("Branches exercised by the scenarios:\n{}", total);
+	println!("Not exercised by any scenario: {:?}", total.missing());
+
+	assert!(total.contains(Branch::PrecommitCrossCheck));
+	assert!(total.contains(Branch::PrevoteQuery));
+	assert!(total.contains(Branch::PrevoteCrossCheck));
+	assert!(total.contains(Branch::RequeueRequest));
+	assert!(total.contains(Branch::BlockRequestFallback));
+	assert!(total.contains(Branch::BlockSync));
+	assert!(total.contains(Branch::QueryIgnored));
+}
+
+// Dave has lost his voting history, and tries to send a block to someone outside the voter set.
+fn setup_voters_with_internal_inconsistencies() -> BTreeMap<VoterId, Voter> {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let dave = voters.get_mut("Dave").unwrap();
+	dave.voting_rounds = VotingRounds::new();
+	dave.add_actions(vec![(5, Action::SendBlock("Eve".to_string(), 1))]);
+	voters
+}
+
+#[test]
+fn strict_mode_collects_protocol_bugs() {
+	let mut world = WorldBuilder::new()
+		.with_voters(setup_voters_with_internal_inconsistencies())
+		.with_strict(true)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	let bugs: Vec<_> = world
+		.protocol_bugs()
+		.into_iter()
+		.map(|event| (event.voter.as_str(), event.summary.as_str()))
+		.collect();
+	assert_eq!(
+		bugs,
+		vec![(
+			"world",
+			"HereAreBlocks(1 blocks) from Dave to unknown voter Eve"
+		)]
+	);
+	// The run carried on, and Alice and Bob's answers are still enough to find them out.
+	assert!(!world.equivocations_detected().is_empty());
+	// Without his history, Dave claims he wasn't a member of the voter set, which he was.
+	assert!(world
+		.equivocations_detected()
+		.contains(&EquivocationDetected::FalseNonMembership {
+			voter: "Dave".to_string(),
+			set_id: 0,
+			round: 2,
+		}));
+}
+
+#[test]
+#[should_panic(expected = "unknown voter Eve")]
+fn protocol_bugs_panic_outside_strict_mode() {
+	let mut world = WorldBuilder::new()
+		.with_voters(setup_voters_with_internal_inconsistencies())
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+}
+
+#[test]
+fn chunked_responses() {
+	// With at most two votes per message all explanations of three votes are chunked, and the
+	// outcome is the same as without chunking.
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_message_budget(2)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	assert!(world.event_log().events().iter().any(|event| event
+		.summary
+		.starts_with("ExplainEstimateChunk(round: 4, block: 2, chunk: 2/2")));
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+}
+
+#[test]
+fn truncated_chunked_responses() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for adversary in &["Alice", "Bob"] {
+		voters.get_mut(*adversary).unwrap().behaviour =
+			Some(Arc::new(Adversary::TruncateResponses));
+	}
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_message_budget(2)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	// Alice and Bob never complete their explanations, but Dave's are enough to walk back to round
+	// 3 and find them out anyway.
+	let partial_response = |voter: &str, round| EquivocationDetected::PartialResponse {
+		voter: voter.to_string(),
+		set_id: 0,
+		round,
+		received: 1,
+		total: 2,
+	};
+	assert_eq!(
+		world.voters()["Carol"].equivocations_detected(),
+		vec![
+			detections_in_basic_example()[1].clone(),
+			partial_response("Alice", 3),
+			partial_response("Bob", 3),
+			partial_response("Alice", 4),
+			partial_response("Bob", 4),
+		]
+	);
+	assert!(world.coverage().contains(Branch::PartialResponse));
+}
+
+#[test]
+fn divergent_commit_broadcasts() {
+	// Alice's commit for block 8 includes a precommit from Carol as well, which Dave's doesn't.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let alice = voters.get_mut("Alice").unwrap();
+	let mut chain = fixtures::paper_chain();
+	for (block, commit) in alice.chain.commits() {
+		let mut commit = commit.clone();
+		let round = *alice.chain.finalized_round(*block).unwrap();
+		if *block == 8 {
+			commit.precommits.push(Precommit::new(round, 8, "Carol"));
+		}
+		chain.finalize_block(*block, round, commit);
+	}
+	alice.chain = chain;
+	// Late enough that the others have already started the protocol based on Dave's commit, since
+	// Alice's would have Carol explain why she didn't precommit for block 8.
+	alice.add_actions(vec![(100, Action::BroadcastCommits)]);
+
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	let ids = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+	let carol = &world.voters()["Carol"];
+	assert_eq!(
+		carol.divergent_commit_variants(),
+		vec![DivergentCommitVariants {
+			block: 8,
+			round: 4,
+			variants: vec![
+				CommitVariant {
+					round: 4,
+					signers: ids(&["Alice", "Bob", "Dave"]),
+					senders: ids(&["Dave"]),
+				},
+				CommitVariant {
+					round: 4,
+					signers: ids(&["Alice", "Bob", "Carol", "Dave"]),
+					senders: ids(&["Alice"]),
+				},
+			],
+		}]
+	);
+	// The second variant doesn't start another instance of the protocol.
+	assert_eq!(carol.instances().len(), 1);
+	assert_eq!(
+		world
+			.divergent_commit_variants()
+			.keys()
+			.cloned()
+			.collect::<Vec<_>>(),
+		vec!["Alice", "Bob", "Carol", "Dave"]
+	);
+}
+
+#[test]
+fn temporal_properties_from_snapshots() {
+	let mut world = WorldBuilder::new()
+		.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+		.with_snapshot_interval(5)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	let snapshots = world.snapshots().unwrap();
+	// The run ends once the protocol concludes, well before the tick limit.
+	assert_eq!(
+		snapshots.snapshots().len(),
+		world.current_tick().div_ceil(5)
+	);
+	assert!(world.current_tick() < 500);
+
+	// No accusations before tick 30
+	let first_accusation = snapshots
+		.first_tick_where(Snapshot::any_accusations)
+		.unwrap();
+	assert!(first_accusation >= 30);
+	assert!(!world
+		.state_at(first_accusation - 1)
+		.unwrap()
+		.any_accusations());
+
+	// Once the conflict is detected the protocol completes within 50 ticks.
+	let protocol_started = snapshots
+		.first_tick_where(Snapshot::protocol_started)
+		.unwrap();
+	assert!(first_accusation - protocol_started <= 50);
+
+	// Carol synced the blocks of the other fork before starting the protocol.
+	let carol_at_start = &world.state_at(protocol_started).unwrap().voters["Carol"];
+	assert_eq!(carol_at_start.known_blocks, 8);
+	assert_eq!(carol_at_start.finalized, vec![1, 2].into_iter().collect());
+
+	// States in between snapshots are given by the preceding one.
+	assert_eq!(world.state_at(12), world.state_at(10));
+	assert_eq!(world.state_at(12).unwrap().tick, 10);
+}
+
+#[test]
+fn verification_failures_are_not_accusations() {
+	let run = |failure_rate| {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+			.with_verification_failure_rate(failure_rate)
+			.with_seed(7)
+			.with_max_ticks(500)
+			.build();
+		run_to_completion(&mut world);
+		world
+	};
+
+	// With some checks failing, retries and the repeated gossip still get Carol's evidence through.
+	let world = run(0.5);
+	for voter in ["Alice", "Bob", "Dave"].iter() {
+		assert!(world.voters()[*voter].proof_store.contains_key("Carol"));
+	}
+
+	// When every check fails the evidence is flagged for audit, and Carol isn't held responsible.
+	let world = run(1.0);
+	let dave = &world.voters()["Dave"];
+	assert!(dave.proof_store.is_empty());
+	assert!(!dave.flagged_for_audit.is_empty());
+	assert!(dave
+		.flagged_for_audit
+		.iter()
+		.all(|evidence| evidence.bundle.detector == "Carol"));
+	assert!(world
+		.voters()
+		.values()
+		.all(|voter| !voter.known_offenders().contains("Carol")));
+}
+
+#[test]
+fn queries_answered_from_archive() {
+	let path = std::env::temp_dir().join(format!(
+		"accountable-safety-bob-{}.json",
+		std::process::id()
+	));
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for (id, voter) in voters.iter_mut() {
+		if id == "Carol" {
+			continue;
+		}
+		// Only the most recent rounds are kept in memory, the rest is in the archive.
+		let pruned = voter.voting_rounds.prune_before(4);
+		assert!(!pruned.is_empty());
+		let archive: Arc<dyn ArchiveBackend> = if id == "Bob" {
+			Arc::new(FileArchive::create(&path, &pruned).unwrap())
+		} else {
+			Arc::new(InMemoryArchive::new(pruned))
+		};
+		voter.archive = Some(archive);
+	}
+
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_strict(true)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+	std::fs::remove_file(path).unwrap();
+
+	assert!(world.protocol_bugs().is_empty());
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+}
+
+#[test]
+fn voting_rounds_kept_on_disk() {
+	let dir =
+		std::env::temp_dir().join(format!("accountable-safety-rounds-{}", std::process::id()));
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for (id, voter) in voters.iter_mut() {
+		voter
+			.keep_rounds_in(FileRounds::open(dir.join(id)).unwrap())
+			.unwrap();
+	}
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	std::fs::remove_dir_all(dir).unwrap();
+
+	assert!(world.protocol_bugs().is_empty());
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+}
+
+#[test]
+fn unreadable_rounds_on_disk_are_reported() {
+	let dir = std::env::temp_dir().join(format!(
+		"accountable-safety-unreadable-rounds-{}",
+		std::process::id()
+	));
+	let mut alice = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove("Alice")
+		.unwrap();
+	alice.key_registry = fixtures::paper_key_registry();
+	alice
+		.keep_rounds_in(FileRounds::open(&dir).unwrap())
+		.unwrap();
+	let round_file = dir.join("round-2.json");
+	std::fs::write(&round_file, "not json").unwrap();
+
+	let statement = fixtures::paper_conflict_statement("Carol").sign(&fixtures::paper_key("Carol"));
+	let query = Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement);
+	alice.handle_request(("Carol".into(), query), 0);
+	let errors = alice.storage_errors();
+	assert!(!errors.is_empty());
+	assert!(errors[0].contains("round-2.json"));
+	alice
+		.voting_rounds
+		.add(VotingRound::new(2, alice.voter_set.clone()));
+
+	// The round we couldn't read is reported each time, and left as it was.
+	assert_eq!(alice.storage_errors().len(), errors.len() + 1);
+	assert_eq!(std::fs::read_to_string(&round_file).unwrap(), "not json");
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn commits_with_mismatched_target_are_rejected() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let alice = &voters["Alice"];
+	let hash = |block| alice.chain.block_hash(block).unwrap();
+	let commit_2 = alice.chain.commit_for_block(2).unwrap().clone();
+	let commit_8 = alice.chain.commit_for_block(8).unwrap().clone();
+
+	// Alice sends Carol commits where the target number and hash refer to different blocks: the
+	// right hash with the wrong number, the wrong hash for a known block, and the wrong hash for a
+	// block Carol first has to sync.
+	let mislabelled = |commit: &Commit, number, hash| Commit {
+		target_number: number,
+		..commit.clone().with_target_hash(hash)
+	};
+	let actions = vec![
+		mislabelled(&commit_2, 3, hash(2)),
+		mislabelled(&commit_2, 2, hash(3)),
+		mislabelled(&commit_8, 8, hash(7)),
+	]
+	.into_iter()
+	.map(|commit| (5, Action::SendCommit("Carol".to_string(), 2, commit)))
+	.collect();
+	voters.get_mut("Alice").unwrap().add_actions(actions);
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+
+	let rejected = world.rejected_commits();
+	let carol: Vec<_> = rejected["Carol"]
+		.iter()
+		.map(|mismatch| {
+			(
+				mismatch.sender.as_str(),
+				mismatch.target_number,
+				mismatch.block_with_hash,
+			)
+		})
+		.collect();
+	assert_eq!(
+		carol,
+		vec![
+			("Alice", 3, Some(2)),
+			("Alice", 2, Some(3)),
+			("Alice", 8, Some(7))
+		]
+	);
+	assert_eq!(rejected.len(), 1);
+	assert!(world.coverage().contains(Branch::CommitTargetMismatch));
+
+	// The honest commits broadcast by Dave still go through.
+	assert_eq!(
+		world.equivocations_detected(),
+		detections_in_basic_example()
+	);
+}
+
+#[test]
+fn commits_go_through_the_pipeline() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	let stages: Vec<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| event.kind == EventKind::CommitStage && event.voter == "Carol")
+		.map(|event| event.summary.as_str())
+		.collect();
+	assert_eq!(
+		stages,
+		vec![
+			"commit for block 8 in round 4 from Dave: awaiting block",
+			"commit for block 8 in round 4 from Dave: synced",
+			"commit for block 8 in round 4 from Dave: validated",
+			"commit for block 8 in round 4 from Dave: conflicts with block 2",
+			"commit for block 8 in round 4 from Dave: instance started for blocks 2 and 8",
+		]
+	);
+
+	// Commits that don't hold up never get as far as a conflict check.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let commit_8 = voters["Dave"].chain.commit_for_block(8).unwrap().clone();
+	let blocks = voters["Dave"].chain.get_chain_of_blocks(8);
+	let carol = voters.get_mut("Carol").unwrap();
+	carol.handle_request(("Dave".to_string(), Request::HereAreBlocks(blocks)), 0);
+	let mut unsigned = commit_8.clone();
+	unsigned.precommits.truncate(2);
+	let mut off_target = commit_8;
+	off_target.precommits[0].target_number = 2;
+	for commit in [unsigned, off_target] {
+		let request = Request::HereIsCommit(4, commit);
+		assert!(carol
+			.handle_request(("Dave".to_string(), request), 0)
+			.is_empty());
+	}
+	let stages: Vec<_> = carol
+		.take_commit_stages()
+		.into_iter()
+		.map(|progress| progress.stage)
+		.collect();
+	assert_eq!(
+		stages,
+		vec![
+			CommitStage::Synced,
+			CommitStage::Invalid(InvalidCommit::NoSupermajority),
+			CommitStage::Synced,
+			CommitStage::Invalid(InvalidCommit::PrecommitOffTarget(2)),
+		]
+	);
+	assert_eq!(carol.instances().count(), 0);
+	assert!(carol.coverage().contains(Branch::InvalidCommit));
+}
+
+#[test]
+fn corrupted_local_state_is_caught() {
+	let run = |voters| {
+		let mut world = World::new(voters);
+		run_to_completion(&mut world);
+		world
+	};
+	let notes_of = |world: &World, kind| -> Vec<String> {
+		world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| event.kind == kind && event.voter == "Carol")
+			.map(|event| event.summary.clone())
+			.collect()
+	};
+
+	// A commit that lost most of its signatures is rejected before anything is started on it.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let mut commit = voters["Dave"].chain.commit_for_block(8).unwrap().clone();
+	commit.precommits.truncate(1);
+	voters
+		.get_mut("Dave")
+		.unwrap()
+		.chain
+		.replace_commit(8, commit);
+	let world = run(voters);
+	assert_eq!(
+		notes_of(&world, EventKind::CommitStage).last().unwrap(),
+		"commit for block 8 in round 4 from Dave: invalid, no supermajority"
+	);
+	assert!(world.offenders().is_empty());
+
+	// Dave serves a block 8 that skips blocks 6 and 7. Once the honest ancestry arrives from Alice
+	// the two disagree, and Carol says so rather than silently mixing them.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters
+		.get_mut("Dave")
+		.unwrap()
+		.chain
+		.corrupt_parent(8, 5)
+		.unwrap();
+	let world = run(voters);
+	assert!(notes_of(&world, EventKind::Note)
+		.iter()
+		.any(|note| note.contains("differs from our Block(8, parent: 5)")));
+	assert_eq!(
+		world.offenders(),
+		["Alice", "Bob"]
+			.iter()
+			.map(|name| name.to_string())
+			.collect()
+	);
+}
+
+#[test]
+fn peers_asking_about_blocks_with_broken_ancestry_are_not_answered() {
+	// Block 6 made a child of block 8 leaves blocks 6 to 8 in a cycle that never reaches genesis.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let alice = voters.get_mut("Alice").unwrap();
+	alice.chain.corrupt_parent(6, 8).unwrap();
+
+	for request in [Request::RequestCommitFor(8), Request::PleaseSendBlock(8)] {
+		assert!(alice
+			.handle_request(("Carol".to_string(), request), 0)
+			.is_empty());
+	}
+	assert!(alice.process_actions(10).is_empty());
+	assert_eq!(alice.chain_errors().len(), 2);
+	assert!(alice.chain_errors()[0].starts_with("finding the commit for block 8: "));
+	assert!(alice.chain_errors()[1].starts_with("failed to send block 8: "));
+	assert!(alice.protocol_bugs.is_empty());
+}
+
+#[test]
+fn subscribers_are_told_of_detections_as_they_happen() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let detections = world.subscribe();
+	// A subscriber that went away doesn't hold up the others.
+	drop(world.subscribe());
+
+	let mut received = Vec::new();
+	while !world.completed() {
+		let requests = world.process_actions();
+		let responses = world.handle_requests(requests);
+		world.handle_responses(responses);
+		// Everything that happened this tick is already there, before moving on to the next.
+		for event in detections.try_iter() {
+			let tick = match &event {
+				DetectionEvent::ConflictDetected { tick, .. }
+				| DetectionEvent::QuerySent { tick, .. }
+				| DetectionEvent::OffenceProven { tick, .. } => *tick,
+			};
+			assert_eq!(tick, world.current_tick());
+			received.push(event);
+		}
+		world.tick();
+	}
+
+	match &received[0] {
+		DetectionEvent::ConflictDetected { conflict, .. } => {
+			assert_eq!(*conflict, ConflictId::new(2, 8))
+		}
+		event => panic!("expected the conflict first, got {:?}", event),
+	}
+	assert!(received.iter().any(|event| matches!(
+		event,
+		DetectionEvent::QuerySent { verifier, responder, kind: QueryKind::Estimate, .. }
+			if verifier == "Carol" && responder == "Alice"
+	)));
+	let offenders: BTreeSet<_> = received
+		.iter()
+		.filter_map(|event| match event {
+			DetectionEvent::OffenceProven { finding, .. } => Some(finding.offenders()),
+			_ => None,
+		})
+		.flatten()
+		.collect();
+	assert_eq!(offenders, world.offenders());
+}
+
+#[test]
+fn deadline_after_first_detection() {
+	let run = |latency, deadline| {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+			.with_network(latency, 0.0)
+			.with_deadline(deadline)
+			.build();
+		let summary = world.run_until_settled();
+		(world, summary)
+	};
+
+	// Carol and Dave have a complete proof 30 ticks after the conflict is first detected.
+	let (world, summary) = run(0, 40);
+	assert!(summary.settled);
+	assert_eq!(
+		summary.offenders.iter().collect::<Vec<_>>(),
+		vec!["Alice", "Bob"]
+	);
+	let report = summary.deadline.as_ref().unwrap();
+	assert!(report.met());
+	assert_eq!(report.first_detection, Some(30));
+	assert_eq!(report.latency(), Some(30));
+	assert!(!summary.deadline_missed());
+	assert!(world
+		.event_log()
+		.events()
+		.iter()
+		.all(|event| event.kind != EventKind::DeadlineMissed));
+
+	// With network latency Dave takes too long.
+	let (world, summary) = run(5, 40);
+	assert!(summary.deadline_missed());
+	let report = summary.deadline.unwrap();
+	assert_eq!(report.violations(), vec!["Dave"]);
+	assert_eq!(report.latency(), Some(55));
+	let missed: Vec<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| event.kind == EventKind::DeadlineMissed)
+		.map(|event| (event.tick, event.voter.as_str()))
+		.collect();
+	assert_eq!(missed, vec![(85, "Dave")]);
+}
+
+#[test]
+fn slow_voter_works_through_its_mailbox() {
+	let run = |budget: Option<usize>| {
+		let mut builder =
+			WorldBuilder::new().with_voters(fixtures::paper_example(Adversary::ReturnPrecommits));
+		if let Some(budget) = budget {
+			builder = builder.with_voter_processing_budget("Carol", budget);
+		}
+		let mut world = builder.build();
+		let summary = world.run_until_settled();
+		(world, summary)
+	};
+
+	let last_detection = |world: &World| {
+		world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| event.voter == "Carol" && event.kind == EventKind::EquivocationDetected)
+			.map(|event| event.tick)
+			.max()
+	};
+	let (world, unlimited) = run(None);
+	let unlimited_detection = last_detection(&world);
+	assert!(unlimited_detection.is_some());
+	assert_eq!(world.messages_carried_over(), 0);
+
+	// Carol only gets to one message per tick, but still ends up with the same offenders once she
+	// has worked through her backlog.
+	let (world, slow) = run(Some(1));
+	assert!(slow.settled);
+	assert_eq!(slow.offenders, unlimited.offenders);
+	assert!(world.protocol_bugs().is_empty());
+	assert_eq!(world.backlog("Carol"), 0);
+	assert!(world.messages_carried_over() > 0);
+	assert!(last_detection(&world) > unlimited_detection);
+}
+
+#[test]
+fn voters_generated_at_runtime() {
+	for num_voters in [4, 7, 10].iter() {
+		let names: Vec<VoterId> = (0..*num_voters).map(|i| format!("voter-{}", i)).collect();
+		let votes: Vec<_> = names.iter().map(|name| (1, name.as_str())).collect();
+		let mut scenario = ScenarioBuilder::new(&names).with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&votes)
+				.precommit(&votes)
+				.finalize(1)],
+		);
+		for name in &names {
+			scenario = scenario.with_voter(name, &[(1, 0)], &["common"], None);
+		}
+		scenario = scenario.with_action(&names[0], 10, Action::BroadcastCommits);
+
+		let mut world = WorldBuilder::new()
+			.with_voters(scenario.build().unwrap())
+			.with_max_ticks(100)
+			.build();
+		let summary = world.run_until_settled();
+
+		assert!(summary.settled);
+		assert!(summary.offenders.is_empty());
+		assert_eq!(
+			summary.peak_memory.is_some(),
+			cfg!(feature = "alloc-tracking")
+		);
+		for report in world.commit_reports() {
+			assert_eq!(report.finalized_blocks(), vec![1]);
+			assert_eq!(report.finalized[0].signers.len(), *num_voters);
+		}
+	}
+}
+
+#[test]
+fn minimal_offender_sets() {
+	let analyses = |voters| {
+		let mut world = World::new(voters);
+		run_to_completion(&mut world);
+		world.offender_analyses()
+	};
+	let names = |set: &BTreeSet<VoterId>| set.iter().cloned().collect::<Vec<_>>();
+
+	// Alice and Bob both lied about their own precommits, so neither can be left out.
+	let found = analyses(fixtures::paper_example(Adversary::ReturnPrecommits));
+	assert_eq!(found.keys().collect::<Vec<_>>(), vec!["Carol"]);
+	let analysis = &found["Carol"][0];
+	assert_eq!(names(&analysis.offenders), vec!["Alice", "Bob"]);
+	assert!(analysis.all_necessary());
+	assert!(analysis.sufficient());
+
+	// With only prevote equivocations, the findings are all accounted for by Alice alone.
+	let found = analyses(fixtures::paper_example_with_prevote_equivocations(
+		Adversary::ReturnPrevotes,
+	));
+	let analysis = &found["Carol"][0];
+	assert_eq!(names(&analysis.offenders), vec!["Alice", "Bob"]);
+	assert_eq!(names(&analysis.minimal), vec!["Alice"]);
+	assert!(!analysis.all_necessary());
+}
+
+#[test]
+fn votes_replayed_from_earlier_rounds() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	voters.get_mut("Alice").unwrap().behaviour = Some(Arc::new(Adversary::ReplayEarlierVotes));
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+
+	// Alice answers both queries with the precommits of the round before the one asked about.
+	// Those are genuine votes, but since they don't come from the round in question they're
+	// reported instead of being cross-checked as if they did, which would implicate Carol.
+	let detected = world.equivocations_detected();
+	let replayed: Vec<_> = detected
+		.iter()
+		.filter_map(|detection| match detection {
+			EquivocationDetected::ReplayedVotes {
+				voter,
+				round,
+				replayed_from,
+				..
+			} => Some((voter.as_str(), *round, replayed_from.clone())),
+			_ => None,
+		})
+		.collect();
+	assert_eq!(replayed, vec![("Alice", 2, vec![1]), ("Alice", 3, vec![2])]);
+
+	// Bob and Dave still answer truthfully, which is enough to catch the equivocations.
+	let expected_offenders: BTreeSet<VoterId> = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	assert_eq!(world.offenders(), expected_offenders);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+// Besides the conflict between block 2 and 8 of the basic example, with Alice and Bob
+// equivocating, the first fork splits again after block 4. Bob and Dave then finalize both 11 and
+// 13. Carol, who finalized 2 and 11, gets the commits for 8 and 13 from Dave.
+//
+//       /-> 2 -> 3 -> 4 -> 11
+//      /               \-> 12 -> 13
+// 0 -> 1 -> 5 -> 6 -> 7 -> 8
+fn setup_voters_with_two_conflicts() -> BTreeMap<VoterId, Voter> {
+	let all = &["Alice", "Bob", "Carol", "Dave"];
+	let mut voters = ScenarioBuilder::new(all)
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+					.finalize(2),
+				RoundSpec::new(3)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+				RoundSpec::new(4)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+			],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(3)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(4)
+					.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.finalize(8),
+			],
+		)
+		.with_history(
+			"a, continued",
+			vec![RoundSpec::new(5)
+				.prevote(&[(4, "Alice"), (4, "Bob"), (4, "Carol"), (4, "Dave")])
+				.precommit(&[(4, "Alice"), (4, "Bob"), (4, "Carol"), (4, "Dave")])],
+		)
+		.with_history(
+			"a, first split",
+			vec![RoundSpec::new(6)
+				.prevote(&[(11, "Bob"), (11, "Carol"), (11, "Dave")])
+				.precommit(&[(11, "Bob"), (11, "Carol"), (11, "Dave")])
+				.finalize(11)],
+		)
+		.with_history(
+			"a, second split",
+			vec![
+				RoundSpec::new(6)
+					.prevote(&[(12, "Alice"), (12, "Bob"), (12, "Dave")])
+					.precommit(&[(12, "Alice"), (12, "Bob"), (12, "Dave")]),
+				RoundSpec::new(7)
+					.prevote(&[(13, "Alice"), (13, "Bob"), (13, "Dave")])
+					.precommit(&[(13, "Alice"), (13, "Bob"), (13, "Dave")])
+					.finalize(13),
+			],
+		);
+
+	let forks = ChainBuilder::new()
+		.chain([1, 2, 3, 4])
+		.named("a")
+		.fork_at(1, [5, 6, 7, 8])
+		.named("b")
+		.fork_at(4, [11])
+		.named("first split")
+		.fork_at(4, [12, 13])
+		.named("second split")
+		.build()
+		.unwrap();
+	let blocks = |names: &[&str]| forks.union(names).unwrap();
+	voters = voters
+		.with_voter(
+			"Alice",
+			blocks(&["a", "b", "second split"]).blocks(),
+			&["common", "a", "b", "a, continued", "a, second split"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.with_voter(
+			"Carol",
+			blocks(&["first split"]).blocks(),
+			&["common", "a", "a, continued", "a, first split"],
+			None,
+		);
+	for equivocator in &["Bob", "Dave"] {
+		voters = voters.with_voter(
+			equivocator,
+			blocks(&["b", "first split", "second split"]).blocks(),
+			&[
+				"common",
+				"a",
+				"b",
+				"a, continued",
+				"a, first split",
+				"a, second split",
+			],
+			Some(Adversary::ReturnPrecommits),
+		);
+	}
+	let mut voters = voters.build().unwrap();
+
+	let dave = voters.get_mut("Dave").unwrap();
+	for block in &[8, 13] {
+		let round = *dave.chain.finalized_round(*block).unwrap();
+		let commit = dave.commits()[block].clone();
+		dave.add_actions(vec![(
+			10,
+			Action::SendCommit("Carol".to_string(), round, commit),
+		)]);
+	}
+	voters
+}
+
+#[test]
+fn voter_behind_on_finality_fetches_the_older_commit() {
+	// Carol only took part in round 1, so she knows the blocks of both forks but not that block 2
+	// was finalized by the time Dave sends her the commit for block 8.
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let views = [
+		("Alice", all.clone()),
+		("Bob", all.clone()),
+		("Carol", all.clone()),
+		("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+	];
+	let voters = basic_scenario(&views, Adversary::ReturnPrecommits)
+		.with_voter("Carol", all.blocks(), &["common"], None)
+		.build()
+		.unwrap();
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::CommitCatchUp));
+
+	// She goes by the commit for block 8 and asks the others for the commit on the fork of block 4,
+	// which Alice and Bob serve.
+	let requests: Vec<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| {
+			event.kind == EventKind::MessageSent && event.summary.starts_with("RequestCommitFor")
+		})
+		.map(|event| event.summary.clone())
+		.collect();
+	assert_eq!(
+		requests,
+		vec![
+			"RequestCommitFor(block: 4) to Alice",
+			"RequestCommitFor(block: 4) to Bob"
+		]
+	);
+	let carol = &world.voters()["Carol"];
+	let finalized: BTreeSet<_> = carol.commits().keys().cloned().collect();
+	assert_eq!(finalized, vec![1, 8].into_iter().collect());
+
+	// With both commits in hand she runs the protocol as if she had seen block 2 finalized.
+	let expected: BTreeMap<_, BTreeSet<VoterId>> = vec![(
+		ConflictId::new(2, 8),
+		vec!["Alice".to_string(), "Bob".to_string()]
+			.into_iter()
+			.collect(),
+	)]
+	.into_iter()
+	.collect();
+	assert_eq!(carol.offenders_per_conflict(), expected);
+}
+
+#[test]
+fn independent_conflicts_in_one_run() {
+	let mut world = World::new(setup_voters_with_two_conflicts());
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+
+	// Carol runs an instance for each of the two conflicts. Block 8 also conflicts with 11, which
+	// she investigates as well, and which Dave took part in by finalizing on both forks.
+	let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+	let expected: BTreeMap<_, BTreeSet<VoterId>> = vec![
+		(ConflictId::new(2, 8), names(&["Alice", "Bob"])),
+		(ConflictId::new(8, 11), names(&["Alice", "Bob", "Dave"])),
+		(ConflictId::new(11, 13), names(&["Bob", "Dave"])),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(world.offenders_per_conflict(), expected);
+	let carol = &world.voters()["Carol"];
+	assert_eq!(carol.offenders_per_conflict(), expected);
+
+	// Her instances are looked up by the conflict they investigate.
+	assert_eq!(
+		carol
+			.instances()
+			.map(|(conflict, _)| *conflict)
+			.collect::<Vec<_>>(),
+		expected.keys().cloned().collect::<Vec<_>>(),
+	);
+	let instance = carol.instance(&ConflictId::new(8, 11)).unwrap();
+	assert_eq!(instance.conflict(), ConflictId::new(8, 11));
+	assert!(carol.instance(&ConflictId::new(11, 8)).is_none());
+	// Between the two conflicts more voters were caught than either one needed.
+	assert_eq!(
+		world.guarantee(),
+		GuaranteeStatus::Exceeded {
+			proven: 3,
+			required: 2
+		}
+	);
+}
+
+#[test]
+fn protocol_instances_share_answers() {
+	// Dave also sends Carol a commit for block 6, justified by the same precommits for block 8.
+	// Investigating it asks the same questions as for block 8, which are only sent out once.
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let dave = voters.get_mut("Dave").unwrap();
+	let precommits = dave.commits()[&8].precommits.clone();
+	dave.add_actions(vec![(
+		10,
+		Action::SendCommit("Carol".to_string(), 4, Commit::new(6, precommits)),
+	)]);
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.coverage().contains(Branch::SharedResponse));
+
+	let queries = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| {
+			event.kind == EventKind::MessageSent
+				&& event
+					.summary
+					.starts_with("WhyDidEstimateForRoundNotIncludeBlock")
+		})
+		.count();
+	assert_eq!(queries, 6);
+
+	let offenders: BTreeSet<VoterId> = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	let expected: BTreeMap<_, _> = vec![
+		(ConflictId::new(2, 6), offenders.clone()),
+		(ConflictId::new(2, 8), offenders),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(world.offenders_per_conflict(), expected);
+}
+
+#[test]
+fn estimates_are_audited_against_the_next_precommit() {
+	// Alice and Bob explain the estimate of round 2 with their own precommits for block 9 and
+	// nothing from Dave, which leaves an estimate of at least block 9, yet both precommit for block
+	// 1 in round 3.
+	let mut voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(2)
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+					.finalize(2),
+				RoundSpec::new(3).precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+				RoundSpec::new(4).precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")]),
+			],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(2).precommit(&[(9, "Alice"), (9, "Bob")]),
+				RoundSpec::new(3).precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(4)
+					.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.finalize(8),
+			],
+		);
+	let forks = ChainBuilder::new()
+		.chain([1, 2, 3, 4])
+		.named("a")
+		.fork_at(1, [5, 6, 7, 8])
+		.named("b")
+		.fork_at(1, [9])
+		.named("c")
+		.build()
+		.unwrap();
+	for voter in &["Alice", "Bob", "Carol", "Dave"] {
+		let (forks_known, histories): (&[&str], &[&str]) = match *voter {
+			"Carol" => (&["a", "c"], &["common", "a"]),
+			"Dave" => (&["b", "c"], &["common", "b"]),
+			_ => (&["a", "b", "c"], &["common", "a", "b"]),
+		};
+		let view = forks.union(forks_known).unwrap();
+		voters = voters.with_voter(
+			voter,
+			view.blocks(),
+			histories,
+			Some(Adversary::ReturnPrecommits),
+		);
+	}
+	voters = voters.with_action("Dave", 10, Action::BroadcastCommits);
+
+	let mut world = World::new(voters.build().unwrap());
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::EstimateInconsistency));
+
+	let detected = world.equivocations_detected();
+	let inconsistent: Vec<_> = detected
+		.iter()
+		.filter_map(|detection| match detection {
+			EquivocationDetected::EstimateInconsistency {
+				voter,
+				round,
+				estimate,
+				precommit,
+				..
+			} => Some((voter.as_str(), *round, *estimate, precommit.target_number)),
+			_ => None,
+		})
+		.collect();
+	assert_eq!(inconsistent, vec![("Alice", 2, 9, 1), ("Bob", 2, 9, 1)]);
+}
+
+#[test]
+fn instances_for_different_blocks_share_answers() {
+	// Carol finalized blocks 2 and 3 on one fork, in rounds 2 and 3, so Dave's commit for block 8
+	// in round 4 conflicts with both.
+	let mut voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(2)
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+					.finalize(2),
+				RoundSpec::new(3)
+					.precommit(&[(3, "Alice"), (3, "Bob"), (3, "Carol")])
+					.finalize(3),
+				RoundSpec::new(4).precommit(&[(3, "Alice"), (3, "Bob"), (3, "Carol")]),
+			],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(2).precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(3).precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(4)
+					.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.finalize(8),
+			],
+		);
+	let chain = fixtures::paper_chain();
+	for voter in &["Alice", "Bob", "Carol", "Dave"] {
+		let (tips, histories): (&[BlockNumber], &[&str]) = match *voter {
+			"Carol" => (&[4], &["common", "a"]),
+			"Dave" => (&[8], &["common", "b"]),
+			_ => (&[4, 8], &["common", "a", "b"]),
+		};
+		let view = ChainView::up_to(&chain, tips).unwrap();
+		voters = voters.with_voter(
+			voter,
+			view.blocks(),
+			histories,
+			Some(Adversary::ReturnPrecommits),
+		);
+	}
+
+	voters = voters.with_action("Dave", 10, Action::BroadcastCommits);
+
+	let mut world = World::new(voters.build().unwrap());
+	run_to_completion(&mut world);
+	println!(
+		"{:?} {:?}",
+		world.offenders_per_conflict(),
+		world.voters()["Carol"].commits().keys().collect::<Vec<_>>()
+	);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::SharedResponse));
+
+	// Both instances ask the signers of block 8 about round 4, but each of them only answers once,
+	// and that one explanation is checked against both blocks 2 and 3. Only the instance for block
+	// 2 goes on to ask about round 3.
+	let queries = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| {
+			event.kind == EventKind::MessageSent
+				&& event
+					.summary
+					.starts_with("WhyDidEstimateForRoundNotIncludeBlock")
+		})
+		.count();
+	assert_eq!(queries, 6);
+
+	let offenders: BTreeSet<VoterId> = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	let expected: BTreeMap<_, _> = vec![
+		(ConflictId::new(2, 8), offenders.clone()),
+		(ConflictId::new(3, 8), offenders),
+	]
+	.into_iter()
+	.collect();
+	assert_eq!(world.offenders_per_conflict(), expected);
+}
+
+// The fork of the basic example, but with the voter set changing from round 3 on: Alice leaves and
+// Eve joins. Alice and Bob equivocate in round 2, in the first set, which lets block 2 be
+// finalized on one fork. Bob, Dave and Eve then finalize block 8 on the other fork in round 4, in
+// the second set. Eve covers for them by replaying votes from earlier rounds when asked.
+fn setup_voters_across_voter_set_change() -> BTreeMap<VoterId, Voter> {
+	let mut voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_voter_set(1, &["Bob", "Carol", "Dave", "Eve"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+					.finalize(2),
+				RoundSpec::new(3)
+					.in_set(1)
+					.prevote(&[(4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Bob"), (2, "Carol")]),
+				RoundSpec::new(4)
+					.in_set(1)
+					.prevote(&[(4, "Bob"), (2, "Carol")])
+					.precommit(&[(2, "Bob"), (2, "Carol")]),
+			],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(2)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(3)
+					.in_set(1)
+					.prevote(&[(1, "Bob"), (5, "Dave"), (1, "Eve")])
+					.precommit(&[(1, "Bob"), (1, "Dave"), (1, "Eve")]),
+				RoundSpec::new(4)
+					.in_set(1)
+					.prevote(&[(8, "Bob"), (8, "Dave"), (8, "Eve")])
+					.precommit(&[(8, "Bob"), (8, "Dave"), (8, "Eve")])
+					.finalize(8),
+			],
+		);
+
+	let forks = paper_forks();
+	let (fork_a, fork_b) = (forks.view("a").unwrap(), forks.view("b").unwrap());
+	let both_forks = forks.all();
+	voters = voters
+		.with_voter(
+			"Alice",
+			both_forks.blocks(),
+			&["common", "a", "b"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.with_voter(
+			"Bob",
+			both_forks.blocks(),
+			&["common", "a", "b"],
+			Some(Adversary::ReturnPrecommits),
+		)
+		.with_voter("Carol", fork_a.blocks(), &["common", "a"], None)
+		.with_voter("Dave", fork_b.blocks(), &["common", "b"], None)
+		.with_voter(
+			"Eve",
+			fork_b.blocks(),
+			&["common", "b"],
+			Some(Adversary::ReplayEarlierVotes),
+		);
+	let mut voters = voters.build().unwrap();
+
+	let dave = voters.get_mut("Dave").unwrap();
+	let commit = dave.commits()[&8].clone();
+	dave.add_actions(vec![(
+		10,
+		Action::SendCommit("Carol".to_string(), 4, commit),
+	)]);
+	voters
+}
+
+#[test]
+fn offences_attributed_across_voter_set_change() {
+	let mut world = World::new(setup_voters_across_voter_set_change());
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+
+	// Alice only voted in the first set and Eve only in the second. Bob equivocated while in the
+	// first set, and is accountable there even though he is still a voter in the second.
+	let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+	let expected: BTreeMap<_, BTreeSet<VoterId>> =
+		vec![(0, names(&["Alice", "Bob"])), (1, names(&["Eve"]))]
+			.into_iter()
+			.collect();
+	assert_eq!(world.offenders_by_set(), expected);
+
+	// The proofs carry the set, so they can be checked against the right membership.
+	let detected = world.equivocations_detected();
+	let precommit_equivocations: BTreeSet<_> = detected
+		.iter()
+		.filter_map(|detection| match detection {
+			EquivocationDetected::Precommit(equivocations) => Some(equivocations),
+			_ => None,
+		})
+		.flatten()
+		.map(|equivocation| (equivocation.voter.as_str(), equivocation.set_id))
+		.collect();
+	assert_eq!(
+		precommit_equivocations,
+		vec![("Alice", 0), ("Bob", 0)].into_iter().collect()
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+// Alice and Bob precommit for both block 2 and block 8 in the same round, so the two commits
+// alone are enough to find them out. Only Dave knows about the fork with block 8.
+fn setup_voters_with_conflicting_commits_in_one_round() -> BTreeMap<VoterId, Voter> {
+	let forks = ChainBuilder::new()
+		.chain([1, 2])
+		.named("a")
+		.fork_at(1, [5, 6, 7, 8])
+		.named("b")
+		.build()
+		.unwrap();
+	let (fork_a, fork_b) = (forks.view("a").unwrap(), forks.view("b").unwrap());
+	let mut voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a",
+			vec![RoundSpec::new(2)
+				.prevote(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+				.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+				.finalize(2)],
+		)
+		.with_history(
+			"b",
+			vec![RoundSpec::new(2)
+				.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+				.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+				.finalize(8)],
+		)
+		.with_voter("Alice", fork_a.blocks(), &["common", "a"], None)
+		.with_voter("Bob", fork_a.blocks(), &["common", "a"], None)
+		.with_voter("Carol", fork_a.blocks(), &["common", "a"], None)
+		.with_voter("Dave", fork_b.blocks(), &["common", "b"], None)
+		.build()
+		.unwrap();
+
+	let dave = voters.get_mut("Dave").unwrap();
+	let commit = dave.commits()[&8].clone();
+	dave.add_actions(vec![(
+		10,
+		Action::SendCommit("Carol".to_string(), 2, commit),
+	)]);
+	voters
+}
+
+#[test]
+fn common_signers_within_fault_tolerance_are_still_recorded() {
+	let chain = fixtures::paper_chain();
+	let commit = |block, round, signers: &[&str]| {
+		Commit::new(
+			block,
+			signers
+				.iter()
+				.map(|signer| Precommit::new(round, block, *signer))
+				.collect(),
+		)
+	};
+	let first = commit(2, 2, &["Alice", "Bob", "Carol"]);
+
+	// Only Alice signed both in the same round, which isn't enough to skip the queries.
+	let second = commit(8, 2, &["Alice", "Dave", "Eve"]);
+	let mut instance = AccountableSafety::start(2, 2, first.clone(), 8, 2, &chain).unwrap();
+	assert!(!instance.check_common_signers(&second, 1));
+	assert_eq!(
+		instance.equivocations_detected(),
+		vec![EquivocationDetected::CommitEquivocation(vec![
+			CommitEquivocation {
+				voter: "Alice".to_string(),
+				set_id: 0,
+				round: 2,
+				targets: [2, 8],
+			}
+		])]
+	);
+	assert!(instance.coverage().contains(Branch::CommitEquivocation));
+	assert!(!instance.coverage().contains(Branch::CommonSigners));
+
+	// Signing commits from different rounds is no offence by itself.
+	let mut instance = AccountableSafety::start(2, 2, first, 8, 4, &chain).unwrap();
+	assert!(!instance.check_common_signers(&commit(8, 4, &["Alice", "Bob", "Dave"]), 1));
+	assert!(instance.equivocations_detected().is_empty());
+}
+
+#[test]
+fn common_signers_need_no_queries() {
+	let messages_sent = |world: &World, prefix: &str| {
+		world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| {
+				event.kind == EventKind::MessageSent && event.summary.starts_with(prefix)
+			})
+			.count()
+	};
+
+	let mut world = World::new(setup_voters_with_conflicting_commits_in_one_round());
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::CommonSigners));
+
+	let expected_offenders: BTreeSet<VoterId> = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	assert_eq!(world.offenders(), expected_offenders);
+	let commit_equivocation = |voter: &str| CommitEquivocation {
+		voter: voter.to_string(),
+		set_id: 0,
+		round: 2,
+		targets: [2, 8],
+	};
+	assert_eq!(
+		world.equivocations_detected(),
+		vec![EquivocationDetected::CommitEquivocation(vec![
+			commit_equivocation("Alice"),
+			commit_equivocation("Bob"),
+		])]
+	);
+	assert!(world.coverage().contains(Branch::CommitEquivocation));
+
+	// Apart from syncing the block, the commit and the evidence, nothing was sent. Finding out
+	// the same two voters when the commits are from different rounds takes queries.
+	assert_eq!(
+		messages_sent(&world, "WhyDidEstimateForRoundNotIncludeBlock"),
+		0
+	);
+	assert_eq!(messages_sent(&world, "ExplainEstimate"), 0);
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	assert_eq!(world.offenders(), expected_offenders);
+	assert!(messages_sent(&world, "WhyDidEstimateForRoundNotIncludeBlock") > 0);
+	assert!(messages_sent(&world, "ExplainEstimate") > 0);
+}
+
+// Alice and Bob keep a single, consistent history: they finalize block 2 in round 2 together with
+// Carol, and then go on to finalize block 8 on the other fork with Dave. Dave saw them precommit
+// for block 1 in round 2, which they only make up once asked about it.
+fn setup_voters_with_lazy_equivocators() -> BTreeMap<VoterId, Voter> {
+	let colluders = ["Alice", "Bob"];
+	let mut voters = ScenarioBuilder::new(&["Alice", "Bob", "Carol", "Dave"])
+		.with_history(
+			"common",
+			vec![RoundSpec::new(1)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Carol"), (1, "Dave")])
+				.finalize(1)],
+		)
+		.with_history(
+			"a, round 2",
+			vec![RoundSpec::new(2)
+				.prevote(&[(4, "Alice"), (4, "Bob"), (2, "Carol")])
+				.precommit(&[(2, "Alice"), (2, "Bob"), (2, "Carol")])
+				.finalize(2)],
+		)
+		.with_history(
+			"a",
+			vec![
+				RoundSpec::new(3)
+					.prevote(&[(2, "Carol")])
+					.precommit(&[(2, "Carol")]),
+				RoundSpec::new(4)
+					.prevote(&[(2, "Carol")])
+					.precommit(&[(2, "Carol")]),
+			],
+		)
+		.with_history(
+			"b, round 2",
+			vec![RoundSpec::new(2)
+				.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+				.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")])],
+		)
+		.with_history(
+			"b",
+			vec![
+				RoundSpec::new(3)
+					.prevote(&[(1, "Alice"), (1, "Bob"), (5, "Dave")])
+					.precommit(&[(1, "Alice"), (1, "Bob"), (1, "Dave")]),
+				RoundSpec::new(4)
+					.prevote(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.precommit(&[(8, "Alice"), (8, "Bob"), (8, "Dave")])
+					.finalize(8),
+			],
+		);
+
+	let forks = paper_forks();
+	let (fork_a, fork_b) = (forks.view("a").unwrap(), forks.view("b").unwrap());
+	let both_forks = forks.all();
+	for colluder in colluders {
+		voters = voters.with_voter(
+			colluder,
+			both_forks.blocks(),
+			&["common", "a, round 2", "b"],
+			Some(Adversary::FabricateOnQuery(
+				colluders
+					.iter()
+					.map(|colluder| colluder.to_string())
+					.collect(),
+			)),
+		);
+	}
+	let mut voters = voters
+		.with_voter(
+			"Carol",
+			fork_a.blocks(),
+			&["common", "a, round 2", "a"],
+			None,
+		)
+		.with_voter(
+			"Dave",
+			fork_b.blocks(),
+			&["common", "b, round 2", "b"],
+			None,
+		)
+		.build()
+		.unwrap();
+
+	let dave = voters.get_mut("Dave").unwrap();
+	let commit = dave.commits()[&8].clone();
+	dave.add_actions(vec![(
+		10,
+		Action::SendCommit("Carol".to_string(), 4, commit),
+	)]);
+	voters
+}
+
+#[test]
+fn lazy_equivocators_are_caught() {
+	let voters = setup_voters_with_lazy_equivocators();
+	for colluder in &["Alice", "Bob"] {
+		let rounds: Vec<_> = voters[*colluder]
+			.rounds()
+			.map(|voting_round| voting_round.round_number)
+			.collect();
+		assert_eq!(rounds, vec![1, 2, 3, 4]);
+	}
+
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+	assert!(world.coverage().contains(Branch::FabricatedVotes));
+
+	// The made up precommits for block 1 in round 2 contradict the ones in the commit for block 2,
+	// as do the ones Dave actually saw.
+	let expected_offenders: BTreeSet<VoterId> = vec!["Alice".to_string(), "Bob".to_string()]
+		.into_iter()
+		.collect();
+	assert_eq!(world.offenders(), expected_offenders);
+	let precommit_equivocators: BTreeSet<_> = world
+		.equivocations_detected()
+		.iter()
+		.filter_map(|detection| match detection {
+			EquivocationDetected::Precommit(equivocations) => Some(equivocations.clone()),
+			_ => None,
+		})
+		.flatten()
+		.map(|equivocation| (equivocation.voter, equivocation.blocks))
+		.collect();
+	assert_eq!(
+		precommit_equivocators,
+		vec![
+			("Alice".to_string(), vec![1, 2]),
+			("Bob".to_string(), vec![1, 2])
+		]
+		.into_iter()
+		.collect()
+	);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+}
+
+#[test]
+fn replies_of_unaccepted_kind_are_invalid() {
+	// Alice and Bob make up precommits, which verifiers only accepting prevotes reject outright.
+	let mut world = WorldBuilder::new()
+		.with_voters(setup_voters_with_lazy_equivocators())
+		.with_accepted_replies(ReplyKinds::Prevotes)
+		.build();
+	run_to_completion(&mut world);
+	assert!(world.coverage().contains(Branch::FabricatedVotes));
+	let invalid: BTreeSet<_> = world
+		.equivocations_detected()
+		.iter()
+		.filter_map(|detection| match detection {
+			EquivocationDetected::InvalidResponse { voter, .. } => Some(voter.clone()),
+			_ => None,
+		})
+		.collect();
+	assert_eq!(
+		invalid,
+		vec!["Alice".to_string(), "Bob".to_string()]
+			.into_iter()
+			.collect()
+	);
+}
+
+#[test]
+fn proofs_verified_with_headers_only() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	let evidence = world.voters()["Carol"].evidence_bundle();
+	assert!(!evidence.bundle.equivocations.is_empty());
+
+	// A verifier that only has the headers of both forks, as a map from block to parent.
+	let headers: BTreeMap<BlockNumber, BlockNumber> = vec![
+		(0, 0),
+		(1, 0),
+		(2, 1),
+		(3, 2),
+		(4, 3),
+		(5, 1),
+		(6, 5),
+		(7, 6),
+		(8, 7),
+	]
+	.into_iter()
+	.collect();
+	for proof in &evidence.bundle.equivocations {
+		assert_eq!(
+			verify_equivocation_proof(proof, &headers),
+			Ok(()),
+			"{:?}",
+			proof
+		);
+	}
+
+	// The proofs are about block 2, so they can't be checked with only the headers of the fork
+	// with block 8.
+	let fork_b_headers: BTreeMap<_, _> = headers
+		.into_iter()
+		.filter(|(n, _)| ![2, 3, 4].contains(n))
+		.collect();
+	assert!(evidence
+		.bundle
+		.equivocations
+		.iter()
+		.any(|proof| verify_equivocation_proof(proof, &fork_b_headers).is_err()));
+}
+
+#[test]
+fn responders_record_the_verifiers_that_asked() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+
+	// Carol is the only one investigating, and everyone she asked has her down for it, with the
+	// votes they answered with.
+	let served: Vec<_> = world
+		.voters()
+		.values()
+		.flat_map(|voter| voter.served_queries.iter())
+		.collect();
+	assert!(!served.is_empty());
+	for query in &served {
+		assert_eq!(query.verifier, "Carol");
+		assert_eq!(query.conflict, ConflictId::new(2, 8));
+		assert!(query.answer.is_some(), "{:?}", query);
+	}
+	for responder in &["Alice", "Bob", "Dave"] {
+		let voter = &world.voters()[*responder];
+		assert_eq!(
+			voter.verifiers_asking(4, QueryKind::Estimate),
+			vec!["Carol".to_string()],
+			"{}",
+			responder
+		);
+		assert!(voter.verifiers_asking(4, QueryKind::Prevotes).is_empty());
+	}
+	// Nobody asks themselves.
+	assert!(world.voters()["Carol"].served_queries.is_empty());
+}
+
+#[test]
+fn fast_forward_preserves_runs() {
+	let setups: Vec<fn() -> BTreeMap<VoterId, Voter>> = vec![
+		|| fixtures::paper_example(Adversary::ReturnPrecommits),
+		|| fixtures::three_way_fork(Adversary::ReturnPrevotes),
+		|| fixtures::large_voterset(10, Adversary::ReturnPrecommits),
+	];
+	for setup in setups {
+		// Idle ticks are jumped over, unless snapshots and deadlines need looking at every tick.
+		for watched in [false, true] {
+			let run = |fast_forward| {
+				let mut builder = WorldBuilder::new()
+					.with_voters(setup())
+					.with_network(3, 0.0)
+					.with_fast_forward(fast_forward);
+				if watched {
+					builder = builder.with_deadline(40).with_snapshot_interval(25);
+				}
+				let mut world = builder.build();
+				let mut summary = world.run_until_settled();
+				summary.peak_memory = None;
+				let snapshots = world
+					.snapshots()
+					.map_or(0, |snapshots| snapshots.snapshots().len());
+				(summary, world.event_log().events().to_vec(), snapshots)
+			};
+			let (summary, events, snapshots) = run(true);
+			assert!(summary.settled);
+			assert_eq!((summary, events, snapshots), run(false));
+		}
+	}
+}
+
+#[test]
+fn pruning_keeps_proofs_verifiable() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	let proofs = world.voters()["Carol"]
+		.evidence_bundle()
+		.bundle
+		.equivocations;
+	assert!(!proofs.is_empty());
+
+	// Pruning to block 8 would drop the fork with block 2, which the proofs are about.
+	let mut chain = world.voters()["Carol"].chain.clone();
+	assert_eq!(chain.prune(8), Ok(vec![2, 3, 4]));
+	assert!(proofs
+		.iter()
+		.any(|proof| verify_equivocation_proof(proof, &chain).is_err()));
+
+	// Carol pins what her proofs need first, so they still verify against her own chain.
+	assert!(world.prune_chain("Carol", 8).is_ok());
+	let chain = &world.voters()["Carol"].chain;
+	assert!(chain.knows_about_block(2));
+	for proof in &proofs {
+		assert_eq!(
+			verify_equivocation_proof(proof, chain),
+			Ok(()),
+			"{:?}",
+			proof
+		);
+	}
+}
+
+#[test]
+fn three_way_fork_fixture() {
+	let mut world = World::new(fixtures::three_way_fork(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+
+	let offenders = world.offenders();
+	assert!(offenders.len() > 2, "offenders: {:?}", offenders);
+	assert!(offenders
+		.iter()
+		.all(|voter| ["Alice", "Bob", "Carol", "Dave"].contains(&voter.as_str())));
+	assert!(world.offenders_per_conflict().len() >= 2);
+	// All four voters that voted on every fork are caught, one more than guaranteed.
+	assert_eq!(
+		world.guarantee(),
+		GuaranteeStatus::Exceeded {
+			proven: 4,
+			required: 3
+		}
+	);
+}
+
+#[test]
+fn large_voterset_fixture() {
+	// Besides sets of 3f + 1, sets where two thirds is a whole number of voters, and where it is
+	// one voter short of one.
+	for num_voters in &[4, 6, 7, 8, 9, 10, 12] {
+		let voters = fixtures::large_voterset(*num_voters, Adversary::ReturnPrecommits);
+		assert_eq!(voters.len(), *num_voters);
+		let mut world = World::new(voters);
+		run_to_completion(&mut world);
+
+		// Everyone that equivocated is caught, and more than a third of the voter set.
+		let byzantine = 2 * (2 * num_voters / 3 + 1) - num_voters;
+		let expected: BTreeSet<_> = (0..byzantine).map(|i| format!("voter{}", i)).collect();
+		assert_eq!(world.offenders(), expected, "{} voters", num_voters);
+		assert!(3 * byzantine > *num_voters);
+		// With n = 3f + 1 exactly f + 1 equivocate, otherwise more do.
+		let max_faulty = supermajority::max_faulty(*num_voters as Weight) as usize;
+		assert_eq!(
+			world.guarantee(),
+			GuaranteeStatus::new(byzantine, max_faulty)
+		);
+		if num_voters % 3 == 1 {
+			assert_eq!(
+				world.guarantee(),
+				GuaranteeStatus::Reached { proven: byzantine }
+			);
+		}
+	}
+}
+
+#[test]
+fn oversized_responses_are_rejected() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	for adversary in &["Alice", "Bob"] {
+		voters.get_mut(*adversary).unwrap().behaviour =
+			Some(Arc::new(Adversary::IgnoreMessageBudget));
+	}
+	let mut world = WorldBuilder::new()
+		.with_voters(voters)
+		.with_message_budget(2)
+		.with_max_ticks(500)
+		.build();
+	run_to_completion(&mut world);
+
+	// Alice and Bob send their explanations of three votes in single messages, which is over the
+	// budget, but as with truncated responses Dave's explanations are enough to find them out.
+	let oversized = |voter: &str, round| EquivocationDetected::OversizedResponse {
+		voter: voter.to_string(),
+		set_id: 0,
+		round,
+		votes: 3,
+		limit: 2,
+	};
+	assert_eq!(
+		world.voters()["Carol"].equivocations_detected(),
+		vec![
+			oversized("Alice", 3),
+			oversized("Bob", 3),
+			detections_in_basic_example()[1].clone(),
+			oversized("Alice", 4),
+			oversized("Bob", 4),
+		]
+	);
+	assert!(world.coverage().contains(Branch::OversizedResponse));
+	assert_eq!(
+		world.verdicts()["Carol"][&ConflictId::new(2, 8)][0].verdicts["Alice"],
+		Verdict::Invalid(InvalidReason::Oversized)
+	);
+}
+
+#[test]
+fn verdicts_per_queried_round() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+
+	// Alice and Bob's explanations of round 3 contradict the precommits they signed in the commit
+	// for block 2, while Dave's is cross-checked with the commit and reveals their equivocations.
+	let round_verdicts = |round, verdicts: &[(&str, Verdict)]| RoundVerdicts {
+		round,
+		prevotes: false,
+		verdicts: verdicts
+			.iter()
+			.map(|(voter, verdict)| (voter.to_string(), verdict.clone()))
+			.collect(),
+	};
+	let inconsistent = Verdict::Invalid(InvalidReason::InconsistentTestimony);
+	assert_eq!(
+		world.verdicts()["Carol"][&ConflictId::new(2, 8)],
+		vec![
+			round_verdicts(
+				3,
+				&[
+					("Alice", inconsistent.clone()),
+					("Bob", inconsistent),
+					("Dave", Verdict::RevealedEquivocations),
+				]
+			),
+			round_verdicts(
+				4,
+				&[
+					("Alice", Verdict::Valid),
+					("Bob", Verdict::Valid),
+					("Dave", Verdict::Valid),
+				]
+			),
+		]
+	);
+	let summary = world.summary();
+	assert_eq!(summary.verdicts.len(), 1);
+	assert_eq!(summary.verdicts[0].detector, "Carol");
+	assert!(summary
+		.to_string()
+		.contains("round 3: Alice invalid (inconsistent testimony)"));
+}
+
+#[test]
+fn voters_refusing_by_policy() {
+	let run = |dave: fn(&mut Voter)| {
+		let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+		dave(voters.get_mut("Dave").unwrap());
+		let mut world = World::new(voters);
+		run_to_completion(&mut world);
+		world
+	};
+
+	// Dave keeps his votes to himself, which leaves Carol without his explanation, but Alice and
+	// Bob still contradict their commit.
+	let world = run(|dave| {
+		dave.policy = ServingPolicy {
+			estimates: Audience::Nobody,
+			..Default::default()
+		}
+	});
+	let verdicts = &world.verdicts()["Carol"][&ConflictId::new(2, 8)];
+	assert_eq!(verdicts[0].verdicts["Dave"], Verdict::Refused);
+	assert_eq!(world.guarantee(), GuaranteeStatus::Reached { proven: 2 });
+	assert!(!world.exonerated().contains("Dave"));
+	assert!(world.coverage().contains(Branch::PolicyRefusal));
+	assert!(world.voters()["Carol"].outstanding_queries().is_empty());
+
+	// Ignoring the query instead is evasion, for which Dave is found unresponsive.
+	let world = run(|dave| {
+		dave.behaviour = Some(Arc::new(Adversary::IgnoreQueriesFrom(vec!["Carol".into()])));
+	});
+	let verdicts = &world.verdicts()["Carol"][&ConflictId::new(2, 8)];
+	assert_eq!(verdicts[0].verdicts["Dave"], Verdict::Unresponsive);
+}
+
+#[test]
+fn observers_refused_by_privacy_conscious_voters() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let mut voters = basic_scenario(
+		&[
+			("Alice", all.clone()),
+			("Bob", all.clone()),
+			("Carol", ChainView::up_to(&chain, &[4]).unwrap()),
+			("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+		],
+		Adversary::ReturnPrecommits,
+	)
+	.with_observer("Olive", all.blocks())
+	.with_action("Carol", 10, Action::BroadcastCommits)
+	.build()
+	.unwrap();
+	for voter in voters.values_mut() {
+		voter.policy = ServingPolicy::members_only_votes();
+	}
+	let mut world = World::new(voters);
+	run_to_completion(&mut world);
+	assert!(world.protocol_bugs().is_empty());
+
+	// Olive is refused the votes, so only finds what the commits themselves give away, while the
+	// members of the voter set still get their answers.
+	let refused: BTreeSet<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| {
+			event.kind == EventKind::MessageDelivered && event.summary.starts_with("PolicyRefusal")
+		})
+		.map(|event| event.voter.as_str())
+		.collect();
+	assert_eq!(refused, vec!["Olive"].into_iter().collect());
+	assert_eq!(
+		world.voters()["Carol"].known_offenders(),
+		vec!["Alice".to_string(), "Bob".to_string()]
+			.into_iter()
+			.collect()
+	);
+	assert!(world.voters()["Olive"]
+		.equivocations_detected()
+		.iter()
+		.all(|detection| matches!(detection, EquivocationDetected::Precommit(..))));
+}
+
+#[test]
+fn detectors_coordinate_on_a_primary_verifier() {
+	let chain = fixtures::paper_chain();
+	let all = ChainView::up_to(&chain, &[4, 8]).unwrap();
+	let voters = || {
+		basic_scenario(
+			&[
+				("Alice", all.clone()),
+				("Bob", all.clone()),
+				("Carol", ChainView::up_to(&chain, &[4]).unwrap()),
+				("Dave", ChainView::up_to(&chain, &[8]).unwrap()),
+			],
+			Adversary::ReturnPrecommits,
+		)
+		.with_observer("Olive", all.blocks())
+		.with_action("Carol", 10, Action::BroadcastCommits)
+		.build()
+		.unwrap()
+	};
+	let queriers = |world: &World| {
+		world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| {
+				event.kind == EventKind::MessageSent
+					&& event
+						.summary
+						.starts_with("WhyDidEstimateForRoundNotIncludeBlock")
+			})
+			.map(|event| event.voter.clone())
+			.collect::<BTreeSet<_>>()
+	};
+	let run = |builder: WorldBuilder| {
+		let mut world = builder.with_voters(voters()).build();
+		let summary = world.run_until_settled();
+		assert_eq!(summary.guarantee, GuaranteeStatus::Reached { proven: 2 });
+		(world, summary)
+	};
+
+	// Olive detects the conflict first. Left to themselves Carol and Dave query the same voters
+	// once they detect it too, but when coordinating they stand by for her.
+	let (world, uncoordinated) = run(WorldBuilder::new());
+	assert_eq!(queriers(&world).len(), 3);
+	let (world, coordinated) = run(WorldBuilder::new().with_verifier_coordination(100));
+	assert_eq!(
+		queriers(&world),
+		vec!["Olive".to_string()].into_iter().collect()
+	);
+	assert!(coordinated.messages < uncoordinated.messages);
+	assert!(world
+		.voters()
+		.values()
+		.all(|voter| voter.known_offenders().len() == 2));
+	assert!(!world.coverage().contains(Branch::VerifierTakeover));
+
+	// If Olive is cut off after claiming the conflict, the others take over once the timeout is
+	// up.
+	let isolated = PartitionSchedule::new(vec![Partition::new(
+		12,
+		1000,
+		vec![
+			vec!["Olive".to_string()],
+			vec![
+				"Alice".to_string(),
+				"Bob".to_string(),
+				"Carol".to_string(),
+				"Dave".to_string(),
+			],
+		],
+	)]);
+	let (world, _) = run(WorldBuilder::new()
+		.with_verifier_coordination(100)
+		.with_partition_schedule(isolated)
+		.with_max_ticks(1000));
+	assert!(queriers(&world).contains("Carol"));
+	assert!(world.coverage().contains(Branch::VerifierTakeover));
+	assert_eq!(world.voters()["Carol"].known_offenders().len(), 2);
+}
+
+#[test]
+fn outstanding_queries_are_capped() {
+	let mut uncapped_world = World::new(setup_voters_with_two_conflicts());
+	let uncapped = uncapped_world.run_until_settled();
+
+	let mut world = WorldBuilder::new()
+		.with_voters(setup_voters_with_two_conflicts())
+		.with_max_outstanding_queries(1)
+		.build();
+	let capped = world.run_until_settled();
+
+	// Carol's instances put their questions one at a time, which takes longer but finds the same
+	// offenders for each conflict.
+	assert!(uncapped.peak_outstanding_queries > 1);
+	assert_eq!(capped.peak_outstanding_queries, 1);
+	assert!(capped.settled);
+	assert!(capped.ticks > uncapped.ticks);
+	assert_eq!(
+		world.offenders_per_conflict(),
+		uncapped_world.offenders_per_conflict()
+	);
+	assert!(world.voters()["Carol"].outstanding_queries().is_empty());
+}
+
+#[test]
+fn queries_need_standing() {
+	let statement = fixtures::paper_conflict_statement;
+	let signed =
+		|statement: ConflictStatement, signer: &str| statement.sign(&fixtures::paper_key(signer));
+
+	let mut dave = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove("Dave")
+		.unwrap();
+	dave.key_registry = fixtures::paper_key_registry();
+	let mut ask = |sender: &str, round, statement| {
+		let query = Request::WhyDidEstimateForRoundNotIncludeBlock(
+			round,
+			2,
+			ReplyKinds::default(),
+			statement,
+		);
+		dave.handle_request((sender.to_string(), query), 0)
+	};
+
+	// Carol has both commits, and asks about a round between them.
+	assert!(!ask("Carol", 3, signed(statement("Carol"), "Carol")).is_empty());
+
+	// Mallory isn't a voter, and Carol can't speak for Bob.
+	assert!(ask("Mallory", 3, signed(statement("Mallory"), "Mallory")).is_empty());
+	assert!(ask("Carol", 3, signed(statement("Bob"), "Bob")).is_empty());
+	// A single precommit doesn't finalize anything.
+	let mut forged = statement("Carol");
+	forged.commit_for_new_block.precommits.truncate(1);
+	assert!(ask("Carol", 3, signed(forged, "Carol")).is_empty());
+	// The conflict gives no grounds to ask about later rounds.
+	assert!(ask("Carol", 6, signed(statement("Carol"), "Carol")).is_empty());
+
+	assert_eq!(
+		dave.rejected_queries,
+		vec![
+			("Mallory".to_string(), QueryRejection::NoStanding),
+			("Carol".to_string(), QueryRejection::BadSignature),
+			("Carol".to_string(), QueryRejection::InvalidCommit(8)),
+			("Carol".to_string(), QueryRejection::OutsideConflict(6)),
+		]
+	);
+	assert!(dave.coverage().contains(Branch::QueryRejected));
+}
+
+#[test]
+fn explanations_are_cached() {
+	let mut alice = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove("Alice")
+		.unwrap();
+	alice.key_registry = fixtures::paper_key_registry();
+	let ask = |alice: &mut Voter, verifier: &str, accepted_replies| {
+		let statement =
+			fixtures::paper_conflict_statement(verifier).sign(&fixtures::paper_key(verifier));
+		let query =
+			Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, accepted_replies, statement);
+		match &alice.handle_request((verifier.to_string(), query), 0)[..] {
+			[(_, Response::ExplainEstimate(3, 2, response, _))] => response.clone(),
+			responses => panic!("unexpected responses {:?}", responses),
+		}
+	};
+
+	// Alice has two histories for round 2, but both verifiers get the same one.
+	let for_carol = ask(&mut alice, "Carol", ReplyKinds::Either);
+	let for_dave = ask(&mut alice, "Dave", ReplyKinds::Either);
+	assert_eq!(for_carol, for_dave);
+	assert_eq!(alice.explanation_cache(), CacheStats { hits: 1, misses: 1 });
+
+	// A verifier that only accepts prevotes can't be given the cached precommits.
+	assert!(matches!(
+		ask(&mut alice, "Bob", ReplyKinds::Prevotes),
+		QueryResponse::Prevotes(..)
+	));
+	assert_eq!(alice.explanation_cache(), CacheStats { hits: 1, misses: 2 });
+}
+
+#[test]
+fn voters_without_history_claim_non_membership() {
+	// Dave answers that he has no history for the round, which any voter that joined later could
+	// say just as well.
+	let mut dave = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove("Dave")
+		.unwrap();
+	dave.voting_rounds = VotingRounds::new();
+	dave.key_registry = fixtures::paper_key_registry();
+	let statement = fixtures::paper_conflict_statement("Carol").sign(&fixtures::paper_key("Carol"));
+	let query = Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement);
+	assert!(matches!(
+		&dave.handle_request(("Carol".to_string(), query), 0)[..],
+		[(_, Response::NotAMemberAtRound(3))]
+	));
+
+	// Only Eve, who wasn't a member of the voter set in round 2, can rightly say so.
+	let chain = fixtures::paper_chain();
+	let commit = fixtures::paper_conflict_statement("Carol").commit_for_block_not_included;
+	let voter_set = VoterSet::new(fixtures::PAPER_EXAMPLE_VOTERS);
+	let mut instance = AccountableSafety::start(2, 2, commit, 8, 4, &chain)
+		.unwrap()
+		.with_voter_sets(vec![(0, voter_set)].into_iter().collect());
+	instance.start_query_round(3, vec!["Dave".to_string(), "Eve".to_string()]);
+	instance
+		.add_non_member_response(3, "Dave".to_string())
+		.unwrap();
+	instance
+		.add_non_member_response(3, "Eve".to_string())
+		.unwrap();
+	let verdicts = &instance.verdicts()[0].verdicts;
+	assert_eq!(
+		verdicts["Dave"],
+		Verdict::Invalid(InvalidReason::FalseNonMembership)
+	);
+	assert_eq!(verdicts["Eve"], Verdict::NotAMember);
+	assert_eq!(
+		instance.equivocations_detected(),
+		vec![EquivocationDetected::FalseNonMembership {
+			voter: "Dave".to_string(),
+			set_id: 0,
+			round: 2,
+		}]
+	);
+	assert!(instance.coverage().contains(Branch::NonMemberResponse));
+}
+
+#[test]
+fn responders_are_held_to_their_commitments() {
+	// Alice commits to her votes in round 2 with her first answer about it, and sticks to that.
+	let mut alice = fixtures::paper_example(Adversary::ReturnPrecommits)
+		.remove("Alice")
+		.unwrap();
+	alice.key_registry = fixtures::paper_key_registry();
+	let mut ask = || {
+		let statement =
+			fixtures::paper_conflict_statement("Carol").sign(&fixtures::paper_key("Carol"));
+		let query =
+			Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement);
+		match alice
+			.handle_request(("Carol".to_string(), query), 0)
+			.remove(0)
+			.1
+		{
+			Response::ExplainEstimate(3, 2, response, commitment) => (response, commitment),
+			response => panic!("unexpected response {:?}", response),
+		}
+	};
+	let (response, commitment) = ask();
+	assert_eq!(commitment.commitment.round, 2);
+	assert!(commitment.matches(&response));
+	assert!(commitment.verify(&fixtures::paper_key_registry()));
+	assert_eq!(ask().1, commitment);
+
+	let chain = fixtures::paper_chain();
+	let commit = fixtures::paper_conflict_statement("Carol").commit_for_block_not_included;
+	let mut instance = AccountableSafety::start(2, 2, commit, 8, 4, &chain).unwrap();
+	instance.start_query_round(3, vec!["Alice".into(), "Bob".into(), "Dave".into()]);
+	let precommits = |block| -> Vec<_> {
+		["Alice", "Bob", "Dave"]
+			.iter()
+			.map(|voter| Precommit::new(2, block, *voter))
+			.collect()
+	};
+	let answer = QueryResponse::Precommits(precommits(1));
+	let commit_to = |voter: &str, prevotes: &[Prevote], precommits: &[Precommit]| {
+		VoteCommitment::new(voter.to_string(), 2, 0, 0, prevotes, precommits)
+			.sign(&fixtures::paper_key(voter))
+	};
+	instance
+		.add_response(3, "Alice".into(), response, commitment, &chain)
+		.unwrap();
+	// Bob answers with other votes than those he committed to.
+	let bob = commit_to("Bob", &[], &precommits(2)).revealing(0, &answer);
+	instance
+		.add_response(3, "Bob".into(), answer.clone(), bob.clone(), &chain)
+		.unwrap();
+	// Dave keeps to his first commitment, but then commits to other votes for the same round.
+	let dave = commit_to("Dave", &[], &precommits(1)).revealing(0, &answer);
+	instance
+		.add_response(3, "Dave".into(), answer.clone(), dave.clone(), &chain)
+		.unwrap();
+	instance.start_prevote_query(2, vec!["Dave".into()]);
+	let prevotes = vec![Prevote::new(2, 5, "Dave")];
+	let revealed = QueryResponse::Prevotes(prevotes.clone());
+	let second = commit_to("Dave", &prevotes, &precommits(1)).revealing(0, &revealed);
+	instance
+		.add_prevote_response(2, "Dave".into(), revealed, second.clone())
+		.unwrap();
+
+	// Alice's answer contradicts the commit she signed, but not her commitment.
+	let verdicts = &instance.verdicts()[0].verdicts;
+	assert_eq!(
+		verdicts["Alice"],
+		Verdict::Invalid(InvalidReason::InconsistentTestimony)
+	);
+	assert_eq!(
+		verdicts["Bob"],
+		Verdict::Invalid(InvalidReason::BrokenCommitment)
+	);
+	assert_eq!(verdicts["Dave"], Verdict::RevealedEquivocations);
+	let expected = vec![
+		EquivocationDetected::BrokenCommitment {
+			voter: "Bob".into(),
+			set_id: 0,
+			round: 2,
+			committed: Box::new(bob),
+			revealed: Box::new(Revealed::Votes(answer)),
+		},
+		EquivocationDetected::BrokenCommitment {
+			voter: "Dave".into(),
+			set_id: 0,
+			round: 2,
+			committed: Box::new(dave.clone()),
+			revealed: Box::new(Revealed::Commitment(second)),
+		},
+	];
+	let broken: Vec<_> = instance
+		.equivocations_detected()
+		.into_iter()
+		.filter(|finding| matches!(finding, EquivocationDetected::BrokenCommitment { .. }))
+		.collect();
+	assert_eq!(broken, expected);
+	assert!(instance.coverage().contains(Branch::BrokenCommitment));
+
+	// The signed commitments make the findings checkable by anyone, unlike other findings about
+	// responses.
+	for proof in &expected {
+		assert_eq!(verify_equivocation_proof(proof, &chain), Ok(()));
+	}
+	let kept = EquivocationDetected::BrokenCommitment {
+		voter: "Dave".into(),
+		set_id: 0,
+		round: 2,
+		committed: Box::new(dave),
+		revealed: Box::new(Revealed::Votes(QueryResponse::Precommits(precommits(1)))),
+	};
+	assert_eq!(
+		verify_equivocation_proof(&kept, &chain),
+		Err(ProofError::KeptCommitment("Dave".into()))
+	);
+}
+
+#[test]
+fn commitments_only_give_away_the_votes_revealed() {
+	let precommits: Vec<_> = ["Alice", "Bob", "Dave"]
+		.iter()
+		.map(|voter| Precommit::new(2, 1, *voter))
+		.collect();
+	let nonce = fixtures::paper_key("Alice").nonce(&2u64.to_le_bytes());
+	let commitment = VoteCommitment::new("Alice".to_string(), 2, 0, nonce, &[], &precommits)
+		.sign(&fixtures::paper_key("Alice"));
+	let first = QueryResponse::Precommits(precommits[..1].to_vec());
+	let rest = QueryResponse::Precommits(precommits[1..].to_vec());
+
+	// Each vote is checked with the salt revealed for it, so those left out of an answer can't be
+	// checked for, even with the salts of the others.
+	let opened = commitment.clone().revealing(nonce, &first);
+	assert!(opened.matches(&first));
+	assert!(!opened.matches(&rest));
+	assert!(!commitment.matches(&first));
+	assert!(commitment.revealing(nonce, &rest).matches(&rest));
+
+	// Salts can't be made up for votes that weren't committed to.
+	let other = QueryResponse::Precommits(vec![Precommit::new(2, 2, "Alice")]);
+	assert!(!opened.revealing(nonce, &other).matches(&other));
+}
+
+#[test]
+fn prevote_queries_answered_with_precommits_are_invalid_responses() {
+	let chain = fixtures::paper_chain();
+	let commit = fixtures::paper_conflict_statement("Carol").commit_for_block_not_included;
+	let mut instance = AccountableSafety::start(2, 2, commit, 8, 4, &chain).unwrap();
+	instance.start_prevote_query(2, vec!["Dave".into()]);
+	let precommits = vec![Precommit::new(2, 1, "Dave")];
+	let answer = QueryResponse::Precommits(precommits.clone());
+	let commitment = VoteCommitment::new("Dave".into(), 2, 0, 0, &[], &precommits)
+		.sign(&fixtures::paper_key("Dave"))
+		.revealing(0, &answer);
+	let next = instance.add_prevote_response(2, "Dave".into(), answer, commitment);
+	assert!(matches!(next, Ok(None)));
+	assert!(instance
+		.equivocations_detected()
+		.contains(&EquivocationDetected::InvalidResponse {
+			voter: "Dave".into(),
+			set_id: 0,
+		}));
+	let verdicts = instance.verdicts();
+	let prevotes = verdicts.iter().find(|round| round.prevotes).unwrap();
+	assert_eq!(
+		prevotes.verdicts["Dave"],
+		Verdict::Invalid(InvalidReason::WrongKind)
+	);
+}
+
+#[test]
+fn observers_ignore_queries() {
+	let chain = fixtures::paper_chain();
+	let voter_set = fixtures::paper_example(Adversary::ReturnPrecommits)["Alice"]
+		.voter_set
+		.clone();
+	let mut olive = Voter::new_observer("Olive".into(), chain, voter_set);
+	let statement = fixtures::paper_conflict_statement("Carol").sign(&fixtures::paper_key("Carol"));
+	let query = Request::WhyDidEstimateForRoundNotIncludeBlock(3, 2, ReplyKinds::Either, statement);
+	assert!(olive.handle_request(("Carol".into(), query), 0).is_empty());
+	assert!(olive.protocol_bugs.is_empty());
+	assert!(olive.coverage().contains(Branch::QueryIgnored));
+}
+
+#[test]
+fn thread_per_voter_matches_single_threaded() {
+	let setups: Vec<fn() -> BTreeMap<VoterId, Voter>> = vec![
+		|| fixtures::paper_example(Adversary::ReturnPrecommits),
+		|| fixtures::paper_example(Adversary::ReturnPrevotes),
+		|| fixtures::three_way_fork(Adversary::ReturnPrecommits),
+		|| fixtures::large_voterset(10, Adversary::ReturnPrecommits),
+	];
+	for setup in setups {
+		let run = |execution| {
+			let mut world = WorldBuilder::new()
+				.with_voters(setup())
+				.with_execution(execution)
+				.build();
+			let summary = world.run_until_settled();
+			let detected: BTreeSet<_> = world
+				.equivocations_detected()
+				.iter()
+				.map(|equivocation| format!("{:?}", equivocation))
+				.collect();
+			(summary, detected)
+		};
+		let (single_threaded, detected) = run(Execution::SingleThreaded);
+		let (threaded, detected_threaded) = run(Execution::ThreadPerVoter);
+
+		assert!(threaded.settled);
+		assert!(!threaded.offenders.is_empty());
+		assert_eq!(threaded.offenders, single_threaded.offenders);
+		assert_eq!(detected_threaded, detected);
+		assert_eq!(threaded.protocol_bugs, 0);
+	}
+}
+
+#[test]
+fn thread_per_voter_runs_are_reproducible() {
+	let run = || {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::large_voterset(10, Adversary::ReturnPrecommits))
+			.with_execution(Execution::ThreadPerVoter)
+			.with_seed(7)
+			.build();
+		world.run_until_settled();
+		world.event_log().events().to_vec()
+	};
+	let first = run();
+	for _ in 0..3 {
+		assert_eq!(run(), first);
+	}
+}
+
+#[test]
+fn voter_notes_are_logged() {
+	let mut world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	run_to_completion(&mut world);
+	let notes: Vec<_> = world
+		.event_log()
+		.events()
+		.iter()
+		.filter(|event| event.kind == EventKind::Note)
+		.collect();
+	assert!(!notes.is_empty());
+}
+
+#[test]
+fn gossip_reaches_around_partitions() {
+	let run = |gossip_hops: Option<u32>| {
+		let mut builder = WorldBuilder::new()
+			.with_voters(fixtures::paper_example(Adversary::ReturnPrecommits))
+			.with_partition_schedule(PartitionSchedule::new(vec![Partition::new(
+				0,
+				100,
+				vec![vec!["Dave".to_string()], vec!["Carol".to_string()]],
+			)]))
+			.with_max_ticks(1000);
+		if let Some(max_hops) = gossip_hops {
+			builder = builder.with_gossip_hops(max_hops);
+		}
+		let mut world = builder.build();
+		let summary = world.run_until_settled();
+		(world, summary)
+	};
+
+	// Dave's commits never reach Carol directly.
+	let (world, summary) = run(None);
+	assert!(!world.voters()["Carol"].chain.knows_about_block(8));
+	assert_eq!(summary.duplicates_suppressed, 0);
+
+	// Alice and Bob pass them on, and each drop the copy passed on by the other.
+	let (world, summary) = run(Some(1));
+	assert!(summary.settled);
+	assert!(world.voters()["Carol"].chain.knows_about_block(8));
+	assert!(world.voters()["Carol"]
+		.coverage()
+		.contains(Branch::ProtocolStarted));
+	assert!(summary.duplicates_suppressed > 0);
+	assert!(world.voters()["Alice"]
+		.coverage()
+		.contains(Branch::DuplicateSuppressed));
+}
+
+#[test]
+fn gossip_stops_after_its_hops() {
+	for max_hops in 0..3 {
+		let mut world = WorldBuilder::new()
+			.with_voters(fixtures::large_voterset(7, Adversary::ReturnPrecommits))
+			.with_gossip_hops(max_hops)
+			.with_max_ticks(1000)
+			.build();
+		let summary = world.run_until_settled();
+		assert!(summary.settled);
+		assert!(!summary.offenders.is_empty());
+		let hops: BTreeSet<u32> = world
+			.event_log()
+			.events()
+			.iter()
+			.filter(|event| event.kind == EventKind::MessageSent)
+			.filter_map(|event| event.summary.strip_prefix("Gossip(originator: "))
+			.map(|summary| {
+				let hops = summary.split("hops: ").nth(1).unwrap();
+				hops[..hops.find(',').unwrap()].parse().unwrap()
+			})
+			.collect();
+		// Everyone hears from the originator directly, so whatever is passed on once is a duplicate
+		// and goes no further.
+		assert_eq!(hops, (0..=max_hops.min(1)).collect());
+	}
+}
+
+#[test]
+fn voting_history_accessors() {
+	let voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+
+	// The common history has the first tag, and the histories of the two forks the next ones.
+	let alice = &voters["Alice"];
+	let rounds: Vec<_> = alice
+		.rounds()
+		.map(|voting_round| (voting_round.round_number, voting_round.tag))
+		.collect();
+	assert_eq!(
+		rounds,
+		vec![(1, 0), (2, 1), (2, 2), (3, 1), (3, 2), (4, 1), (4, 2)]
+	);
+	assert!(alice
+		.round(4, 2)
+		.unwrap()
+		.precommits
+		.contains(&Precommit::new(4, 8, "Alice")));
+	assert!(alice.round(4, 0).is_none());
+	assert!(alice.round(5, 0).is_none());
+
+	// Alice voted on both forks in round 2.
+	let votes = alice.own_vote_in(2);
+	assert_eq!(votes.len(), 2);
+	assert_eq!(votes[0].tag, 1);
+	assert_eq!(votes[0].precommit, Some(Precommit::new(2, 2, "Alice")));
+	assert_eq!(votes[1].tag, 2);
+	assert_eq!(votes[1].precommit, Some(Precommit::new(2, 1, "Alice")));
+
+	let votes = voters["Carol"].own_vote_in(2);
+	assert_eq!(votes.len(), 1);
+	assert_eq!(votes[0].prevote.as_ref().unwrap().target_number, 2);
+	assert!(voters["Carol"].own_vote_in(5).is_empty());
+}
+
+#[test]
+fn unsolicited_answers_are_ignored() {
+	let mut voters = fixtures::paper_example(Adversary::ReturnPrecommits);
+	let carol = voters.get_mut("Carol").unwrap();
+	assert!(!carol.strict);
+	carol.handle_response(("Alice".into(), Response::DontKnowRound(3)), 0);
+	assert!(carol.protocol_bugs.is_empty());
+	assert!(carol.coverage().contains(Branch::UnsolicitedAnswer));
+	assert!(carol.equivocations_detected().is_empty());
+}
+
+#[test]
+fn voters_vote_in_rounds_of_their_own_and_finalize_the_best_chain() {
+	let names = ["Alice", "Bob", "Carol", "Dave"];
+	let blocks = [(1, 0), (2, 1), (3, 2)];
+	let mut scenario = ScenarioBuilder::new(&names);
+	for name in &names {
+		scenario = scenario.with_voter(name, &blocks, &[], None);
+	}
+	let mut world = WorldBuilder::new()
+		.with_voters(scenario.build().unwrap())
+		.with_network(2, 0.0)
+		.with_active_voting(3)
+		.build();
+	let summary = world.run_until_settled();
+	assert_eq!(summary.termination, Some(Termination::Concluded));
+
+	for voter in world.voters().values() {
+		let rounds: Vec<_> = voter.rounds().collect();
+		assert_eq!(
+			rounds
+				.iter()
+				.map(|round| round.round_number)
+				.collect::<Vec<_>>(),
+			vec![1, 2, 3]
+		);
+		for round in &rounds {
+			assert!(round
+				.prevotes
+				.iter()
+				.all(|prevote| prevote.target_number == 3));
+			assert_eq!(round.precommits.len(), names.len());
+		}
+		// The first round already finalizes the head, which the later rounds only confirm.
+		assert_eq!(rounds[0].finalized, Some(3));
+		assert_eq!(voter.chain.finalized_round(3), Some(&1));
+		assert_eq!(voter.commits().len(), 1);
+	}
+	// Each voter sends its prevote and precommit in each round to the three others.
+	assert_eq!(world.messages_sent(), 3 * 2 * names.len() * 3);
+}
+
+#[test]
+fn voters_with_a_finalization_lag_leave_the_head_unfinalized() {
+	let names = ["Alice", "Bob", "Carol", "Dave"];
+	let blocks = [(1, 0), (2, 1), (3, 2)];
+	let mut scenario = ScenarioBuilder::new(&names);
+	for name in &names {
+		scenario = scenario.with_voter(name, &blocks, &[], None);
+	}
+	let mut world = WorldBuilder::new()
+		.with_voters(scenario.build().unwrap())
+		.with_network(2, 0.0)
+		.with_active_voting(3)
+		.with_finalization_lag(1)
+		.build();
+	let summary = world.run_until_settled();
+	assert_eq!(summary.termination, Some(Termination::Concluded));
+
+	for voter in world.voters().values() {
+		let rounds: Vec<_> = voter.rounds().collect();
+		assert_eq!(rounds.len(), 3);
+		// Every round votes for the head, but finalizes only its parent.
+		for round in &rounds {
+			assert_eq!(round.precommits.len(), names.len());
+			assert_eq!(round.finalized, Some(2));
+		}
+		assert_eq!(voter.chain.finalized_round(2), Some(&1));
+		assert_eq!(voter.chain.finalized_round(3), None);
+		assert_eq!(voter.commits().len(), 1);
+	}
+}
+
+#[test]
+fn batching_votes_cuts_the_messages_of_voting() {
+	let names: Vec<_> = (0..10).map(|i| format!("voter{}", i)).collect();
+	let run = |batch_interval: Option<usize>| {
+		let mut scenario = ScenarioBuilder::new(&names);
+		for name in &names {
+			scenario = scenario.with_voter(name, &[(1, 0), (2, 1)], &[], None);
+		}
+		let mut builder = WorldBuilder::new()
+			.with_voters(scenario.build().unwrap())
+			.with_network(2, 0.0)
+			.with_active_voting(5);
+		if let Some(interval) = batch_interval {
+			builder = builder.with_vote_batching(interval);
+		}
+		let mut world = builder.build();
+		let summary = world.run_until_settled();
+		assert_eq!(summary.termination, Some(Termination::Concluded));
+		for voter in world.voters().values() {
+			assert_eq!(voter.rounds().count(), 5);
+			assert_eq!(voter.chain.finalized_round(2), Some(&1));
+		}
+		world.messages_sent()
+	};
+	let one_by_one = run(None);
+	let batched = run(Some(4));
+	// Each voter sends its prevote and its precommit to each of the others in every round.
+	assert_eq!(one_by_one, 5 * 2 * 10 * 9);
+	// Batches a few ticks apart carry the votes of a round, and the next, in one message.
+	assert_eq!(batched, 540);
 }
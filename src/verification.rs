@@ -0,0 +1,490 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Checking proofs of misbehaviour without a full [`Chain`], as a light client or an on-chain
+//! verifier would. Besides the proof itself, all that's needed is the ancestry of the blocks it
+//! refers to, supplied by a [`HeaderProvider`].
+//!
+//! Votes aren't signed in this simulation, so the checks are about whether the votes in a proof
+//! amount to misbehaviour. Findings about responses to queries can't be checked by a third party,
+//! since only the voter that asked saw the responses, except for broken commitments, where the
+//! responder's signed commitment is part of the proof.
+
+use crate::{
+	block::BlockNumber,
+	chain::Chain,
+	protocol::{
+		CommitEquivocation, Equivocation, EquivocationDetected, InconsistentTestimony, Revealed,
+		SignedVoteCommitment, VoteCommitment,
+	},
+	voter::VoterId,
+	voting::RoundNumber,
+};
+use std::{
+	collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+	hash::{Hash, Hasher},
+};
+
+/// Identifies a proof, for keeping what's needed to verify it, see [`Chain::pin_for_proof`].
+pub type ProofId = u64;
+
+pub fn proof_id(proof: &EquivocationDetected) -> ProofId {
+	let mut hasher = DefaultHasher::new();
+	proof.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// The blocks the proof refers to. Verifying it takes their headers and those of their ancestors.
+pub fn referenced_blocks(proof: &EquivocationDetected) -> BTreeSet<BlockNumber> {
+	match proof {
+		EquivocationDetected::Prevote(equivocations)
+		| EquivocationDetected::Precommit(equivocations) => equivocations
+			.iter()
+			.flat_map(|equivocation| equivocation.blocks.iter().copied())
+			.collect(),
+		EquivocationDetected::CommitEquivocation(equivocations) => equivocations
+			.iter()
+			.flat_map(|equivocation| equivocation.targets)
+			.collect(),
+		EquivocationDetected::InconsistentTestimony(testimony) => testimony
+			.claimed
+			.iter()
+			.map(|precommit| precommit.target_number)
+			.chain([testimony.finalized, testimony.signed.target_number])
+			.collect(),
+		EquivocationDetected::EstimateInconsistency {
+			estimate,
+			precommit,
+			..
+		} => [*estimate, precommit.target_number]
+			.iter()
+			.copied()
+			.collect(),
+		EquivocationDetected::FabricatedAccusation { accusation, .. } => {
+			referenced_blocks(accusation)
+		}
+		EquivocationDetected::InvalidResponse { .. }
+		| EquivocationDetected::PartialResponse { .. }
+		| EquivocationDetected::ReplayedVotes { .. }
+		| EquivocationDetected::MisplacedVotes { .. }
+		| EquivocationDetected::OversizedResponse { .. }
+		| EquivocationDetected::FalseNonMembership { .. }
+		| EquivocationDetected::BrokenCommitment { .. } => BTreeSet::new(),
+	}
+}
+
+/// The block headers a verifier knows about, reduced to what's needed to follow the ancestry.
+pub trait HeaderProvider {
+	/// The parent of the block, or `None` if its header isn't known. Genesis is its own parent.
+	fn parent(&self, block: BlockNumber) -> Option<BlockNumber>;
+
+	/// Whether `block` is `ancestor` or one of its descendants.
+	fn block_includes(
+		&self,
+		block: BlockNumber,
+		ancestor: BlockNumber,
+	) -> Result<bool, ProofError> {
+		let mut current = block;
+		loop {
+			if current == ancestor {
+				return Ok(true);
+			}
+			let parent = self
+				.parent(current)
+				.ok_or(ProofError::MissingHeader(current))?;
+			if parent == current {
+				return Ok(false);
+			}
+			current = parent;
+		}
+	}
+}
+
+impl HeaderProvider for Chain {
+	fn parent(&self, block: BlockNumber) -> Option<BlockNumber> {
+		self.get_block(block).map(|block| block.parent)
+	}
+}
+
+/// Headers given as a map from each block to its parent.
+impl HeaderProvider for BTreeMap<BlockNumber, BlockNumber> {
+	fn parent(&self, block: BlockNumber) -> Option<BlockNumber> {
+		self.get(&block).copied()
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofError {
+	MissingHeader(BlockNumber),
+	// The proof doesn't name anyone.
+	NoOffenders,
+	// The votes of the voter are all for the same block.
+	NoConflictingVotes(VoterId),
+	// The two commits the voter signed are for blocks on the same fork.
+	CompatibleCommits(VoterId),
+	// The signed precommit isn't the voter's, isn't from the round, or isn't for the finalized
+	// block.
+	SignedPrecommitMismatch(VoterId),
+	// The precommits the voter claimed include its signed one after all.
+	ConsistentTestimony(VoterId),
+	// Only the voter that asked the query can check findings about the responses.
+	Unverifiable,
+	// A detector was said to have fabricated an accusation that holds up.
+	GenuineAccusation,
+	// What the voter revealed about the round is what it committed to, or isn't about the same
+	// round.
+	KeptCommitment(VoterId),
+}
+
+impl ProofError {
+	/// Whether the proof is shown to be false, rather than just beyond what we can check. A missing
+	/// header might be one we haven't synced yet.
+	pub fn is_fabrication(&self) -> bool {
+		match self {
+			ProofError::MissingHeader(_) | ProofError::Unverifiable => false,
+			ProofError::NoOffenders
+			| ProofError::NoConflictingVotes(_)
+			| ProofError::CompatibleCommits(_)
+			| ProofError::SignedPrecommitMismatch(_)
+			| ProofError::ConsistentTestimony(_)
+			| ProofError::GenuineAccusation
+			| ProofError::KeptCommitment(_) => true,
+		}
+	}
+}
+
+impl Display for ProofError {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			ProofError::MissingHeader(block) => write!(f, "no header for block {}", block),
+			ProofError::NoOffenders => write!(f, "the proof names no offenders"),
+			ProofError::NoConflictingVotes(voter) => {
+				write!(f, "{} only voted for a single block", voter)
+			}
+			ProofError::CompatibleCommits(voter) => {
+				write!(f, "the commits {} signed are on the same fork", voter)
+			}
+			ProofError::SignedPrecommitMismatch(voter) => write!(
+				f,
+				"the signed precommit doesn't match {} finalizing the block",
+				voter
+			),
+			ProofError::ConsistentTestimony(voter) => write!(
+				f,
+				"{} claimed a precommit consistent with the one it signed",
+				voter
+			),
+			ProofError::Unverifiable => write!(f, "the finding can't be checked by a third party"),
+			ProofError::GenuineAccusation => write!(f, "the accusation holds up"),
+			ProofError::KeptCommitment(voter) => {
+				write!(f, "{} revealed the votes it committed to", voter)
+			}
+		}
+	}
+}
+
+impl std::error::Error for ProofError {}
+
+/// Check that the proof shows the voters it names misbehaved, using only the headers of the blocks
+/// it refers to.
+pub fn verify_equivocation_proof(
+	proof: &EquivocationDetected,
+	headers: &impl HeaderProvider,
+) -> Result<(), ProofError> {
+	match proof {
+		EquivocationDetected::Prevote(equivocations)
+		| EquivocationDetected::Precommit(equivocations) => {
+			if equivocations.is_empty() {
+				return Err(ProofError::NoOffenders);
+			}
+			equivocations
+				.iter()
+				.try_for_each(|equivocation| verify_conflicting_votes(equivocation, headers))
+		}
+		EquivocationDetected::CommitEquivocation(equivocations) => {
+			if equivocations.is_empty() {
+				return Err(ProofError::NoOffenders);
+			}
+			equivocations
+				.iter()
+				.try_for_each(|equivocation| verify_commit_equivocation(equivocation, headers))
+		}
+		EquivocationDetected::InconsistentTestimony(testimony) => {
+			verify_testimony(testimony, headers)
+		}
+		EquivocationDetected::BrokenCommitment {
+			voter,
+			round,
+			committed,
+			revealed,
+			..
+		} => verify_broken_commitment(voter, *round, committed, revealed),
+		EquivocationDetected::InvalidResponse { .. }
+		| EquivocationDetected::PartialResponse { .. }
+		| EquivocationDetected::ReplayedVotes { .. }
+		| EquivocationDetected::MisplacedVotes { .. }
+		| EquivocationDetected::OversizedResponse { .. }
+		| EquivocationDetected::FalseNonMembership { .. }
+		| EquivocationDetected::EstimateInconsistency { .. } => Err(ProofError::Unverifiable),
+		// Since votes would be signed, the accusation can only cite votes the accused really cast,
+		// and it's the accusation not holding up that proves the detector made it up.
+		EquivocationDetected::FabricatedAccusation { accusation, .. } => {
+			match verify_equivocation_proof(accusation, headers) {
+				Ok(()) => Err(ProofError::GenuineAccusation),
+				Err(error) if error.is_fabrication() => Ok(()),
+				Err(error) => Err(error),
+			}
+		}
+	}
+}
+
+// The signatures on commitments would be checked against the keys of the voter set, which leaves
+// whether what was revealed contradicts the commitment.
+fn verify_broken_commitment(
+	voter: &VoterId,
+	round: RoundNumber,
+	committed: &SignedVoteCommitment,
+	revealed: &Revealed,
+) -> Result<(), ProofError> {
+	let about_voter =
+		|commitment: &VoteCommitment| &commitment.responder == voter && commitment.round == round;
+	let broken = about_voter(&committed.commitment)
+		&& match revealed {
+			Revealed::Votes(votes) => !committed.matches(votes),
+			Revealed::Commitment(other) => {
+				about_voter(&other.commitment)
+					&& !committed.commitment.same_votes(&other.commitment)
+			}
+		};
+	if broken {
+		Ok(())
+	} else {
+		Err(ProofError::KeptCommitment(voter.clone()))
+	}
+}
+
+// Votes for different blocks in the same round are an offence whatever the blocks, but they have
+// to be blocks that exist.
+fn verify_conflicting_votes(
+	equivocation: &Equivocation,
+	headers: &impl HeaderProvider,
+) -> Result<(), ProofError> {
+	let blocks: BTreeSet<_> = equivocation.blocks.iter().copied().collect();
+	if blocks.len() < 2 {
+		return Err(ProofError::NoConflictingVotes(equivocation.voter.clone()));
+	}
+	for block in blocks {
+		headers
+			.parent(block)
+			.ok_or(ProofError::MissingHeader(block))?;
+	}
+	Ok(())
+}
+
+// Precommits in the same round for blocks on different forks. Since each precommit in a commit
+// includes the block finalized, that makes the two commits conflict.
+fn verify_commit_equivocation(
+	equivocation: &CommitEquivocation,
+	headers: &impl HeaderProvider,
+) -> Result<(), ProofError> {
+	let [first, second] = equivocation.targets;
+	if headers.block_includes(first, second)? || headers.block_includes(second, first)? {
+		return Err(ProofError::CompatibleCommits(equivocation.voter.clone()));
+	}
+	Ok(())
+}
+
+// The same check as the protocol makes, see `AccountableSafety::check_testimony`.
+fn verify_testimony(
+	testimony: &InconsistentTestimony,
+	headers: &impl HeaderProvider,
+) -> Result<(), ProofError> {
+	let voter = testimony.voter.as_str();
+	let signed = &testimony.signed;
+	if signed.id != voter
+		|| signed.round != testimony.round
+		|| !headers.block_includes(signed.target_number, testimony.finalized)?
+	{
+		return Err(ProofError::SignedPrecommitMismatch(testimony.voter.clone()));
+	}
+	for claimed in &testimony.claimed {
+		if claimed.id == voter
+			&& claimed.round == testimony.round
+			&& headers.block_includes(claimed.target_number, testimony.finalized)?
+		{
+			return Err(ProofError::ConsistentTestimony(testimony.voter.clone()));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::voting::Precommit;
+
+	//       /-> 2 -> 3
+	// 0 -> 1 -> 5
+	fn headers() -> BTreeMap<BlockNumber, BlockNumber> {
+		vec![(0, 0), (1, 0), (2, 1), (3, 2), (5, 1)]
+			.into_iter()
+			.collect()
+	}
+
+	fn equivocation(blocks: Vec<BlockNumber>) -> EquivocationDetected {
+		EquivocationDetected::Precommit(vec![Equivocation {
+			voter: "Alice".to_string(),
+			set_id: 0,
+			blocks,
+		}])
+	}
+
+	fn testimony(signed: BlockNumber, claimed: BlockNumber) -> EquivocationDetected {
+		EquivocationDetected::InconsistentTestimony(InconsistentTestimony {
+			voter: "Alice".to_string(),
+			round: 2,
+			finalized: 2,
+			signed: Precommit::new(2, signed, "Alice"),
+			claimed: vec![
+				Precommit::new(2, claimed, "Alice"),
+				Precommit::new(2, 5, "Bob"),
+			],
+		})
+	}
+
+	#[test]
+	fn ancestry() {
+		let headers = headers();
+		assert_eq!(headers.block_includes(3, 1), Ok(true));
+		assert_eq!(headers.block_includes(5, 2), Ok(false));
+		assert_eq!(
+			headers.block_includes(7, 1),
+			Err(ProofError::MissingHeader(7))
+		);
+	}
+
+	#[test]
+	fn equivocations() {
+		let headers = headers();
+		assert_eq!(
+			verify_equivocation_proof(&equivocation(vec![2, 5]), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&equivocation(vec![2, 2]), &headers),
+			Err(ProofError::NoConflictingVotes("Alice".to_string()))
+		);
+		assert_eq!(
+			verify_equivocation_proof(&equivocation(vec![2, 7]), &headers),
+			Err(ProofError::MissingHeader(7))
+		);
+		assert_eq!(
+			verify_equivocation_proof(&EquivocationDetected::Prevote(vec![]), &headers),
+			Err(ProofError::NoOffenders)
+		);
+	}
+
+	#[test]
+	fn commit_equivocations() {
+		let headers = headers();
+		let equivocation = |targets| {
+			EquivocationDetected::CommitEquivocation(vec![CommitEquivocation {
+				voter: "Alice".to_string(),
+				set_id: 0,
+				round: 2,
+				targets,
+			}])
+		};
+		assert_eq!(
+			verify_equivocation_proof(&equivocation([3, 5]), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&equivocation([3, 2]), &headers),
+			Err(ProofError::CompatibleCommits("Alice".to_string()))
+		);
+		assert_eq!(
+			verify_equivocation_proof(&equivocation([3, 7]), &headers),
+			Err(ProofError::MissingHeader(7))
+		);
+	}
+
+	#[test]
+	fn testimonies() {
+		let headers = headers();
+		assert_eq!(
+			verify_equivocation_proof(&testimony(2, 5), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&testimony(2, 1), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&testimony(3, 2), &headers),
+			Err(ProofError::ConsistentTestimony("Alice".to_string()))
+		);
+		assert_eq!(
+			verify_equivocation_proof(&testimony(5, 1), &headers),
+			Err(ProofError::SignedPrecommitMismatch("Alice".to_string()))
+		);
+	}
+
+	#[test]
+	fn fabricated_accusations() {
+		let headers = headers();
+		let fabricated =
+			|accusation| EquivocationDetected::fabricated("Carol".to_string(), accusation);
+		assert_eq!(
+			verify_equivocation_proof(&fabricated(equivocation(vec![2, 2])), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&fabricated(testimony(3, 2)), &headers),
+			Ok(())
+		);
+		assert_eq!(
+			verify_equivocation_proof(&fabricated(equivocation(vec![2, 5])), &headers),
+			Err(ProofError::GenuineAccusation)
+		);
+		// Not knowing the blocks, or not being able to check at all, isn't proof of anything.
+		assert_eq!(
+			verify_equivocation_proof(&fabricated(equivocation(vec![2, 7])), &headers),
+			Err(ProofError::MissingHeader(7))
+		);
+		let response = EquivocationDetected::InvalidResponse {
+			voter: "Alice".to_string(),
+			set_id: 0,
+		};
+		assert_eq!(
+			verify_equivocation_proof(&fabricated(response), &headers),
+			Err(ProofError::Unverifiable)
+		);
+	}
+
+	#[test]
+	fn responses_are_unverifiable() {
+		let proof = EquivocationDetected::InvalidResponse {
+			voter: "Alice".to_string(),
+			set_id: 0,
+		};
+		assert_eq!(
+			verify_equivocation_proof(&proof, &headers()),
+			Err(ProofError::Unverifiable)
+		);
+	}
+}
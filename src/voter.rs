@@ -16,33 +16,415 @@
 
 use crate::{
 	action::{Action, TriggerAtTick},
-	block::BlockNumber,
-	chain::Chain,
-	message::{Message, Payload, Request, Response},
-	protocol::{AccountableSafety, EquivocationDetected, NextQuery, Query, QueryResponse},
-	voting::{check_query_reply_is_valid, Commit, VoterSet, VotingRounds},
+	archive::ArchiveBackend,
+	behaviour::{Adversary, Behaviour, BehaviourSchedule, EstimateQuestion, PrevoteQuestion},
+	block::{Block, BlockHash, BlockNumber},
+	chain::{Chain, ChainError, CommitProvenance},
+	coverage::{Branch, Coverage},
+	crypto::{KeyRegistry, SecretKey, VerificationFaults},
+	message::{Gossip, Message, Payload, Request, Response},
+	policy::{Refusal, ServingPolicy},
+	protocol::{
+		AccountableSafety, ConflictId, ConflictStatement, Equivocation, EquivocationDetected,
+		EvidenceBundle, NextQuery, Outcome, PlannedRound, ProtocolBug, Query, QueryRejection,
+		QueryResponse, ReplyKinds, SignedConflictStatement, SignedEvidenceBundle,
+		SignedVoteCommitment, VoteCommitment,
+	},
+	report::{
+		CacheStats, CommitReport, Exoneration, FinalizedBlock, OffenderAnalysis, RoundVerdicts,
+		UnjustifiedCommitParticipation,
+	},
+	storage::RoundStorage,
+	verification::{proof_id, referenced_blocks, verify_equivocation_proof, ProofError},
+	voting::{
+		minimal_query_reply, Commit, CommitVariant, DivergentCommitVariants, InvalidCommit,
+		MismatchedCommitTarget, Precommit, Prevote, RoundNumber, RoundStage, RoundState,
+		RoundTimers, SetId, VoteBatch, VoterSet, VotingRound, VotingRounds,
+	},
 };
 use itertools::Itertools;
-use std::{collections::HashMap, fmt::Display};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+	borrow::Borrow,
+	collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+	fmt::Display,
+	io,
+	sync::Arc,
+};
 
-pub type VoterName = &'static str;
 pub type VoterId = String;
 
+// A question put to a single peer about a round. Instances investigating different blocks share
+// the answers explaining the estimate, so that a voter has to stand by a single explanation for
+// each round, whichever of the conflicting blocks it's asked about.
+type QueryKey = (RoundNumber, QueryKind, VoterId);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+	// Why the estimate for the round didn't include a block.
+	Estimate,
+	// Which prevotes were seen in the round.
+	Prevotes,
+}
+
+/// A query we accepted, with the verifier that asked as named in the conflict statement it signed,
+/// rather than whoever passed the query on to us.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServedQuery {
+	pub verifier: VoterId,
+	pub conflict: ConflictId,
+	pub round: RoundNumber,
+	pub kind: QueryKind,
+	// The votes we answered with, if we had any to give.
+	pub answer: Option<QueryResponse>,
+}
+
+/// A question put to a peer that hasn't been answered yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutstandingQuery {
+	pub round: RoundNumber,
+	pub kind: QueryKind,
+	pub voter: VoterId,
+	// The tick the question was put.
+	pub since: TriggerAtTick,
+	// The protocol instances waiting for the answer.
+	pub conflicts: Vec<ConflictId>,
+}
+
+/// How far a received commit got on its way to starting protocol instances: its target block is
+/// synced first, then the commit is validated and checked for conflicts with the commits we know.
+/// Only then are instances started.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommitStage {
+	// The target block was requested, and the commit requeued until it arrives.
+	AwaitingBlock,
+	Synced,
+	// The commit doesn't hold up, and goes no further.
+	Invalid(InvalidCommit),
+	Validated,
+	// It conflicts with none of the commits we know.
+	NoConflict,
+	// It conflicts with the commit for the given block.
+	Conflicting(BlockNumber),
+	InstanceStarted(ConflictId),
+}
+
+/// A commit reaching a stage of [`CommitStage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitProgress {
+	pub sender: VoterId,
+	pub round: RoundNumber,
+	pub block: BlockNumber,
+	pub stage: CommitStage,
+}
+
+impl Display for CommitProgress {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"commit for block {} in round {} from {}: ",
+			self.block, self.round, self.sender
+		)?;
+		match &self.stage {
+			CommitStage::AwaitingBlock => write!(f, "awaiting block"),
+			CommitStage::Synced => write!(f, "synced"),
+			CommitStage::Invalid(reason) => write!(f, "invalid, {}", reason),
+			CommitStage::Validated => write!(f, "validated"),
+			CommitStage::NoConflict => write!(f, "no conflict"),
+			CommitStage::Conflicting(block) => write!(f, "conflicts with block {}", block),
+			CommitStage::InstanceStarted(conflict) => write!(
+				f,
+				"instance started for blocks {} and {}",
+				conflict.block_not_included, conflict.new_block
+			),
+		}
+	}
+}
+
+/// The votes a voter cast itself in one of its histories of a round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnVote {
+	// Which of the voter's histories of the round the votes are from.
+	pub tag: u32,
+	pub prevote: Option<Prevote>,
+	pub precommit: Option<Precommit>,
+}
+
+/// Cheap to clone handle to the id of a voter, used in votes and voter sets which get copied
+/// around a lot.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VoterName(Arc<str>);
+
+impl VoterName {
+	pub fn new(id: &str) -> Self {
+		Self(id.into())
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
+impl From<&str> for VoterName {
+	fn from(id: &str) -> Self {
+		Self::new(id)
+	}
+}
+
+impl From<&VoterId> for VoterName {
+	fn from(id: &VoterId) -> Self {
+		Self::new(id)
+	}
+}
+
+impl AsRef<str> for VoterName {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl Borrow<str> for VoterName {
+	fn borrow(&self) -> &str {
+		&self.0
+	}
+}
+
+impl PartialEq<str> for VoterName {
+	fn eq(&self, other: &str) -> bool {
+		&*self.0 == other
+	}
+}
+
+impl PartialEq<&str> for VoterName {
+	fn eq(&self, other: &&str) -> bool {
+		&*self.0 == *other
+	}
+}
+
+impl PartialEq<VoterId> for VoterName {
+	fn eq(&self, other: &VoterId) -> bool {
+		*self.0 == **other
+	}
+}
+
+impl Serialize for VoterName {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.as_str())
+	}
+}
+
+impl<'de> Deserialize<'de> for VoterName {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer).map(|id| Self::new(&id))
+	}
+}
+
+impl Display for VoterName {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+// How often, and how many times, voters send out the evidence they have found.
+const EVIDENCE_GOSSIP_INTERVAL: usize = 50;
+const EVIDENCE_GOSSIP_REPEATS: usize = 5;
+// How many times, and how often, to check evidence whose signature doesn't verify before flagging
+// it for audit.
+const EVIDENCE_VERIFICATION_ATTEMPTS: usize = 3;
+const EVIDENCE_VERIFICATION_RETRY_INTERVAL: usize = 10;
+// How long to wait for the remaining chunks of a response after the first one arrives.
+const RESPONSE_CHUNK_TIMEOUT: usize = 50;
+// On top of the time for a query to reach a voter and the answer to come back, how long to wait
+// before asking the next nearest voter. Covers the delay before queries go out.
+const QUERY_EXPANSION_SLACK: usize = 15;
+
+// How long detectors that coordinate wait for each other's claims before designating the primary
+// verifier among them.
+const CLAIM_WINDOW: usize = 5;
+
+// A question unanswered for this long no longer counts against the cap on outstanding queries, so
+// that peers that never answer can't hold back the deferred ones forever, and its receiver is
+// found unresponsive.
+const OUTSTANDING_QUERY_TIMEOUT: usize = 100;
+
 pub struct Voter {
 	pub id: VoterId,
+	pub role: Role,
 	pub chain: Chain,
 	pub voter_set: VoterSet,
-	pub voting_rounds: VotingRounds,
-	pub actions: Vec<(TriggerAtTick, Action)>,
-	pub accountable_safety: Vec<AccountableSafety>,
-	pub behaviour: Option<Behaviour>,
+	// Read through `rounds`, `round` and `own_vote_in`.
+	pub(crate) voting_rounds: VotingRounds,
+	// The nodes following the chain without voting. They are sent our commits and evidence, but
+	// never queried.
+	pub observers: BTreeSet<VoterId>,
+	actions: Vec<(TriggerAtTick, Action)>,
+	// The protocol instances we started, one for each conflict.
+	accountable_safety: BTreeMap<ConflictId, AccountableSafety>,
+	// Instances of the protocol that concluded without running any queries.
+	pub outcomes: Vec<Outcome>,
+	// How we act wherever we could deviate from the protocol. Without one we act honestly.
+	pub behaviour: Option<Arc<dyn Behaviour>>,
+	// The changes to our behaviour still to come during the run.
+	pub behaviour_schedule: BehaviourSchedule,
+	// The peers we have asked for a block we don't know about yet.
+	pub block_requests: HashMap<BlockNumber, Vec<VoterId>>,
+	// The blocks we asked our peers for the commits of, while catching up on finality.
+	commit_requests: BTreeSet<BlockNumber>,
+	// The target hashes of the commits waiting for their block, which the blocks served must match.
+	pending_block_hashes: HashMap<BlockNumber, BlockHash>,
+	// The peers that served ancestry for a block we couldn't trust.
+	untrusted_ancestry: BTreeMap<BlockNumber, BTreeSet<VoterId>>,
+	pub secret_key: SecretKey,
+	// Used to check the signatures on evidence gossiped by others.
+	pub key_registry: KeyRegistry,
+	// The accusations we verified in the latest evidence received from each other detector.
+	pub proof_store: BTreeMap<VoterId, Vec<EquivocationDetected>>,
+	// The accusations in that evidence that we couldn't check, such as findings about responses to
+	// queries only the detector sent. They are claims, not proof, so they never make an offender.
+	pub unconfirmed_claims: BTreeMap<VoterId, Vec<EquivocationDetected>>,
+	// Branches of the voter logic hit so far. Protocol branches are tracked by each instance.
+	coverage: Coverage,
+	// In strict mode, internal inconsistencies are collected here instead of panicking.
+	pub strict: bool,
+	pub protocol_bugs: Vec<ProtocolBug>,
+	// The errors walking our chain for blocks peers sent us, or asked us about, which left those
+	// unused or unanswered.
+	chain_errors: Vec<String>,
+	// The most votes we put in a single message. Larger responses are sent in chunks.
+	pub message_budget: Option<usize>,
+	// Every distinct commit received for each block.
+	pub received_commits: BTreeMap<BlockNumber, Vec<CommitVariant>>,
+	// Commits rejected since their target didn't match our chain, attributed to the sender.
+	pub rejected_commits: Vec<MismatchedCommitTarget>,
+	// If set, some signature checks fail even though the signature is valid.
+	pub verification_faults: Option<VerificationFaults>,
+	// Evidence that repeatedly failed verification. This isn't proof of anything against the
+	// claimed detector, so it's set aside for a closer look rather than acted on.
+	pub flagged_for_audit: Vec<SignedEvidenceBundle>,
+	// Findings against detectors that gossiped evidence with accusations that don't hold up.
+	fabricated_evidence: Vec<EquivocationDetected>,
+	// Consulted for rounds no longer in `voting_rounds`.
+	pub archive: Option<Arc<dyn ArchiveBackend>>,
+	// The kinds of votes we accept as explanations when running the protocol.
+	pub accepted_replies: ReplyKinds,
+	// Whether we ask all voters about a round at once, or the nearest first.
+	pub query_plan: QueryPlan,
+	// How many ticks a message takes to reach each peer, for planning queries.
+	pub link_latencies: BTreeMap<VoterId, usize>,
+	// The questions put to peers by any of our protocol instances, so that each is only asked once,
+	// and when they were put. Those left unanswered are dropped once they time out.
+	asked: BTreeMap<QueryKey, TriggerAtTick>,
+	// The answers received so far, handed to instances that ask a question after it was answered.
+	answers: BTreeMap<QueryKey, Vec<Response>>,
+	// The most questions we put to peers at once, if limited. Those beyond wait in
+	// `deferred_queries`, in the order they were asked, until answers come in.
+	pub max_outstanding_queries: Option<usize>,
+	deferred_queries: VecDeque<NextQuery>,
+	// The most questions we had outstanding at any one time.
+	peak_outstanding_queries: usize,
+	// The signed statement of each conflict we investigate, sent along with our queries about it.
+	conflict_statements: BTreeMap<ConflictId, SignedConflictStatement>,
+	// The queries we refused to answer, and why.
+	pub rejected_queries: Vec<(VoterId, QueryRejection)>,
+	// The queries we accepted, in the order they came in.
+	pub served_queries: Vec<ServedQuery>,
+	// Our explanation of each estimate we were asked about, with the kinds of votes the verifier
+	// that asked first accepted, so that later verifiers asking the same get the same answer.
+	explanations: BTreeMap<(RoundNumber, BlockNumber), (ReplyKinds, QueryResponse)>,
+	explanation_cache: CacheStats,
+	// What we committed to for each round and history we answered about, which every later answer
+	// about the round from the history goes out with.
+	vote_commitments: BTreeMap<(RoundNumber, u32), SignedVoteCommitment>,
+	// What we did since the world last took the notes.
+	notes: Vec<String>,
+	// If set, our commits and evidence go out as gossip, which we pass on until it has made the
+	// given number of hops.
+	pub gossip_hops: Option<u32>,
+	// The gossip seen so far, by id, so that we neither act on nor pass on any of it twice.
+	seen_gossip: BTreeSet<u64>,
+	duplicates_suppressed: usize,
+	// Which requests we serve, and to whom.
+	pub policy: ServingPolicy,
+	// Stages reached by received commits, until moved to the event log of the world.
+	commit_stages: Vec<CommitProgress>,
+	// If set, the detectors of a conflict leave the queries to the one with the lowest id, and
+	// the others take over if they have no complete proof after this many ticks.
+	pub coordination_timeout: Option<usize>,
+	// The detectors that claimed each conflict, ourselves included.
+	claims: BTreeMap<ConflictId, BTreeSet<VoterId>>,
+	// The first query of each conflict we detected, held back while the primary verifier works.
+	standby: BTreeMap<ConflictId, NextQuery>,
+	// Whether the first query of the protocol also goes to the voters we saw prevote for the new
+	// block, not only to those that precommitted for it.
+	pub query_prevoters: bool,
+	// Whether we explain estimates with the fewest of our votes that show the block couldn't have
+	// been included, rather than with all of them.
+	pub minimal_answers: bool,
+	// Whether we lost our voting history, so that we can't tell the rounds we have no votes for
+	// from the rounds we weren't a member for.
+	lost_history: bool,
+	// The rounds we are still to start voting in during the run, after the latest we have votes
+	// for.
+	pub active_rounds: u64,
+	// The ticks it takes our votes to reach every voter, which the timers of our rounds are set
+	// from.
+	pub vote_delay: usize,
+	// If set, rather than sending each of our votes as we cast it, we send all the votes we have
+	// seen in our live rounds, at most once every this many ticks.
+	pub vote_batch_interval: Option<usize>,
+	// How many blocks below the precommit GHOST of our rounds we finalize, so that the tip of the
+	// chain is left for later rounds.
+	pub finalization_lag: u32,
+	live_rounds: LiveRounds,
+}
+
+// The rounds we vote in during the run: the one we are voting in, and the one before it, which can
+// still finalize a block and whose estimate our votes build on. Rounds are added to our voting
+// history once we are done with them.
+#[derive(Default)]
+struct LiveRounds {
+	current: Option<RoundState>,
+	previous: Option<RoundState>,
+	// Votes for rounds we are yet to start, counted once we do.
+	early: BTreeMap<RoundNumber, (Vec<Prevote>, Vec<Precommit>)>,
+}
+
+impl LiveRounds {
+	fn round_mut(&mut self, round: RoundNumber) -> Option<&mut RoundState> {
+		self.current
+			.iter_mut()
+			.chain(self.previous.iter_mut())
+			.find(|state| state.round().round_number == round)
+	}
+
+	fn latest_round(&self) -> Option<RoundNumber> {
+		self.current
+			.as_ref()
+			.or(self.previous.as_ref())
+			.map(|state| state.round().round_number)
+	}
+}
+
+/// How a verifier spreads a query about a round over the voters it could ask.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryPlan {
+	// Ask every voter at once.
+	#[default]
+	BroadcastAll,
+	// Ask the voter with the lowest latency first, and only ask the next nearest if there is no
+	// valid answer by the time one could have arrived.
+	NearestFirst,
 }
 
-/// If present, controls the behavior of primarily misbehaving entities
-#[derive(Copy, Clone)]
-pub enum Behaviour {
-	ReturnPrecommits,
-	ReturnPrevotes,
+/// Whether a node takes part in voting, or only follows the chain.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+	Voter,
+	// Never votes, so is never asked to explain any votes, but finalizes the blocks of the commits
+	// it receives and can run the protocol as a verifier when they conflict.
+	Observer,
 }
 
 impl Voter {
@@ -51,28 +433,107 @@ impl Voter {
 		chain: Chain,
 		voter_set: VoterSet,
 		voting_rounds: VotingRounds,
-		behaviour: Option<Behaviour>,
+		behaviour: Option<Arc<dyn Behaviour>>,
 	) -> Self {
 		Self {
-			id,
+			role: Role::Voter,
 			chain,
 			voter_set,
 			voting_rounds,
+			observers: Default::default(),
 			actions: Default::default(),
 			accountable_safety: Default::default(),
+			outcomes: Default::default(),
 			behaviour,
+			behaviour_schedule: Default::default(),
+			block_requests: Default::default(),
+			commit_requests: Default::default(),
+			pending_block_hashes: Default::default(),
+			untrusted_ancestry: Default::default(),
+			secret_key: SecretKey::random(),
+			key_registry: Default::default(),
+			proof_store: Default::default(),
+			unconfirmed_claims: Default::default(),
+			coverage: Default::default(),
+			strict: false,
+			protocol_bugs: Default::default(),
+			chain_errors: Default::default(),
+			message_budget: None,
+			received_commits: Default::default(),
+			rejected_commits: Default::default(),
+			verification_faults: None,
+			flagged_for_audit: Default::default(),
+			fabricated_evidence: Default::default(),
+			archive: None,
+			accepted_replies: Default::default(),
+			query_plan: Default::default(),
+			link_latencies: Default::default(),
+			asked: Default::default(),
+			answers: Default::default(),
+			max_outstanding_queries: None,
+			deferred_queries: Default::default(),
+			peak_outstanding_queries: 0,
+			conflict_statements: Default::default(),
+			rejected_queries: Default::default(),
+			served_queries: Default::default(),
+			explanations: Default::default(),
+			vote_commitments: Default::default(),
+			explanation_cache: Default::default(),
+			notes: Default::default(),
+			gossip_hops: None,
+			seen_gossip: Default::default(),
+			duplicates_suppressed: 0,
+			policy: Default::default(),
+			commit_stages: Default::default(),
+			coordination_timeout: None,
+			claims: Default::default(),
+			standby: Default::default(),
+			query_prevoters: false,
+			minimal_answers: false,
+			lost_history: false,
+			active_rounds: 0,
+			vote_delay: 0,
+			vote_batch_interval: None,
+			finalization_lag: 0,
+			live_rounds: Default::default(),
+			id,
+		}
+	}
+
+	/// A node following the chain of the voter set without being a member of it.
+	pub fn new_observer(id: VoterId, chain: Chain, voter_set: VoterSet) -> Self {
+		Self {
+			role: Role::Observer,
+			..Self::new(id, chain, voter_set, VotingRounds::new(), None)
 		}
 	}
 
+	pub fn is_observer(&self) -> bool {
+		self.role == Role::Observer
+	}
+
 	pub fn add_actions(&mut self, actions: Vec<(usize, Action)>) {
 		for (tick, action) in actions {
 			self.actions.push((tick, action));
 		}
 	}
 
-	pub fn list_commits(&self) {
-		for c in self.chain.commits() {
-			println!("{}", &c.1);
+	pub fn commit_report(&self) -> CommitReport {
+		let finalized = self
+			.chain
+			.commits()
+			.values()
+			.sorted_by_key(|commit| commit.target_number)
+			.map(|commit| {
+				FinalizedBlock::new(
+					commit,
+					self.chain.finalized_round(commit.target_number).cloned(),
+				)
+			})
+			.collect();
+		CommitReport {
+			voter: self.id.clone(),
+			finalized,
 		}
 	}
 
@@ -81,6 +542,8 @@ impl Voter {
 	}
 
 	pub fn process_actions(&mut self, current_tick: usize) -> Vec<Message> {
+		self.apply_behaviour_schedule(current_tick);
+
 		// Get the actions we should act on, and remove them from the queue
 		let actions = self
 			.actions
@@ -92,27 +555,45 @@ impl Voter {
 
 		let mut messages = Vec::new();
 		for (trigger_time, ref action) in actions {
-			println!("{}: acting on {:?}", self.id, action);
 			match action {
 				Action::BroadcastCommits => {
 					messages.append(&mut self.create_broadcast_commit_messages());
 				}
+				Action::SendCommit(id, round, commit) => {
+					messages.push(Message {
+						sender: self.id.clone(),
+						receiver: id.clone(),
+						content: Payload::Request(Request::HereIsCommit(*round, commit.clone())),
+					});
+				}
 				Action::SendBlock(id, block_number) => {
-					let blocks = self.chain.get_chain_of_blocks(*block_number);
-					if !blocks.is_empty() {
-						messages.push(Message {
+					match self
+						.effective_behaviour()
+						.serve_blocks(&self.chain, *block_number)
+					{
+						Ok(blocks) if !blocks.is_empty() => messages.push(Message {
 							sender: self.id.clone(),
 							receiver: id.clone(),
 							content: Payload::Request(Request::HereAreBlocks(blocks)),
-						});
-					} else {
-						println!(
-							"{}: failed to send block {} as it's not in our chain",
-							self.id, block_number
-						);
+						}),
+						Ok(_) | Err(ChainError::UnknownBlock(_)) => self.note(format!(
+							"failed to send block {} as it's not in our chain",
+							block_number
+						)),
+						Err(error) => self
+							.chain_error(format!("failed to send block {}", block_number), error),
 					}
 				}
 				Action::RequeueRequest((sender, request)) => {
+					if let Request::HereIsCommit(_round, commit) = request {
+						if self.ancestry_untrusted(commit.target_number) {
+							self.note(format!(
+								"giving up on the commit for block {}",
+								commit.target_number
+							));
+							continue;
+						}
+					}
 					let should_queue_up = match request {
 						Request::HereIsCommit(_round, commit) => {
 							self.chain.knows_about_block(commit.target_number)
@@ -126,16 +607,56 @@ impl Voter {
 							content: Payload::Request(request.clone()),
 						});
 					} else {
-						// Postpone
+						// Postpone, and in case the peer we asked isn't serving the block, ask
+						// someone else.
+						if let Request::HereIsCommit(_round, commit) = request {
+							if let Some(peer) =
+								self.next_peer_to_ask_for_block(commit.target_number)
+							{
+								self.coverage.hit(Branch::BlockRequestFallback);
+								self.actions.push((
+									trigger_time,
+									Action::RequestBlock(peer, commit.target_number),
+								));
+							}
+						}
 						self.actions.push((trigger_time + 10, action.clone()));
 					}
 				}
+				Action::RequestBlock(peer, block_number) => {
+					self.note(format!("requesting block {} from {}", block_number, peer));
+					self.block_requests
+						.entry(*block_number)
+						.or_default()
+						.push(peer.clone());
+					messages.push(Message {
+						sender: self.id.clone(),
+						receiver: peer.clone(),
+						content: Payload::Request(Request::PleaseSendBlock(*block_number)),
+					});
+				}
+				Action::RequestCommit(peer, block_number) => {
+					messages.push(Message {
+						sender: self.id.clone(),
+						receiver: peer.clone(),
+						content: Payload::Request(Request::RequestCommitFor(*block_number)),
+					});
+				}
 				Action::AskVotersAboutEstimate(query) => {
 					let Query {
 						round,
 						receivers,
-						block_not_included,
+						conflict,
+						accepted_replies,
 					} = query;
+					let statement = match self.conflict_statements.get(conflict) {
+						Some(statement) => statement.clone(),
+						None => {
+							let bug = format!("no conflict statement for {:?}", conflict);
+							self.protocol_bug(ProtocolBug(bug));
+							continue;
+						}
+					};
 					for receiver in receivers {
 						messages.push(Message {
 							sender: self.id.clone(),
@@ -143,23 +664,136 @@ impl Voter {
 							content: Payload::Request(
 								Request::WhyDidEstimateForRoundNotIncludeBlock(
 									*round,
-									*block_not_included,
+									conflict.block_not_included,
+									*accepted_replies,
+									statement.clone(),
 								),
 							),
 						});
 					}
 				}
+				Action::GossipEvidence(repeats) => {
+					self.coverage.hit(Branch::EvidenceGossip);
+					let evidence = self.evidence_bundle();
+					for receiver in self.peers().into_iter().sorted() {
+						messages.push(Message {
+							sender: self.id.clone(),
+							receiver,
+							content: Payload::Request(
+								self.gossip(Request::HereIsEvidence(evidence.clone())),
+							),
+						});
+					}
+					if *repeats > 1 {
+						self.actions.push((
+							trigger_time + EVIDENCE_GOSSIP_INTERVAL,
+							Action::GossipEvidence(repeats - 1),
+						));
+					}
+				}
+				Action::RetryEvidenceVerification(evidence, attempts) => {
+					self.check_evidence(evidence.clone(), *attempts, trigger_time);
+				}
+				Action::AskIfUnanswered(query) => {
+					let answered = self
+						.accountable_safety
+						.get(&query.conflict)
+						.is_none_or(|instance| instance.has_valid_response(query.round));
+					if !answered {
+						self.coverage.hit(Branch::QueryExpanded);
+						self.dispatch_query(NextQuery::AskAboutRound(query.clone()), trigger_time);
+					}
+				}
+				Action::ExpireResponseChunks(round, voter) => {
+					for instance in self.accountable_safety.values_mut() {
+						instance.expire_partial_response(*round, voter);
+					}
+				}
+				Action::ExpireQuery(round, kind, voter) => {
+					let key = (*round, *kind, voter.clone());
+					if self.answers.contains_key(&key) {
+						continue;
+					}
+					self.asked.remove(&key);
+					self.note(format!(
+						"{} didn't answer our query about round {} in time",
+						voter, round
+					));
+					for conflict in self.asking_instances(&key) {
+						if let Some(instance) = self.accountable_safety.get_mut(&conflict) {
+							instance.expire_query(*round, voter, *kind == QueryKind::Prevotes);
+						}
+					}
+				}
+				Action::ReleaseDeferredQueries => self.release_deferred_queries(trigger_time),
+				Action::ClaimConflict(conflict) => {
+					for receiver in self.peers() {
+						messages.push(Message {
+							sender: self.id.clone(),
+							receiver,
+							content: Payload::Request(Request::ClaimConflict(*conflict)),
+						});
+					}
+				}
+				Action::DesignateVerifier(conflict) => {
+					let primary = self.primary_verifier(*conflict);
+					if primary.as_ref() == Some(&self.id) {
+						if let Some(query) = self.standby.remove(conflict) {
+							self.dispatch_query(query, trigger_time);
+						}
+					} else if let Some(primary) = primary {
+						let timeout = self.coordination_timeout.unwrap_or_default();
+						self.stand_by(*conflict, &primary, trigger_time + timeout);
+					}
+				}
+				Action::TakeOverVerification(conflict) => {
+					if self.known_offenders().len() > self.voter_set.max_faulty() {
+						self.standby.remove(conflict);
+					} else if let Some(query) = self.standby.remove(conflict) {
+						self.note(format!(
+							"taking over verifying blocks {} and {}",
+							conflict.block_not_included, conflict.new_block
+						));
+						self.coverage.hit(Branch::VerifierTakeover);
+						self.dispatch_query(query, trigger_time);
+					}
+				}
+				Action::Vote => messages.append(&mut self.vote(trigger_time)),
+				Action::RelayGossip(from, gossip) => {
+					let relayed = gossip.relayed();
+					for receiver in self
+						.peers()
+						.into_iter()
+						.filter(|peer| peer != from && *peer != gossip.originator)
+						.sorted()
+					{
+						messages.push(Message {
+							sender: self.id.clone(),
+							receiver,
+							content: Payload::Request(Request::Gossip(relayed.clone())),
+						});
+					}
+				}
 				Action::AskVotersWhichPrevotesSeen(query) => {
+					let statement = match self.conflict_statements.get(&query.conflict) {
+						Some(statement) => statement.clone(),
+						None => {
+							let bug = format!("no conflict statement for {:?}", query.conflict);
+							self.protocol_bug(ProtocolBug(bug));
+							continue;
+						}
+					};
 					for receiver in &query.receivers {
-						println!(
-							"{}: asking {} about prevotes seen in round {}",
-							self.id, receiver, query.round,
-						);
+						self.note(format!(
+							"asking {} about prevotes seen in round {}",
+							receiver, query.round
+						));
 						messages.push(Message {
 							sender: self.id.clone(),
 							receiver: receiver.clone(),
 							content: Payload::Request(Request::WhichPrevotesSeenInRound(
 								query.round,
+								statement.clone(),
 							)),
 						});
 					}
@@ -169,21 +803,39 @@ impl Voter {
 		messages
 	}
 
+	// Everyone we send our commits and evidence to: the rest of the voter set and the observers.
+	fn peers(&self) -> Vec<VoterId> {
+		self.voter_set
+			.voter_ids()
+			.into_iter()
+			.chain(self.observers.iter().cloned())
+			.filter(|peer| *peer != self.id)
+			.collect()
+	}
+
 	fn create_broadcast_commit_messages(&mut self) -> Vec<Message> {
-		let receivers = self
-			.voter_set
-			.voters
-			.iter()
-			.filter(|voter| **voter != self.id);
-		let payloads_to_send = self.commits().values().map(|commit| {
-			let round = *self.chain.finalized_round(commit.target_number).unwrap();
-			Payload::Request(Request::HereIsCommit(round, commit.clone()))
-		});
+		let receivers = self.peers();
+		// In block order, so that runs are reproducible.
+		let requests: Vec<_> = self
+			.commits()
+			.values()
+			.sorted_by_key(|commit| commit.target_number)
+			.map(|commit| {
+				let round = *self.chain.finalized_round(commit.target_number).unwrap();
+				let hash = self.chain.block_hash(commit.target_number).unwrap();
+				Request::HereIsCommit(round, commit.clone().with_target_hash(hash))
+			})
+			.collect();
+		let payloads_to_send: Vec<_> = requests
+			.into_iter()
+			.map(|request| Payload::Request(self.gossip(request)))
+			.collect();
 		receivers
+			.into_iter()
 			.cartesian_product(payloads_to_send)
 			.map(|(receiver, payload)| Message {
 				sender: self.id.clone(),
-				receiver: receiver.to_string(),
+				receiver,
 				content: payload,
 			})
 			.collect()
@@ -194,203 +846,2289 @@ impl Voter {
 		request: (VoterId, Request),
 		current_tick: usize,
 	) -> Vec<(VoterId, Response)> {
-		println!("{}: received {:?}", self.id, request);
+		self.apply_behaviour_schedule(current_tick);
+		if let Some(refusal) = self.policy.refusal(&request.1, self.is_voter(&request.0)) {
+			self.note(format!("refusing {} to {}", refusal, request.0));
+			self.coverage.hit(Branch::PolicyRefusal);
+			return vec![(request.0, Response::PolicyRefusal(refusal))];
+		}
 		match request.1 {
 			Request::HereIsCommit(round_number, ref commit) => {
-				// Ignore commits we already know about
-				if let Some(chain_commit) = self.chain.commit_for_block(commit.target_number) {
-					assert_eq!(commit, chain_commit);
-					return Default::default();
-				}
-
-				// Requeue request for later if we don't yet know about the block, which we send out
-				// a request for.
-				if !self.chain.knows_about_block(commit.target_number) {
-					self.actions
-						.push((current_tick + 10, Action::RequeueRequest(request.clone())));
-					println!("{}: requesting block {}", self.id, commit.target_number);
-					return vec![(request.0, Response::RequestBlock(commit.target_number))];
-				}
-
-				// Find if any of our already known commits are conflicting with this new commit.
-				let conflicting_commits: Vec<_> = self
-					.chain
-					.commits()
-					.values()
-					.filter(|previous_commit| {
-						!self
-							.chain
-							.is_descendent(commit.target_number, previous_commit.target_number)
-					})
-					.collect();
-
-				// For each of these mutually conflicting commits we start up the accountable safety
-				// protocol
-				for previous_commit in conflicting_commits {
-					println!(
-						"{}: received commit is not descendent of {}, \
-						triggering accountable safety protocol!",
-						self.id, previous_commit,
-					);
-					// Setup and start accountable safety protocol instance
-					let block_not_included = previous_commit.target_number;
-					let round_for_block_not_included =
-						self.chain.finalized_round(block_not_included).unwrap();
-					let commit_for_block_not_included = previous_commit;
-
-					let mut accountable_safety_instance = AccountableSafety::start(
-						block_not_included,
-						*round_for_block_not_included,
-						commit_for_block_not_included.clone(),
-					);
-
-					// Create the first query
-					let voters_in_precommit = commit
-						.precommits
-						.iter()
-						.map(|pc| pc.id.to_string())
-						.collect::<Vec<VoterId>>();
-					let round_for_new_block = round_number;
-					let query = accountable_safety_instance
-						.start_query_round(round_for_new_block, voters_in_precommit);
-					self.actions
-						.push((current_tick + 10, Action::AskVotersAboutEstimate(query)));
-
-					self.accountable_safety.push(accountable_safety_instance);
-				}
+				return self.handle_commit(&request, round_number, commit, current_tick);
 			}
 			Request::HereAreBlocks(blocks) => {
+				// Rather than building our chain on blocks we can't trust, we leave the commit for
+				// them waiting for someone else to serve them.
+				if let Err(reason) = self.check_ancestry(&blocks) {
+					self.note(format!(
+						"not trusting blocks from {}: {}",
+						request.0, reason
+					));
+					self.coverage.hit(Branch::UntrustedAncestry);
+					if let Some(tip) = blocks.last() {
+						self.distrust_ancestry(tip.number, request.0);
+					}
+					return Default::default();
+				}
 				for block in blocks {
-					if let Some(chain_block) = self.chain.get_block(block.number) {
-						assert_eq!(&block, chain_block);
-					} else {
-						println!("{}: adding block {}", self.id, block);
-						self.chain.add_block(block);
+					if !self.chain.knows_about_block(block.number) {
+						self.note(format!("adding block {}", block));
+						self.coverage.hit(Branch::BlockSync);
+						self.pending_block_hashes.remove(&block.number);
+						let number = block.number;
+						if let Err(error) = self.chain.try_add_block(block) {
+							let context = format!("adding block {} from {}", number, request.0);
+							self.chain_error(context, error);
+						}
 					}
 				}
+				self.recheck_unconfirmed_claims(current_tick);
+			}
+			Request::WhyDidEstimateForRoundNotIncludeBlock(..)
+			| Request::WhichPrevotesSeenInRound(..)
+				if self.is_observer() =>
+			{
+				// We have no votes to explain, and verifiers know better than to ask us.
+				self.note(format!(
+					"ignoring query from {}, not being a voter",
+					request.0
+				));
+				self.coverage.hit(Branch::QueryIgnored);
 			}
-			Request::WhyDidEstimateForRoundNotIncludeBlock(round, block_not_included) => {
+			Request::WhyDidEstimateForRoundNotIncludeBlock(..)
+			| Request::WhichPrevotesSeenInRound(..)
+				if self.ignores_queries_from(&request.0) =>
+			{
+				self.note(format!("ignoring query from {}", request.0));
+				self.coverage.hit(Branch::QueryIgnored);
+			}
+			// Before revealing any votes, make sure the verifier has a conflict to investigate.
+			Request::WhyDidEstimateForRoundNotIncludeBlock(round, block, _, ref statement)
+				if self.rejects_query(&request.0, statement, round, Some(block)) => {}
+			Request::WhichPrevotesSeenInRound(round, ref statement)
+				if self.rejects_query(&request.0, statement, round, None) => {}
+			Request::WhyDidEstimateForRoundNotIncludeBlock(
+				round,
+				block_not_included,
+				accepted_replies,
+				_,
+			) => {
+				if let Some(response) =
+					self.cached_explanation(round, block_not_included, accepted_replies)
+				{
+					return self.explain_estimate(request.0, round, block_not_included, response);
+				}
+				self.explanation_cache.misses += 1;
+
 				// This is a container of voting rounds, since some voters might have equivocated
 				// and have multiple parallel sets of histories that it presents to different
 				// voters.
-				let voting_rounds_for_previous_block =
-					self.voting_rounds.get(&(round - 1)).unwrap();
-
-				let response = match self.behaviour {
-					// Returning commits is also the default behaviour.
-					Some(Behaviour::ReturnPrecommits) | None => {
-						// Now if this is a equivocating voter, they will want to return the set of
-						// commits corresponding to the valid round.
-						//
-						// A simple way to make this choice is by checking which of the sets of
-						// precommits are considered valid
-						let potential_query_responses =
-							voting_rounds_for_previous_block.iter().map(|voting_round| {
-								QueryResponse::Precommits(voting_round.precommits.clone())
-							});
-						self.select_valid_query_response(
-							potential_query_responses,
-							block_not_included,
-						)
+				let voting_rounds_for_previous_block = match self.voting_rounds_for(round - 1) {
+					Some(voting_rounds) => voting_rounds,
+					// We only have history for the rounds we voted in, unless we lost it.
+					None if self.lost_history => {
+						self.note(format!("lost our votes for round {}", round - 1));
+						return vec![(request.0, Response::DontKnowRound(round))];
 					}
-					Some(Behaviour::ReturnPrevotes) => {
-						let potential_query_responses =
-							voting_rounds_for_previous_block.iter().map(|voting_round| {
-								QueryResponse::Prevotes(voting_round.prevotes.clone())
-							});
-						self.select_valid_query_response(
-							potential_query_responses,
-							block_not_included,
-						)
+					None => return vec![(request.0, Response::NotAMemberAtRound(round))],
+				};
+
+				let voters = voting_rounds_for_previous_block
+					.first()
+					.map_or_else(Vec::new, |voting_round| voting_round.voter_set.voter_ids());
+				let earlier_histories = round
+					.checked_sub(2)
+					.and_then(|round| self.voting_rounds_for(round));
+				let question = EstimateQuestion {
+					verifier: &request.0,
+					round,
+					block_not_included,
+					accepted_replies,
+					histories: &voting_rounds_for_previous_block,
+					earlier_histories: earlier_histories.as_deref(),
+					voters: &voters,
+					chain: &self.chain,
+				};
+				let response = self.effective_behaviour().answer_estimate_query(&question);
+				if let Some(response) = &response {
+					self.note_fabricated_votes(response, &voting_rounds_for_previous_block);
+				}
+				let response = match response {
+					// Where no fewer votes show as much, or we can't tell, the whole answer is given.
+					Some(response) if self.minimal_answers => {
+						minimal_query_reply(&response, block_not_included, &voters, &self.chain)
+							.ok()
+							.flatten()
+							.or(Some(response))
 					}
+					response => response,
 				};
-				return vec![(request.0, Response::ExplainEstimate(round, response))];
-			}
-			Request::WhichPrevotesSeenInRound(round) => {
-				let voting_rounds = self.voting_rounds.get(&round).unwrap();
-				if voting_rounds.len() > 1 {
-					// We have two rounds that we voted in here, so lets keep quiet
-					// WIP: consider alternative ways to answer
-					return Vec::new();
+				match response {
+					Some(response) => {
+						self.explanations.insert(
+							(round, block_not_included),
+							(accepted_replies, response.clone()),
+						);
+						return self.explain_estimate(
+							request.0,
+							round,
+							block_not_included,
+							response,
+						);
+					}
+					// Only a voter whose histories include the fork can explain it, but the verifier
+					// may ask us without knowing which fork we were on.
+					None => {
+						self.note(format!(
+							"no valid explanation for round {} not including block {}, not answering",
+							round, block_not_included
+						));
+						self.coverage.hit(Branch::QueryIgnored);
+					}
+				}
+			}
+			Request::PleaseSendBlock(block_number) => {
+				if self.effective_behaviour().withholds_blocks() {
+					self.note(format!("withholding block {}", block_number));
+					self.coverage.hit(Branch::BlocksWithheld);
 				} else {
-					let prevotes = voting_rounds.iter().next().unwrap().prevotes.clone();
+					self.actions.push((
+						current_tick + 10,
+						Action::SendBlock(request.0, block_number),
+					));
+				}
+			}
+			Request::RequestCommitFor(block_number) => {
+				let blocks = match self.chain.try_get_chain_of_blocks(block_number) {
+					Ok(blocks) => blocks,
+					Err(ChainError::UnknownBlock(_)) => Vec::new(),
+					Err(error) => {
+						let context = format!("finding the commit for block {}", block_number);
+						self.chain_error(context, error);
+						return Default::default();
+					}
+				};
+				let finalized = blocks.iter().rev().find_map(|block| {
+					let round = self.chain.finalized_round(block.number)?;
+					Some((*round, self.chain.commit_for_block(block.number)?.clone()))
+				});
+				match finalized {
+					Some((round, commit)) => self.actions.push((
+						current_tick + 10,
+						Action::SendCommit(request.0, round, commit),
+					)),
+					None => self.note(format!(
+						"no commit to send for block {} as it's not in our chain",
+						block_number
+					)),
+				}
+			}
+			Request::HereIsEvidence(evidence) => {
+				self.check_evidence(evidence, 0, current_tick);
+			}
+			Request::ClaimConflict(conflict) => {
+				self.claims.entry(conflict).or_default().insert(request.0);
+			}
+			Request::Gossip(gossip) => {
+				if !self.seen_gossip.insert(gossip.id()) {
+					self.coverage.hit(Branch::DuplicateSuppressed);
+					self.duplicates_suppressed += 1;
+					return Default::default();
+				}
+				if self
+					.gossip_hops
+					.is_some_and(|max_hops| gossip.hops < max_hops)
+				{
+					self.actions.push((
+						current_tick,
+						Action::RelayGossip(request.0.clone(), gossip.clone()),
+					));
+				}
+				return self.handle_request((request.0, *gossip.payload), current_tick);
+			}
+			Request::Prevote(prevote) => {
+				if self.accepts_vote(&request.0, &prevote.id, prevote.set_id) {
+					let chain = &self.chain;
+					match self.live_rounds.round_mut(prevote.round) {
+						Some(state) => state.add_prevote(prevote, chain),
+						None => self
+							.hold_early_vote(prevote.round, |(prevotes, _)| prevotes.push(prevote)),
+					}
+				}
+			}
+			Request::Precommit(precommit) => {
+				if self.accepts_vote(&request.0, &precommit.id, precommit.set_id) {
+					let chain = &self.chain;
+					match self.live_rounds.round_mut(precommit.round) {
+						Some(state) => state.add_precommit(precommit, chain),
+						None => self.hold_early_vote(precommit.round, |(_, precommits)| {
+							precommits.push(precommit)
+						}),
+					}
+				}
+			}
+			Request::RoundVotes(batches) => {
+				for batch in batches {
+					self.receive_batch(&request.0, batch);
+				}
+			}
+			Request::WhichPrevotesSeenInRound(round, _) => match self.voting_rounds_for(round) {
+				None if self.lost_history => {
+					self.note(format!("lost the prevotes we saw in round {}", round));
+				}
+				// Verifiers only ask those whose votes they saw, but nothing stops others asking.
+				None => {
+					self.note(format!(
+						"ignoring query about prevotes in round {}, which we have no votes for",
+						round
+					));
+					self.coverage.hit(Branch::QueryIgnored);
+				}
+				Some(voting_rounds) => {
+					let selected = self.select_prevote_history(round, &voting_rounds);
+					let question = PrevoteQuestion {
+						verifier: &request.0,
+						round,
+						histories: &voting_rounds,
+						selected,
+					};
+					let response = self.effective_behaviour().answer_prevotes_query(&question);
+					self.record_answer(&response);
+					let commitment = self.commit_to_votes(round, &response);
 					return vec![(
 						request.0,
-						Response::PrevotesSeen(round, QueryResponse::Prevotes(prevotes)),
+						Response::PrevotesSeen(round, response, commitment),
 					)];
 				}
-			}
+			},
 		}
 		Default::default()
 	}
 
-	fn select_valid_query_response(
-		&self,
-		potential_query_responses: impl Iterator<Item = QueryResponse>,
-		block_not_included: BlockNumber,
-	) -> QueryResponse {
-		let valid_voting_round: Vec<_> = potential_query_responses
-			.filter(|response| {
-				check_query_reply_is_valid(
-					response,
-					block_not_included,
-					&self.voter_set.voter_ids(),
-					&self.chain,
-				)
-				.is_none()
-			})
-			.collect();
-
-		assert_eq!(valid_voting_round.len(), 1);
-		valid_voting_round.into_iter().next().unwrap()
+	/// Vote in the given number of rounds during the run, after the latest we have votes for, with
+	/// the timers of each round set for votes taking `delay` ticks to reach every voter. Only
+	/// members of the voter set vote.
+	pub fn vote_in_rounds(&mut self, rounds: u64, delay: usize) {
+		self.active_rounds = rounds;
+		self.vote_delay = delay;
+		if rounds > 0 && self.voter_set.is_member(&self.id) {
+			self.actions.push((0, Action::Vote));
+		}
 	}
 
-	pub fn handle_response(&mut self, response: (VoterId, Response), current_tick: usize) {
-		println!("{}: received {:?}", self.id, response);
-		match response.1 {
-			Response::RequestBlock(block_number) => {
-				self.actions.push((
-					current_tick + 10,
-					Action::SendBlock(response.0, block_number),
+	// Take the next step in the rounds we vote in: cast our votes once the timers of the round or
+	// the votes seen let us, finalize the blocks the rounds allow, and move on to the next round
+	// once the current one is completable.
+	fn vote(&mut self, now: usize) -> Vec<Message> {
+		let id = match self.voter_set.lookup(&self.id) {
+			Some(id) => id,
+			None => return Vec::new(),
+		};
+		if self.live_rounds.current.is_none() {
+			self.start_round(id, now);
+		}
+		let last_finalized = self.last_finalized();
+		let chain = &self.chain;
+		let live = &mut self.live_rounds;
+		let current = match live.current.as_mut() {
+			Some(current) => current,
+			None => return Vec::new(),
+		};
+		// Before our first round, the last block we finalized stands in for the estimate of the
+		// round before.
+		let (previous_estimate, previous_completable) = match &live.previous {
+			Some(previous) => (previous.estimate(chain), previous.is_completable(chain)),
+			None => (Some(last_finalized), true),
+		};
+		let mut votes = Vec::new();
+		if current.should_prevote(now, previous_completable) {
+			// The best chain that includes the estimate of the round before.
+			let base = previous_estimate.unwrap_or(last_finalized);
+			let head = chain.best_head();
+			let target = match chain.try_block_includes(head, base) {
+				Ok(true) => head,
+				_ => base,
+			};
+			votes.push(Request::Prevote(current.prevote(target, chain)));
+		}
+		if let Some(previous_estimate) = previous_estimate {
+			if current.should_precommit(now, previous_estimate, chain) {
+				votes.extend(current.precommit(chain).map(Request::Precommit));
+			}
+		}
+		let completable = current.stage() >= RoundStage::Completable;
+		// The last round is kept until it finalizes, or until late votes had time to arrive.
+		let last_done = now >= current.timers().precommit_at + self.vote_delay
+			|| current.stage() == RoundStage::Finalized;
+		// Our votes go out with the batches of the rounds they were cast in, which carry the
+		// rounds as they were before we move on from them.
+		if self.vote_batch_interval.is_some() {
+			let live = &mut self.live_rounds;
+			let batches: Vec<_> = live
+				.previous
+				.iter_mut()
+				.chain(live.current.iter_mut())
+				.filter_map(|state| state.take_batch(now))
+				.collect();
+			votes.clear();
+			if !batches.is_empty() {
+				votes.push(Request::RoundVotes(batches));
+			}
+		}
+		self.finalize_live_rounds();
+
+		if completable && (self.active_rounds > 0 || last_done) {
+			if let Some(previous) = self.live_rounds.previous.take() {
+				self.voting_rounds.add(previous.round().clone());
+			}
+			self.live_rounds.previous = self.live_rounds.current.take();
+			if self.active_rounds == 0 {
+				let last = self.live_rounds.previous.take().unwrap();
+				self.note(format!(
+					"done voting after round {}",
+					last.round().round_number
 				));
+				self.voting_rounds.add(last.round().clone());
+				self.live_rounds.early.clear();
 			}
-			Response::ExplainEstimate(round_number, query_response) => {
-				// WIP: assume a single instance
-				let next_query = self
-					.accountable_safety
-					.iter_mut()
-					.next()
-					.unwrap()
-					.add_response(round_number, response.0, query_response, &self.chain);
-
-				let next_action = next_query.map(|next_query| match next_query {
-					NextQuery::AskAboutRound(next_query) => {
-						Action::AskVotersAboutEstimate(next_query)
-					}
-					NextQuery::PrevotesForRound(next_query) => {
-						Action::AskVotersWhichPrevotesSeen(next_query)
-					}
+		}
+		if self.live_rounds.latest_round().is_some() {
+			self.actions.push((now + 1, Action::Vote));
+		}
+
+		let receivers = self
+			.voter_set
+			.voter_ids()
+			.into_iter()
+			.filter(|receiver| *receiver != self.id)
+			.collect::<Vec<_>>();
+		let mut messages = Vec::new();
+		for vote in votes {
+			for receiver in &receivers {
+				messages.push(Message {
+					sender: self.id.clone(),
+					receiver: receiver.clone(),
+					content: Payload::Request(vote.clone()),
 				});
-				if let Some(next_action) = next_action {
-					self.actions.push((current_tick + 10, next_action));
-				}
-			}
-			Response::PrevotesSeen(round_number, query_response) => {
-				// WIP: assume a single instance
-				self.accountable_safety
-					.iter_mut()
-					.next()
-					.unwrap()
-					.add_prevote_response(round_number, response.0, query_response);
 			}
 		}
+		messages
+	}
+
+	// Start the round after the latest we voted in or have votes for, if we are to vote in any more.
+	fn start_round(&mut self, id: VoterName, now: usize) {
+		if self.active_rounds == 0 {
+			return;
+		}
+		self.active_rounds -= 1;
+		let round_number = self
+			.live_rounds
+			.latest_round()
+			.or_else(|| self.voting_rounds.latest_round())
+			.map_or(1, |round| round + 1);
+		let round = VotingRound::new(round_number, self.voter_set.clone());
+		let mut state = RoundState::new(id, round, RoundTimers::new(now, self.vote_delay))
+			.with_finalization_lag(self.finalization_lag);
+		if let Some(interval) = self.vote_batch_interval {
+			state = state.with_batch_interval(interval);
+		}
+		let (prevotes, precommits) = self
+			.live_rounds
+			.early
+			.remove(&round_number)
+			.unwrap_or_default();
+		for prevote in prevotes {
+			state.add_prevote(prevote, &self.chain);
+		}
+		for precommit in precommits {
+			state.add_precommit(precommit, &self.chain);
+		}
+		self.note(format!("starting round {}", round_number));
+		self.live_rounds.current = Some(state);
 	}
 
-	pub fn equivocations_detected(&self) -> Vec<EquivocationDetected> {
-		self.accountable_safety
+	// Finalize the blocks our live rounds finalized that we haven't yet, with the precommits for
+	// them we saw.
+	fn finalize_live_rounds(&mut self) {
+		let chain = &self.chain;
+		let finalized: Vec<_> = self
+			.live_rounds
+			.current
 			.iter()
-			.flat_map(|acc_safety| acc_safety.equivocations_detected())
-			.collect()
+			.chain(self.live_rounds.previous.iter())
+			.filter(|state| state.stage() == RoundStage::Finalized)
+			.filter_map(|state| {
+				let round = state.round();
+				let block = round.finalized?;
+				let precommits = round
+					.precommits
+					.iter()
+					.filter(|precommit| {
+						matches!(
+							chain.try_block_includes(precommit.target_number, block),
+							Ok(true)
+						)
+					})
+					.cloned()
+					.collect();
+				(chain.commit_for_block(block).is_none())
+					.then(|| (round.round_number, Commit::new(block, precommits)))
+			})
+			.collect();
+		for (round, commit) in finalized {
+			let block = commit.target_number;
+			match self.chain.try_finalize_block(block, round, commit) {
+				Ok(_) => self.note(format!("finalized block {} in round {}", block, round)),
+				Err(error) => self.note(format!(
+					"failed to finalize block {} in round {}: {}",
+					block, round, error
+				)),
+			}
+		}
+	}
+
+	// The highest block we finalized, or genesis.
+	fn last_finalized(&self) -> BlockNumber {
+		self.chain
+			.commits()
+			.keys()
+			.copied()
+			.max_by_key(|block| self.chain.try_block_height(*block).unwrap_or_default())
+			.unwrap_or_default()
+	}
+
+	// Votes are counted from members of our voter set only, and only as sent by the voter that
+	// cast them, since votes aren't passed on.
+	fn accepts_vote(&mut self, sender: &VoterId, voter: &VoterName, set_id: SetId) -> bool {
+		let accepted = sender == voter.as_str()
+			&& set_id == self.voter_set.set_id
+			&& self.voter_set.is_member(voter.as_str());
+		if !accepted {
+			self.note(format!("ignoring vote of {} sent by {}", voter, sender));
+		}
+		accepted
+	}
+
+	// Merge the votes of members in a batch from another member into the round it is for. Batches
+	// carry votes seen from anyone, so unlike single votes they aren't held to their sender.
+	fn receive_batch(&mut self, sender: &VoterId, mut batch: VoteBatch) {
+		if !self.voter_set.is_member(sender) || batch.set_id != self.voter_set.set_id {
+			self.note(format!(
+				"ignoring votes of round {} sent by {}",
+				batch.round, sender
+			));
+			return;
+		}
+		let voter_set = &self.voter_set;
+		batch
+			.prevotes
+			.retain(|prevote| voter_set.is_member(prevote.id.as_str()));
+		batch
+			.precommits
+			.retain(|precommit| voter_set.is_member(precommit.id.as_str()));
+		let chain = &self.chain;
+		match self.live_rounds.round_mut(batch.round) {
+			Some(state) => {
+				state.merge_batch(&batch, chain);
+			}
+			None => self.hold_early_vote(batch.round, |(prevotes, precommits)| {
+				prevotes.extend(batch.prevotes);
+				precommits.extend(batch.precommits);
+			}),
+		}
+	}
+
+	// Hold on to a vote for a round we are yet to start, while we still have rounds to vote in.
+	// Votes for rounds we are done with are dropped.
+	fn hold_early_vote(
+		&mut self,
+		round: RoundNumber,
+		hold: impl FnOnce(&mut (Vec<Prevote>, Vec<Precommit>)),
+	) {
+		let started = self.live_rounds.latest_round();
+		let ahead = started.map_or(self.active_rounds > 0, |started| round > started);
+		if ahead && self.active_rounds > 0 {
+			hold(self.live_rounds.early.entry(round).or_default());
+		} else {
+			self.note(format!(
+				"ignoring vote for round {}, which we aren't voting in",
+				round
+			));
+		}
+	}
+
+	// Received commits go through a pipeline: the target block is synced, the commit validated and
+	// checked for conflicts with the commits we know, and only then are protocol instances started
+	// on it. Each stage reached is recorded.
+	fn handle_commit(
+		&mut self,
+		request: &(VoterId, Request),
+		round: RoundNumber,
+		commit: &Commit,
+		current_tick: usize,
+	) -> Vec<(VoterId, Response)> {
+		let sender = &request.0;
+		if !self.effective_behaviour().on_commit(sender, round, commit) {
+			self.note(format!(
+				"ignoring the commit for block {} from {}",
+				commit.target_number, sender
+			));
+			return Default::default();
+		}
+		if let Some(mismatch) = self.check_commit_target(round, commit, sender) {
+			self.note(format!("rejecting {}", mismatch));
+			self.coverage.hit(Branch::CommitTargetMismatch);
+			self.rejected_commits.push(mismatch);
+			return Default::default();
+		}
+
+		self.record_commit_variant(round, commit, sender);
+		self.chain
+			.record_commit_receipt(commit.target_number, sender, current_tick);
+
+		// Ignore commits we already know about. If it differs from ours it's kept as a variant
+		// above.
+		if self.chain.commit_for_block(commit.target_number).is_some() {
+			return Default::default();
+		}
+
+		if let Some(responses) = self.sync_commit_target(request, commit, current_tick) {
+			self.commit_stage(sender, round, commit, CommitStage::AwaitingBlock);
+			return responses;
+		}
+		self.commit_stage(sender, round, commit, CommitStage::Synced);
+
+		if let Err(reason) = self.validate_commit(round, commit) {
+			self.note(format!(
+				"rejecting commit for block {} from {}: {}",
+				commit.target_number, sender, reason
+			));
+			self.coverage.hit(Branch::InvalidCommit);
+			self.commit_stage(sender, round, commit, CommitStage::Invalid(reason));
+			return Default::default();
+		}
+		self.commit_stage(sender, round, commit, CommitStage::Validated);
+
+		let conflicting_commits = self.conflicting_commits(commit);
+		if conflicting_commits.is_empty() {
+			self.commit_stage(sender, round, commit, CommitStage::NoConflict);
+		}
+		for previous_commit in &conflicting_commits {
+			let stage = CommitStage::Conflicting(previous_commit.target_number);
+			self.commit_stage(sender, round, commit, stage);
+		}
+
+		// For each of these mutually conflicting commits we start up the accountable safety
+		// protocol
+		let has_conflicts = !conflicting_commits.is_empty();
+		for previous_commit in conflicting_commits {
+			if let Some(conflict) =
+				self.start_instance(round, commit, previous_commit, current_tick)
+			{
+				self.commit_stage(
+					sender,
+					round,
+					commit,
+					CommitStage::InstanceStarted(conflict),
+				);
+			}
+		}
+
+		// Observers have no votes of their own to finalize blocks with, so they go by the commits
+		// they receive. So do voters catching up on rounds they didn't vote in, as long as the
+		// commit doesn't conflict with what they know to be finalized, and since they might have
+		// missed commits on other forks too, they ask around for those.
+		let catching_up = !has_conflicts && self.catching_up(round);
+		if self.is_observer() || catching_up {
+			self.note(format!("finalizing block {}", commit.target_number));
+			if let Err(error) =
+				self.chain
+					.try_finalize_block(commit.target_number, round, commit.clone())
+			{
+				let context = format!("finalizing block {} from {}", commit.target_number, sender);
+				self.chain_error(context, error);
+			}
+		}
+		if catching_up {
+			self.request_conflicting_commits(commit.target_number, sender, current_tick);
+		}
+		Default::default()
+	}
+
+	fn commit_stage(
+		&mut self,
+		sender: &VoterId,
+		round: RoundNumber,
+		commit: &Commit,
+		stage: CommitStage,
+	) {
+		self.commit_stages.push(CommitProgress {
+			sender: sender.clone(),
+			round,
+			block: commit.target_number,
+			stage,
+		});
+	}
+
+	/// Take the stages reached by received commits since they were last taken.
+	pub fn take_commit_stages(&mut self) -> Vec<CommitProgress> {
+		std::mem::take(&mut self.commit_stages)
+	}
+
+	// If we don't yet know about the target block, request it and requeue the commit for later.
+	// Returns what to answer the sender with in that case.
+	fn sync_commit_target(
+		&mut self,
+		request: &(VoterId, Request),
+		commit: &Commit,
+		current_tick: usize,
+	) -> Option<Vec<(VoterId, Response)>> {
+		if self.chain.knows_about_block(commit.target_number) {
+			return None;
+		}
+		if self.ancestry_untrusted(commit.target_number) {
+			return Some(Default::default());
+		}
+		if let Some(hash) = commit.target_hash {
+			self.pending_block_hashes.insert(commit.target_number, hash);
+		}
+		self.coverage.hit(Branch::RequeueRequest);
+		self.actions
+			.push((current_tick + 10, Action::RequeueRequest(request.clone())));
+		self.note(format!("requesting block {}", commit.target_number));
+		self.block_requests
+			.entry(commit.target_number)
+			.or_default()
+			.push(request.0.clone());
+		Some(vec![(
+			request.0.clone(),
+			Response::RequestBlock(commit.target_number),
+		)])
+	}
+
+	// A commit holds up if its precommits are from its round and voter set, signed by a
+	// supermajority, and for blocks including the target. Precommits for blocks we don't know
+	// can't be checked, and are taken as they are.
+	fn validate_commit(&self, round: RoundNumber, commit: &Commit) -> Result<(), InvalidCommit> {
+		if commit
+			.precommits
+			.iter()
+			.any(|precommit| precommit.round != round || precommit.set_id != commit.set_id)
+		{
+			return Err(InvalidCommit::ForeignPrecommits);
+		}
+		if !self.commit_is_valid(round, commit) {
+			return Err(InvalidCommit::NoSupermajority);
+		}
+		let off_target = commit.precommits.iter().find(|precommit| {
+			matches!(
+				self.chain
+					.try_block_includes(precommit.target_number, commit.target_number),
+				Ok(false)
+			)
+		});
+		match off_target {
+			Some(precommit) => Err(InvalidCommit::PrecommitOffTarget(precommit.target_number)),
+			None => Ok(()),
+		}
+	}
+
+	// The commits we know for blocks on other branches than the target of the commit.
+	fn conflicting_commits(&mut self, commit: &Commit) -> Vec<Commit> {
+		let mut conflicting_commits = Vec::new();
+		let mut errors = Vec::new();
+		for previous_commit in self.chain.commits().values() {
+			match self
+				.chain
+				.try_blocks_conflict(commit.target_number, previous_commit.target_number)
+			{
+				Ok(true) => conflicting_commits.push(previous_commit.clone()),
+				Ok(false) => {}
+				Err(error) => errors.push(error),
+			}
+		}
+		for error in errors {
+			let context = format!("checking the commit for {}", commit.target_number);
+			self.chain_error(context, error);
+		}
+		conflicting_commits
+	}
+
+	// Start a protocol instance on the conflict between a received commit and one we know, unless
+	// we are already investigating it. Returns the conflict if an instance was started.
+	fn start_instance(
+		&mut self,
+		round_number: RoundNumber,
+		commit: &Commit,
+		previous_commit: Commit,
+		current_tick: usize,
+	) -> Option<ConflictId> {
+		// The protocol walks back from the later of the two rounds, so the block finalized first
+		// is the one that wasn't included.
+		let previous_round = *self
+			.chain
+			.finalized_round(previous_commit.target_number)
+			.unwrap();
+		let (
+			(commit_for_block_not_included, round_for_block_not_included),
+			(new_commit, round_for_new_block),
+		) = if previous_round <= round_number {
+			((&previous_commit, previous_round), (commit, round_number))
+		} else {
+			((commit, round_number), (&previous_commit, previous_round))
+		};
+		let block_not_included = commit_for_block_not_included.target_number;
+		let conflict = ConflictId::new(block_not_included, new_commit.target_number);
+
+		// Another variant of a commit we are already investigating. Commits finalized in the same
+		// round can come in either order.
+		if self.accountable_safety.contains_key(&conflict)
+			|| self.accountable_safety.contains_key(&conflict.reversed())
+		{
+			return None;
+		}
+		self.note(format!(
+			"received commit is not descendent of {}, triggering accountable safety protocol!",
+			previous_commit
+		));
+		// Setup and start accountable safety protocol instance.
+
+		let mut accountable_safety_instance = match AccountableSafety::start(
+			block_not_included,
+			round_for_block_not_included,
+			commit_for_block_not_included.clone(),
+			new_commit.target_number,
+			round_for_new_block,
+			&self.chain,
+		) {
+			Ok(instance) => instance
+				.with_sessions(self.sessions())
+				.with_voter_sets(self.known_voter_sets())
+				.with_accepted_replies(self.accepted_replies)
+				.with_message_budget(self.message_budget)
+				.with_commit_for_new_block(new_commit),
+			Err(outcome) => {
+				self.note(format!("not starting protocol: {:?}", outcome));
+				if let Outcome::NoConflict { .. } = outcome {
+					self.coverage.hit(Branch::NoConflict);
+				}
+				self.outcomes.push(outcome);
+				return None;
+			}
+		};
+
+		self.conflict_statements.insert(
+			conflict,
+			ConflictStatement {
+				detector: self.id.clone(),
+				round_for_block_not_included,
+				commit_for_block_not_included: commit_for_block_not_included.clone(),
+				round_for_new_block,
+				commit_for_new_block: new_commit.clone(),
+			}
+			.sign(&self.secret_key),
+		);
+
+		// Skip the queries if the commits are already proof enough.
+		if accountable_safety_instance.check_common_signers(new_commit, self.voter_set.max_faulty())
+		{
+			self.note(format!(
+				"commits for {} and {} share enough signers, no queries needed",
+				block_not_included, new_commit.target_number
+			));
+			self.coverage.hit(Branch::ProtocolStarted);
+			self.accountable_safety
+				.insert(conflict, accountable_safety_instance);
+			self.schedule_evidence_gossip(current_tick);
+			return Some(conflict);
+		}
+
+		// Create the first query
+		let mut voters_in_precommit = new_commit
+			.precommits
+			.iter()
+			.map(|pc| pc.id.to_string())
+			.filter(|voter| !self.observers.contains(voter))
+			.collect::<Vec<VoterId>>();
+		if self.query_prevoters {
+			let prevoters = self.prevoters_for(round_for_new_block, new_commit.target_number);
+			let extra: Vec<_> = prevoters
+				.into_iter()
+				.filter(|voter| !voters_in_precommit.contains(voter))
+				.collect();
+			if !extra.is_empty() {
+				self.note(format!(
+					"also asking {} who prevoted for {} in round {}",
+					extra.join(", "),
+					new_commit.target_number,
+					round_for_new_block
+				));
+				self.coverage.hit(Branch::PrevoterQuery);
+				voters_in_precommit.extend(extra);
+			}
+		}
+		let query =
+			accountable_safety_instance.start_query_round(round_for_new_block, voters_in_precommit);
+
+		self.coverage.hit(Branch::ProtocolStarted);
+		self.accountable_safety
+			.insert(conflict, accountable_safety_instance);
+		if let Some(timeout) = self.coordination_timeout {
+			self.standby
+				.insert(conflict, NextQuery::AskAboutRound(query));
+			// Someone who claimed the conflict before we detected it is already past choosing the
+			// primary verifier, so we leave it to them.
+			if let Some(primary) = self.primary_verifier(conflict) {
+				self.stand_by(conflict, &primary, current_tick + timeout);
+				return Some(conflict);
+			}
+			self.claims
+				.entry(conflict)
+				.or_default()
+				.insert(self.id.clone());
+			self.actions
+				.push((current_tick, Action::ClaimConflict(conflict)));
+			self.actions.push((
+				current_tick + CLAIM_WINDOW,
+				Action::DesignateVerifier(conflict),
+			));
+		} else {
+			self.dispatch_query(NextQuery::AskAboutRound(query), current_tick);
+		}
+		Some(conflict)
+	}
+
+	// The voters we saw prevote for the block, or for a block built on it, in the round.
+	fn prevoters_for(&self, round: RoundNumber, block: BlockNumber) -> BTreeSet<VoterId> {
+		self.voting_rounds
+			.get(&round)
+			.into_iter()
+			.flatten()
+			.flat_map(|voting_round| voting_round.prevotes)
+			.filter(|prevote| {
+				self.chain
+					.try_block_includes(prevote.target_number, block)
+					.unwrap_or(false)
+			})
+			.map(|prevote| prevote.id.to_string())
+			.filter(|voter| !self.observers.contains(voter))
+			.collect()
+	}
+
+	// The request as we send it to our peers: as gossip, if we gossip, or as is.
+	fn gossip(&mut self, request: Request) -> Request {
+		if self.gossip_hops.is_none() {
+			return request;
+		}
+		let gossip = Gossip::new(self.id.clone(), request);
+		self.seen_gossip.insert(gossip.id());
+		Request::Gossip(gossip)
+	}
+
+	// The voter sets of the rounds we took part in or observed, and our own.
+	fn known_voter_sets(&self) -> BTreeMap<SetId, VoterSet> {
+		self.voting_rounds
+			.iter()
+			.map(|voting_round| voting_round.voter_set)
+			.chain(std::iter::once(self.voter_set.clone()))
+			.map(|voter_set| (voter_set.set_id, voter_set))
+			.collect()
+	}
+
+	// The voter set in effect from each round on, for the rounds we took part in or observed.
+	fn sessions(&self) -> BTreeMap<RoundNumber, SetId> {
+		self.voting_rounds
+			.iter()
+			.unique_by(|voting_round| voting_round.round_number)
+			.map(|voting_round| (voting_round.round_number, voting_round.voter_set.set_id))
+			.collect()
+	}
+
+	// The voting rounds for the round, from the archive if we no longer have them in memory.
+	fn voting_rounds_for(&self, round: RoundNumber) -> Option<Vec<VotingRound>> {
+		if let Some(voting_rounds) = self.voting_rounds.get(&round) {
+			return Some(voting_rounds.clone());
+		}
+		self.archive
+			.as_ref()?
+			.voting_rounds(round, &self.voter_set)
+			.unwrap_or_else(|err| self.voting_rounds.record_error(err))
+	}
+
+	// Store evidence once its signature checks out. A failed check might be a transient fault
+	// rather than a forgery, so retry a few times before giving up and flagging it for audit.
+	fn check_evidence(
+		&mut self,
+		evidence: SignedEvidenceBundle,
+		failed_attempts: usize,
+		current_tick: usize,
+	) {
+		let valid = match &mut self.verification_faults {
+			Some(faults) => evidence.verify_with_faults(&self.key_registry, faults),
+			None => evidence.verify(&self.key_registry),
+		};
+		if valid {
+			if evidence.bundle.detector != self.id {
+				self.check_accusations(evidence, current_tick);
+			}
+		} else if failed_attempts + 1 < EVIDENCE_VERIFICATION_ATTEMPTS {
+			self.note(format!(
+				"signature on evidence attributed to {} doesn't verify, retrying",
+				evidence.bundle.detector
+			));
+			self.actions.push((
+				current_tick + EVIDENCE_VERIFICATION_RETRY_INTERVAL,
+				Action::RetryEvidenceVerification(evidence, failed_attempts + 1),
+			));
+		} else {
+			self.note(format!(
+				"flagging evidence attributed to {} for audit",
+				evidence.bundle.detector
+			));
+			self.flagged_for_audit.push(evidence);
+		}
+	}
+
+	// Verify each accusation in evidence with a genuine signature against our own chain. Evidence
+	// with any accusation that doesn't hold up is rejected as a whole, and the detector that signed
+	// it is on record for the fabrication. Accusations we can't check are kept apart, and those
+	// about blocks we haven't seen are checked again once the detector has served them.
+	fn check_accusations(&mut self, evidence: SignedEvidenceBundle, current_tick: usize) {
+		let detector = evidence.bundle.detector.clone();
+		let mut verified = Vec::new();
+		let mut unconfirmed = Vec::new();
+		let mut fabricated = Vec::new();
+		for accusation in evidence.bundle.equivocations {
+			match verify_equivocation_proof(&accusation, &self.chain) {
+				Ok(()) => verified.push(accusation),
+				Err(error) if error.is_fabrication() => fabricated.push(accusation),
+				Err(error) => {
+					self.request_missing_header(&detector, &error, current_tick);
+					unconfirmed.push(accusation);
+				}
+			}
+		}
+		if fabricated.is_empty() {
+			if unconfirmed.is_empty() {
+				self.unconfirmed_claims.remove(&detector);
+			} else {
+				self.note(format!(
+					"keeping {} accusations from {} that we can't verify as unconfirmed",
+					unconfirmed.len(),
+					detector
+				));
+				self.unconfirmed_claims
+					.insert(detector.clone(), unconfirmed);
+			}
+			self.proof_store.insert(detector, verified);
+			// The evidence may accuse us, which an adaptive adversary reacts to right away.
+			self.apply_behaviour_schedule(current_tick);
+			return;
+		}
+		self.note(format!(
+			"rejecting evidence from {} with fabricated accusations",
+			detector
+		));
+		self.coverage.hit(Branch::FabricationRejected);
+		for accusation in fabricated {
+			let finding = EquivocationDetected::fabricated(detector.clone(), accusation);
+			if !self.fabricated_evidence.contains(&finding) {
+				self.fabricated_evidence.push(finding);
+			}
+		}
+		self.schedule_evidence_gossip(current_tick);
+	}
+
+	fn request_missing_header(&mut self, detector: &VoterId, error: &ProofError, tick: usize) {
+		if let ProofError::MissingHeader(block) = *error {
+			let requested = self
+				.block_requests
+				.get(&block)
+				.is_some_and(|peers| peers.contains(detector));
+			if !requested {
+				self.actions
+					.push((tick, Action::RequestBlock(detector.clone(), block)));
+			}
+		}
+	}
+
+	// Check the unconfirmed claims again after syncing blocks, keeping those that now verify as
+	// proof.
+	fn recheck_unconfirmed_claims(&mut self, current_tick: usize) {
+		let mut confirmed = false;
+		for (detector, claims) in std::mem::take(&mut self.unconfirmed_claims) {
+			let mut unconfirmed = Vec::new();
+			for claim in claims {
+				match verify_equivocation_proof(&claim, &self.chain) {
+					Ok(()) => {
+						self.proof_store
+							.entry(detector.clone())
+							.or_default()
+							.push(claim);
+						confirmed = true;
+					}
+					Err(error) => {
+						self.request_missing_header(&detector, &error, current_tick);
+						unconfirmed.push(claim);
+					}
+				}
+			}
+			if !unconfirmed.is_empty() {
+				self.unconfirmed_claims.insert(detector, unconfirmed);
+			}
+		}
+		if confirmed {
+			self.note("confirmed accusations against the blocks synced".to_string());
+			self.apply_behaviour_schedule(current_tick);
+		}
+	}
+
+	// Check that the number and hash of the commit target refer to the same block in our chain.
+	// When we know neither, the commit is requeued and checked again once the block is synced.
+	fn check_commit_target(
+		&self,
+		round: RoundNumber,
+		commit: &Commit,
+		sender: &VoterId,
+	) -> Option<MismatchedCommitTarget> {
+		let target_hash = commit.target_hash?;
+		let block_with_hash = self.chain.block_with_hash(target_hash);
+		let matches = match self.chain.block_hash(commit.target_number) {
+			Some(hash) => hash == target_hash,
+			None => block_with_hash.is_none(),
+		};
+		if matches {
+			return None;
+		}
+		Some(MismatchedCommitTarget {
+			sender: sender.clone(),
+			round,
+			target_number: commit.target_number,
+			target_hash,
+			block_with_hash,
+		})
+	}
+
+	fn record_commit_variant(&mut self, round: RoundNumber, commit: &Commit, sender: &VoterId) {
+		let variants = self
+			.received_commits
+			.entry(commit.target_number)
+			.or_default();
+		add_commit_variant(variants, round, commit, sender);
+	}
+
+	/// Who sent us commits for the block and when the first arrived, unless we only have our own.
+	pub fn commit_provenance(&self, block: BlockNumber) -> Option<&CommitProvenance> {
+		self.chain.commit_provenance(block)
+	}
+
+	/// Blocks for which we have seen commits, including our own, with different signer sets in the
+	/// same round.
+	pub fn divergent_commit_variants(&self) -> Vec<DivergentCommitVariants> {
+		let mut variants = self.received_commits.clone();
+		for (block, commit) in self.chain.commits() {
+			let round = *self.chain.finalized_round(*block).unwrap();
+			add_commit_variant(variants.entry(*block).or_default(), round, commit, &self.id);
+		}
+
+		variants
+			.into_iter()
+			.flat_map(|(block, block_variants)| {
+				block_variants
+					.into_iter()
+					.into_group_map_by(|variant| variant.round)
+					.into_iter()
+					.filter(|(_, variants)| variants.len() > 1)
+					.map(move |(round, variants)| DivergentCommitVariants {
+						block,
+						round,
+						variants,
+					})
+			})
+			.sorted_by_key(|divergent| (divergent.block, divergent.round))
+			.collect()
+	}
+
+	// Send the explanation, split into chunks if it doesn't fit within our message budget.
+	fn explain_estimate(
+		&mut self,
+		receiver: VoterId,
+		round: RoundNumber,
+		block_not_included: BlockNumber,
+		response: QueryResponse,
+	) -> Vec<(VoterId, Response)> {
+		self.record_answer(&response);
+		// The estimate is explained with the votes of the round before.
+		let commitment = self.commit_to_votes(round - 1, &response);
+		let ignore_budget = !self.effective_behaviour().respects_message_budget();
+		let budget = match self.message_budget {
+			Some(budget) if response.len() > budget && !ignore_budget => budget,
+			_ => {
+				return vec![(
+					receiver,
+					Response::ExplainEstimate(round, block_not_included, response, commitment),
+				)]
+			}
+		};
+		self.effective_behaviour()
+			.send_chunks(response.into_chunks(budget))
+			.into_iter()
+			.map(|chunk| {
+				(
+					receiver.clone(),
+					Response::ExplainEstimateChunk(
+						round,
+						block_not_included,
+						chunk,
+						commitment.clone(),
+					),
+				)
+			})
+			.collect()
+	}
+
+	// Put the query to its receivers, except those that one of our instances already asked the same
+	// question. Whatever they answered so far is handed to the instance asking straight away, and
+	// anything still to come is routed to every instance that asked.
+	fn dispatch_query(&mut self, next_query: NextQuery, current_tick: usize) {
+		let mut queries = vec![next_query];
+		while let Some(next_query) = queries.pop() {
+			let conflict = next_query.conflict();
+			let (round, kind, receivers) = match &next_query {
+				NextQuery::AskAboutRound(query) if self.query_plan == QueryPlan::NearestFirst => (
+					query.round,
+					QueryKind::Estimate,
+					self.hold_back_all_but_nearest(query, current_tick),
+				),
+				NextQuery::AskAboutRound(query) => {
+					(query.round, QueryKind::Estimate, query.receivers.clone())
+				}
+				NextQuery::PrevotesForRound(query) => {
+					(query.round, QueryKind::Prevotes, query.receivers.clone())
+				}
+			};
+
+			let mut to_ask = Vec::new();
+			for receiver in receivers {
+				// Observers never vote, so there is nothing for them to explain.
+				if self.observers.contains(&receiver) {
+					continue;
+				}
+				let key = (round, kind, receiver.clone());
+				if !self.asked.contains_key(&key) {
+					if self.at_query_cap(current_tick) {
+						self.defer_query(next_query.with_receivers(vec![receiver]), current_tick);
+						continue;
+					}
+					self.asked.insert(key, current_tick);
+					self.actions.push((
+						current_tick + OUTSTANDING_QUERY_TIMEOUT,
+						Action::ExpireQuery(round, kind, receiver.clone()),
+					));
+					self.peak_outstanding_queries = self
+						.peak_outstanding_queries
+						.max(self.num_outstanding_queries(current_tick));
+					to_ask.push(receiver);
+					continue;
+				}
+				self.coverage.hit(Branch::SharedResponse);
+				for answer in self.answers.get(&key).cloned().unwrap_or_default() {
+					queries.extend(self.deliver_answer(conflict, &receiver, answer, current_tick));
+				}
+			}
+			if to_ask.is_empty() {
+				continue;
+			}
+
+			let action = match next_query {
+				NextQuery::AskAboutRound(mut query) => {
+					query.receivers = to_ask;
+					Action::AskVotersAboutEstimate(query)
+				}
+				NextQuery::PrevotesForRound(mut query) => {
+					query.receivers = to_ask;
+					Action::AskVotersWhichPrevotesSeen(query)
+				}
+			};
+			self.actions.push((current_tick + 10, action));
+		}
+	}
+
+	fn num_outstanding_queries(&self, current_tick: usize) -> usize {
+		self.asked
+			.iter()
+			.filter(|(key, since)| {
+				!self.answers.contains_key(*key)
+					&& current_tick < **since + OUTSTANDING_QUERY_TIMEOUT
+			})
+			.count()
+	}
+
+	fn at_query_cap(&self, current_tick: usize) -> bool {
+		self.max_outstanding_queries
+			.is_some_and(|cap| self.num_outstanding_queries(current_tick) >= cap)
+	}
+
+	// Hold back the query until an answer frees up room for it, or until the questions outstanding
+	// now have timed out.
+	fn defer_query(&mut self, query: NextQuery, current_tick: usize) {
+		if self.deferred_queries.is_empty() {
+			self.actions.push((
+				current_tick + OUTSTANDING_QUERY_TIMEOUT,
+				Action::ReleaseDeferredQueries,
+			));
+		}
+		self.deferred_queries.push_back(query);
+	}
+
+	fn release_deferred_queries(&mut self, current_tick: usize) {
+		while !self.deferred_queries.is_empty() && !self.at_query_cap(current_tick) {
+			let query = self.deferred_queries.pop_front().unwrap();
+			self.dispatch_query(query, current_tick);
+		}
+		if !self.deferred_queries.is_empty() {
+			self.actions.push((
+				current_tick + OUTSTANDING_QUERY_TIMEOUT,
+				Action::ReleaseDeferredQueries,
+			));
+		}
+	}
+
+	// Only the nearest of the receivers is asked right away. The others are asked one at a time, in
+	// order of latency, each once an answer from the one before could have arrived, unless there is
+	// a valid answer by then.
+	fn hold_back_all_but_nearest(&mut self, query: &Query, current_tick: usize) -> Vec<VoterId> {
+		let latency = |receiver: &VoterId| self.link_latencies.get(receiver).copied().unwrap_or(0);
+		let mut receivers: Vec<_> = query
+			.receivers
+			.iter()
+			.filter(|receiver| !self.observers.contains(*receiver))
+			.map(|receiver| (latency(receiver), receiver.clone()))
+			.sorted()
+			.collect();
+		let mut ask_at = current_tick;
+		for window in receivers.windows(2) {
+			let (previous_latency, _) = &window[0];
+			let (_, receiver) = &window[1];
+			ask_at += 2 * previous_latency + QUERY_EXPANSION_SLACK;
+			let mut held_back = query.clone();
+			held_back.receivers = vec![receiver.clone()];
+			self.actions
+				.push((ask_at, Action::AskIfUnanswered(held_back)));
+		}
+		receivers.truncate(1);
+		receivers
+			.into_iter()
+			.map(|(_, receiver)| receiver)
+			.collect()
+	}
+
+	// The instances that put the question to the peer.
+	fn asking_instances(&self, (round, kind, voter): &QueryKey) -> Vec<ConflictId> {
+		self.accountable_safety
+			.iter()
+			.filter(|(_, instance)| match kind {
+				QueryKind::Estimate => instance.asked_about_round(*round, voter),
+				QueryKind::Prevotes => instance.asked_about_prevotes(*round, voter),
+			})
+			.map(|(conflict, _)| *conflict)
+			.collect()
+	}
+
+	// Hand an answer to every instance that asked the question, and keep it for those asking later.
+	fn route_answer(&mut self, key: QueryKey, answer: Response, current_tick: usize) {
+		let (round, _, voter) = key.clone();
+		let conflicts = self.asking_instances(&key);
+		// Anyone can send us answers, but only those to our own queries are kept to be replayed.
+		if conflicts.is_empty() {
+			self.note(format!(
+				"ignoring answer from {} about round {} that no protocol instance asked for",
+				voter, round
+			));
+			self.coverage.hit(Branch::UnsolicitedAnswer);
+			return;
+		}
+		self.answers.entry(key).or_default().push(answer.clone());
+
+		for conflict in conflicts {
+			if let Some(next_query) =
+				self.deliver_answer(conflict, &voter, answer.clone(), current_tick)
+			{
+				self.dispatch_query(next_query, current_tick);
+			}
+		}
+		self.release_deferred_queries(current_tick);
+	}
+
+	fn deliver_answer(
+		&mut self,
+		conflict: ConflictId,
+		voter: &VoterId,
+		answer: Response,
+		current_tick: usize,
+	) -> Option<NextQuery> {
+		// Estimates are explained with the votes of the round before.
+		let commitment = match &answer {
+			Response::ExplainEstimate(round, .., commitment)
+			| Response::ExplainEstimateChunk(round, .., commitment) => Some((round - 1, commitment)),
+			Response::PrevotesSeen(round, _, commitment) => Some((*round, commitment)),
+			_ => None,
+		};
+		// Without a commitment to the votes revealed that the responder signed, the answer can't
+		// be held against it.
+		if let Some((round, commitment)) = commitment {
+			if commitment.commitment.responder != *voter
+				|| commitment.commitment.round != round
+				|| !commitment.verify(&self.key_registry)
+			{
+				self.note(format!(
+					"ignoring answer from {} without a valid commitment to its votes",
+					voter
+				));
+				return None;
+			}
+		}
+		let accountable_safety = match self.accountable_safety.get_mut(&conflict) {
+			Some(accountable_safety) => accountable_safety,
+			None => {
+				let bug = format!("answer for {:?}, which we aren't investigating", conflict);
+				self.protocol_bug(ProtocolBug(bug));
+				return None;
+			}
+		};
+		let next_query = match answer {
+			Response::ExplainEstimate(round, _, query_response, commitment) => accountable_safety
+				.add_response(
+					round,
+					voter.clone(),
+					query_response,
+					commitment,
+					&self.chain,
+				),
+			Response::ExplainEstimateChunk(round, _, chunk, commitment) => {
+				// Don't wait forever for the rest of the response.
+				if !accountable_safety.has_partial_response(round, voter) {
+					self.actions.push((
+						current_tick + RESPONSE_CHUNK_TIMEOUT,
+						Action::ExpireResponseChunks(round, voter.clone()),
+					));
+				}
+				accountable_safety.add_response_chunk(
+					round,
+					voter.clone(),
+					chunk,
+					commitment,
+					&self.chain,
+				)
+			}
+			Response::PrevotesSeen(round, query_response, commitment) => accountable_safety
+				.add_prevote_response(round, voter.clone(), query_response, commitment),
+			Response::NotAMemberAtRound(round) => accountable_safety
+				.add_non_member_response(round, voter.clone())
+				.map(|()| None),
+			Response::DontKnowRound(round) => accountable_safety
+				.add_lost_history_response(round, voter.clone())
+				.map(|()| None),
+			Response::PolicyRefusal(Refusal::Estimate(round, _)) => accountable_safety
+				.add_policy_refusal(round, voter.clone(), false)
+				.map(|()| None),
+			Response::PolicyRefusal(Refusal::Prevotes(round)) => accountable_safety
+				.add_policy_refusal(round, voter.clone(), true)
+				.map(|()| None),
+			// Not an answer to any of our queries.
+			Response::RequestBlock(..)
+			| Response::PolicyRefusal(Refusal::Block(..))
+			| Response::PolicyRefusal(Refusal::Commit(..)) => Ok(None),
+		};
+		next_query.unwrap_or_else(|bug| {
+			self.protocol_bug(bug);
+			None
+		})
+	}
+
+	// Narrate what we're doing. The notes are moved to the event log of the world.
+	fn note(&mut self, note: String) {
+		self.notes.push(note);
+	}
+
+	/// Take the notes made since they were last taken.
+	pub fn take_notes(&mut self) -> Vec<String> {
+		std::mem::take(&mut self.notes)
+	}
+
+	// Blocks from peers, or that peers ask about, can't always be placed in our chain. That's for
+	// the peer to answer for, or for the block to still arrive, so the error is kept and noted
+	// rather than taken for a bug of our own.
+	fn chain_error(&mut self, context: String, error: ChainError) {
+		let error = format!("{}: {}", context, error);
+		self.note(error.clone());
+		self.chain_errors.push(error);
+	}
+
+	// Internal inconsistencies are fatal, unless running in strict mode where they are collected so
+	// that a long run can report all of them.
+	fn protocol_bug(&mut self, bug: ProtocolBug) {
+		if !self.strict {
+			panic!("{}: {}", self.id, bug);
+		}
+		self.protocol_bugs.push(bug);
+	}
+
+	// Votes of the round explained that are in none of our histories of it can only have been
+	// made up.
+	fn note_fabricated_votes(&mut self, response: &QueryResponse, histories: &[VotingRound]) {
+		let fabricated: Vec<_> = match response {
+			QueryResponse::Precommits(precommits) => precommits
+				.iter()
+				.filter(|precommit| {
+					histories.iter().all(|voting_round| {
+						precommit.round == voting_round.round_number
+							&& !voting_round.precommits.contains(precommit)
+					})
+				})
+				.map(|precommit| format!("{:?}", precommit))
+				.collect(),
+			QueryResponse::Prevotes(prevotes) => prevotes
+				.iter()
+				.filter(|prevote| {
+					histories.iter().all(|voting_round| {
+						prevote.round == voting_round.round_number
+							&& !voting_round.prevotes.contains(prevote)
+					})
+				})
+				.map(|prevote| format!("{:?}", prevote))
+				.collect(),
+		};
+		if let (Some(voting_round), false) = (histories.first(), fabricated.is_empty()) {
+			self.coverage.hit(Branch::FabricatedVotes);
+			self.note(format!(
+				"fabricated votes for round {}: {}",
+				voting_round.round_number,
+				fabricated.join(", ")
+			));
+		}
+	}
+
+	// The explanation given before for the estimate of the round, unless the verifier asking now
+	// accepts different kinds of votes.
+	fn cached_explanation(
+		&mut self,
+		round: RoundNumber,
+		block_not_included: BlockNumber,
+		accepted_replies: ReplyKinds,
+	) -> Option<QueryResponse> {
+		let (kinds, response) = self.explanations.get(&(round, block_not_included))?;
+		if *kinds != accepted_replies {
+			return None;
+		}
+		self.explanation_cache.hits += 1;
+		Some(response.clone())
+	}
+
+	// Whether the sender may ask about the round: it must be one of the voters or a designated
+	// observer, and have signed a statement of a conflict that the query is about. Rejections are
+	// recorded, so that spam can be told apart from queries we simply had no answer to.
+	fn rejects_query(
+		&mut self,
+		sender: &VoterId,
+		statement: &SignedConflictStatement,
+		round: RoundNumber,
+		block: Option<BlockNumber>,
+	) -> bool {
+		let rejection = match self.check_query_standing(sender, statement) {
+			Err(rejection) => rejection,
+			Ok(()) => {
+				let covered = match block {
+					Some(block) => statement.covers_estimate_query(round, block),
+					None => statement.covers_prevote_query(round),
+				};
+				if covered {
+					self.served_queries.push(ServedQuery {
+						verifier: statement.statement.detector.clone(),
+						conflict: statement.conflict(),
+						round,
+						kind: block.map_or(QueryKind::Prevotes, |_| QueryKind::Estimate),
+						answer: None,
+					});
+					return false;
+				}
+				QueryRejection::OutsideConflict(round)
+			}
+		};
+		self.note(format!("rejecting query from {}: {}", sender, rejection));
+		self.coverage.hit(Branch::QueryRejected);
+		self.rejected_queries.push((sender.clone(), rejection));
+		true
+	}
+
+	// The query being handled was accepted and recorded when checking it, so this is its answer.
+	// Commit to the votes of the round in the history the answer comes from, the first time we
+	// answer about it from that history. Answers that don't come from any of our histories are
+	// passed off as coming from the first, so once we committed to that, they break the commitment.
+	fn commit_to_votes(
+		&mut self,
+		round: RoundNumber,
+		answer: &QueryResponse,
+	) -> SignedVoteCommitment {
+		let voting_rounds = self.voting_rounds_for(round).unwrap_or_default();
+		// Answers may reveal only some of the votes of the history they come from.
+		let reveals = |voting_round: &VotingRound| match answer {
+			QueryResponse::Prevotes(prevotes) => prevotes
+				.iter()
+				.all(|prevote| voting_round.prevotes.contains(prevote)),
+			QueryResponse::Precommits(precommits) => precommits
+				.iter()
+				.all(|precommit| voting_round.precommits.contains(precommit)),
+		};
+		let (tag, prevotes, precommits) = match voting_rounds
+			.iter()
+			.find(|voting_round| reveals(voting_round))
+		{
+			Some(voting_round) => (
+				voting_round.tag,
+				voting_round.prevotes.clone(),
+				voting_round.precommits.clone(),
+			),
+			// Votes made up for the answer are committed to in place of those of the history.
+			None => {
+				let (tag, mut prevotes, mut precommits) =
+					voting_rounds
+						.first()
+						.map_or((0, Vec::new(), Vec::new()), |voting_round| {
+							(
+								voting_round.tag,
+								voting_round.prevotes.clone(),
+								voting_round.precommits.clone(),
+							)
+						});
+				match answer {
+					QueryResponse::Prevotes(votes) => prevotes = votes.clone(),
+					QueryResponse::Precommits(votes) => precommits = votes.clone(),
+				}
+				(tag, prevotes, precommits)
+			}
+		};
+		// Commitments to the same votes of the round from other histories must agree, so the nonce
+		// is the same for all of them.
+		let nonce = self.secret_key.nonce(&round.to_le_bytes());
+		let (id, key) = (self.id.clone(), self.secret_key.clone());
+		self.vote_commitments
+			.entry((round, tag))
+			.or_insert_with(|| {
+				VoteCommitment::new(id, round, tag, nonce, &prevotes, &precommits).sign(&key)
+			})
+			.clone()
+			.revealing(nonce, answer)
+	}
+
+	fn record_answer(&mut self, answer: &QueryResponse) {
+		if let Some(served) = self.served_queries.last_mut() {
+			served.answer = Some(answer.clone());
+		}
+	}
+
+	/// The verifiers that asked us about the round, in the order they first did.
+	pub fn verifiers_asking(&self, round: RoundNumber, kind: QueryKind) -> Vec<VoterId> {
+		self.served_queries
+			.iter()
+			.filter(|served| served.round == round && served.kind == kind)
+			.map(|served| served.verifier.clone())
+			.unique()
+			.collect()
+	}
+
+	// Whether the voter is a member of our voter set or of that of any round we know.
+	fn is_voter(&self, voter: &str) -> bool {
+		self.voter_set.is_member(voter)
+			|| self
+				.voting_rounds
+				.iter()
+				.any(|voting_round| voting_round.voter_set.is_member(voter))
+	}
+
+	fn check_query_standing(
+		&self,
+		sender: &VoterId,
+		statement: &SignedConflictStatement,
+	) -> Result<(), QueryRejection> {
+		if !self.is_voter(sender) && !self.observers.contains(sender) {
+			return Err(QueryRejection::NoStanding);
+		}
+		if statement.statement.detector != *sender || !statement.verify(&self.key_registry) {
+			return Err(QueryRejection::BadSignature);
+		}
+
+		let ConflictStatement {
+			round_for_block_not_included,
+			commit_for_block_not_included,
+			round_for_new_block,
+			commit_for_new_block,
+			..
+		} = &statement.statement;
+		for (round, commit) in [
+			(*round_for_block_not_included, commit_for_block_not_included),
+			(*round_for_new_block, commit_for_new_block),
+		] {
+			if !self.commit_is_valid(round, commit) {
+				return Err(QueryRejection::InvalidCommit(commit.target_number));
+			}
+		}
+
+		// Blocks we don't know about yet can't be held against the verifier.
+		let conflict = statement.conflict();
+		if let Ok(false) = self
+			.chain
+			.try_blocks_conflict(conflict.block_not_included, conflict.new_block)
+		{
+			return Err(QueryRejection::NoConflict(
+				conflict.block_not_included,
+				conflict.new_block,
+			));
+		}
+		Ok(())
+	}
+
+	// Whether the commit is made up of precommits in the round, signed by a supermajority of the
+	// voter set it was cast in. Commits in voter sets we don't know are given the benefit of the
+	// doubt.
+	fn commit_is_valid(&self, round: RoundNumber, commit: &Commit) -> bool {
+		if commit
+			.precommits
+			.iter()
+			.any(|precommit| precommit.round != round || precommit.set_id != commit.set_id)
+		{
+			return false;
+		}
+		let voter_set = self
+			.voting_rounds_for(round)
+			.and_then(|voting_rounds| voting_rounds.first().map(|vr| vr.voter_set.clone()))
+			.filter(|voter_set| voter_set.set_id == commit.set_id)
+			.or_else(|| Some(self.voter_set.clone()).filter(|vs| vs.set_id == commit.set_id));
+		voter_set.is_none_or(|voter_set| {
+			voter_set.is_supermajority(voter_set.count_members(commit.names()))
+		})
+	}
+
+	fn ignores_queries_from(&self, verifier: &VoterId) -> bool {
+		self.effective_behaviour().ignores_queries_from(verifier)
+	}
+
+	// Switch to the behaviour of the scheduled changes that are due by now.
+	fn apply_behaviour_schedule(&mut self, current_tick: usize) {
+		if self.behaviour_schedule.is_empty() {
+			return;
+		}
+		let accused = self.known_offenders().contains(&self.id);
+		if let Some(behaviour) = self.behaviour_schedule.take_due(current_tick, accused) {
+			self.note(format!("switching behaviour to {:?}", behaviour));
+			self.coverage.hit(Branch::BehaviourSwitched);
+			self.behaviour = Some(behaviour);
+		}
+	}
+
+	// Voters without a behaviour of their own act like an honest voter.
+	fn effective_behaviour(&self) -> &dyn Behaviour {
+		self.behaviour
+			.as_deref()
+			.unwrap_or(&Adversary::ReturnPrecommits)
+	}
+
+	// The detector with the lowest id among those that claimed the conflict, in either order of the
+	// blocks.
+	fn primary_verifier(&self, conflict: ConflictId) -> Option<VoterId> {
+		[conflict, conflict.reversed()]
+			.iter()
+			.filter_map(|conflict| self.claims.get(conflict))
+			.flatten()
+			.min()
+			.cloned()
+	}
+
+	// Leave the conflict to the primary verifier, and take over at the given tick unless we have a
+	// complete proof by then.
+	fn stand_by(&mut self, conflict: ConflictId, primary: &VoterId, take_over_at: usize) {
+		self.note(format!(
+			"standing by while {} verifies blocks {} and {}",
+			primary, conflict.block_not_included, conflict.new_block
+		));
+		self.actions
+			.push((take_over_at, Action::TakeOverVerification(conflict)));
+	}
+
+	// Start sending out our evidence once we have any.
+	fn schedule_evidence_gossip(&mut self, current_tick: usize) {
+		let already_scheduled = self
+			.actions
+			.iter()
+			.any(|(_, action)| matches!(action, Action::GossipEvidence(..)));
+		if !already_scheduled && !self.equivocations_detected().is_empty() {
+			self.actions.push((
+				current_tick + 10,
+				Action::GossipEvidence(EVIDENCE_GOSSIP_REPEATS),
+			));
+		}
+	}
+
+	// Served blocks are only trusted if they agree with the blocks we already have, hang together
+	// from our chain on, and match the target hash of any commit waiting for them.
+	fn check_ancestry(&self, blocks: &[Block]) -> Result<(), String> {
+		let mut served = BTreeSet::new();
+		for block in blocks {
+			if let Some(ours) = self.chain.get_block(block.number) {
+				if ours != block {
+					return Err(format!("{} differs from our {}", block, ours));
+				}
+			} else if !self.chain.knows_about_block(block.parent) && !served.contains(&block.parent)
+			{
+				return Err(format!("{} doesn't connect to our chain", block));
+			}
+			if let Some(hash) = self.pending_block_hashes.get(&block.number) {
+				if *hash != block.hash() {
+					return Err(format!("{} doesn't match the commit for it", block));
+				}
+			}
+			served.insert(block.number);
+		}
+		Ok(())
+	}
+
+	fn distrust_ancestry(&mut self, block: BlockNumber, supplier: VoterId) {
+		let newly_untrusted = self
+			.untrusted_ancestry
+			.entry(block)
+			.or_default()
+			.insert(supplier);
+		if newly_untrusted && self.ancestry_untrusted(block) {
+			self.note(format!("no peer served trustworthy blocks for {}", block));
+			self.outcomes.push(Outcome::UntrustedAncestry {
+				block,
+				suppliers: self.untrusted_ancestry[&block].iter().cloned().collect(),
+			});
+		}
+	}
+
+	// Whether every peer served ancestry for the block that we couldn't trust, leaving no one to
+	// get it from.
+	fn ancestry_untrusted(&self, block: BlockNumber) -> bool {
+		self.untrusted_ancestry
+			.get(&block)
+			.is_some_and(|suppliers| {
+				self.voter_set
+					.voter_ids()
+					.iter()
+					.filter(|peer| **peer != self.id)
+					.all(|peer| suppliers.contains(peer))
+			})
+	}
+
+	// Whether the round is past every round we voted in, so that we learn what was finalized in it
+	// from the commits we receive.
+	fn catching_up(&self, round: RoundNumber) -> bool {
+		!self.is_observer()
+			&& self
+				.voting_rounds
+				.latest_round()
+				.is_some_and(|latest| latest < round)
+	}
+
+	// The leaves of the forks conflicting with the block that we know of no commit on.
+	fn unfinalized_forks_conflicting_with(
+		&self,
+		block: BlockNumber,
+	) -> Result<Vec<BlockNumber>, ChainError> {
+		let chain = &self.chain;
+		let mut forks = Vec::new();
+		'leaves: for leaf in chain.leaves() {
+			if !chain.try_blocks_conflict(leaf, block)? {
+				continue;
+			}
+			for finalized in chain.commits().keys() {
+				if chain.try_block_includes(leaf, *finalized)?
+					&& chain.try_blocks_conflict(*finalized, block)?
+				{
+					continue 'leaves;
+				}
+			}
+			forks.push(leaf);
+		}
+		Ok(forks)
+	}
+
+	// Ask our peers, other than the sender of the commit for the block, for the commits on the forks
+	// conflicting with it, unless we already know of one there.
+	fn request_conflicting_commits(
+		&mut self,
+		block: BlockNumber,
+		sender: &VoterId,
+		current_tick: usize,
+	) {
+		let forks = match self.unfinalized_forks_conflicting_with(block) {
+			Ok(forks) => forks,
+			Err(error) => {
+				let context = format!("looking for the forks conflicting with block {}", block);
+				self.chain_error(context, error);
+				return;
+			}
+		};
+		for fork in forks {
+			if !self.commit_requests.insert(fork) {
+				continue;
+			}
+			self.coverage.hit(Branch::CommitCatchUp);
+			for peer in self.peers().into_iter().sorted() {
+				if peer != *sender {
+					self.note(format!(
+						"requesting the commit for block {} from {}",
+						fork, peer
+					));
+					self.actions
+						.push((current_tick, Action::RequestCommit(peer, fork)));
+				}
+			}
+		}
+	}
+
+	// Pick the next peer, in a deterministic order, that we haven't yet asked for the block.
+	fn next_peer_to_ask_for_block(&self, block: BlockNumber) -> Option<VoterId> {
+		let asked = self.block_requests.get(&block);
+		self.voter_set
+			.voter_ids()
+			.into_iter()
+			.sorted()
+			.find(|peer| *peer != self.id && !asked.is_some_and(|asked| asked.contains(peer)))
+	}
+
+	// An equivocator has several histories for the round to answer which prevotes it saw from. It
+	// sticks to the one it already committed to answering about the round, so as not to be caught
+	// out by its commitment, and otherwise picks one whose prevotes justify its precommit.
+	fn select_prevote_history<'a>(
+		&mut self,
+		round: RoundNumber,
+		voting_rounds: &'a [VotingRound],
+	) -> &'a VotingRound {
+		if let [voting_round] = voting_rounds {
+			return voting_round;
+		}
+		self.coverage.hit(Branch::PrevoteHistorySelected);
+		let committed = voting_rounds.iter().find(|voting_round| {
+			self.vote_commitments
+				.contains_key(&(round, voting_round.tag))
+		});
+		let justified = || {
+			voting_rounds.iter().find(|voting_round| {
+				voting_round.prevotes_justify_precommit_of(&self.id, &self.chain)
+			})
+		};
+		let voting_round = committed.or_else(justified).unwrap_or(&voting_rounds[0]);
+		self.note(format!(
+			"answering with the prevotes of history {} of round {}",
+			voting_round.tag, round
+		));
+		voting_round
+	}
+
+	pub fn handle_response(&mut self, response: (VoterId, Response), current_tick: usize) {
+		self.apply_behaviour_schedule(current_tick);
+		match response.1 {
+			Response::RequestBlock(block_number) => {
+				if self.effective_behaviour().withholds_blocks() {
+					self.note(format!("withholding block {}", block_number));
+					self.coverage.hit(Branch::BlocksWithheld);
+					return;
+				}
+				self.actions.push((
+					current_tick + 10,
+					Action::SendBlock(response.0, block_number),
+				));
+			}
+			Response::ExplainEstimate(round_number, ..)
+			| Response::ExplainEstimateChunk(round_number, ..)
+			| Response::NotAMemberAtRound(round_number)
+			| Response::DontKnowRound(round_number)
+			| Response::PolicyRefusal(Refusal::Estimate(round_number, _)) => {
+				let key = (round_number, QueryKind::Estimate, response.0);
+				self.route_answer(key, response.1, current_tick);
+			}
+			Response::PrevotesSeen(round_number, ..)
+			| Response::PolicyRefusal(Refusal::Prevotes(round_number)) => {
+				let key = (round_number, QueryKind::Prevotes, response.0);
+				self.route_answer(key, response.1, current_tick);
+			}
+			// Others might still send us what we asked for.
+			Response::PolicyRefusal(refusal) => {
+				self.note(format!("{} refused to send {}", response.0, refusal));
+				self.coverage.hit(Branch::PolicyRefusal);
+			}
+		}
+		self.schedule_evidence_gossip(current_tick);
+	}
+
+	/// The branches hit by this voter, including by the protocol instances it has run.
+	pub fn coverage(&self) -> Coverage {
+		let mut coverage = self.coverage;
+		for instance in self.accountable_safety.values() {
+			coverage.merge(instance.coverage());
+		}
+		coverage
+	}
+
+	pub fn equivocations_detected(&self) -> Vec<EquivocationDetected> {
+		self.accountable_safety
+			.values()
+			.flat_map(|acc_safety| acc_safety.equivocations_detected())
+			.chain(self.fabricated_evidence.iter().cloned())
+			.collect()
+	}
+
+	/// For each protocol instance, the offenders it found and the fewest of them needed to account for
+	/// its findings.
+	pub fn offender_analyses(&self) -> Vec<OffenderAnalysis> {
+		self.accountable_safety
+			.values()
+			.map(|instance| {
+				OffenderAnalysis::new(
+					&instance.equivocations_detected(),
+					self.voter_set.max_faulty(),
+				)
+			})
+			.collect()
+	}
+
+	/// The offenders found by our protocol instances, for each conflict they investigated.
+	pub fn offenders_per_conflict(&self) -> BTreeMap<ConflictId, BTreeSet<VoterId>> {
+		self.accountable_safety
+			.iter()
+			.map(|(conflict, instance)| {
+				let offenders = instance
+					.equivocations_detected()
+					.iter()
+					.flat_map(|equivocation| equivocation.offenders())
+					.collect();
+				(*conflict, offenders)
+			})
+			.collect()
+	}
+
+	/// For each protocol instance, the voters it queried without finding them to have misbehaved.
+	pub fn exonerations(&self) -> BTreeMap<ConflictId, Vec<Exoneration>> {
+		self.accountable_safety
+			.iter()
+			.map(|(conflict, instance)| (*conflict, instance.exonerations()))
+			.filter(|(_, exonerations)| !exonerations.is_empty())
+			.collect()
+	}
+
+	/// Keep our voting rounds in the storage from now on, moving those we have into it. If they
+	/// can't all be moved, we keep them where they were.
+	pub fn keep_rounds_in(&mut self, storage: impl RoundStorage + 'static) -> io::Result<()> {
+		self.voting_rounds = self.voting_rounds.clone().move_to(storage)?;
+		Ok(())
+	}
+
+	/// The errors reading or writing our votes, in our round storage or the archive.
+	pub fn storage_errors(&self) -> Vec<String> {
+		self.voting_rounds.errors()
+	}
+
+	/// The errors walking our chain for blocks peers sent us or asked us about.
+	pub fn chain_errors(&self) -> &[String] {
+		&self.chain_errors
+	}
+
+	/// Lose the votes of every round, as after a restart with the disk lost, keeping the chain and
+	/// the commits in it. We answer queries about the rounds that we don't know them any more.
+	pub fn lose_voting_history(&mut self) {
+		self.voting_rounds = VotingRounds::new();
+		self.archive = None;
+		self.explanations.clear();
+		self.vote_commitments.clear();
+		self.lost_history = true;
+	}
+
+	/// The signers of the conflicting commits each protocol instance found to have lost their votes.
+	pub fn unjustified_participations(
+		&self,
+	) -> BTreeMap<ConflictId, Vec<UnjustifiedCommitParticipation>> {
+		self.accountable_safety
+			.iter()
+			.map(|(conflict, instance)| (*conflict, instance.unjustified_participations().to_vec()))
+			.filter(|(_, participations)| !participations.is_empty())
+			.collect()
+	}
+
+	/// Whether every protocol instance we started has run its course.
+	pub fn instances_concluded(&self) -> bool {
+		self.accountable_safety
+			.values()
+			.all(AccountableSafety::concluded)
+	}
+
+	/// The verdicts on the responses to the queries of each protocol instance.
+	pub fn verdicts(&self) -> BTreeMap<ConflictId, Vec<RoundVerdicts>> {
+		self.accountable_safety
+			.iter()
+			.map(|(conflict, instance)| (*conflict, instance.verdicts()))
+			.filter(|(_, verdicts)| !verdicts.is_empty())
+			.collect()
+	}
+
+	/// For each protocol instance that still has or had rounds to ask about, the rounds and whom
+	/// it would ask.
+	pub fn query_plans(&self) -> BTreeMap<ConflictId, Vec<PlannedRound>> {
+		self.accountable_safety
+			.iter()
+			.map(|(conflict, instance)| (*conflict, instance.plan()))
+			.filter(|(_, plan)| !plan.is_empty())
+			.collect()
+	}
+
+	/// The voting rounds we keep in memory, ordered by round number and then by tag. A voter that
+	/// equivocated has several histories of a round, told apart by their tag. Rounds moved to the
+	/// archive are left out.
+	pub fn rounds(&self) -> impl Iterator<Item = VotingRound> + '_ {
+		self.voting_rounds.iter()
+	}
+
+	/// Our history of the round with the given tag, if we keep it in memory.
+	pub fn round(&self, round: RoundNumber, tag: u32) -> Option<VotingRound> {
+		self.voting_rounds
+			.get(&round)?
+			.into_iter()
+			.find(|voting_round| voting_round.tag == tag)
+	}
+
+	/// The votes we cast ourselves in each of our histories of the round kept in memory, ordered by
+	/// tag, leaving out histories in which we didn't vote. More than one means we equivocated.
+	pub fn own_vote_in(&self, round: RoundNumber) -> Vec<OwnVote> {
+		let is_ours = |id: &VoterName| id.as_str() == self.id;
+		self.voting_rounds
+			.get(&round)
+			.into_iter()
+			.flatten()
+			.sorted_by_key(|voting_round| voting_round.tag)
+			.map(|voting_round| OwnVote {
+				tag: voting_round.tag,
+				prevote: voting_round
+					.prevotes
+					.iter()
+					.find(|prevote| is_ours(&prevote.id))
+					.cloned(),
+				precommit: voting_round
+					.precommits
+					.iter()
+					.find(|precommit| is_ours(&precommit.id))
+					.cloned(),
+			})
+			.filter(|vote| vote.prevote.is_some() || vote.precommit.is_some())
+			.collect()
+	}
+
+	/// The protocol instance investigating the conflict, if we started one.
+	pub fn instance(&self, conflict: &ConflictId) -> Option<&AccountableSafety> {
+		self.accountable_safety.get(conflict)
+	}
+
+	/// Our protocol instances, ordered by the conflict they investigate.
+	pub fn instances(&self) -> impl ExactSizeIterator<Item = (&ConflictId, &AccountableSafety)> {
+		self.accountable_safety.iter()
+	}
+
+	/// The actions we have scheduled, in the order they are due.
+	pub fn pending_actions(&self) -> impl Iterator<Item = &(TriggerAtTick, Action)> {
+		self.actions.iter().sorted_by_key(|(tick, _)| *tick)
+	}
+
+	/// At worst, how many ticks from `current_tick` on we take to do all that's left: our scheduled
+	/// actions, walking back through every one of our rounds asking each voter in turn, waiting out
+	/// the timeouts, and spreading and checking the evidence we find.
+	pub fn estimated_completion_bound(&self, current_tick: usize) -> usize {
+		let last_action = self
+			.actions
+			.iter()
+			.map(|(tick, _)| tick.saturating_sub(current_tick))
+			.max()
+			.unwrap_or_default();
+		let round_trip = 2 * self
+			.link_latencies
+			.values()
+			.max()
+			.copied()
+			.unwrap_or_default()
+			+ QUERY_EXPANSION_SLACK;
+		let rounds = self.voting_rounds.latest_round().unwrap_or_default() as usize;
+		let walk_back = rounds * self.voter_set.len().max(1) * round_trip;
+		let timeouts = CLAIM_WINDOW
+			+ self.coordination_timeout.unwrap_or_default()
+			+ RESPONSE_CHUNK_TIMEOUT
+			+ OUTSTANDING_QUERY_TIMEOUT;
+		let evidence = EVIDENCE_GOSSIP_INTERVAL * EVIDENCE_GOSSIP_REPEATS
+			+ EVIDENCE_VERIFICATION_ATTEMPTS * EVIDENCE_VERIFICATION_RETRY_INTERVAL;
+		// Rounds we vote in complete by their precommit timer, at 4 delays, unless the votes don't
+		// let them, and the last one is kept for another delay.
+		let live = self.live_rounds.latest_round().map_or(0, |_| 1);
+		let voting = (self.active_rounds as usize + live) * 5 * self.vote_delay.max(1);
+		last_action + walk_back + timeouts + evidence + voting
+	}
+
+	/// The most questions we had put to peers and not had answered at any one time.
+	pub fn peak_outstanding_queries(&self) -> usize {
+		self.peak_outstanding_queries
+	}
+
+	/// How often we could answer a query about an estimate with an explanation given before.
+	pub fn explanation_cache(&self) -> CacheStats {
+		self.explanation_cache
+	}
+
+	/// How much gossip we dropped for having seen it before.
+	pub fn duplicates_suppressed(&self) -> usize {
+		self.duplicates_suppressed
+	}
+
+	/// The questions put to peers that are still waiting for an answer, oldest first.
+	pub fn outstanding_queries(&self) -> Vec<OutstandingQuery> {
+		self.asked
+			.iter()
+			.filter(|(key, _)| !self.answers.contains_key(*key))
+			.map(|(key, since)| {
+				let (round, kind, voter) = key.clone();
+				OutstandingQuery {
+					round,
+					kind,
+					voter,
+					since: *since,
+					conflicts: self.asking_instances(key),
+				}
+			})
+			.sorted_by_key(|query| query.since)
+			.collect()
+	}
+
+	/// Everyone proven to have misbehaved, either by ourselves or by verified evidence from others,
+	/// grouped by the voter set they misbehaved in.
+	pub fn known_offenders_by_set(&self) -> BTreeMap<SetId, BTreeSet<VoterId>> {
+		let mut offenders: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+		let offences = self
+			.equivocations_detected()
+			.iter()
+			.chain(self.proof_store.values().flatten())
+			.flat_map(|equivocation| equivocation.offences())
+			.collect::<Vec<_>>();
+		for (set_id, voter) in offences {
+			offenders.entry(set_id).or_default().insert(voter);
+		}
+		offenders
+	}
+
+	/// Everyone proven to have misbehaved, either by ourselves or by verified evidence from others.
+	pub fn known_offenders(&self) -> BTreeSet<VoterId> {
+		self.equivocations_detected()
+			.iter()
+			.chain(self.proof_store.values().flatten())
+			.flat_map(|equivocation| equivocation.offenders())
+			.collect()
+	}
+
+	/// Prune our chain back to the finalized block, first pinning the blocks that the proofs we hold
+	/// refer to, so that they can still be verified against our own chain afterwards.
+	pub fn prune_chain(&mut self, finalized: BlockNumber) -> Result<Vec<BlockNumber>, ChainError> {
+		let proofs: Vec<_> = self
+			.equivocations_detected()
+			.into_iter()
+			.chain(self.proof_store.values().flatten().cloned())
+			.collect();
+		for proof in &proofs {
+			// Blocks we never heard of can't be pinned, nor can we verify the proof anyway.
+			let blocks: Vec<_> = referenced_blocks(proof)
+				.into_iter()
+				.filter(|block| self.chain.knows_about_block(*block))
+				.collect();
+			self.chain.pin_for_proof(proof_id(proof), blocks)?;
+		}
+		self.chain.prune(finalized)
+	}
+
+	// Report what we found, signed by us.
+	pub fn evidence_bundle(&self) -> SignedEvidenceBundle {
+		let mut equivocations = self.equivocations_detected();
+		if self.effective_behaviour().fabricates_accusations() && !equivocations.is_empty() {
+			equivocations.extend(self.fabricated_accusations());
+		}
+		let commit_provenance = self
+			.accountable_safety
+			.keys()
+			.flat_map(|conflict| [conflict.block_not_included, conflict.new_block])
+			.filter_map(|block| {
+				let provenance = self.chain.commit_provenance(block)?;
+				Some((block, provenance.clone()))
+			})
+			.collect();
+		EvidenceBundle {
+			detector: self.id.clone(),
+			equivocations,
+			commit_provenance,
+		}
+		.sign(&self.secret_key)
+	}
+
+	// Accuse the voters whose answers held up of having voted twice in a round they explained. Since
+	// votes would be signed, all we can cite is the one vote they really cast there, twice over.
+	fn fabricated_accusations(&self) -> Vec<EquivocationDetected> {
+		self.exonerations()
+			.into_values()
+			.flatten()
+			.filter_map(|exoneration| {
+				let voter = exoneration.voter;
+				exoneration
+					.explanations
+					.values()
+					.find_map(|explanation| match explanation {
+						QueryResponse::Precommits(precommits) => precommits
+							.iter()
+							.find(|precommit| precommit.id == voter)
+							.map(|precommit| {
+								EquivocationDetected::Precommit(vec![Equivocation {
+									voter: voter.clone(),
+									set_id: precommit.set_id,
+									blocks: vec![precommit.target_number; 2],
+								}])
+							}),
+						QueryResponse::Prevotes(prevotes) => prevotes
+							.iter()
+							.find(|prevote| prevote.id == voter)
+							.map(|prevote| {
+								EquivocationDetected::Prevote(vec![Equivocation {
+									voter: voter.clone(),
+									set_id: prevote.set_id,
+									blocks: vec![prevote.target_number; 2],
+								}])
+							}),
+					})
+			})
+			.unique()
+			.collect()
+	}
+}
+
+fn add_commit_variant(
+	variants: &mut Vec<CommitVariant>,
+	round: RoundNumber,
+	commit: &Commit,
+	sender: &VoterId,
+) {
+	let signers = commit.ids().collect();
+	match variants
+		.iter_mut()
+		.find(|variant| variant.round == round && variant.signers == signers)
+	{
+		Some(variant) => {
+			variant.senders.insert(sender.clone());
+		}
+		None => variants.push(CommitVariant {
+			round,
+			signers,
+			senders: std::iter::once(sender.clone()).collect(),
+		}),
 	}
 }
 
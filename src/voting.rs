@@ -15,64 +15,235 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
-	block::BlockNumber,
-	chain::Chain,
+	block::{BlockHash, BlockNumber},
+	chain::{Chain, ChainError},
 	protocol::{Equivocation, EquivocationDetected, QueryResponse},
+	storage::{InMemoryRounds, RoundStorage},
+	supermajority::{self, Weight},
 	voter::{VoterId, VoterName},
 };
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{BTreeSet, HashMap, HashSet},
 	fmt::{Display, Formatter},
+	io,
+	ops::{Bound, RangeBounds},
+	sync::Mutex,
 };
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VoterSetError {
+	DuplicateVoter(VoterId),
+}
+
+/// Identifies a voter set. Each change of the voter set starts a new session with the next id.
+pub type SetId = u64;
+
 #[derive(Clone, Debug)]
 pub struct VoterSet {
-	// WIP: consider store as VoterId to avoid ugly conversions
+	pub set_id: SetId,
 	pub voters: HashSet<VoterName>,
+	// Human readable names, which unlike the ids don't have to be unique.
+	display_names: HashMap<VoterName, String>,
 }
 
 impl VoterSet {
-	pub fn new(voter_ids: &[VoterName]) -> Self {
-		Self {
-			voters: voter_ids.iter().cloned().collect(),
+	pub fn new<S: AsRef<str>>(voter_ids: &[S]) -> Self {
+		Self::try_new(voter_ids).expect("voter ids are unique")
+	}
+
+	pub fn try_new<S: AsRef<str>>(voter_ids: &[S]) -> Result<Self, VoterSetError> {
+		let voters_with_names: Vec<_> = voter_ids
+			.iter()
+			.map(|id| (id.as_ref(), id.as_ref()))
+			.collect();
+		Self::with_display_names(&voters_with_names)
+	}
+
+	/// Create a voter set where each voter has a display name in addition to its unique id.
+	pub fn with_display_names(voters: &[(&str, &str)]) -> Result<Self, VoterSetError> {
+		let mut voter_set = Self {
+			set_id: 0,
+			voters: Default::default(),
+			display_names: Default::default(),
+		};
+		for (id, display_name) in voters {
+			let id = VoterName::new(id);
+			if !voter_set.voters.insert(id.clone()) {
+				return Err(VoterSetError::DuplicateVoter(id.to_string()));
+			}
+			voter_set.display_names.insert(id, display_name.to_string());
 		}
+		Ok(voter_set)
+	}
+
+	pub fn with_set_id(mut self, set_id: SetId) -> Self {
+		self.set_id = set_id;
+		self
 	}
 
-	pub fn is_member(&self, voter: VoterName) -> bool {
+	pub fn is_member(&self, voter: &str) -> bool {
 		self.voters.contains(voter)
 	}
 
+	/// The handle for the voter in the set with the given id.
+	pub fn lookup(&self, id: &str) -> Option<VoterName> {
+		self.voters.get(id).cloned()
+	}
+
+	pub fn display_name(&self, voter: &str) -> Option<&str> {
+		self.display_names.get(voter).map(|name| name.as_str())
+	}
+
+	pub fn len(&self) -> usize {
+		self.voters.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.voters.is_empty()
+	}
+
+	/// Returns true if the given number of distinct voters form a supermajority of the set.
+	pub fn is_supermajority(&self, num_voters: usize) -> bool {
+		supermajority::is_supermajority(num_voters as Weight, self.len() as Weight)
+	}
+
+	/// The most faulty voters the protocol tolerates, f in n = 3f + 1.
+	pub fn max_faulty(&self) -> usize {
+		supermajority::max_faulty(self.len() as Weight) as usize
+	}
+
+	/// Number of distinct members of the set among the given voters.
+	pub fn count_members<S: AsRef<str>>(&self, voters: impl IntoIterator<Item = S>) -> usize {
+		voters
+			.into_iter()
+			.filter_map(|voter| self.lookup(voter.as_ref()))
+			.unique()
+			.count()
+	}
+
+	/// The ids of the voters, sorted.
 	pub fn voter_ids(&self) -> Vec<VoterId> {
-		self.voters.iter().map(|v| String::from(*v)).collect()
+		self.voters.iter().map(|v| v.to_string()).sorted().collect()
 	}
 }
 
 pub type RoundNumber = u64;
 
-#[derive(Clone, Debug)]
-pub struct VotingRounds(pub HashMap<RoundNumber, Vec<VotingRound>>);
+/// The voting rounds of a voter, in whatever [`RoundStorage`] they are kept in.
+///
+/// Rounds the storage fails to read are treated as rounds we have no votes for, and the errors are
+/// kept to be reported by [`VotingRounds::errors`].
+#[derive(Debug)]
+pub struct VotingRounds {
+	storage: Box<dyn RoundStorage>,
+	errors: Mutex<Vec<String>>,
+}
 
 impl VotingRounds {
 	pub fn new() -> Self {
-		Self(HashMap::new())
+		Self::with_storage(InMemoryRounds::default())
+	}
+
+	/// Keep the voting rounds in the storage, along with any it already has.
+	pub fn with_storage(storage: impl RoundStorage + 'static) -> Self {
+		Self {
+			storage: Box::new(storage),
+			errors: Default::default(),
+		}
+	}
+
+	/// Move the voting rounds into the storage, failing on the first round that can't be moved.
+	pub fn move_to(self, storage: impl RoundStorage + 'static) -> io::Result<Self> {
+		let mut moved = Self::with_storage(storage);
+		for voting_round in self.storage.range(Bound::Unbounded, Bound::Unbounded) {
+			moved.storage.add(voting_round?)?;
+		}
+		Ok(moved)
 	}
 
-	pub fn get(&self, round_number: &RoundNumber) -> Option<&Vec<VotingRound>> {
-		self.0.get(round_number)
+	pub fn get(&self, round_number: &RoundNumber) -> Option<Vec<VotingRound>> {
+		self.storage
+			.get(*round_number)
+			.unwrap_or_else(|err| self.record_error(err))
+	}
+
+	/// The latest round we have votes for.
+	pub fn latest_round(&self) -> Option<RoundNumber> {
+		self.storage
+			.latest_round()
+			.unwrap_or_else(|err| self.record_error(err))
 	}
 
 	pub fn add(&mut self, voting_round: VotingRound) {
-		let round_number = voting_round.round_number;
-		if let Some(vr) = self.0.get_mut(&round_number) {
-			vr.push(voting_round)
-		} else {
-			self.0.insert(round_number, vec![voting_round]);
+		if let Err(err) = self.storage.add(voting_round) {
+			self.record_error(err)
 		}
 	}
 
 	pub fn extend(&mut self, other: VotingRounds) {
-		self.0.extend(other.0);
+		for voting_round in other.iter() {
+			self.add(voting_round);
+		}
+	}
+
+	/// Every history of every round, ordered by round and then by tag.
+	pub fn iter(&self) -> impl Iterator<Item = VotingRound> + '_ {
+		self.range(..)
+	}
+
+	/// The histories of the rounds in the range, ordered by round and then by tag.
+	pub fn range(
+		&self,
+		range: impl RangeBounds<RoundNumber>,
+	) -> impl Iterator<Item = VotingRound> + '_ {
+		self.storage
+			.range(range.start_bound().cloned(), range.end_bound().cloned())
+			.filter_map(move |voting_round| match voting_round {
+				Ok(voting_round) => Some(voting_round),
+				Err(err) => self.record_error(err),
+			})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.latest_round().is_none()
+	}
+
+	/// Remove and return all rounds before the given round, kept in memory.
+	pub fn prune_before(&mut self, round: RoundNumber) -> VotingRounds {
+		let mut pruned = VotingRounds::new();
+		let removed = self
+			.storage
+			.remove_before(round)
+			.unwrap_or_else(|err| self.record_error(err));
+		for voting_round in removed {
+			pruned.add(voting_round);
+		}
+		pruned
+	}
+
+	/// The errors reading or writing our votes so far.
+	pub fn errors(&self) -> Vec<String> {
+		self.errors.lock().expect("not poisoned").clone()
+	}
+
+	// Keep the error to be reported, going on as if there was nothing to read.
+	pub(crate) fn record_error<T: Default>(&self, err: io::Error) -> T {
+		self.errors
+			.lock()
+			.expect("not poisoned")
+			.push(err.to_string());
+		T::default()
+	}
+}
+
+impl Clone for VotingRounds {
+	fn clone(&self) -> Self {
+		Self {
+			storage: self.storage.boxed_clone(),
+			errors: Mutex::new(self.errors()),
+		}
 	}
 }
 
@@ -117,50 +288,102 @@ impl VotingRound {
 		}
 	}
 
-	pub fn prevote(&mut self, votes: &[(BlockNumber, VoterName)]) {
+	pub fn prevote<S: AsRef<str>>(&mut self, votes: &[(BlockNumber, S)]) {
 		let mut votes = votes
 			.iter()
 			.map(|(n, id)| {
-				assert!(self.voter_set.is_member(id));
-				Prevote::new(*n, id)
+				let id = self
+					.voter_set
+					.lookup(id.as_ref())
+					.expect("voter is in the set");
+				Prevote::new(self.round_number, *n, id).in_set(self.voter_set.set_id)
 			})
 			.collect::<Vec<_>>();
 		self.prevotes.append(&mut votes);
 	}
 
-	pub fn precommit(&mut self, votes: &[(BlockNumber, VoterName)]) {
+	pub fn precommit<S: AsRef<str>>(&mut self, votes: &[(BlockNumber, S)]) {
 		let mut votes = votes
 			.iter()
 			.map(|(n, id)| {
-				assert!(self.voter_set.is_member(id));
-				Precommit::new(*n, id)
+				let id = self
+					.voter_set
+					.lookup(id.as_ref())
+					.expect("voter is in the set");
+				Precommit::new(self.round_number, *n, id).in_set(self.voter_set.set_id)
 			})
 			.collect::<Vec<_>>();
 		self.precommits.append(&mut votes);
 	}
+
+	/// Whether the voter's precommit is for a block in the chain of g(V), so that the prevotes
+	/// justify it.
+	pub fn prevotes_justify_precommit_of(&self, voter: &str, chain: &Chain) -> bool {
+		let Some(precommit) = self
+			.precommits
+			.iter()
+			.find(|precommit| precommit.id == voter)
+		else {
+			return false;
+		};
+		ghost(&self.prevotes, &self.voter_set, chain).is_some_and(|ghost| {
+			matches!(
+				chain.try_block_includes(ghost, precommit.target_number),
+				Ok(true)
+			)
+		})
+	}
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Prevote {
+	// The voter set and round are part of what's signed, so a vote can't be passed off as one from
+	// another session or round.
+	pub set_id: SetId,
+	pub round: RoundNumber,
 	pub target_number: BlockNumber,
 	pub id: VoterName,
 }
 
 impl Prevote {
-	pub fn new(target_number: BlockNumber, id: VoterName) -> Self {
-		Self { target_number, id }
+	/// A vote in the first voter set, see [`Prevote::in_set`] for later ones.
+	pub fn new(round: RoundNumber, target_number: BlockNumber, id: impl Into<VoterName>) -> Self {
+		Self {
+			set_id: 0,
+			round,
+			target_number,
+			id: id.into(),
+		}
+	}
+
+	pub fn in_set(mut self, set_id: SetId) -> Self {
+		self.set_id = set_id;
+		self
 	}
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Precommit {
+	pub set_id: SetId,
+	pub round: RoundNumber,
 	pub target_number: BlockNumber,
 	pub id: VoterName,
 }
 
 impl Precommit {
-	pub fn new(target_number: BlockNumber, id: VoterName) -> Self {
-		Self { target_number, id }
+	/// A vote in the first voter set, see [`Precommit::in_set`] for later ones.
+	pub fn new(round: RoundNumber, target_number: BlockNumber, id: impl Into<VoterName>) -> Self {
+		Self {
+			set_id: 0,
+			round,
+			target_number,
+			id: id.into(),
+		}
+	}
+
+	pub fn in_set(mut self, set_id: SetId) -> Self {
+		self.set_id = set_id;
+		self
 	}
 }
 
@@ -168,21 +391,33 @@ impl Display for Precommit {
 	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
 		write!(
 			f,
-			"Precommit {{ target_number: {}, id: {} }}",
-			self.target_number, self.id
+			"Precommit {{ round: {}, target_number: {}, id: {} }}",
+			self.round, self.target_number, self.id
 		)
 	}
 }
 
 pub trait Vote: std::hash::Hash + Eq {
-	fn id(&self) -> VoterName;
+	fn id(&self) -> &VoterName;
+
+	fn set_id(&self) -> SetId;
+
+	fn round(&self) -> RoundNumber;
 
 	fn target(&self) -> BlockNumber;
 }
 
 impl Vote for Prevote {
-	fn id(&self) -> VoterName {
-		self.id
+	fn id(&self) -> &VoterName {
+		&self.id
+	}
+
+	fn set_id(&self) -> SetId {
+		self.set_id
+	}
+
+	fn round(&self) -> RoundNumber {
+		self.round
 	}
 
 	fn target(&self) -> BlockNumber {
@@ -191,8 +426,16 @@ impl Vote for Prevote {
 }
 
 impl Vote for Precommit {
-	fn id(&self) -> VoterName {
-		self.id
+	fn id(&self) -> &VoterName {
+		&self.id
+	}
+
+	fn set_id(&self) -> SetId {
+		self.set_id
+	}
+
+	fn round(&self) -> RoundNumber {
+		self.round
 	}
 
 	fn target(&self) -> BlockNumber {
@@ -200,22 +443,33 @@ impl Vote for Precommit {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Commit {
+	// The voter set the precommits were cast in.
+	pub set_id: SetId,
 	pub target_number: BlockNumber,
+	// Attached when broadcasting, so that receivers can check it against their own chain.
+	pub target_hash: Option<BlockHash>,
 	pub precommits: Vec<Precommit>,
 }
 
 impl Commit {
 	pub fn new(target_number: BlockNumber, precommits: Vec<Precommit>) -> Self {
 		Self {
+			set_id: precommits.first().map_or(0, |precommit| precommit.set_id),
 			target_number,
+			target_hash: None,
 			precommits,
 		}
 	}
 
-	pub fn names(&self) -> impl Iterator<Item = VoterName> + '_ {
-		self.precommits.iter().map(|precommit| precommit.id)
+	pub fn with_target_hash(mut self, target_hash: BlockHash) -> Self {
+		self.target_hash = Some(target_hash);
+		self
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &VoterName> + '_ {
+		self.precommits.iter().map(|precommit| &precommit.id)
 	}
 
 	pub fn ids(&self) -> impl Iterator<Item = VoterId> + '_ {
@@ -231,51 +485,380 @@ impl Display for Commit {
 			f,
 			"Commit({}, {{ {} }})",
 			self.target_number,
-			self.precommits.iter().map(|pc| pc.id).format(", ")
+			self.precommits.iter().map(|pc| &pc.id).format(", ")
 		)
 	}
 }
 
+/// A distinct set of signers seen in commits for a block, and who sent us a commit with them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitVariant {
+	pub round: RoundNumber,
+	pub signers: BTreeSet<VoterId>,
+	pub senders: BTreeSet<VoterId>,
+}
+
+/// A commit whose target number and hash refer to different blocks in the receiver's chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MismatchedCommitTarget {
+	pub sender: VoterId,
+	pub round: RoundNumber,
+	pub target_number: BlockNumber,
+	pub target_hash: BlockHash,
+	// The block we know by that hash, if any.
+	pub block_with_hash: Option<BlockNumber>,
+}
+
+impl Display for MismatchedCommitTarget {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"commit from {} in round {} targets block {} with hash {:x}",
+			self.sender, self.round, self.target_number, self.target_hash
+		)?;
+		match self.block_with_hash {
+			Some(block) => write!(f, ", which is the hash of block {}", block),
+			None => write!(f, ", which is not the hash of any known block"),
+		}
+	}
+}
+
+/// Why a received commit doesn't hold up, once its target block is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidCommit {
+	// Some of the precommits are from another round or voter set than the commit.
+	ForeignPrecommits,
+	// The signers aren't a supermajority of the voter set.
+	NoSupermajority,
+	// A precommit is for a known block that doesn't include the target.
+	PrecommitOffTarget(BlockNumber),
+}
+
+impl Display for InvalidCommit {
+	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+		match self {
+			InvalidCommit::ForeignPrecommits => {
+				write!(f, "precommits from another round or voter set")
+			}
+			InvalidCommit::NoSupermajority => write!(f, "no supermajority"),
+			InvalidCommit::PrecommitOffTarget(block) => {
+				write!(f, "precommit for block {} not including the target", block)
+			}
+		}
+	}
+}
+
+/// Commits for the same block and round with different signer sets. Each is a valid commit on its
+/// own, but more precommits were in circulation than any single commit shows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DivergentCommitVariants {
+	pub block: BlockNumber,
+	pub round: RoundNumber,
+	pub variants: Vec<CommitVariant>,
+}
+
+// Sets of up to this many voters are kept on the stack when checking responses.
+const INLINE_VOTERS: usize = 1024;
+
+// The voters seen in a response, by their index in the sorted list of voters.
+struct VoterBitset {
+	inline: [u64; INLINE_VOTERS / 64],
+	// Only used for larger voter sets.
+	spilled: Vec<u64>,
+}
+
+impl VoterBitset {
+	fn new(num_voters: usize) -> Self {
+		let words = num_voters.div_ceil(64);
+		Self {
+			inline: [0; INLINE_VOTERS / 64],
+			spilled: if num_voters > INLINE_VOTERS {
+				vec![0; words]
+			} else {
+				Vec::new()
+			},
+		}
+	}
+
+	fn words(&self) -> &[u64] {
+		if self.spilled.is_empty() {
+			&self.inline
+		} else {
+			&self.spilled
+		}
+	}
+
+	// Returns false if the voter was already in the set.
+	fn insert(&mut self, index: usize) -> bool {
+		let words = if self.spilled.is_empty() {
+			&mut self.inline[..]
+		} else {
+			&mut self.spilled[..]
+		};
+		let (word, bit) = (index / 64, 1 << (index % 64));
+		let inserted = words[word] & bit == 0;
+		words[word] |= bit;
+		inserted
+	}
+
+	fn len(&self) -> usize {
+		self.words()
+			.iter()
+			.map(|word| word.count_ones() as usize)
+			.sum()
+	}
+}
+
 // Check the validity of a response.
 // The purpose of the response is to return a set of votes showing it is impossible to have a
-// supermajority for the given block.
+// supermajority for the given block. Fails if the ancestry of a block voted for can't be walked,
+// since then there's no telling what the votes include.
+//
+// The voters are expected to be sorted and distinct, as returned by `VoterSet::voter_ids`, so that
+// the votes can be matched to them without allocating.
 pub fn check_query_reply_is_valid(
 	response: &QueryResponse,
 	block: BlockNumber,
 	voters: &[VoterId],
 	chain: &Chain,
-) -> Option<EquivocationDetected> {
-	let unique_voters: HashSet<VoterId> = response.ids().into_iter().unique().collect();
+) -> Result<Option<EquivocationDetected>, ChainError> {
+	debug_assert!(voters.windows(2).all(|pair| pair[0] < pair[1]));
 
-	let num_equivocations_in_response =
-		response.ids().iter().count() - unique_voters.iter().count();
+	let mut present = VoterBitset::new(voters.len());
+	let mut num_equivocations_in_response = 0;
+	for (position, id) in response.ids().enumerate() {
+		let first_vote = match voters.binary_search_by(|voter| voter.as_str().cmp(id)) {
+			Ok(index) => present.insert(index),
+			// Votes from outside the voter set aren't indexed, so look for them in the rest of the
+			// response instead.
+			Err(_) => !response.ids().take(position).any(|earlier| earlier == id),
+		};
+		if !first_vote {
+			num_equivocations_in_response += 1;
+		}
+	}
+	// A response with more than one vote from the same voter explains nothing.
 	if num_equivocations_in_response > 0 {
-		todo!("Equivocation detected!");
+		return Ok(Some(EquivocationDetected::InvalidResponse {
+			voter: "placeholder".to_string(),
+			set_id: 0,
+		}));
 	}
 
 	// Check impossible to have supermajority for the block
-	let prevotes_includes_block = response
-		.target_numbers()
-		.into_iter()
-		.filter(|target_number| chain.block_includes(*target_number, block))
-		.count();
+	let mut prevotes_includes_block = 0;
+	for target_number in response.target_numbers() {
+		if chain.try_block_includes(target_number, block)? {
+			prevotes_includes_block += 1;
+		}
+	}
 
 	// + Add absent votes
-	let voters = voters.iter().cloned().collect::<HashSet<_>>();
 	let num_voters = voters.len();
-	let absent_voters = voters.difference(&unique_voters).count();
+	let absent_voters = num_voters - present.len();
 
 	// A valid response has votes showing it's impossible to have supermajority for the earlier
 	// finalized block on the other branch
-	if 3 * (prevotes_includes_block + absent_voters) <= 2 * num_voters {
-		None
+	if supermajority::cannot_have_supermajority(
+		prevotes_includes_block as Weight,
+		absent_voters as Weight,
+		num_voters as Weight,
+	) {
+		Ok(None)
 	} else {
 		// WIP: return a proper response.
 		// We can't have a todo! here as the Byzantine voter logic uses the return value to
 		// determine which response to send.
-		Some(EquivocationDetected::InvalidResponse(
-			"placeholder".to_string(),
-		))
+		Ok(Some(EquivocationDetected::InvalidResponse {
+			voter: "placeholder".to_string(),
+			set_id: 0,
+		}))
+	}
+}
+
+// The fewest of the votes in a response that still show it's impossible to have a supermajority for
+// the block, for responders that would rather not reveal more of their history than they have to.
+// Only the first vote of each voter in the set, for a block not including the block, shows
+// anything, and every such vote shows as much as any other, so it's enough to count them off in
+// order. `None` if even the whole response doesn't show it.
+pub fn minimal_query_reply(
+	response: &QueryResponse,
+	block: BlockNumber,
+	voters: &[VoterId],
+	chain: &Chain,
+) -> Result<Option<QueryResponse>, ChainError> {
+	fn fewest<V: Vote + Clone>(
+		votes: &[V],
+		block: BlockNumber,
+		voters: &[VoterId],
+		chain: &Chain,
+	) -> Result<Option<Vec<V>>, ChainError> {
+		let total = voters.len() as Weight;
+		let shown = |kept: &[V]| {
+			supermajority::cannot_have_supermajority(0, total - kept.len() as Weight, total)
+		};
+		let mut kept: Vec<V> = Vec::new();
+		for vote in votes {
+			if shown(&kept) {
+				break;
+			}
+			let counts = voters.iter().any(|voter| vote.id() == voter)
+				&& !chain.try_block_includes(vote.target(), block)?
+				&& !kept.iter().any(|earlier| earlier.id() == vote.id());
+			if counts {
+				kept.push(vote.clone());
+			}
+		}
+		Ok(Some(kept).filter(|kept| shown(kept)))
+	}
+
+	Ok(match response {
+		QueryResponse::Prevotes(prevotes) => {
+			fewest(prevotes, block, voters, chain)?.map(QueryResponse::Prevotes)
+		}
+		QueryResponse::Precommits(precommits) => {
+			fewest(precommits, block, voters, chain)?.map(QueryResponse::Precommits)
+		}
+	})
+}
+
+// Check that a response for the round preceding the round where `new_block` was finalized is
+// consistent with the responder later precommitting for `new_block`.
+// If the claimed precommits have a supermajority for a block on a different branch than
+// `new_block`, the estimate for that round could not have been an ancestor of `new_block`, and so
+// the responder could not have validly voted for it in the following round. Prevotes only bound
+// the ghost, below which the estimate can still be an ancestor of `new_block`, so they never
+// contradict the commit.
+pub fn query_reply_is_consistent_with_commit(
+	response: &QueryResponse,
+	new_block: BlockNumber,
+	voters: &[VoterId],
+	chain: &Chain,
+) -> Result<bool, ChainError> {
+	if let QueryResponse::Prevotes(_) = response {
+		return Ok(true);
+	}
+	let num_voters = voters.iter().unique().count();
+	let targets: Vec<_> = response.target_numbers().collect();
+
+	// Every block that has a vote for it, or for a descendant of it, could potentially have a
+	// supermajority.
+	let mut candidates = HashSet::new();
+	for target in &targets {
+		candidates.extend(
+			chain
+				.try_get_chain_of_blocks(*target)?
+				.into_iter()
+				.map(|block| block.number),
+		);
+	}
+
+	for candidate in candidates {
+		if !chain.try_blocks_conflict(new_block, candidate)? {
+			continue;
+		}
+		let mut votes_including_candidate = 0;
+		for target in &targets {
+			if chain.try_block_includes(*target, candidate)? {
+				votes_including_candidate += 1;
+			}
+		}
+		if supermajority::is_supermajority(votes_including_candidate, num_voters as Weight) {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+// The lowest block the estimate of a round could have been for the voter, given the precommits it
+// claims to have seen there, including its own. An honest voter precommits for g(V), which only
+// moves up its chain as more prevotes come in, and the estimate is the highest block on the chain of
+// g(V) that can still get a supermajority of the precommits. So it includes the highest block on
+// the chain of the voter's own precommit that can.
+pub fn estimate_lower_bound(
+	precommits: &[Precommit],
+	voter: &str,
+	voters: &[VoterId],
+	chain: &Chain,
+) -> Result<Option<BlockNumber>, ChainError> {
+	let Some(own) = precommits.iter().find(|precommit| precommit.id == voter) else {
+		return Ok(None);
+	};
+	let num_voters = voters.iter().unique().count();
+	let absent_voters = voters
+		.iter()
+		.filter(|id| {
+			!precommits
+				.iter()
+				.any(|precommit| precommit.id == id.as_str())
+		})
+		.count();
+	for block in chain
+		.try_get_chain_of_blocks(own.target_number)?
+		.iter()
+		.rev()
+	{
+		let mut supporters = 0;
+		for precommit in precommits {
+			if chain.try_block_includes(precommit.target_number, block.number)? {
+				supporters += 1;
+			}
+		}
+		if supermajority::supermajority_possible(
+			supporters,
+			absent_voters as Weight,
+			num_voters as Weight,
+		) {
+			return Ok(Some(block.number));
+		}
+	}
+	Ok(None)
+}
+
+/// A vote presented as cast in a round and voter set other than those it was signed for.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MisplacedVote {
+	pub voter: VoterId,
+	pub target: BlockNumber,
+	// The voter set and round the vote was signed for.
+	pub set_id: SetId,
+	pub round: RoundNumber,
+}
+
+/// What cross-checking votes presented for a round against the votes known from it turned up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrossCheck {
+	pub equivocations: Vec<Equivocation>,
+	pub misplaced: Vec<MisplacedVote>,
+}
+
+/// Cross-check the votes presented as cast in the round of the voter set against those known to
+/// have been. A presented vote signed for another round or voter set is the same vote presented in
+/// an incompatible context, rather than an equivocation. Left in, it would be compared only with
+/// votes of its own context and hide any equivocation, so it's set aside and reported instead.
+pub fn cross_check_votes_in_round<V: Vote>(
+	set_id: SetId,
+	round: RoundNumber,
+	presented: Vec<V>,
+	known: Vec<V>,
+) -> CrossCheck {
+	let (in_context, misplaced): (Vec<_>, Vec<_>) = presented
+		.into_iter()
+		.partition(|vote| vote.set_id() == set_id && vote.round() == round);
+	let misplaced = misplaced
+		.iter()
+		.map(|vote| MisplacedVote {
+			voter: vote.id().to_string(),
+			target: vote.target(),
+			set_id: vote.set_id(),
+			round: vote.round(),
+		})
+		.sorted()
+		.dedup()
+		.collect();
+	CrossCheck {
+		equivocations: cross_check_votes(in_context, known).unwrap_or_default(),
+		misplaced,
 	}
 }
 
@@ -285,24 +868,28 @@ pub fn cross_check_votes<V: Vote>(votes0: Vec<V>, votes1: Vec<V>) -> Option<Vec<
 	let votes1: HashSet<_> = votes1.iter().collect();
 	let union: HashSet<_> = votes0.union(&votes1).collect();
 
-	let mut unique_ids: Vec<_> = union.iter().map(|vote| vote.id()).unique().collect();
+	// Votes are only compared within the voter set and round they were cast in.
+	let mut unique_ids: Vec<_> = union
+		.iter()
+		.map(|vote| (vote.id(), vote.set_id(), vote.round()))
+		.unique()
+		.collect();
 	unique_ids.sort_unstable();
 
 	// Find any duplicate id in the union
 	let mut equivocations = Vec::new();
-	for id in unique_ids {
-		let duplicates: Vec<_> = union.iter().filter(|vote| vote.id() == id).collect();
+	for (id, set_id, round) in unique_ids {
+		let duplicates: Vec<_> = union
+			.iter()
+			.filter(|vote| vote.id() == id && vote.set_id() == set_id && vote.round() == round)
+			.collect();
 		if duplicates.len() > 1 {
 			let mut duplicate_blocks: Vec<_> =
 				duplicates.iter().map(|vote| vote.target()).collect();
 			duplicate_blocks.sort_unstable();
-			println!(
-				"Equivocation detected: {} voted for blocks {:?}",
-				id, duplicate_blocks,
-			);
-
 			let new_equivocation = Equivocation {
 				voter: id.to_string(),
+				set_id,
 				blocks: duplicate_blocks,
 			};
 
@@ -318,6 +905,343 @@ pub fn cross_check_votes<V: Vote>(votes0: Vec<V>, votes1: Vec<V>) -> Option<Vec<
 	}
 }
 
+/// Stages of a round we take part in, in the order they are reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RoundStage {
+	Start,
+	Prevoted,
+	Precommitted,
+	Completable,
+	Finalized,
+}
+
+/// The ticks by which we prevote and precommit at the latest, unless the rounds complete earlier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundTimers {
+	pub started_at: usize,
+	pub prevote_at: usize,
+	pub precommit_at: usize,
+}
+
+impl RoundTimers {
+	/// Timers for a round started at the given tick, where `delay` is the time it takes votes to
+	/// reach everyone. As in the paper, we prevote after 2 delays and precommit after 4.
+	pub fn new(started_at: usize, delay: usize) -> Self {
+		Self {
+			started_at,
+			prevote_at: started_at + 2 * delay,
+			precommit_at: started_at + 4 * delay,
+		}
+	}
+}
+
+/// All the votes a voter has seen in a round, sent to its peers in a single message instead of
+/// vote by vote.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoteBatch {
+	pub round: RoundNumber,
+	pub set_id: SetId,
+	pub prevotes: Vec<Prevote>,
+	pub precommits: Vec<Precommit>,
+}
+
+impl VoteBatch {
+	pub fn len(&self) -> usize {
+		self.prevotes.len() + self.precommits.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// State of a round we are actively voting in, advanced by our own votes and the votes we receive.
+#[derive(Clone, Debug)]
+pub struct RoundState {
+	id: VoterName,
+	round: VotingRound,
+	stage: RoundStage,
+	timers: RoundTimers,
+	// How many blocks below g(C) we finalize, leaving the tip of what the precommits agree on to
+	// later rounds.
+	finalization_lag: u32,
+	// The fewest ticks between two batches of our votes.
+	batch_interval: usize,
+	// When we last sent a batch, and how many votes it had.
+	last_batch: Option<(usize, usize)>,
+}
+
+impl RoundState {
+	pub fn new(id: VoterName, round: VotingRound, timers: RoundTimers) -> Self {
+		Self {
+			id,
+			round,
+			stage: RoundStage::Start,
+			timers,
+			finalization_lag: 0,
+			batch_interval: 1,
+			last_batch: None,
+		}
+	}
+
+	/// Send our batch of votes at most once every `interval` ticks.
+	pub fn with_batch_interval(mut self, interval: usize) -> Self {
+		self.batch_interval = interval.max(1);
+		self
+	}
+
+	/// Only finalize the ancestor of g(C) the given number of blocks below it.
+	pub fn with_finalization_lag(mut self, finalization_lag: u32) -> Self {
+		self.finalization_lag = finalization_lag;
+		self
+	}
+
+	pub fn stage(&self) -> RoundStage {
+		self.stage
+	}
+
+	pub fn timers(&self) -> RoundTimers {
+		self.timers
+	}
+
+	/// The votes seen so far in the round.
+	pub fn round(&self) -> &VotingRound {
+		&self.round
+	}
+
+	/// g(V), the highest block with a supermajority of the prevotes.
+	pub fn prevote_ghost(&self, chain: &Chain) -> Option<BlockNumber> {
+		ghost(&self.round.prevotes, &self.round.voter_set, chain)
+	}
+
+	/// g(C), the highest block with a supermajority of the precommits.
+	pub fn precommit_ghost(&self, chain: &Chain) -> Option<BlockNumber> {
+		ghost(&self.round.precommits, &self.round.voter_set, chain)
+	}
+
+	/// The last block in the chain of g(V) for which the precommits can still have a supermajority.
+	pub fn estimate(&self, chain: &Chain) -> Option<BlockNumber> {
+		let mut block = self.prevote_ghost(chain)?;
+		loop {
+			if self.precommit_supermajority_possible(block, chain) {
+				return Some(block);
+			}
+			let parent = chain.get_block(block)?;
+			if parent.is_genesis() {
+				return None;
+			}
+			block = parent.parent;
+		}
+	}
+
+	/// The estimate is below g(V), or none of the children of g(V) can get a supermajority of the
+	/// precommits.
+	pub fn is_completable(&self, chain: &Chain) -> bool {
+		let (ghost, estimate) = match (self.prevote_ghost(chain), self.estimate(chain)) {
+			(Some(ghost), Some(estimate)) => (ghost, estimate),
+			_ => return false,
+		};
+		estimate != ghost
+			|| chain
+				.children(ghost)
+				.all(|child| !self.precommit_supermajority_possible(child, chain))
+	}
+
+	/// Time to prevote, either since the timer expired or since the previous round is completable.
+	pub fn should_prevote(&self, now: usize, previous_completable: bool) -> bool {
+		self.stage == RoundStage::Start && (now >= self.timers.prevote_at || previous_completable)
+	}
+
+	/// Time to precommit, once g(V) includes the estimate of the previous round and either the
+	/// timer expired or the round is completable.
+	pub fn should_precommit(
+		&self,
+		now: usize,
+		previous_estimate: BlockNumber,
+		chain: &Chain,
+	) -> bool {
+		self.stage == RoundStage::Prevoted
+			&& self
+				.prevote_ghost(chain)
+				.is_some_and(|ghost| chain.block_includes(ghost, previous_estimate))
+			&& (now >= self.timers.precommit_at || self.is_completable(chain))
+	}
+
+	/// Cast our prevote for the given block.
+	pub fn prevote(&mut self, target: BlockNumber, chain: &Chain) -> Prevote {
+		assert_eq!(self.stage, RoundStage::Start, "prevoting twice");
+		let prevote = Prevote::new(self.round.round_number, target, self.id.clone())
+			.in_set(self.round.voter_set.set_id);
+		self.round.prevotes.push(prevote.clone());
+		self.stage = RoundStage::Prevoted;
+		self.update(chain);
+		prevote
+	}
+
+	/// Cast our precommit for g(V), if there is one.
+	pub fn precommit(&mut self, chain: &Chain) -> Option<Precommit> {
+		assert_eq!(
+			self.stage,
+			RoundStage::Prevoted,
+			"precommitting out of turn"
+		);
+		let precommit = Precommit::new(
+			self.round.round_number,
+			self.prevote_ghost(chain)?,
+			self.id.clone(),
+		)
+		.in_set(self.round.voter_set.set_id);
+		self.round.precommits.push(precommit.clone());
+		self.stage = RoundStage::Precommitted;
+		self.update(chain);
+		Some(precommit)
+	}
+
+	pub fn add_prevote(&mut self, prevote: Prevote, chain: &Chain) {
+		if !self.round.prevotes.contains(&prevote) {
+			self.round.prevotes.push(prevote);
+			self.update(chain);
+		}
+	}
+
+	pub fn add_precommit(&mut self, precommit: Precommit, chain: &Chain) {
+		if !self.round.precommits.contains(&precommit) {
+			self.round.precommits.push(precommit);
+			self.update(chain);
+		}
+	}
+
+	/// Every vote we have seen in the round, if it's time to send them to our peers: no sooner than
+	/// the batch interval after the last batch, and only once we have seen votes since.
+	pub fn take_batch(&mut self, now: usize) -> Option<VoteBatch> {
+		let seen = self.round.prevotes.len() + self.round.precommits.len();
+		if let Some((sent_at, sent)) = self.last_batch {
+			if now < sent_at + self.batch_interval || seen == sent {
+				return None;
+			}
+		}
+		if seen == 0 {
+			return None;
+		}
+		self.last_batch = Some((now, seen));
+		Some(VoteBatch {
+			round: self.round.round_number,
+			set_id: self.round.voter_set.set_id,
+			prevotes: self.round.prevotes.clone(),
+			precommits: self.round.precommits.clone(),
+		})
+	}
+
+	/// Merge the votes of a batch from a peer into ours, and return how many of them we hadn't
+	/// seen. Votes from other rounds or voter sets are left out.
+	pub fn merge_batch(&mut self, batch: &VoteBatch, chain: &Chain) -> usize {
+		let round = self.round.round_number;
+		let set_id = self.round.voter_set.set_id;
+		if batch.round != round || batch.set_id != set_id {
+			return 0;
+		}
+		let mut merged = 0;
+		for prevote in &batch.prevotes {
+			if prevote.round == round
+				&& prevote.set_id == set_id
+				&& !self.round.prevotes.contains(prevote)
+			{
+				self.round.prevotes.push(prevote.clone());
+				merged += 1;
+			}
+		}
+		for precommit in &batch.precommits {
+			if precommit.round == round
+				&& precommit.set_id == set_id
+				&& !self.round.precommits.contains(precommit)
+			{
+				self.round.precommits.push(precommit.clone());
+				merged += 1;
+			}
+		}
+		// Tallying once for the whole batch rather than for each vote.
+		if merged > 0 {
+			self.update(chain);
+		}
+		merged
+	}
+
+	fn update(&mut self, chain: &Chain) {
+		if self.stage == RoundStage::Precommitted && self.is_completable(chain) {
+			self.stage = RoundStage::Completable;
+		}
+		if self.stage == RoundStage::Completable {
+			if let Some(block) = self.finalization_candidate(chain) {
+				self.round.finalized = Some(block);
+				self.stage = RoundStage::Finalized;
+			}
+		}
+	}
+
+	// The block `finalization_lag` blocks below g(C), as long as it is included in the estimate.
+	// With too few blocks below g(C) there's nothing new to finalize.
+	fn finalization_candidate(&self, chain: &Chain) -> Option<BlockNumber> {
+		let mut block = self.precommit_ghost(chain)?;
+		for _ in 0..self.finalization_lag {
+			let current = chain.get_block(block)?;
+			if current.is_genesis() {
+				return None;
+			}
+			block = current.parent;
+		}
+		if chain.get_block(block)?.is_genesis() {
+			return None;
+		}
+		let estimate = self.estimate(chain)?;
+		chain.block_includes(estimate, block).then_some(block)
+	}
+
+	// Voters that precommitted for the block or one of its descendants, equivocators, and those
+	// yet to precommit could together form a supermajority.
+	fn precommit_supermajority_possible(&self, block: BlockNumber, chain: &Chain) -> bool {
+		let voter_set = &self.round.voter_set;
+		let precommitted = voter_set.count_members(self.round.precommits.iter().map(|pc| &pc.id));
+		let remaining = voter_set.len() - precommitted;
+		voter_set.is_supermajority(supporters(&self.round.precommits, block, chain) + remaining)
+	}
+}
+
+// Number of voters voting for the block or one of its descendants. Equivocators count as voting
+// for every block, while votes for blocks whose ancestry we can't walk count for none.
+fn supporters<V: Vote>(votes: &[V], block: BlockNumber, chain: &Chain) -> usize {
+	votes
+		.iter()
+		.into_group_map_by(|vote| vote.id())
+		.into_values()
+		.filter(|votes| {
+			votes.iter().map(|vote| vote.target()).unique().count() > 1
+				|| votes
+					.iter()
+					.any(|vote| matches!(chain.try_block_includes(vote.target(), block), Ok(true)))
+		})
+		.count()
+}
+
+// The GHOST function g(S): the highest block with a supermajority of the votes, found by following
+// the child with the most support from genesis.
+fn ghost<V: Vote>(votes: &[V], voter_set: &VoterSet, chain: &Chain) -> Option<BlockNumber> {
+	let has_supermajority = |block| voter_set.is_supermajority(supporters(votes, block, chain));
+	if !has_supermajority(0) {
+		return None;
+	}
+	let mut ghost = 0;
+	loop {
+		let best_child = chain
+			.children(ghost)
+			.filter(|child| has_supermajority(*child))
+			.max_by_key(|child| (supporters(votes, *child, chain), std::cmp::Reverse(*child)));
+		match best_child {
+			Some(child) => ghost = child,
+			None => return Some(ghost),
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -326,24 +1250,34 @@ mod tests {
 	fn cross_check_votes_without_equivocations() {
 		let precommits = vec![
 			Precommit {
+				set_id: 0,
+				round: 1,
 				target_number: 1,
-				id: "Alice",
+				id: "Alice".into(),
 			},
 			Precommit {
+				set_id: 0,
+				round: 1,
 				target_number: 1,
-				id: "Bob",
+				id: "Bob".into(),
 			},
 		];
 		let commit = Commit {
+			set_id: 0,
 			target_number: 1,
+			target_hash: None,
 			precommits: vec![
 				Precommit {
+					set_id: 0,
+					round: 1,
 					target_number: 1,
-					id: "Alice",
+					id: "Alice".into(),
 				},
 				Precommit {
+					set_id: 0,
+					round: 1,
 					target_number: 1,
-					id: "Bob",
+					id: "Bob".into(),
 				},
 			],
 		};
@@ -354,24 +1288,34 @@ mod tests {
 	fn cross_check_votes_with_equivocations() {
 		let precommits = vec![
 			Precommit {
+				set_id: 0,
+				round: 1,
 				target_number: 1,
-				id: "Alice",
+				id: "Alice".into(),
 			},
 			Precommit {
+				set_id: 0,
+				round: 1,
 				target_number: 1,
-				id: "Bob",
+				id: "Bob".into(),
 			},
 		];
 		let commit = Commit {
+			set_id: 0,
 			target_number: 1,
+			target_hash: None,
 			precommits: vec![
 				Precommit {
+					set_id: 0,
+					round: 1,
 					target_number: 2,
-					id: "Alice",
+					id: "Alice".into(),
 				},
 				Precommit {
+					set_id: 0,
+					round: 1,
 					target_number: 1,
-					id: "Bob",
+					id: "Bob".into(),
 				},
 			],
 		};
@@ -379,8 +1323,542 @@ mod tests {
 			cross_check_votes(precommits, commit.precommits),
 			Some(vec![Equivocation {
 				voter: "Alice".to_string(),
+				set_id: 0,
 				blocks: vec![1, 2],
 			}]),
 		)
 	}
+
+	#[test]
+	fn cross_check_votes_from_different_rounds() {
+		// Voting for different blocks in different rounds is just how voting progresses.
+		let precommits = vec![Precommit::new(1, 1, "Alice"), Precommit::new(1, 1, "Bob")];
+		let later = vec![Precommit::new(2, 2, "Alice"), Precommit::new(2, 1, "Bob")];
+		assert_eq!(cross_check_votes(precommits, later), None);
+	}
+
+	#[test]
+	fn cross_check_votes_presented_in_other_contexts() {
+		let known = vec![Precommit::new(1, 2, "Alice"), Precommit::new(1, 2, "Bob")];
+		// Bob's precommit for block 1 is from another voter set, and Carol's from another round.
+		let presented = vec![
+			Precommit::new(1, 1, "Alice"),
+			Precommit::new(1, 1, "Bob").in_set(1),
+			Precommit::new(2, 1, "Carol"),
+		];
+
+		// Compared by its own context, Bob's vote equivocates with nothing, which hides that it
+		// doesn't belong with the others.
+		assert_eq!(
+			cross_check_votes(presented.clone(), known.clone())
+				.unwrap()
+				.len(),
+			1
+		);
+		assert_eq!(
+			cross_check_votes_in_round(0, 1, presented, known),
+			CrossCheck {
+				equivocations: vec![Equivocation {
+					voter: "Alice".to_string(),
+					set_id: 0,
+					blocks: vec![1, 2],
+				}],
+				misplaced: vec![
+					MisplacedVote {
+						voter: "Bob".to_string(),
+						target: 1,
+						set_id: 1,
+						round: 1,
+					},
+					MisplacedVote {
+						voter: "Carol".to_string(),
+						target: 1,
+						set_id: 0,
+						round: 2,
+					},
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn query_reply_consistent_with_commit() {
+		// 0 -> 1 -> 2
+		//       \-> 3
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+		let voters: Vec<VoterId> = ["Alice", "Bob", "Carol", "Dave"]
+			.iter()
+			.map(|v| v.to_string())
+			.collect();
+
+		// A supermajority for the common ancestor does not rule out block 3
+		let precommits = QueryResponse::Precommits(vec![
+			Precommit::new(1, 1, "Alice"),
+			Precommit::new(1, 1, "Bob"),
+			Precommit::new(1, 2, "Carol"),
+		]);
+		assert!(query_reply_is_consistent_with_commit(&precommits, 3, &voters, &chain).unwrap());
+
+		// A supermajority for block 2 on the other branch does
+		let precommits = QueryResponse::Precommits(vec![
+			Precommit::new(1, 2, "Alice"),
+			Precommit::new(1, 2, "Bob"),
+			Precommit::new(1, 2, "Carol"),
+		]);
+		assert!(!query_reply_is_consistent_with_commit(&precommits, 3, &voters, &chain).unwrap());
+
+		// The same supermajority of prevotes doesn't, as the precommits can still keep the
+		// estimate at block 1
+		let prevotes = QueryResponse::Prevotes(vec![
+			Prevote::new(1, 2, "Alice"),
+			Prevote::new(1, 2, "Bob"),
+			Prevote::new(1, 2, "Carol"),
+		]);
+		assert!(query_reply_is_consistent_with_commit(&prevotes, 3, &voters, &chain).unwrap());
+	}
+
+	#[test]
+	fn query_reply_validity_beyond_inline_voters() {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+		for num_voters in [4, INLINE_VOTERS, INLINE_VOTERS + 1, 3001] {
+			let voter_set = VoterSet::new(
+				&(0..num_voters)
+					.map(|index| format!("voter{}", index))
+					.collect::<Vec<_>>(),
+			);
+			let voters = voter_set.voter_ids();
+			let precommits = |for_block_2: usize, for_block_3: usize| {
+				let targets =
+					std::iter::repeat_n(2, for_block_2).chain(std::iter::repeat_n(3, for_block_3));
+				QueryResponse::Precommits(
+					voters
+						.iter()
+						.zip(targets)
+						.map(|(voter, target)| Precommit::new(2, target, voter))
+						.collect(),
+				)
+			};
+
+			// Block 2 can't have a supermajority when more than a third precommit for block 3.
+			let third = num_voters / 3;
+			let valid = precommits(num_voters - third - 1, third + 1);
+			assert!(check_query_reply_is_valid(&valid, 2, &voters, &chain)
+				.unwrap()
+				.is_none());
+			let invalid = precommits(num_voters - third, third);
+			assert!(check_query_reply_is_valid(&invalid, 2, &voters, &chain)
+				.unwrap()
+				.is_some());
+
+			// Voters that didn't vote could still have voted for block 2.
+			let absent = precommits(0, third);
+			assert!(check_query_reply_is_valid(&absent, 2, &voters, &chain)
+				.unwrap()
+				.is_some());
+		}
+	}
+
+	#[test]
+	fn query_reply_validity_at_the_boundary() {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+		for num_voters in [6, 9, 12] {
+			let voters = VoterSet::new(
+				&(0..num_voters)
+					.map(|index| format!("voter{}", index))
+					.collect::<Vec<_>>(),
+			)
+			.voter_ids();
+			// Precommits for block 2 from the first voters and for block 3 from the next, with the
+			// rest absent.
+			let precommits = |for_block_2: usize, for_block_3: usize| {
+				let targets =
+					std::iter::repeat_n(2, for_block_2).chain(std::iter::repeat_n(3, for_block_3));
+				QueryResponse::Precommits(
+					voters
+						.iter()
+						.zip(targets)
+						.map(|(voter, target)| Precommit::new(2, target, voter))
+						.collect(),
+				)
+			};
+
+			// Votes including block 2 and absent voters making up exactly two thirds of the set
+			// rule out a supermajority for it, one voter more doesn't.
+			let two_thirds = 2 * num_voters / 3;
+			for absent in [0, 1, two_thirds] {
+				let voted = num_voters - absent;
+				let valid = precommits(two_thirds - absent, voted - (two_thirds - absent));
+				assert!(check_query_reply_is_valid(&valid, 2, &voters, &chain)
+					.unwrap()
+					.is_none());
+				let invalid =
+					precommits(two_thirds - absent + 1, voted - (two_thirds - absent + 1));
+				assert!(check_query_reply_is_valid(&invalid, 2, &voters, &chain)
+					.unwrap()
+					.is_some());
+			}
+		}
+	}
+
+	#[test]
+	fn minimal_query_replies() {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+		for num_voters in [4, 7, 10] {
+			let voters = VoterSet::new(
+				&(0..num_voters)
+					.map(|index| format!("voter{}", index))
+					.collect::<Vec<_>>(),
+			)
+			.voter_ids();
+			// A third of the voters precommit for block 2, the rest for block 3.
+			let third = num_voters / 3;
+			let full = QueryResponse::Precommits(
+				voters
+					.iter()
+					.enumerate()
+					.map(|(index, voter)| {
+						Precommit::new(2, if index < third { 2 } else { 3 }, voter)
+					})
+					.collect(),
+			);
+			let minimal = minimal_query_reply(&full, 2, &voters, &chain)
+				.unwrap()
+				.unwrap();
+			assert!(check_query_reply_is_valid(&minimal, 2, &voters, &chain)
+				.unwrap()
+				.is_none());
+
+			// Only votes for block 3 are kept, and leaving out any of them no longer shows anything.
+			assert!(minimal.target_numbers().all(|target| target == 3));
+			assert!(minimal.len() < full.len());
+			let QueryResponse::Precommits(precommits) = &minimal else {
+				unreachable!()
+			};
+			for left_out in 0..precommits.len() {
+				let mut fewer = precommits.clone();
+				fewer.remove(left_out);
+				let fewer = QueryResponse::Precommits(fewer);
+				assert!(check_query_reply_is_valid(&fewer, 2, &voters, &chain)
+					.unwrap()
+					.is_some());
+			}
+		}
+
+		// Nothing to reduce if even the whole response shows nothing.
+		let voters = VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]).voter_ids();
+		let for_block_2 = QueryResponse::Precommits(
+			voters
+				.iter()
+				.map(|voter| Precommit::new(2, 2, voter))
+				.collect(),
+		);
+		assert_eq!(
+			minimal_query_reply(&for_block_2, 2, &voters, &chain),
+			Ok(None)
+		);
+	}
+
+	#[test]
+	fn voter_set_rejects_duplicates() {
+		assert_eq!(
+			VoterSet::try_new(&["Alice", "Bob", "Alice"]).unwrap_err(),
+			VoterSetError::DuplicateVoter("Alice".to_string()),
+		);
+		assert_eq!(
+			VoterSet::try_new(&["Alice", "Bob", "Carol", "Dave"])
+				.unwrap()
+				.len(),
+			4
+		);
+	}
+
+	#[test]
+	fn voter_set_with_equal_display_names() {
+		let voter_set = VoterSet::with_display_names(&[
+			("alice-1", "Alice"),
+			("alice-2", "Alice"),
+			("bob", "Bob"),
+		])
+		.unwrap();
+		assert_eq!(voter_set.len(), 3);
+		assert_eq!(voter_set.display_name("alice-1"), Some("Alice"));
+		assert_eq!(voter_set.display_name("alice-2"), Some("Alice"));
+
+		// The two Alices count as distinct voters, while the same voter counted twice doesn't
+		assert_eq!(voter_set.count_members(["alice-1", "alice-2"]), 2);
+		assert_eq!(voter_set.count_members(["alice-1", "alice-1", "eve"]), 1);
+		assert!(voter_set.is_supermajority(voter_set.count_members(["alice-1", "alice-2", "bob"])));
+		assert!(!voter_set.is_supermajority(voter_set.count_members(["alice-1", "alice-1", "bob"])));
+	}
+
+	fn round_state_for(id: &str) -> (RoundState, Chain) {
+		// 0 - 1 - 2 - 3
+		//      \- 4
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 2), (4, 1)]);
+		let voter_set = VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]);
+		let round = VotingRound::new(2, voter_set);
+		let state = RoundState::new(id.into(), round, RoundTimers::new(100, 10));
+		(state, chain)
+	}
+
+	#[test]
+	fn round_state_transitions() {
+		let (mut state, chain) = round_state_for("Alice");
+		assert_eq!(state.stage(), RoundStage::Start);
+		assert_eq!(state.timers().prevote_at, 120);
+		assert_eq!(state.timers().precommit_at, 140);
+
+		// Prevote once the timer expires, or earlier if the previous round is completable.
+		assert!(!state.should_prevote(110, false));
+		assert!(state.should_prevote(110, true));
+		assert!(state.should_prevote(120, false));
+		state.prevote(3, &chain);
+		assert_eq!(state.stage(), RoundStage::Prevoted);
+		assert_eq!(state.prevote_ghost(&chain), None);
+
+		state.add_prevote(Prevote::new(2, 3, "Bob"), &chain);
+		state.add_prevote(Prevote::new(2, 2, "Carol"), &chain);
+		assert_eq!(state.prevote_ghost(&chain), Some(2));
+		assert_eq!(state.estimate(&chain), Some(2));
+
+		// Without precommits, block 3 could still get a supermajority so we wait for the timer.
+		assert!(!state.is_completable(&chain));
+		assert!(!state.should_precommit(130, 1, &chain));
+		assert!(state.should_precommit(140, 1, &chain));
+		// Never precommit for anything not including the previous estimate.
+		assert!(!state.should_precommit(140, 4, &chain));
+
+		assert_eq!(state.precommit(&chain), Some(Precommit::new(2, 2, "Alice")));
+		assert_eq!(state.stage(), RoundStage::Precommitted);
+
+		// With two precommits for block 2, block 3 can no longer get a supermajority.
+		state.add_precommit(Precommit::new(2, 2, "Bob"), &chain);
+		assert_eq!(state.stage(), RoundStage::Completable);
+		assert_eq!(state.precommit_ghost(&chain), None);
+		state.add_precommit(Precommit::new(2, 3, "Carol"), &chain);
+		assert_eq!(state.precommit_ghost(&chain), Some(2));
+		assert!(state.is_completable(&chain));
+		assert_eq!(state.stage(), RoundStage::Finalized);
+		assert_eq!(state.round().finalized, Some(2));
+	}
+
+	#[test]
+	fn round_state_estimate_below_ghost() {
+		let (mut state, chain) = round_state_for("Alice");
+		state.prevote(3, &chain);
+		state.add_prevote(Prevote::new(2, 3, "Bob"), &chain);
+		state.add_prevote(Prevote::new(2, 3, "Carol"), &chain);
+		assert_eq!(state.prevote_ghost(&chain), Some(3));
+		state.precommit(&chain);
+
+		// Bob and Carol precommit on the other fork, so only block 1 can still be finalized.
+		state.add_precommit(Precommit::new(2, 4, "Bob"), &chain);
+		assert_eq!(state.estimate(&chain), Some(3));
+		state.add_precommit(Precommit::new(2, 4, "Carol"), &chain);
+		assert_eq!(state.estimate(&chain), Some(1));
+		assert!(state.is_completable(&chain));
+		assert_eq!(state.precommit_ghost(&chain), Some(1));
+		assert_eq!(state.stage(), RoundStage::Finalized);
+	}
+
+	#[test]
+	fn round_state_finalization_lag() {
+		let finalize_with_lag = |lag| {
+			let (state, chain) = round_state_for("Alice");
+			let mut state = state.with_finalization_lag(lag);
+			state.prevote(3, &chain);
+			for voter in &["Bob", "Carol"] {
+				state.add_prevote(Prevote::new(2, 3, *voter), &chain);
+			}
+			state.precommit(&chain);
+			for voter in &["Bob", "Carol"] {
+				state.add_precommit(Precommit::new(2, 3, *voter), &chain);
+			}
+			assert_eq!(state.precommit_ghost(&chain), Some(3));
+			state.round().finalized
+		};
+		assert_eq!(finalize_with_lag(0), Some(3));
+		assert_eq!(finalize_with_lag(2), Some(1));
+		// Genesis is final already.
+		assert_eq!(finalize_with_lag(3), None);
+	}
+
+	#[test]
+	fn round_state_batches() {
+		let (state, chain) = round_state_for("Alice");
+		let mut alice = state.with_batch_interval(5);
+		let (mut bob, _) = round_state_for("Bob");
+		assert_eq!(alice.take_batch(100), None);
+
+		alice.prevote(3, &chain);
+		alice.add_prevote(Prevote::new(2, 3, "Carol"), &chain);
+		let batch = alice.take_batch(100).unwrap();
+		assert_eq!(batch.len(), 2);
+		// Nothing new to send, and then too soon to send again.
+		assert_eq!(alice.take_batch(110), None);
+		alice.add_prevote(Prevote::new(2, 3, "Dave"), &chain);
+		assert_eq!(alice.take_batch(104), None);
+		assert_eq!(alice.take_batch(105).unwrap().len(), 3);
+
+		bob.prevote(3, &chain);
+		assert_eq!(bob.merge_batch(&batch, &chain), 2);
+		assert_eq!(bob.merge_batch(&batch, &chain), 0);
+		assert_eq!(bob.prevote_ghost(&chain), Some(3));
+
+		// Votes from other rounds don't count towards this one.
+		let mut replayed = batch.clone();
+		replayed.prevotes = vec![Prevote::new(1, 3, "Dave")];
+		assert_eq!(bob.merge_batch(&replayed, &chain), 0);
+		replayed.round = 1;
+		assert_eq!(bob.merge_batch(&replayed, &chain), 0);
+	}
+
+	// Run a round with every voter voting for block 3 as soon as it can, and return the number of
+	// messages sent until every voter has finalized it.
+	fn messages_to_finalize(num_voters: usize, batched: bool) -> usize {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 2)]);
+		let names: Vec<_> = (0..num_voters)
+			.map(|index| format!("voter{}", index))
+			.collect();
+		let voter_set = VoterSet::new(&names);
+		let mut states: Vec<_> = names
+			.iter()
+			.map(|name| {
+				let round = VotingRound::new(1, voter_set.clone());
+				RoundState::new(name.as_str().into(), round, RoundTimers::new(0, 0))
+			})
+			.collect();
+		// For sending votes one by one, how many of the votes seen each voter has passed on.
+		let mut passed_on = vec![(0, 0); num_voters];
+		let mut messages = 0;
+		for now in 0.. {
+			if states
+				.iter()
+				.all(|state| state.stage() == RoundStage::Finalized)
+			{
+				break;
+			}
+			assert!(now < 10, "the round never finalized");
+			let mut outgoing = Vec::new();
+			for (index, state) in states.iter_mut().enumerate() {
+				if state.should_prevote(now, false) {
+					state.prevote(3, &chain);
+				}
+				if state.should_precommit(now, 0, &chain) {
+					state.precommit(&chain);
+				}
+				if batched {
+					outgoing.extend(state.take_batch(now).map(|batch| (index, batch)));
+				} else {
+					let round = state.round();
+					let (prevotes, precommits) = &mut passed_on[index];
+					for prevote in &round.prevotes[*prevotes..] {
+						let batch = VoteBatch {
+							round: 1,
+							set_id: 0,
+							prevotes: vec![prevote.clone()],
+							precommits: vec![],
+						};
+						outgoing.push((index, batch));
+					}
+					for precommit in &round.precommits[*precommits..] {
+						let batch = VoteBatch {
+							round: 1,
+							set_id: 0,
+							prevotes: vec![],
+							precommits: vec![precommit.clone()],
+						};
+						outgoing.push((index, batch));
+					}
+					*prevotes = round.prevotes.len();
+					*precommits = round.precommits.len();
+				}
+			}
+			for (sender, batch) in outgoing {
+				for (index, state) in states.iter_mut().enumerate() {
+					if index != sender {
+						messages += 1;
+						state.merge_batch(&batch, &chain);
+					}
+				}
+			}
+		}
+		messages
+	}
+
+	#[test]
+	fn batching_votes_saves_messages() {
+		for num_voters in [4, 7, 10] {
+			let batched = messages_to_finalize(num_voters, true);
+			// Every voter sends its state of the round once after prevoting and once after
+			// precommitting, instead of passing on every vote it sees.
+			assert_eq!(batched, 2 * num_voters * (num_voters - 1));
+			// Passing on votes one by one takes a number of messages cubic in the number of voters.
+			let unbatched = messages_to_finalize(num_voters, false);
+			assert!(batched * num_voters <= 2 * unbatched);
+		}
+	}
+
+	#[test]
+	fn estimate_lower_bound_follows_own_precommit() {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 2), (4, 1)]);
+		let voters: Vec<VoterId> = ["Alice", "Bob", "Carol", "Dave"]
+			.iter()
+			.map(|id| id.to_string())
+			.collect();
+		let precommits = vec![
+			Precommit::new(2, 3, "Alice"),
+			Precommit::new(2, 3, "Bob"),
+			Precommit::new(2, 4, "Carol"),
+		];
+		// Dave could still have precommitted for block 3.
+		assert_eq!(
+			estimate_lower_bound(&precommits, "Alice", &voters, &chain),
+			Ok(Some(3))
+		);
+
+		// With Dave on the other fork, only block 1 can still get a supermajority.
+		let mut precommits = precommits;
+		precommits.push(Precommit::new(2, 4, "Dave"));
+		assert_eq!(
+			estimate_lower_bound(&precommits, "Alice", &voters, &chain),
+			Ok(Some(1))
+		);
+		assert_eq!(
+			estimate_lower_bound(&precommits, "Eve", &voters, &chain),
+			Ok(None)
+		);
+	}
+
+	#[test]
+	fn equivocators_count_for_every_block() {
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 1)]);
+		let voter_set = VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]);
+		let prevotes = vec![
+			Prevote::new(2, 2, "Alice"),
+			Prevote::new(2, 2, "Bob"),
+			Prevote::new(2, 3, "Carol"),
+			Prevote::new(2, 2, "Carol"),
+		];
+		assert_eq!(supporters(&prevotes, 2, &chain), 3);
+		assert_eq!(supporters(&prevotes, 3, &chain), 1);
+		assert_eq!(ghost(&prevotes, &voter_set, &chain), Some(2));
+	}
+
+	#[test]
+	fn prevotes_justifying_precommits() {
+		// 0 - 1 - 2 - 3
+		//      \- 4
+		let chain = Chain::new_from(&[(1, 0), (2, 1), (3, 2), (4, 1)]);
+		let mut round = VotingRound::new(2, VoterSet::new(&["Alice", "Bob", "Carol", "Dave"]));
+		round.prevote(&[(3, "Alice"), (3, "Bob"), (2, "Carol"), (4, "Dave")]);
+		round.precommit(&[(2, "Alice"), (3, "Bob"), (4, "Dave")]);
+
+		// g(V) is block 2, so only precommits for blocks up to it are justified.
+		assert!(round.prevotes_justify_precommit_of("Alice", &chain));
+		assert!(!round.prevotes_justify_precommit_of("Bob", &chain));
+		assert!(!round.prevotes_justify_precommit_of("Dave", &chain));
+		// Nor is a precommit never made.
+		assert!(!round.prevotes_justify_precommit_of("Carol", &chain));
+	}
 }
@@ -15,90 +15,960 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
-	message::{Message, Payload},
-	protocol::EquivocationDetected,
-	voter::{Voter, VoterId},
+	behaviour::Behaviour,
+	block::BlockNumber,
+	chain::ChainError,
+	coverage::Coverage,
+	crypto::{KeyRegistry, SecretKey, VerificationFaults},
+	event::{DetectionEvent, Event, EventId, EventKind, EventLog},
+	execution::{Execution, Job, JobResult, VoterThreads},
+	inference::{EvidenceChain, Transcript},
+	memory,
+	message::{Message, Request, Response},
+	network::{NetworkModel, PartitionSchedule, Rng},
+	protocol::{ConflictId, EquivocationDetected, PlannedRound, ReplyKinds, SignedEvidenceBundle},
+	report::{
+		CommitReport, DeadlineReport, Exoneration, GuaranteeStatus, InstanceVerdicts,
+		OffenderAnalysis, RoundVerdicts, RunSummary, SimulationReport, Termination,
+		UnjustifiedCommitParticipation,
+	},
+	snapshot::{Snapshot, SnapshotHistory},
+	verification::{verify_equivocation_proof, HeaderProvider},
+	voter::{QueryKind, QueryPlan, Voter, VoterId},
+	voting::{DivergentCommitVariants, MismatchedCommitTarget, SetId, VoterSet},
 };
-use std::collections::BTreeMap;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, BTreeSet, VecDeque},
+	io::Write,
+	sync::Arc,
+};
+
+// Unless set, runs are cut off at this many times the estimated completion bound of the world.
+const COMPLETION_BOUND_MULTIPLE: usize = 4;
+const DEFAULT_SEED: u64 = 0;
+// Mixed into the seed for drawing the keys of the voters, so that they are independent of the
+// other draws from it.
+const KEY_SEED_SALT: u64 = 0x6b65_7973;
 
-const MAX_TICKS: usize = 5000;
+/// A check run against the world after every tick. Returns a description of the violation if the
+/// invariant doesn't hold.
+pub type Invariant = Box<dyn Fn(&World) -> Option<String>>;
+
+/// When [`World::run_until_settled`] stops, short of the tick limit.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopCondition {
+	// Once there is nothing left to do.
+	#[default]
+	Settled,
+	// As soon as the honest voters between them have proven more than a third of a voter set to
+	// have misbehaved, which is what the protocol guarantees.
+	AccountabilityReached,
+	// As soon as any voter has detected a conflict, before any of its queries are sent.
+	Detected,
+}
 
 pub struct World {
 	voters: BTreeMap<VoterId, Voter>,
 	current_tick: usize,
+	max_ticks: usize,
+	network: NetworkModel,
+	partitions: PartitionSchedule,
+	rng: Rng,
+	// Messages sent but not yet delivered, together with the tick they are due.
+	in_flight: Vec<(usize, Message)>,
+	// Messages delivered to each voter but not yet handled, numbered in the order they arrived.
+	mailboxes: BTreeMap<VoterId, VecDeque<(usize, Message)>>,
+	delivered_count: usize,
+	// Messages that went out over the network, whether or not they arrived.
+	sent_count: usize,
+	// Queries about estimates and prevotes sent by any voter.
+	queries_sent: usize,
+	// The most messages each voter handles per tick, unless overridden for the voter. Unlimited if
+	// not set.
+	processing_budget: Option<usize>,
+	processing_budgets: BTreeMap<VoterId, usize>,
+	// Messages each voter has handled so far this tick.
+	handled_this_tick: BTreeMap<VoterId, usize>,
+	// Times a message was left in a mailbox for a later tick because its receiver was out of budget.
+	carried_over: usize,
+	invariants: Vec<Invariant>,
+	invariant_violations: Vec<(usize, String)>,
+	key_registry: KeyRegistry,
+	event_log: EventLog,
+	// Links the queries and responses in the event log to the offences they proved.
+	transcript: Transcript,
+	// Record internal inconsistencies as events instead of panicking.
+	strict: bool,
+	snapshots: Option<SnapshotHistory>,
+	// Ticks after the first detected conflict by which every honest voter should have a complete
+	// proof.
+	deadline: Option<usize>,
+	first_detection: Option<usize>,
+	proofs_completed: BTreeMap<VoterId, usize>,
+	stop_condition: StopCondition,
+	// Skip over the ticks at which nothing is due, see `WorldBuilder::with_fast_forward`.
+	fast_forward: bool,
+	// The tick at which the honest voters had proven more than a third of a voter set to have
+	// misbehaved.
+	accountable_at: Option<usize>,
+	// The voters' own threads, when each voter runs on a thread of its own.
+	threads: Option<VoterThreads>,
+	subscribers: Vec<Sender<DetectionEvent>>,
 }
 
 impl World {
 	pub fn new(voters: BTreeMap<VoterId, Voter>) -> Self {
-		Self {
-			voters,
-			current_tick: 0,
+		WorldBuilder::new().with_voters(voters).build()
+	}
+
+	pub fn voters(&self) -> &BTreeMap<VoterId, Voter> {
+		&self.voters
+	}
+
+	pub fn current_tick(&self) -> usize {
+		self.current_tick
+	}
+
+	pub fn invariant_violations(&self) -> &[(usize, String)] {
+		&self.invariant_violations
+	}
+
+	pub fn event_log(&self) -> &EventLog {
+		&self.event_log
+	}
+
+	fn record(&mut self, voter: &VoterId, kind: EventKind, summary: String) -> EventId {
+		self.event_log
+			.record(Event::new(self.current_tick, voter.clone(), kind, summary))
+	}
+
+	/// The event recorded under the id, as referred to by the evidence chains.
+	pub fn event(&self, id: EventId) -> Option<&Event> {
+		self.event_log.get(id)
+	}
+
+	/// How each offence proven so far was arrived at, in the order they were found.
+	pub fn evidence_chains(&self) -> &[EvidenceChain] {
+		self.transcript.chains()
+	}
+
+	/// The evidence chains of the offences proven against the voter.
+	pub fn evidence_chains_against(&self, offender: &str) -> Vec<&EvidenceChain> {
+		self.transcript
+			.chains()
+			.iter()
+			.filter(|chain| chain.offender == offender)
+			.collect()
+	}
+
+	/// Be told about detected conflicts, queries and proven offences as they happen. Events are
+	/// buffered until received, and dropping the receiver unsubscribes.
+	pub fn subscribe(&mut self) -> Receiver<DetectionEvent> {
+		let (sender, receiver) = unbounded();
+		self.subscribers.push(sender);
+		receiver
+	}
+
+	fn notify(&mut self, event: DetectionEvent) {
+		self.subscribers
+			.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+	}
+
+	// Move the protocol bugs the voter ran into to the event log.
+	fn record_protocol_bugs(&mut self, voter: &VoterId) {
+		let bugs = match self.voters.get_mut(voter) {
+			Some(voter) => std::mem::take(&mut voter.protocol_bugs),
+			None => return,
+		};
+		for bug in bugs {
+			self.record(voter, EventKind::ProtocolBug, bug.to_string());
 		}
 	}
 
-	pub fn list_commits(&self) {
-		for voter in self.voters.values() {
-			println!("{}:", voter);
-			voter.list_commits();
+	// Move what the voter noted about what it was doing to the event log.
+	fn record_notes(&mut self, voter: &VoterId) {
+		let (notes, stages) = match self.voters.get_mut(voter) {
+			Some(voter) => (voter.take_notes(), voter.take_commit_stages()),
+			None => return,
+		};
+		for note in notes {
+			self.record(voter, EventKind::Note, note);
+		}
+		for stage in stages {
+			self.record(voter, EventKind::CommitStage, stage.to_string());
+		}
+	}
+
+	// Have each voter do its job, on its own thread if each voter has one, and hand back what came
+	// of the jobs, in the order of the voters' ids when they ran on their own threads.
+	fn run_jobs(&mut self, jobs: Vec<(VoterId, Job)>) -> Vec<(VoterId, JobResult)> {
+		let tick = self.current_tick;
+		let voters = &mut self.voters;
+		match &mut self.threads {
+			None => jobs
+				.into_iter()
+				.map(|(id, job)| {
+					let result = job.run(voters.get_mut(&id).unwrap(), tick);
+					(id, result)
+				})
+				.collect(),
+			Some(threads) => {
+				let work = jobs
+					.into_iter()
+					.map(|(id, job)| (voters.remove(&id).unwrap(), job))
+					.collect();
+				threads
+					.run(work, tick)
+					.into_iter()
+					.map(|(voter, result)| {
+						let id = voter.id.clone();
+						voters.insert(id.clone(), voter);
+						(id, result)
+					})
+					.collect()
+			}
 		}
 	}
 
+	// Messages are handled one at a time, in the order they arrived, unless each voter has a
+	// thread of its own, in which case each voter is handed all of its messages at once.
+	fn jobs_for(
+		&self,
+		messages: Vec<Message>,
+		job: fn(Vec<Message>) -> Job,
+	) -> Vec<(VoterId, Job)> {
+		if self.threads.is_none() {
+			return messages
+				.into_iter()
+				.map(|message| (message.receiver.clone(), job(vec![message])))
+				.collect();
+		}
+		let mut by_voter: BTreeMap<VoterId, Vec<Message>> = BTreeMap::new();
+		for message in messages {
+			by_voter
+				.entry(message.receiver.clone())
+				.or_default()
+				.push(message);
+		}
+		by_voter
+			.into_iter()
+			.map(|(voter, messages)| (voter, job(messages)))
+			.collect()
+	}
+
+	// A message for a voter that doesn't exist can only come from a bug in the simulation.
+	fn unknown_receiver(&mut self, message: &Message) {
+		let bug = format!(
+			"{} from {} to unknown voter {}",
+			message.content.summary(),
+			message.sender,
+			message.receiver
+		);
+		if !self.strict {
+			panic!("{}", bug);
+		}
+		self.record(&"world".to_string(), EventKind::ProtocolBug, bug);
+	}
+
+	pub fn protocol_bugs(&self) -> Vec<&Event> {
+		self.event_log
+			.events()
+			.iter()
+			.filter(|event| event.kind == EventKind::ProtocolBug)
+			.collect()
+	}
+
+	fn record_sent(&mut self, messages: &[Message]) {
+		for message in messages {
+			let summary = format!("{} to {}", message.content.summary(), message.receiver);
+			let event = self.record(&message.sender, EventKind::MessageSent, summary);
+			let query = match message.content.request() {
+				Some(Request::WhyDidEstimateForRoundNotIncludeBlock(round, ..)) => {
+					Some((*round, QueryKind::Estimate))
+				}
+				Some(Request::WhichPrevotesSeenInRound(round, _)) => {
+					Some((*round, QueryKind::Prevotes))
+				}
+				_ => None,
+			};
+			if let Some((round, kind)) = query {
+				self.queries_sent += 1;
+				self.transcript
+					.query_sent(&message.sender, &message.receiver, round, kind, event);
+				self.notify(DetectionEvent::QuerySent {
+					tick: self.current_tick,
+					verifier: message.sender.clone(),
+					responder: message.receiver.clone(),
+					round,
+					kind,
+				});
+			}
+		}
+	}
+
+	pub fn commit_reports(&self) -> Vec<CommitReport> {
+		self.voters
+			.values()
+			.map(|voter| voter.commit_report())
+			.collect()
+	}
+
 	pub fn tick(&mut self) {
+		self.check_invariants();
+		self.track_proofs();
+		if let Some(snapshots) = &mut self.snapshots {
+			if snapshots.is_due(self.current_tick) {
+				snapshots.push(Snapshot::new(self.current_tick, &self.voters));
+			}
+		}
+		self.handled_this_tick.clear();
 		self.current_tick += 1;
 	}
 
+	/// Number of messages delivered to the voter that it hasn't got around to handling yet.
+	pub fn backlog(&self, voter: &str) -> usize {
+		self.mailboxes.get(voter).map_or(0, VecDeque::len)
+	}
+
+	/// Number of times a voter was out of budget for a message, which was then left for a later
+	/// tick. A message counts again for each tick it's left over.
+	pub fn messages_carried_over(&self) -> usize {
+		self.carried_over
+	}
+
+	/// Prune the chain of the voter back to the finalized block, keeping what the proofs it holds
+	/// need, see [`Voter::prune_chain`].
+	pub fn prune_chain(
+		&mut self,
+		voter: &str,
+		finalized: BlockNumber,
+	) -> Result<Vec<BlockNumber>, ChainError> {
+		self.voters
+			.get_mut(voter)
+			.expect("pruning the chain of a known voter")
+			.prune_chain(finalized)
+	}
+
+	/// The snapshots taken so far, if enabled with [`WorldBuilder::with_snapshot_interval`].
+	pub fn snapshots(&self) -> Option<&SnapshotHistory> {
+		self.snapshots.as_ref()
+	}
+
+	/// The state of the voters at the end of the given tick, as of the latest snapshot.
+	pub fn state_at(&self, tick: usize) -> Option<&Snapshot> {
+		self.snapshots.as_ref()?.state_at(tick)
+	}
+
+	// Note when conflicts are first detected and when each voter has a complete proof, and record
+	// the voters without one once the deadline, if any, has passed.
+	fn track_proofs(&mut self) {
+		let current_tick = self.current_tick;
+		if self.first_detection.is_none()
+			&& self
+				.voters
+				.values()
+				.any(|voter| voter.instances().len() > 0)
+		{
+			self.first_detection = Some(current_tick);
+		}
+		for (id, voter) in &self.voters {
+			if !self.proofs_completed.contains_key(id) && has_complete_proof(voter) {
+				self.proofs_completed.insert(id.clone(), current_tick);
+			}
+		}
+		if self.first_detection.is_some()
+			&& self.accountable_at.is_none()
+			&& self.accountability_reached()
+		{
+			self.accountable_at = Some(current_tick);
+		}
+
+		let deadline = match self.deadline {
+			Some(deadline) => deadline,
+			None => return,
+		};
+		if self.first_detection.map(|tick| tick + deadline) == Some(current_tick) {
+			let offenders = self.offenders();
+			let missed: Vec<_> = self
+				.voters
+				.keys()
+				.filter(|id| !offenders.contains(*id) && !self.proofs_completed.contains_key(*id))
+				.cloned()
+				.collect();
+			for voter in missed {
+				let summary = format!("no complete proof {} ticks after detection", deadline);
+				self.record(&voter, EventKind::DeadlineMissed, summary);
+			}
+		}
+	}
+
+	/// Everyone proven to have misbehaved by any of the voters.
+	pub fn offenders(&self) -> BTreeSet<VoterId> {
+		self.voters
+			.values()
+			.flat_map(|voter| voter.known_offenders())
+			.collect()
+	}
+
+	/// Everyone proven to have misbehaved by any of the voters, grouped by the voter set they
+	/// misbehaved in, which is the session they should be slashed in.
+	pub fn offenders_by_set(&self) -> BTreeMap<SetId, BTreeSet<VoterId>> {
+		let mut offenders: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+		for voter in self.voters.values() {
+			for (set_id, found) in voter.known_offenders_by_set() {
+				offenders.entry(set_id).or_default().extend(found);
+			}
+		}
+		offenders
+	}
+
+	/// The offenders found for each conflict, by any of the voters that investigated it.
+	pub fn offenders_per_conflict(&self) -> BTreeMap<ConflictId, BTreeSet<VoterId>> {
+		let mut offenders: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+		for voter in self.voters.values() {
+			for (conflict, found) in voter.offenders_per_conflict() {
+				offenders.entry(conflict).or_default().extend(found);
+			}
+		}
+		offenders
+	}
+
+	/// How the honest voters did against the deadline, if one was set with
+	/// [`WorldBuilder::with_deadline`]. Voters not found to have misbehaved count as honest.
+	pub fn deadline_report(&self) -> Option<DeadlineReport> {
+		let deadline = self.deadline?;
+		let offenders = self.offenders();
+		let completed = self
+			.voters
+			.keys()
+			.filter(|id| !offenders.contains(*id))
+			.map(|id| (id.clone(), self.proofs_completed.get(id).cloned()))
+			.collect();
+		Some(DeadlineReport {
+			deadline,
+			first_detection: self.first_detection,
+			completed,
+		})
+	}
+
+	/// Ticks from the first detected conflict until the last honest voter had a complete proof, if
+	/// they all have one. Voters not found to have misbehaved count as honest.
+	pub fn proof_latency(&self) -> Option<usize> {
+		let first_detection = self.first_detection?;
+		let offenders = self.offenders();
+		self.voters
+			.keys()
+			.filter(|id| !offenders.contains(*id))
+			.map(|id| {
+				self.proofs_completed
+					.get(id)
+					.map(|tick| tick - first_detection)
+			})
+			.collect::<Option<Vec<_>>>()?
+			.into_iter()
+			.max()
+	}
+
+	// The offenders in each voter set that the voters not found to have misbehaved have between
+	// them proven.
+	fn proven_by_set(&self) -> BTreeMap<SetId, BTreeSet<VoterId>> {
+		let offenders = self.offenders();
+		let mut proven: BTreeMap<SetId, BTreeSet<VoterId>> = BTreeMap::new();
+		for (id, voter) in &self.voters {
+			if offenders.contains(id) {
+				continue;
+			}
+			for (set_id, found) in voter.known_offenders_by_set() {
+				proven.entry(set_id).or_default().extend(found);
+			}
+		}
+		proven
+	}
+
+	// Whether the voters not found to have misbehaved have between them proven more than a third
+	// of any voter set to have misbehaved.
+	fn accountability_reached(&self) -> bool {
+		self.guarantee().is_met()
+	}
+
+	/// How the offenders proven by the honest voters measure up to the f + 1 of a voter set the
+	/// protocol guarantees to find. With several voter sets, the one with the most offenders
+	/// proven beyond its f + 1 counts.
+	pub fn guarantee(&self) -> GuaranteeStatus {
+		if self.first_detection.is_none() {
+			return GuaranteeStatus::NoConflict;
+		}
+		let proven = self.proven_by_set();
+		self.voter_sets()
+			.into_iter()
+			.map(|(set_id, set)| {
+				let found = proven
+					.get(&set_id)
+					.map_or(0, |found| set.count_members(found));
+				(
+					found as isize - set.max_faulty() as isize,
+					found,
+					set.max_faulty(),
+				)
+			})
+			.max()
+			.map_or(GuaranteeStatus::NoConflict, |(_, found, max_faulty)| {
+				GuaranteeStatus::new(found, max_faulty)
+			})
+	}
+
+	// Every voter set known to any voter.
+	fn voter_sets(&self) -> BTreeMap<SetId, VoterSet> {
+		self.voters
+			.values()
+			.flat_map(|voter| {
+				let in_rounds = voter.rounds().map(|round| round.voter_set);
+				std::iter::once(voter.voter_set.clone()).chain(in_rounds)
+			})
+			.map(|set| (set.set_id, set))
+			.collect()
+	}
+
+	/// Ticks from the first detected conflict until the honest voters between them had proven more
+	/// than a third of a voter set to have misbehaved, if they have.
+	pub fn accountability_latency(&self) -> Option<usize> {
+		Some(self.accountable_at? - self.first_detection?)
+	}
+
+	/// Number of messages sent over the network so far, including those that were lost.
+	pub fn messages_sent(&self) -> usize {
+		self.sent_count
+	}
+
+	/// Queries about estimates and prevotes sent by any voter, including those that were lost.
+	pub fn queries_sent(&self) -> usize {
+		self.queries_sent
+	}
+
+	/// The protocol instances started by all the voters together.
+	pub fn instances_started(&self) -> usize {
+		self.voters
+			.values()
+			.map(|voter| voter.instances().len())
+			.sum()
+	}
+
+	// Nothing left to deliver or handle and no voter has anything scheduled.
+	fn settled(&self) -> bool {
+		self.in_flight.is_empty()
+			&& self.mailboxes.values().all(VecDeque::is_empty)
+			&& self
+				.voters
+				.values()
+				.all(|voter| voter.pending_actions().next().is_none())
+	}
+
+	// Whether the run can stop before settling, as set with `WorldBuilder::with_stop_condition`.
+	fn stop_condition_met(&self) -> bool {
+		match self.stop_condition {
+			StopCondition::Settled => false,
+			StopCondition::AccountabilityReached => self.accountable_at.is_some(),
+			StopCondition::Detected => self.first_detection.is_some(),
+		}
+	}
+
+	/// Run until there is nothing left to do, the stop condition is met or the tick limit is
+	/// reached. The peak memory in the summary is measured from the start of this call.
+	pub fn run_until_settled(&mut self) -> RunSummary {
+		memory::reset_peak();
+		while !self.completed() {
+			let requests = self.process_actions();
+			let responses = self.handle_requests(requests);
+			self.handle_responses(responses);
+			self.tick();
+			if self.fast_forward {
+				self.skip_idle_ticks();
+			}
+		}
+		self.summary()
+	}
+
+	/// Run to the end, as [`World::run_until_settled`] does, and report what the voters detected
+	/// along the way.
+	pub fn run(&mut self) -> SimulationReport {
+		let summary = self.run_until_settled();
+		SimulationReport {
+			ticks: summary.ticks,
+			termination: summary.termination,
+			messages: summary.messages,
+			queries_sent: self.queries_sent,
+			instances_started: self.instances_started(),
+			equivocations: self
+				.voters
+				.iter()
+				.map(|(id, voter)| (id.clone(), voter.equivocations_detected()))
+				.collect(),
+		}
+	}
+
+	/// The first tick from the current one on at which anything is due: an action of a voter, the
+	/// delivery of a message or the handling of one waiting in a mailbox. `None` if nothing is.
+	pub fn next_scheduled_tick(&self) -> Option<usize> {
+		if self.mailboxes.values().any(|mailbox| !mailbox.is_empty()) {
+			return Some(self.current_tick);
+		}
+		let deliveries = self.in_flight.iter().map(|(deliver_at, _)| *deliver_at);
+		let actions = self
+			.voters
+			.values()
+			.filter_map(|voter| voter.pending_actions().next().map(|(tick, _)| *tick));
+		deliveries
+			.chain(actions)
+			.min()
+			.map(|tick| tick.max(self.current_tick))
+	}
+
+	// Until the next tick at which anything is due, no voter has anything to do and nothing about
+	// the voters changes. Unless invariants, snapshots or a deadline look at every tick, we jump
+	// straight there, otherwise only the bookkeeping at the end of each tick is done.
+	fn skip_idle_ticks(&mut self) {
+		let next = match self.next_scheduled_tick() {
+			Some(next) => next.min(self.max_ticks),
+			None => return,
+		};
+		if self.stop_condition_met() {
+			return;
+		}
+		if self.invariants.is_empty() && self.snapshots.is_none() && self.deadline.is_none() {
+			self.current_tick = self.current_tick.max(next);
+			return;
+		}
+		while self.current_tick < next && !self.stop_condition_met() {
+			self.tick();
+		}
+	}
+
+	pub fn summary(&self) -> RunSummary {
+		RunSummary {
+			ticks: self.current_tick,
+			settled: self.settled(),
+			termination: self.termination(),
+			offenders: self.offenders(),
+			guarantee: self.guarantee(),
+			exonerated: self.exonerated(),
+			protocol_bugs: self.protocol_bugs().len(),
+			invariant_violations: self.invariant_violations.len(),
+			messages: self.sent_count,
+			proof_latency: self.proof_latency(),
+			accountability_latency: self.accountability_latency(),
+			deadline: self.deadline_report(),
+			peak_memory: memory::peak_bytes(),
+			peak_outstanding_queries: self
+				.voters
+				.values()
+				.map(Voter::peak_outstanding_queries)
+				.max()
+				.unwrap_or(0),
+			explanation_cache: self
+				.voters
+				.values()
+				.map(Voter::explanation_cache)
+				.fold(Default::default(), |total, stats| total + stats),
+			duplicates_suppressed: self.voters.values().map(Voter::duplicates_suppressed).sum(),
+			verdicts: self
+				.verdicts()
+				.into_iter()
+				.flat_map(|(detector, instances)| {
+					instances
+						.into_iter()
+						.map(move |(conflict, rounds)| InstanceVerdicts {
+							detector: detector.clone(),
+							block_not_included: conflict.block_not_included,
+							new_block: conflict.new_block,
+							rounds,
+						})
+				})
+				.collect(),
+			evidence_chains: self.transcript.chains().to_vec(),
+		}
+	}
+
+	/// At worst, how many ticks from the current one on it takes for the run to settle, estimated
+	/// from the scenario: the rounds the voters may have to walk back through, the latencies between
+	/// them, their scheduled actions and partitions, and the timeouts and retries along the way.
+	/// Messages beyond a processing budget wait in mailboxes, which stretches everything else.
+	pub fn estimated_completion_bound(&self) -> usize {
+		let voters = self
+			.voters
+			.values()
+			.map(|voter| voter.estimated_completion_bound(self.current_tick))
+			.max()
+			.unwrap_or_default();
+		let partitions = self
+			.partitions
+			.0
+			.iter()
+			.map(|partition| partition.end.saturating_sub(self.current_tick))
+			.max()
+			.unwrap_or_default();
+		let stretch = self
+			.processing_budget
+			.into_iter()
+			.chain(self.processing_budgets.values().copied())
+			.min()
+			.map_or(1, |budget| self.voters.len().div_ceil(budget.max(1)));
+		(partitions + voters) * stretch.max(1)
+	}
+
+	/// The tick at which the run is cut off, if it hasn't settled by then.
+	pub fn max_ticks(&self) -> usize {
+		self.max_ticks
+	}
+
+	/// Whether the run is over, for whichever reason [`World::termination`] gives.
 	pub fn completed(&self) -> bool {
-		self.current_tick >= MAX_TICKS
+		self.termination().is_some()
+	}
+
+	/// Why the run is over, or `None` if it isn't. It ends once nothing is left to do, whether or
+	/// not every protocol instance concluded, or when the stop condition is met. The tick limit is
+	/// only a safety net for runs that would otherwise go on forever.
+	pub fn termination(&self) -> Option<Termination> {
+		if self.settled() {
+			if self.voters.values().all(Voter::instances_concluded) {
+				Some(Termination::Concluded)
+			} else {
+				Some(Termination::Stalled)
+			}
+		} else if self.stop_condition_met() {
+			Some(Termination::StopCondition)
+		} else if self.current_tick >= self.max_ticks {
+			Some(Termination::TickLimit)
+		} else {
+			None
+		}
+	}
+
+	fn check_invariants(&mut self) {
+		let violations: Vec<_> = self
+			.invariants
+			.iter()
+			.filter_map(|invariant| invariant(self))
+			.collect();
+		for violation in violations {
+			self.record(
+				&"world".to_string(),
+				EventKind::InvariantViolated,
+				violation.clone(),
+			);
+			self.invariant_violations
+				.push((self.current_tick, violation));
+		}
+	}
+
+	// Send the messages over the network, and return the messages of the same kind that are due
+	// for delivery this tick.
+	fn transmit(&mut self, messages: Vec<Message>, requests: bool) -> Vec<Message> {
+		for message in messages {
+			if self
+				.partitions
+				.separates(self.current_tick, &message.sender, &message.receiver)
+			{
+				let summary = format!(
+					"{} to {}: network partition",
+					message.content.summary(),
+					message.receiver
+				);
+				self.record(&message.sender, EventKind::MessageDropped, summary);
+				continue;
+			}
+			if self.network.loss > 0.0 && self.rng.next_f64() < self.network.loss {
+				let summary = format!(
+					"{} to {}: lost in transit",
+					message.content.summary(),
+					message.receiver
+				);
+				self.record(&message.sender, EventKind::MessageDropped, summary);
+				continue;
+			}
+			self.sent_count += 1;
+			let latency = self
+				.network
+				.latency_between(&message.sender, &message.receiver);
+			self.in_flight.push((self.current_tick + latency, message));
+		}
+
+		let current_tick = self.current_tick;
+		let (due, in_flight) =
+			std::mem::take(&mut self.in_flight)
+				.into_iter()
+				.partition(|(deliver_at, message)| {
+					*deliver_at <= current_tick && message.content.request().is_some() == requests
+				});
+		self.in_flight = in_flight;
+		let due: Vec<Message> = due.into_iter().map(|(_, message)| message).collect();
+		for message in &due {
+			let summary = format!("{} from {}", message.content.summary(), message.sender);
+			let event = self.record(&message.receiver, EventKind::MessageDelivered, summary);
+			let answer = match message.content.response() {
+				Some(Response::ExplainEstimate(round, _, votes, _)) => {
+					Some((*round, QueryKind::Estimate, votes.voters()))
+				}
+				Some(Response::ExplainEstimateChunk(round, _, chunk, _)) => {
+					Some((*round, QueryKind::Estimate, chunk.votes.voters()))
+				}
+				Some(Response::PrevotesSeen(round, votes, _)) => {
+					Some((*round, QueryKind::Prevotes, votes.voters()))
+				}
+				Some(Response::NotAMemberAtRound(round)) | Some(Response::DontKnowRound(round)) => {
+					Some((*round, QueryKind::Estimate, Default::default()))
+				}
+				_ => None,
+			};
+			if let Some((round, kind, revealed)) = answer {
+				self.transcript.response_delivered(
+					&message.receiver,
+					&message.sender,
+					round,
+					kind,
+					revealed,
+					event,
+				);
+			}
+		}
+		due
+	}
+
+	// Put the delivered messages in the mailboxes of their receivers.
+	fn deposit(&mut self, messages: Vec<Message>) {
+		for message in messages {
+			if !self.voters.contains_key(&message.receiver) {
+				self.unknown_receiver(&message);
+				continue;
+			}
+			self.mailboxes
+				.entry(message.receiver.clone())
+				.or_default()
+				.push_back((self.delivered_count, message));
+			self.delivered_count += 1;
+		}
+	}
+
+	// Take the messages of the given kind the voters have the budget to handle this tick, in the
+	// order they arrived. The rest stay in the mailboxes for later ticks.
+	fn take_from_mailboxes(&mut self, requests: bool) -> Vec<Message> {
+		let mut taken = Vec::new();
+		for (voter, mailbox) in &mut self.mailboxes {
+			let budget = self
+				.processing_budgets
+				.get(voter)
+				.copied()
+				.or(self.processing_budget)
+				.unwrap_or(usize::MAX);
+			let handled = self.handled_this_tick.entry(voter.clone()).or_default();
+			let mut remaining = VecDeque::new();
+			for (index, message) in mailbox.drain(..) {
+				if message.content.request().is_some() != requests {
+					remaining.push_back((index, message));
+				} else if *handled < budget {
+					*handled += 1;
+					taken.push((index, message));
+				} else {
+					self.carried_over += 1;
+					remaining.push_back((index, message));
+				}
+			}
+			*mailbox = remaining;
+		}
+		taken.sort_by_key(|(index, _)| *index);
+		taken.into_iter().map(|(_, message)| message).collect()
 	}
 
 	pub fn process_actions(&mut self) -> Vec<Message> {
+		let current_tick = self.current_tick;
+		let jobs = self
+			.voters
+			.keys()
+			.map(|id| (id.clone(), Job::ProcessActions))
+			.collect();
 		let mut requests = Vec::new();
-		for voter in self.voters.values_mut() {
-			let voter_requests = voter.process_actions(self.current_tick);
-			requests.extend(voter_requests);
+		for (voter, result) in self.run_jobs(jobs) {
+			let voter_requests = match result {
+				JobResult::Sent(messages) => messages,
+				_ => unreachable!("processing actions only sends messages"),
+			};
+			self.record_notes(&voter);
+			// Requests a voter requeued for itself keep their original sender. These never
+			// went out over the network, so deliver them directly.
+			let (requeued, sent): (Vec<_>, Vec<_>) = voter_requests
+				.into_iter()
+				.partition(|message| message.sender != voter);
+			self.in_flight
+				.extend(requeued.into_iter().map(|message| (current_tick, message)));
+			requests.extend(sent);
 		}
+		self.record_sent(&requests);
 		requests
 	}
 
 	pub fn handle_requests(&mut self, requests: Vec<Message>) -> Vec<Message> {
 		let mut responses = Vec::new();
-		for Message {
-			sender,
-			receiver,
-			content,
-		} in requests
-		{
-			let request = content.request().unwrap();
-			let receiving_voter = self
-				.voters
-				.get_mut(&receiver)
-				.expect("all requests are to known voters");
-			let voter_responses = receiving_voter
-				.handle_request((sender, request.clone()), self.current_tick)
-				.into_iter()
-				.map(|(response_receiver, res)| Message {
-					receiver: response_receiver,
-					sender: receiver.clone(),
-					content: Payload::Response(res),
-				});
-			responses.extend(voter_responses);
+		let delivered = self.transmit(requests, true);
+		self.deposit(delivered);
+		let messages = self.take_from_mailboxes(true);
+		let jobs = self.jobs_for(messages, Job::HandleRequests);
+		for (receiver, result) in self.run_jobs(jobs) {
+			let handled = match result {
+				JobResult::Requests(handled) => handled,
+				_ => unreachable!("handling requests only answers them"),
+			};
+			self.record_notes(&receiver);
+			for request in handled {
+				for conflict in request.conflicts_started {
+					let event = self.record(
+						&receiver,
+						EventKind::ProtocolStarted,
+						request.summary.clone(),
+					);
+					self.transcript.protocol_started(&receiver, event);
+					self.notify(DetectionEvent::ConflictDetected {
+						tick: self.current_tick,
+						detector: receiver.clone(),
+						conflict,
+					});
+				}
+				responses.extend(request.responses);
+			}
+			self.record_protocol_bugs(&receiver);
 		}
+		self.record_sent(&responses);
 		responses
 	}
 
 	pub fn handle_responses(&mut self, responses: Vec<Message>) {
-		for Message {
-			sender,
-			receiver,
-			content,
-		} in responses
-		{
-			let response = content.response().unwrap();
-			let receiving_voter = self
-				.voters
-				.get_mut(&receiver)
-				.expect("all responses are to known voters");
-			receiving_voter.handle_response((sender, response.clone()), self.current_tick);
+		let delivered = self.transmit(responses, false);
+		self.deposit(delivered);
+		let messages = self.take_from_mailboxes(false);
+		let jobs = self.jobs_for(messages, Job::HandleResponses);
+		for (receiver, result) in self.run_jobs(jobs) {
+			let newly_detected = match result {
+				JobResult::Responses(detected) => detected,
+				_ => unreachable!("handling responses only takes them in"),
+			};
+			self.record_notes(&receiver);
+			for equivocation in newly_detected.into_iter().flatten() {
+				let event = self.record(
+					&receiver,
+					EventKind::EquivocationDetected,
+					format!("{:?}", equivocation),
+				);
+				self.transcript
+					.offence_found(&receiver, &equivocation, event);
+				self.notify(DetectionEvent::OffenceProven {
+					tick: self.current_tick,
+					detector: receiver.clone(),
+					finding: equivocation,
+				});
+			}
+			self.record_protocol_bugs(&receiver);
 		}
 	}
 
@@ -108,4 +978,525 @@ impl World {
 			.flat_map(|voter| voter.equivocations_detected())
 			.collect()
 	}
+
+	/// The divergent commits found by each voter.
+	pub fn divergent_commit_variants(&self) -> BTreeMap<VoterId, Vec<DivergentCommitVariants>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.divergent_commit_variants()))
+			.filter(|(_, divergent)| !divergent.is_empty())
+			.collect()
+	}
+
+	/// The analyses of the offenders found by each protocol instance, for the voters that ran any.
+	pub fn offender_analyses(&self) -> BTreeMap<VoterId, Vec<OffenderAnalysis>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.offender_analyses()))
+			.filter(|(_, analyses)| !analyses.is_empty())
+			.collect()
+	}
+
+	/// The voters each protocol instance queried without finding them to have misbehaved, for the
+	/// voters that ran any.
+	pub fn exonerations(&self) -> BTreeMap<VoterId, BTreeMap<ConflictId, Vec<Exoneration>>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.exonerations()))
+			.filter(|(_, exonerations)| !exonerations.is_empty())
+			.collect()
+	}
+
+	/// The signers of the conflicting commits found to have lost their votes, by the voters that run
+	/// the protocol instances that asked.
+	pub fn unjustified_participations(
+		&self,
+	) -> BTreeMap<VoterId, BTreeMap<ConflictId, Vec<UnjustifiedCommitParticipation>>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.unjustified_participations()))
+			.filter(|(_, participations)| !participations.is_empty())
+			.collect()
+	}
+
+	/// The verdicts on the responses each protocol instance got, for the voters that run any that
+	/// asked.
+	pub fn verdicts(&self) -> BTreeMap<VoterId, BTreeMap<ConflictId, Vec<RoundVerdicts>>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.verdicts()))
+			.filter(|(_, verdicts)| !verdicts.is_empty())
+			.collect()
+	}
+
+	/// The query plans of each protocol instance, for the voters that run any that ask.
+	pub fn query_plans(&self) -> BTreeMap<VoterId, BTreeMap<ConflictId, Vec<PlannedRound>>> {
+		self.voters
+			.iter()
+			.map(|(id, voter)| (id.clone(), voter.query_plans()))
+			.filter(|(_, plans)| !plans.is_empty())
+			.collect()
+	}
+
+	/// Voters that were queried and only ever gave responses that held up, and that weren't found
+	/// to have misbehaved some other way.
+	pub fn exonerated(&self) -> BTreeSet<VoterId> {
+		let offenders = self.offenders();
+		self.exonerations()
+			.values()
+			.flat_map(|exonerations| exonerations.values().flatten())
+			.map(|exoneration| exoneration.voter.clone())
+			.filter(|voter| !offenders.contains(voter))
+			.collect()
+	}
+
+	/// Commits each voter rejected since their target number and hash didn't match.
+	pub fn rejected_commits(&self) -> BTreeMap<VoterId, Vec<MismatchedCommitTarget>> {
+		self.voters
+			.iter()
+			.filter(|(_, voter)| !voter.rejected_commits.is_empty())
+			.map(|(id, voter)| (id.clone(), voter.rejected_commits.clone()))
+			.collect()
+	}
+
+	/// The branches hit by any of the voters during the run.
+	pub fn coverage(&self) -> Coverage {
+		let mut coverage = Coverage::new();
+		for voter in self.voters.values() {
+			coverage.merge(voter.coverage());
+		}
+		coverage
+	}
+
+	pub fn key_registry(&self) -> &KeyRegistry {
+		&self.key_registry
+	}
+
+	// The signed reports of all voters that detected anything.
+	pub fn evidence_bundles(&self) -> Vec<SignedEvidenceBundle> {
+		self.voters
+			.values()
+			.map(|voter| voter.evidence_bundle())
+			.filter(|bundle| !bundle.bundle.equivocations.is_empty())
+			.collect()
+	}
+
+	// Combine reports, discarding any whose signature doesn't match the claimed detector. Each
+	// accusation is verified against the blocks known to any voter, and one that doesn't hold up is
+	// replaced by a finding against the detector that made it up. What was rejected, and why, is
+	// noted in the event log.
+	pub fn aggregate_evidence(
+		&mut self,
+		bundles: &[SignedEvidenceBundle],
+	) -> Vec<EquivocationDetected> {
+		let mut rejections = Vec::new();
+		let mut aggregated = Vec::new();
+		for bundle in bundles {
+			let detector = &bundle.bundle.detector;
+			if !bundle.verify(&self.key_registry) {
+				rejections.push(format!(
+					"rejecting evidence attributed to {} with invalid signature",
+					detector
+				));
+				continue;
+			}
+			for accusation in &bundle.bundle.equivocations {
+				match verify_equivocation_proof(accusation, self) {
+					Err(error) if error.is_fabrication() => {
+						rejections.push(format!("rejecting accusation by {}: {}", detector, error));
+						aggregated.push(EquivocationDetected::fabricated(
+							detector.clone(),
+							accusation.clone(),
+						));
+					}
+					_ => aggregated.push(accusation.clone()),
+				}
+			}
+		}
+		for rejection in rejections {
+			self.record(&"world".to_string(), EventKind::Note, rejection);
+		}
+		aggregated
+	}
+}
+
+/// The headers of the blocks known to any voter.
+impl HeaderProvider for World {
+	fn parent(&self, block: BlockNumber) -> Option<BlockNumber> {
+		self.voters
+			.values()
+			.find_map(|voter| voter.chain.get_block(block))
+			.map(|block| block.parent)
+	}
+}
+
+/// Fluent configuration of a [`World`].
+pub struct WorldBuilder {
+	voters: BTreeMap<VoterId, Voter>,
+	network: NetworkModel,
+	seed: u64,
+	invariants: Vec<Invariant>,
+	max_ticks: Option<usize>,
+	partitions: PartitionSchedule,
+	event_sink: Option<Box<dyn Write>>,
+	strict: bool,
+	message_budget: Option<usize>,
+	snapshot_interval: Option<usize>,
+	verification_failure_rate: f64,
+	deadline: Option<usize>,
+	processing_budget: Option<usize>,
+	processing_budgets: BTreeMap<VoterId, usize>,
+	accepted_replies: ReplyKinds,
+	query_plan: QueryPlan,
+	max_outstanding_queries: Option<usize>,
+	stop_condition: StopCondition,
+	execution: Execution,
+	gossip_hops: Option<u32>,
+	coordination_timeout: Option<usize>,
+	fast_forward: bool,
+	query_prevoters: bool,
+	minimal_answers: bool,
+	behaviour_override: Option<Arc<dyn Behaviour>>,
+	active_rounds: u64,
+	vote_batch_interval: Option<usize>,
+	finalization_lag: u32,
+}
+
+impl WorldBuilder {
+	pub fn new() -> Self {
+		Self {
+			voters: Default::default(),
+			network: Default::default(),
+			seed: DEFAULT_SEED,
+			invariants: Default::default(),
+			max_ticks: None,
+			partitions: Default::default(),
+			event_sink: None,
+			strict: false,
+			message_budget: None,
+			snapshot_interval: None,
+			verification_failure_rate: 0.0,
+			deadline: None,
+			processing_budget: None,
+			processing_budgets: Default::default(),
+			accepted_replies: Default::default(),
+			query_plan: Default::default(),
+			max_outstanding_queries: None,
+			stop_condition: Default::default(),
+			execution: Default::default(),
+			gossip_hops: None,
+			coordination_timeout: None,
+			fast_forward: true,
+			query_prevoters: false,
+			minimal_answers: false,
+			behaviour_override: None,
+			active_rounds: 0,
+			vote_batch_interval: None,
+			finalization_lag: 0,
+		}
+	}
+
+	pub fn with_voters(mut self, voters: BTreeMap<VoterId, Voter>) -> Self {
+		self.voters.extend(voters);
+		self
+	}
+
+	pub fn with_network(mut self, latency: usize, loss: f64) -> Self {
+		let links = std::mem::take(&mut self.network.links);
+		self.network = NetworkModel::new(latency, loss);
+		self.network.links = links;
+		self
+	}
+
+	/// Make messages between the two voters take the given number of ticks, in both directions,
+	/// instead of the latency of the network.
+	pub fn with_link_latency(mut self, a: &str, b: &str, latency: usize) -> Self {
+		self.network = self.network.with_link(a, b, latency);
+		self
+	}
+
+	/// How voters spread their queries over the voters they ask.
+	pub fn with_query_plan(mut self, query_plan: QueryPlan) -> Self {
+		self.query_plan = query_plan;
+		self
+	}
+
+	/// When to stop a run before there is nothing left to do.
+	pub fn with_stop_condition(mut self, stop_condition: StopCondition) -> Self {
+		self.stop_condition = stop_condition;
+		self
+	}
+
+	/// Run each voter on a thread of its own, or all of them on the thread running the world.
+	pub fn with_execution(mut self, execution: Execution) -> Self {
+		self.execution = execution;
+		self
+	}
+
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.seed = seed;
+		self
+	}
+
+	pub fn with_invariants(mut self, invariants: Vec<Invariant>) -> Self {
+		self.invariants.extend(invariants);
+		self
+	}
+
+	/// Cut the run off at the given tick, instead of at a multiple of
+	/// [`World::estimated_completion_bound`].
+	pub fn with_max_ticks(mut self, max_ticks: usize) -> Self {
+		self.max_ticks = Some(max_ticks);
+		self
+	}
+
+	pub fn with_partition_schedule(mut self, partitions: PartitionSchedule) -> Self {
+		self.partitions = partitions;
+		self
+	}
+
+	/// Stream the event log as JSON Lines to the given writer while the world runs.
+	pub fn with_event_sink(mut self, sink: Box<dyn Write>) -> Self {
+		self.event_sink = Some(sink);
+		self
+	}
+
+	/// Collect internal inconsistencies as `ProtocolBug` events instead of panicking on the first one.
+	pub fn with_strict(mut self, strict: bool) -> Self {
+		self.strict = strict;
+		self
+	}
+
+	/// Limit the number of questions each voter has put to peers and not had answered, across all of
+	/// its protocol instances. Further questions are put as answers come in.
+	pub fn with_max_outstanding_queries(mut self, max_queries: usize) -> Self {
+		self.max_outstanding_queries = Some(max_queries);
+		self
+	}
+
+	/// Have voters send their commits and evidence as gossip, which is passed on from voter to voter
+	/// until it has made the given number of hops. Voters drop gossip they have seen before.
+	pub fn with_gossip_hops(mut self, max_hops: u32) -> Self {
+		self.gossip_hops = Some(max_hops);
+		self
+	}
+
+	/// Have the voters that detect the same conflict leave the queries to the one among them with
+	/// the lowest id. The others stand by, and take over if they have no complete proof after
+	/// `timeout` ticks.
+	pub fn with_verifier_coordination(mut self, timeout: usize) -> Self {
+		self.coordination_timeout = Some(timeout);
+		self
+	}
+
+	/// Have voters also put the first query of the protocol to the voters they saw prevote for the
+	/// new block, besides those whose precommits are in its commit. Their answers count alongside
+	/// those of the precommitters.
+	pub fn with_prevoter_queries(mut self, query_prevoters: bool) -> Self {
+		self.query_prevoters = query_prevoters;
+		self
+	}
+
+	/// Have voters explain estimates with the fewest of their votes that show the block couldn't
+	/// have been included, instead of with all their votes for the round. Verifiers count the votes
+	/// left out as possibly for the block, as they would the votes of voters that didn't vote.
+	pub fn with_minimal_answers(mut self, minimal_answers: bool) -> Self {
+		self.minimal_answers = minimal_answers;
+		self
+	}
+
+	/// Give every voter that misbehaves the behaviour instead of its own, so that the same scenario
+	/// can be run against different adversary strategies, for the whole run rather than switching
+	/// on a schedule. Voters without a behaviour or schedule are left alone.
+	pub fn with_behaviour_override(mut self, behaviour: impl Behaviour + 'static) -> Self {
+		self.behaviour_override = Some(Arc::new(behaviour));
+		self
+	}
+
+	/// Whether [`World::run_until_settled`] skips over the ticks at which no voter has anything to
+	/// do, which it does by default. Runs come out the same either way, so this is only worth
+	/// turning off to pace a run tick by tick, say to watch it at a fixed rate.
+	pub fn with_fast_forward(mut self, fast_forward: bool) -> Self {
+		self.fast_forward = fast_forward;
+		self
+	}
+
+	/// Limit the number of votes in a single message, so that larger responses are chunked. Voters
+	/// also hold each other to the limit, treating a larger message as misbehaviour.
+	pub fn with_message_budget(mut self, max_votes: usize) -> Self {
+		self.message_budget = Some(max_votes);
+		self
+	}
+
+	/// Take a snapshot of the state of all voters every `interval` ticks.
+	pub fn with_snapshot_interval(mut self, interval: usize) -> Self {
+		self.snapshot_interval = Some(interval);
+		self
+	}
+
+	/// Make the given fraction of signature checks by voters fail, even for valid signatures.
+	pub fn with_verification_failure_rate(mut self, failure_rate: f64) -> Self {
+		self.verification_failure_rate = failure_rate;
+		self
+	}
+
+	/// Expect every honest voter to have a complete proof within the given number of ticks after
+	/// the first conflict is detected.
+	pub fn with_deadline(mut self, ticks: usize) -> Self {
+		self.deadline = Some(ticks);
+		self
+	}
+
+	/// Limit how many messages each voter handles per tick. Messages beyond the budget wait in the
+	/// voter's mailbox for later ticks.
+	pub fn with_processing_budget(mut self, messages: usize) -> Self {
+		self.processing_budget = Some(messages);
+		self
+	}
+
+	/// Limit how many messages the given voter handles per tick, overriding the world-wide budget.
+	pub fn with_voter_processing_budget(mut self, voter: &str, messages: usize) -> Self {
+		self.processing_budgets.insert(voter.to_string(), messages);
+		self
+	}
+
+	/// Have every voter only accept estimates explained with the given kinds of votes when running
+	/// the protocol.
+	pub fn with_accepted_replies(mut self, accepted_replies: ReplyKinds) -> Self {
+		self.accepted_replies = accepted_replies;
+		self
+	}
+
+	/// Have the members of the voter set vote in the given number of rounds during the run, after
+	/// the latest round of the histories they were given. The timers of each round are set for the
+	/// highest latency between voters.
+	pub fn with_active_voting(mut self, rounds: u64) -> Self {
+		self.active_rounds = rounds;
+		self
+	}
+
+	/// Have voters voting in rounds send all the votes they have seen in their live rounds in a
+	/// single message, at most once every `interval` ticks, instead of each of their votes as they
+	/// cast it.
+	pub fn with_vote_batching(mut self, interval: usize) -> Self {
+		self.vote_batch_interval = Some(interval);
+		self
+	}
+
+	/// Have voters voting in rounds finalize the block `lag` blocks below the one their rounds
+	/// could finalize, rather than that block itself.
+	pub fn with_finalization_lag(mut self, lag: u32) -> Self {
+		self.finalization_lag = lag;
+		self
+	}
+
+	pub fn build(self) -> World {
+		let mut voters = self.voters;
+		// Each voter gets a key of its own drawn from the seed, so that runs are reproducible, and
+		// everyone gets the public keys.
+		let mut key_rng = Rng::new(self.seed ^ KEY_SEED_SALT);
+		let mut key_registry = KeyRegistry::new();
+		for voter in voters.values_mut() {
+			voter.secret_key = SecretKey::generate(&mut key_rng);
+			key_registry.register(voter.id.clone(), voter.secret_key.public());
+		}
+		let observers: BTreeSet<_> = voters
+			.values()
+			.filter(|voter| voter.is_observer())
+			.map(|voter| voter.id.clone())
+			.collect();
+		let ids: Vec<_> = voters.keys().cloned().collect();
+		let network = &self.network;
+		for (index, voter) in voters.values_mut().enumerate() {
+			if self.verification_failure_rate > 0.0 {
+				voter.verification_faults = Some(VerificationFaults::new(
+					self.verification_failure_rate,
+					self.seed.wrapping_add(index as u64 + 1),
+				));
+			}
+			voter.key_registry = key_registry.clone();
+			voter.observers = observers.clone();
+			voter.strict = self.strict;
+			voter.message_budget = self.message_budget;
+			voter.accepted_replies = self.accepted_replies;
+			voter.query_plan = self.query_plan;
+			voter.max_outstanding_queries = self.max_outstanding_queries;
+			voter.gossip_hops = self.gossip_hops;
+			voter.coordination_timeout = self.coordination_timeout;
+			voter.query_prevoters = self.query_prevoters;
+			voter.minimal_answers = self.minimal_answers;
+			let misbehaves = voter.behaviour.is_some() || !voter.behaviour_schedule.is_empty();
+			if let Some(behaviour) = self.behaviour_override.clone().filter(|_| misbehaves) {
+				voter.behaviour = Some(behaviour);
+				voter.behaviour_schedule = Default::default();
+			}
+			voter.link_latencies = ids
+				.iter()
+				.filter(|id| **id != voter.id)
+				.map(|id| (id.clone(), network.latency_between(&voter.id, id)))
+				.collect();
+		}
+		let vote_delay = voters
+			.values()
+			.flat_map(|voter| voter.link_latencies.values())
+			.max()
+			.copied()
+			.unwrap_or_default();
+		for voter in voters.values_mut() {
+			voter.vote_batch_interval = self.vote_batch_interval;
+			voter.finalization_lag = self.finalization_lag;
+			voter.vote_in_rounds(self.active_rounds, vote_delay);
+		}
+		let threads = match self.execution {
+			Execution::SingleThreaded => None,
+			Execution::ThreadPerVoter => Some(VoterThreads::spawn(voters.keys())),
+		};
+		let mut world = World {
+			voters,
+			current_tick: 0,
+			max_ticks: self.max_ticks.unwrap_or_default(),
+			network: self.network,
+			partitions: self.partitions,
+			rng: Rng::new(self.seed),
+			in_flight: Default::default(),
+			mailboxes: Default::default(),
+			delivered_count: 0,
+			sent_count: 0,
+			queries_sent: 0,
+			processing_budget: self.processing_budget,
+			processing_budgets: self.processing_budgets,
+			handled_this_tick: Default::default(),
+			carried_over: 0,
+			invariants: self.invariants,
+			invariant_violations: Default::default(),
+			key_registry,
+			event_log: self.event_sink.map(EventLog::with_sink).unwrap_or_default(),
+			transcript: Default::default(),
+			strict: self.strict,
+			snapshots: self.snapshot_interval.map(SnapshotHistory::new),
+			deadline: self.deadline,
+			first_detection: None,
+			proofs_completed: Default::default(),
+			stop_condition: self.stop_condition,
+			fast_forward: self.fast_forward,
+			accountable_at: None,
+			threads,
+			subscribers: Default::default(),
+		};
+		if self.max_ticks.is_none() {
+			world.max_ticks = COMPLETION_BOUND_MULTIPLE * world.estimated_completion_bound();
+		}
+		world
+	}
+}
+
+// The voter knows of at least f + 1 offenders, as many as the protocol guarantees to find.
+fn has_complete_proof(voter: &Voter) -> bool {
+	voter.known_offenders().len() > voter.voter_set.max_faulty()
+}
+
+impl Default for WorldBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
 }
@@ -0,0 +1,155 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs the example configurations in `configs/`.
+
+use accountable_safety::{
+	behaviour::Adversary,
+	config::{ConfigError, ExperimentConfig},
+	execution::Execution,
+	fixtures,
+	report::RunSummary,
+	voter::{QueryPlan, Voter},
+	world::World,
+};
+use std::{collections::BTreeSet, path::Path};
+
+fn load(name: &str) -> ExperimentConfig {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("configs")
+		.join(name);
+	ExperimentConfig::from_file(&path).unwrap()
+}
+
+fn run(name: &str) -> RunSummary {
+	load(name).build().unwrap().run_until_settled()
+}
+
+fn offenders(names: &[&str]) -> BTreeSet<String> {
+	names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn two_forks() {
+	let summary = run("two_forks.toml");
+	assert!(summary.settled);
+	assert_eq!(summary.offenders, offenders(&["Alice", "Bob"]));
+	assert_eq!(summary.protocol_bugs, 0);
+}
+
+#[test]
+fn two_forks_is_the_paper_example() {
+	let from_config = load("two_forks.toml").build().unwrap();
+	let from_fixture = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	assert_eq!(
+		from_config.voters().keys().collect::<Vec<_>>(),
+		from_fixture.voters().keys().collect::<Vec<_>>()
+	);
+	for (id, voter) in from_config.voters() {
+		let finalized = |voter: &Voter| {
+			voter
+				.chain
+				.commits()
+				.keys()
+				.copied()
+				.collect::<BTreeSet<_>>()
+		};
+		assert_eq!(
+			finalized(voter),
+			finalized(&from_fixture.voters()[id]),
+			"{}",
+			id
+		);
+	}
+}
+
+#[test]
+fn lazy_equivocators() {
+	let summary = run("lazy_equivocators.toml");
+	assert!(summary.settled);
+	assert_eq!(summary.offenders, offenders(&["Alice", "Bob"]));
+	assert_eq!(summary.protocol_bugs, 0);
+	assert!(!summary.deadline_missed());
+}
+
+#[test]
+fn nearest_first_against_broadcast_all() {
+	let nearest_first = run("nearest_first.toml");
+	let mut config = load("nearest_first.toml");
+	config.world.query_plan = Some(QueryPlan::BroadcastAll);
+	let broadcast_all = config.build().unwrap().run_until_settled();
+
+	for summary in &[&nearest_first, &broadcast_all] {
+		assert!(summary.settled);
+		assert_eq!(summary.offenders, offenders(&["Alice", "Bob"]));
+		assert_eq!(summary.protocol_bugs, 0);
+	}
+	// Alice is the nearest signer and answers right away, so nobody else needs asking, and the
+	// proof is found just as quickly.
+	assert!(nearest_first.messages < broadcast_all.messages);
+	assert_eq!(nearest_first.proof_latency, broadcast_all.proof_latency);
+}
+
+#[test]
+fn thread_per_voter_finds_the_same_offenders() {
+	for name in &[
+		"two_forks.toml",
+		"lazy_equivocators.toml",
+		"nearest_first.toml",
+	] {
+		let single_threaded = run(name);
+		let mut config = load(name);
+		config.world.execution = Some(Execution::ThreadPerVoter);
+		let threaded = config.build().unwrap().run_until_settled();
+
+		assert!(threaded.settled, "{}", name);
+		assert_eq!(threaded.offenders, single_threaded.offenders, "{}", name);
+		assert_eq!(threaded.protocol_bugs, 0, "{}", name);
+	}
+}
+
+#[test]
+fn alternatives_compared_on_the_paper_example() {
+	let comparison = load("two_forks.toml").compare_alternatives().unwrap();
+	print!("{}", comparison);
+
+	// Either way Alice and Bob are found out, but answering with prevotes drags the verifier into
+	// asking which prevotes were seen.
+	for outcome in &[&comparison.precommits, &comparison.prevotes] {
+		assert_eq!(outcome.offenders, offenders(&["Alice", "Bob"]));
+		assert_eq!(outcome.protocol_bugs, 0);
+	}
+	assert!(comparison.offenders_found_in_one().is_empty());
+	assert!(comparison.precommits.prevote_rounds_queried.is_empty());
+	assert!(!comparison.prevotes.prevote_rounds_queried.is_empty());
+	assert!(comparison.precommits.messages < comparison.prevotes.messages);
+}
+
+#[test]
+fn zero_budgets_and_intervals_are_rejected() {
+	let invalid_key = |config: ExperimentConfig| match config.build() {
+		Err(ConfigError::Invalid { key, .. }) => key,
+		other => panic!("expected an invalid value, got {:?}", other.err()),
+	};
+
+	let mut config = load("two_forks.toml");
+	config.world.message_budget = Some(0);
+	assert_eq!(invalid_key(config), "world.message_budget");
+
+	let mut config = load("two_forks.toml");
+	config.world.snapshot_interval = Some(0);
+	assert_eq!(invalid_key(config), "world.snapshot_interval");
+}
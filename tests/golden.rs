@@ -0,0 +1,251 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Golden-file tests for the reports shown to users, so that changes to their formatting show up as
+//! a diff rather than having to be spotted in hand-written assertions.
+//!
+//! The expected output lives in `tests/golden/`. Running the tests with `BLESS=1` set writes the
+//! current output there instead of comparing against it.
+
+use accountable_safety::{
+	behaviour::Adversary,
+	config::ExperimentConfig,
+	fixtures,
+	schema::{
+		ConflictV1, EvidenceRef, GuaranteeState, GuaranteeV1, OffenceKind, OffenceV1, ReportV1,
+		RunMetadata, REPORT_SCHEMA_VERSION,
+	},
+	world::{StopCondition, World},
+};
+use serde_json::Value;
+use std::{env, fs, path::Path};
+
+// Trailing whitespace and line endings aren't part of the format.
+fn normalize(text: &str) -> String {
+	text.lines()
+		.map(|line| format!("{}\n", line.trim_end()))
+		.collect()
+}
+
+// The lines of both texts, with lines only in the expected text marked `-` and those only in the
+// actual text marked `+`, based on their longest common subsequence.
+fn diff(expected: &str, actual: &str) -> String {
+	let expected: Vec<_> = expected.lines().collect();
+	let actual: Vec<_> = actual.lines().collect();
+	let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+	for i in (0..expected.len()).rev() {
+		for j in (0..actual.len()).rev() {
+			common[i][j] = if expected[i] == actual[j] {
+				common[i + 1][j + 1] + 1
+			} else {
+				common[i + 1][j].max(common[i][j + 1])
+			};
+		}
+	}
+
+	let mut lines = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < expected.len() || j < actual.len() {
+		if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+			lines.push(format!("  {}", expected[i]));
+			i += 1;
+			j += 1;
+		} else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+		{
+			lines.push(format!("- {}", expected[i]));
+			i += 1;
+		} else {
+			lines.push(format!("+ {}", actual[j]));
+			j += 1;
+		}
+	}
+	lines.join("\n")
+}
+
+fn check_golden(name: &str, actual: &str) {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests")
+		.join("golden")
+		.join(name);
+	let actual = normalize(actual);
+	if env::var_os("BLESS").is_some() {
+		fs::write(&path, &actual).unwrap();
+		return;
+	}
+
+	let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+		panic!(
+			"no golden file {}, run with BLESS=1 to create it",
+			path.display()
+		)
+	});
+	let expected = normalize(&expected);
+	if expected != actual {
+		panic!(
+			"output differs from {}, run with BLESS=1 to update it:\n{}",
+			path.display(),
+			diff(&expected, &actual)
+		);
+	}
+}
+
+// Golden files for a versioned schema can't be re-blessed once written, the version has to be
+// bumped instead.
+fn check_schema(name: &str, actual: &str) {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests")
+		.join("golden")
+		.join(name);
+	match fs::read_to_string(&path) {
+		Ok(expected) if normalize(&expected) != normalize(actual) => panic!(
+			"schema differs from {}, bump the schema version rather than changing it:\n{}",
+			path.display(),
+			diff(&normalize(&expected), &normalize(actual))
+		),
+		Ok(_) => {}
+		Err(_) => check_golden(name, actual),
+	}
+}
+
+// One line per field, with its JSON type. Arrays are described by their first element.
+fn shape(value: &Value, path: &str, lines: &mut Vec<String>) {
+	match value {
+		Value::Object(fields) => {
+			lines.push(format!("{}: object", path));
+			for (key, value) in fields {
+				shape(value, &format!("{}.{}", path, key), lines);
+			}
+		}
+		Value::Array(elements) => {
+			lines.push(format!("{}: array", path));
+			if let Some(first) = elements.first() {
+				shape(first, &format!("{}[]", path), lines);
+			}
+		}
+		Value::String(_) => lines.push(format!("{}: string", path)),
+		Value::Number(_) => lines.push(format!("{}: number", path)),
+		Value::Bool(_) => lines.push(format!("{}: bool", path)),
+		Value::Null => lines.push(format!("{}: null", path)),
+	}
+}
+
+fn two_forks() -> ExperimentConfig {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("configs")
+		.join("two_forks.toml");
+	ExperimentConfig::from_file(&path).unwrap()
+}
+
+#[test]
+fn run_summary() {
+	let mut summary = two_forks().build().unwrap().run_until_settled();
+	// Only measured with the `alloc-tracking` feature, and varies with the allocator anyway.
+	summary.peak_memory = None;
+	check_golden("two_forks_summary.txt", &summary.to_string());
+}
+
+#[test]
+fn commit_reports() {
+	let world = World::new(fixtures::paper_example(Adversary::ReturnPrecommits));
+	let reports: String = world
+		.commit_reports()
+		.iter()
+		.map(|report| report.to_string())
+		.collect();
+	check_golden("paper_example_commits.txt", &reports);
+}
+
+#[test]
+fn query_plans() {
+	let mut config = two_forks();
+	config.world.stop_condition = Some(StopCondition::Detected);
+	let mut world = config.build().unwrap();
+	world.run_until_settled();
+	let mut plans = String::new();
+	for (detector, plans_per_conflict) in world.query_plans() {
+		for (conflict, rounds) in plans_per_conflict {
+			plans += &format!(
+				"{} investigating blocks {} and {}:\n",
+				detector, conflict.block_not_included, conflict.new_block
+			);
+			for round in rounds {
+				plans += &format!("  {}\n", round);
+			}
+		}
+	}
+	check_golden("two_forks_plan.txt", &plans);
+}
+
+#[test]
+fn report_schema() {
+	// Every optional field set and every list non-empty, so that the whole schema shows.
+	let report = ReportV1 {
+		schema_version: REPORT_SCHEMA_VERSION,
+		run: RunMetadata {
+			ticks: 40,
+			settled: true,
+			messages: 120,
+			protocol_bugs: 0,
+			invariant_violations: 0,
+			proof_latency: Some(12),
+			accountability_latency: Some(10),
+		},
+		conflicts: vec![ConflictV1 {
+			block_not_included: 4,
+			new_block: 8,
+			detectors: vec!["Carol".to_string()],
+			offenders: vec!["Alice".to_string()],
+		}],
+		offenders: vec!["Alice".to_string()],
+		offences: vec![OffenceV1 {
+			voter: "Alice".to_string(),
+			set_id: 0,
+			kind: OffenceKind::PrecommitEquivocation,
+			detectors: vec!["Carol".to_string()],
+		}],
+		evidence: vec![EvidenceRef {
+			detector: "Carol".to_string(),
+			digest: "0123456789abcdef".to_string(),
+			findings: 1,
+		}],
+		guarantee: GuaranteeV1 {
+			status: GuaranteeState::Reached,
+			proven: 1,
+			required: Some(1),
+		},
+	};
+	let mut lines = Vec::new();
+	shape(
+		&serde_json::to_value(&report).unwrap(),
+		"report",
+		&mut lines,
+	);
+	for kind in &OffenceKind::ALL {
+		lines.push(format!(
+			"offence kind: {}",
+			serde_json::to_value(kind).unwrap()
+		));
+	}
+	check_schema(
+		&format!("report_schema_v{}.txt", REPORT_SCHEMA_VERSION),
+		&lines.join("\n"),
+	);
+}
+
+#[test]
+fn diff_marks_changed_lines() {
+	assert_eq!(diff("a\nb\nc\n", "a\nx\nc\nd\n"), "  a\n- b\n+ x\n  c\n+ d");
+}
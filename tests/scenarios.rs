@@ -0,0 +1,74 @@
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs every registered scenario, the ready-made ones and the example configurations, and checks
+//! each comes to the outcome it is annotated with.
+
+use accountable_safety::{
+	config::ExperimentConfig,
+	fixtures,
+	scenario::{Expected, Mismatch},
+	world::{World, WorldBuilder},
+};
+use std::path::Path;
+
+fn check(world: &mut World, expected: &Expected) -> Vec<Mismatch> {
+	let summary = world.run_until_settled();
+	expected.check(world, &summary)
+}
+
+#[test]
+fn scenarios_come_to_their_expected_outcome() {
+	let mut failures = Vec::new();
+	let mut fail = |name: &str, mismatches: Vec<Mismatch>| {
+		failures.extend(
+			mismatches
+				.iter()
+				.map(|mismatch| format!("{}: {}", name, mismatch)),
+		)
+	};
+
+	for (name, scenario) in fixtures::registered() {
+		let expected = scenario.expected().clone();
+		assert!(!expected.is_empty(), "{} has no expected outcome", name);
+		let mut world = WorldBuilder::new()
+			.with_voters(scenario.build().unwrap())
+			.build();
+		fail(name, check(&mut world, &expected));
+	}
+
+	let configs = Path::new(env!("CARGO_MANIFEST_DIR")).join("configs");
+	let mut paths: Vec<_> = std::fs::read_dir(&configs)
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension() == Some("toml".as_ref()))
+		.collect();
+	paths.sort();
+	assert!(!paths.is_empty());
+	for path in paths {
+		let name = path.file_name().unwrap().to_string_lossy().to_string();
+		let config = ExperimentConfig::from_file(&path).unwrap();
+		assert!(
+			!config.expect.is_empty(),
+			"{} has no [expect] section",
+			name
+		);
+		let mut world = config.build().unwrap();
+		fail(&name, check(&mut world, &config.expect));
+	}
+
+	assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}